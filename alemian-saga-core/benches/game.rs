@@ -0,0 +1,334 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::rc::Rc;
+
+use async_trait::async_trait;
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+
+use alemian_saga_core::game::{Game, MapDistance, ViewPosition};
+use alemian_saga_core::{serialization, style, text, Error, Event, Platform, Vector};
+
+// Width and height of the map in tiles
+const MAP_SIZE: MapDistance = 512;
+// Number of tile types the map is made up of
+const TILE_TYPES: u32 = 16;
+// Number of columns on the screen that redraws are measured at
+const ZOOMS: [MapDistance; 3] = [16, 64, 512];
+// Number of events sent in each burst, and how many of them go by between frames
+const BURST_EVENTS: usize = 10_000;
+const EVENTS_PER_FRAME: usize = 16;
+const LANGUAGE: &str = "bench";
+
+// A platform that draws nothing but counts what it's asked to draw, so that only the game's own
+// work is measured. The count is shared so that it can still be read once the game owns the
+// platform. A platform that batches counts each batch as a single call, the way the web build
+// hands each one to JavaScript at once
+struct CountingPlatform {
+    files: HashMap<String, Vec<u8>>,
+    draw_calls: Rc<Cell<u64>>,
+    batching: bool,
+    // Whether images are being held back for a batch
+    in_batch: Cell<bool>,
+}
+
+impl CountingPlatform {
+    fn new() -> CountingPlatform {
+        let mut files = HashMap::new();
+        let map = rmp_serde::encode::to_vec(&bench_map()).unwrap();
+        files.insert(format!("{}/map.map", LANGUAGE), map);
+        CountingPlatform {
+            files,
+            draw_calls: Rc::new(Cell::new(0)),
+            batching: false,
+            in_batch: Cell::new(false),
+        }
+    }
+
+    fn batching() -> CountingPlatform {
+        CountingPlatform {
+            batching: true,
+            ..CountingPlatform::new()
+        }
+    }
+
+    fn count(&self) {
+        self.draw_calls.set(self.draw_calls.get() + 1);
+    }
+}
+
+#[async_trait(?Send)]
+impl Platform for CountingPlatform {
+    type Image = ();
+    type InputType = String;
+    type MouseDistance = i32;
+    type ScreenDistance = f64;
+    type ImageFuture = futures::future::Ready<Option<()>>;
+    type File = Cursor<Vec<u8>>;
+    type Instant = std::time::Instant;
+    type Duration = std::time::Duration;
+    type SleepFuture = futures::future::Ready<()>;
+    type FrameFuture = futures::future::Ready<()>;
+
+    fn draw_primitive(&self, _img: &(), _left: f64, _top: f64, _width: f64, _height: f64) {
+        if !self.in_batch.get() {
+            self.count();
+        }
+    }
+    fn begin_batch(&self) {
+        self.in_batch.set(self.batching);
+    }
+    fn end_batch(&self) {
+        if self.in_batch.replace(false) {
+            self.count();
+        }
+    }
+    fn fill_rect_primitive(
+        &self,
+        _left: f64,
+        _top: f64,
+        _width: f64,
+        _height: f64,
+        _color: style::Color,
+    ) {
+        self.count();
+    }
+    fn clear(&self) {
+        self.count();
+    }
+    fn draw_text_primitive(
+        &self,
+        _text: &str,
+        _x: f64,
+        _y: f64,
+        _max_width: f64,
+        _style: &style::TextStyle,
+        _direction: text::Direction,
+    ) {
+        self.count();
+    }
+    fn string_to_input(input: String) -> String {
+        input
+    }
+    fn get_width(&self) -> f64 {
+        1920.0
+    }
+    fn get_height(&self) -> f64 {
+        1080.0
+    }
+    fn measure_text(&self, text: &str, style: &style::TextStyle) -> f64 {
+        text.chars().count() as f64 * style.size * 16.0
+    }
+    fn get_image(&self, _path: &str) -> Self::ImageFuture {
+        futures::future::ready(Some(()))
+    }
+    async fn get_file(&self, path: &str) -> Result<Self::File, Error> {
+        match self.files.get(path) {
+            Some(contents) => Ok(Cursor::new(contents.clone())),
+            None => Err(Error::MissingAsset(path.to_owned())),
+        }
+    }
+    fn log(_msg: &str) {}
+    fn now() -> Self::Instant {
+        std::time::Instant::now()
+    }
+    fn nanoseconds(ns: u64) -> Self::Duration {
+        std::time::Duration::from_nanos(ns)
+    }
+    fn to_nanoseconds(duration: Self::Duration) -> u64 {
+        duration.as_nanos() as u64
+    }
+    fn duration_between(first: Self::Instant, second: Self::Instant) -> Self::Duration {
+        second.saturating_duration_since(first)
+    }
+    fn instant_after(instant: Self::Instant, duration: Self::Duration) -> Self::Instant {
+        instant + duration
+    }
+    fn sleep(_duration: Self::Duration) -> Self::SleepFuture {
+        futures::future::ready(())
+    }
+    fn request_frame(&self) -> Self::FrameFuture {
+        futures::future::ready(())
+    }
+    fn random(&self) -> u64 {
+        0
+    }
+}
+
+// A map with tile types laid out in diagonal stripes and a unit of either team every few tiles
+fn bench_map() -> serialization::Map {
+    let tile_types = (0..TILE_TYPES)
+        .map(|i| serialization::TileType {
+            image: format!("tile{}.png", i),
+            name: format!("tile{}", i),
+            defense: (i % 3) as i32,
+            evade: (i % 4) as i32 * 5,
+            move_cost: 1 + i % 3,
+            minimap_color: None,
+            ambient_sound: None,
+            footstep_sound: None,
+        })
+        .collect();
+    let size = MAP_SIZE as usize;
+    let map = ndarray::Array2::from_shape_fn((size, size), |(y, x)| {
+        ((x + y * 3) % TILE_TYPES as usize) as u32
+    });
+    let units = (0..MAP_SIZE)
+        .step_by(16)
+        .flat_map(|y| (0..MAP_SIZE).step_by(16).map(move |x| (x, y)))
+        .enumerate()
+        .map(|(i, (x, y))| serialization::Unit {
+            name: format!("unit{}", i),
+            x: x + (y / 16) % 5,
+            y,
+            image: "unit.png".to_owned(),
+            portrait: None,
+            hp: 20,
+            max_hp: 20,
+            strength: 6,
+            defense: 3,
+            speed: 5,
+            movement: 5,
+            range: 1,
+            range_pattern: None,
+            team: if i % 2 == 0 {
+                serialization::PLAYER_TEAM.to_owned()
+            } else {
+                "enemy".to_owned()
+            },
+            statuses: vec![],
+            level: 1,
+            exp: 0,
+        })
+        .collect();
+    serialization::Map {
+        tile_types,
+        map,
+        background: None,
+        fog: false,
+        triggers: vec![],
+        intro_dialog: vec![],
+        music: None,
+        units,
+    }
+}
+
+fn load_game_on(platform: CountingPlatform) -> Game<CountingPlatform> {
+    futures::executor::block_on(Game::load(platform, LANGUAGE, LANGUAGE))
+        .unwrap_or_else(|e| panic!("Failed to load benchmark map: {}", e))
+}
+
+// Loads a game zoomed to a size that the map is typically played at
+fn load_game() -> Game<CountingPlatform> {
+    let mut game = load_game_on(CountingPlatform::new());
+    show_center(&mut game, ZOOMS[0]);
+    game
+}
+
+// Moves the cursor to the middle of the map with zoom columns of tiles on the screen
+fn show_center(game: &mut Game<CountingPlatform>, zoom: MapDistance) {
+    game.show_position(ViewPosition {
+        cursor: Vector {
+            x: MAP_SIZE / 2,
+            y: MAP_SIZE / 2,
+        },
+        zoom,
+    });
+    game.frame();
+}
+
+// Counts the draw calls that redrawing the screen makes once with zoom columns of tiles on it
+fn draw_calls_per_redraw(platform: CountingPlatform, zoom: MapDistance) -> u64 {
+    let draw_calls = Rc::clone(&platform.draw_calls);
+    let mut game = load_game_on(platform);
+    show_center(&mut game, zoom);
+    draw_calls.set(0);
+    game.handle_event(Event::Redraw);
+    game.frame();
+    draw_calls.get()
+}
+
+fn redraw_with(c: &mut Criterion, name: &str, new_platform: fn() -> CountingPlatform) {
+    let mut group = c.benchmark_group(name);
+    for &zoom in ZOOMS.iter() {
+        // Checked beforehand since filtered out benchmarks never redraw at all
+        let draw_calls = draw_calls_per_redraw(new_platform(), zoom);
+        assert!(draw_calls > 0, "Redrawing drew nothing");
+        let mut game = load_game_on(new_platform());
+        show_center(&mut game, zoom);
+        group.bench_with_input(BenchmarkId::from_parameter(zoom), &zoom, |b, _| {
+            b.iter(|| {
+                game.handle_event(Event::Redraw);
+                game.frame()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn redraw(c: &mut Criterion) {
+    redraw_with(c, "redraw", CountingPlatform::new);
+}
+
+// Redraws on a platform that batches, first reporting how many draw calls batching saves
+fn redraw_batched(c: &mut Criterion) {
+    for &zoom in ZOOMS.iter() {
+        let immediate = draw_calls_per_redraw(CountingPlatform::new(), zoom);
+        let batched = draw_calls_per_redraw(CountingPlatform::batching(), zoom);
+        println!(
+            "zoom {}: {} draw calls per redraw, {} when batched",
+            zoom, immediate, batched
+        );
+        assert!(batched < immediate, "Batching didn't save any draw calls");
+    }
+    redraw_with(c, "redraw_batched", CountingPlatform::batching);
+}
+
+// Gets the i-th event of a burst: mostly cursor movement and mouse motion, with the occasional
+// selection, zoom and danger zone toggle mixed in the way a player would
+fn scripted_event(i: usize) -> Event<i32, String> {
+    let mouse = Vector {
+        x: (i * 37 % 1920) as i32,
+        y: (i * 53 % 1080) as i32,
+    };
+    match i % 32 {
+        0..=5 => Event::Right,
+        6..=11 => Event::Down,
+        12..=15 => Event::Left,
+        16..=18 => Event::Up,
+        19 => Event::Select,
+        20 => Event::Cancel,
+        21..=26 => Event::MouseMove(mouse),
+        27 if i % 64 < 32 => Event::ZoomIn,
+        27 => Event::ZoomOut,
+        28 => Event::DownRight,
+        29 => Event::UpLeft,
+        30 if i % 512 == 30 => Event::ToggleDangerZone,
+        _ => Event::Redraw,
+    }
+}
+
+fn event_burst(c: &mut Criterion) {
+    let mut group = c.benchmark_group("handle_event");
+    // Each sample loads the whole map again, so fewer are taken
+    group.sample_size(10);
+    group.bench_function(BenchmarkId::new("burst", BURST_EVENTS), |b| {
+        b.iter_batched(
+            load_game,
+            |mut game| {
+                for i in 0..BURST_EVENTS {
+                    game.handle_event(scripted_event(i));
+                    if i % EVENTS_PER_FRAME == EVENTS_PER_FRAME - 1 {
+                        game.frame();
+                    }
+                }
+                game
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, redraw, redraw_batched, event_burst);
+criterion_main!(benches);
@@ -0,0 +1,84 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use alemian_saga_core::game::MapDistance;
+use alemian_saga_core::{geometry, pathfinding, Vector};
+
+// Width and height of the maps that are searched
+const MAP_SIZE: MapDistance = 512;
+// Movement budgets searched with: a typical unit's, a long range one's, and enough to reach every
+// tile on the map
+const BUDGETS: [u32; 3] = [8, 64, u32::MAX];
+
+// Walls along every fourth column, with a gap at alternating ends so that paths have to snake back
+// and forth across the whole map
+fn is_maze_wall(pos: Vector<MapDistance>) -> bool {
+    if pos.x % 4 != 2 {
+        return false;
+    }
+    if pos.x % 8 == 2 {
+        pos.y != 0
+    } else {
+        pos.y != MAP_SIZE - 1
+    }
+}
+
+fn budget_name(budget: u32) -> String {
+    if budget == u32::MAX {
+        "unlimited".to_owned()
+    } else {
+        budget.to_string()
+    }
+}
+
+fn reachable(c: &mut Criterion) {
+    let map_size = Vector {
+        x: MAP_SIZE,
+        y: MAP_SIZE,
+    };
+    let center = map_size / 2;
+    let corner = Vector { x: 0, y: 0 };
+    let mut group = c.benchmark_group("reachable");
+    // Searching the whole map is slow enough that fewer samples are still steady
+    group.sample_size(10);
+    for &budget in BUDGETS.iter() {
+        let name = budget_name(budget);
+        group.bench_with_input(
+            BenchmarkId::new("open_field", &name),
+            &budget,
+            |b, &budget| b.iter(|| pathfinding::reachable(center, budget, map_size, |_| Some(1))),
+        );
+        group.bench_with_input(BenchmarkId::new("maze", &name), &budget, |b, &budget| {
+            b.iter(|| {
+                pathfinding::reachable(corner, budget, map_size, |p| {
+                    (!is_maze_wall(p)).then_some(1)
+                })
+            })
+        });
+    }
+    group.finish();
+}
+
+fn attack_range(c: &mut Criterion) {
+    let map_size = Vector {
+        x: MAP_SIZE,
+        y: MAP_SIZE,
+    };
+    let center = map_size / 2;
+    let pattern = geometry::RangePattern::MinMax { min: 1, max: 2 };
+    let mut group = c.benchmark_group("attack_range");
+    for &budget in BUDGETS[..2].iter() {
+        let moves = pathfinding::reachable(center, budget, map_size, |_| Some(1));
+        let name = budget_name(budget);
+        group.bench_with_input(BenchmarkId::new("open_field", &name), &moves, |b, moves| {
+            b.iter(|| {
+                pathfinding::attack_range(moves.keys().copied(), &pattern, map_size, |p| {
+                    moves.contains_key(&p)
+                })
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, reachable, attack_range);
+criterion_main!(benches);
@@ -0,0 +1,361 @@
+use std::collections::HashMap;
+
+use crate::{fetch, log, Action, Platform};
+
+// Key under which rebound keys are stored by the platform
+pub const STORAGE_KEY: &str = "keybindings";
+
+// Gets the path of the keybindings file for a locale, unless the platform keeps it elsewhere
+pub fn default_path(locale: &str) -> String {
+    format!("keybindings/{}.json", locale)
+}
+
+// Situations in which keys may be bound to different actions
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Context {
+    // Moving the cursor around the map
+    Map,
+    Menu,
+    Dialog,
+}
+
+impl Context {
+    pub const ALL: [Context; 3] = [Context::Map, Context::Menu, Context::Dialog];
+}
+
+// Serialized format for the keybindings file. Actions listed at the top level are bound in the map
+// context, and each other context has its own optional section
+#[derive(Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct KeybindingsFile {
+    #[serde(flatten)]
+    pub map: Keybindings,
+    #[serde(default)]
+    pub menu: Keybindings,
+    #[serde(default)]
+    pub dialog: Keybindings,
+}
+
+impl KeybindingsFile {
+    pub fn context(&self, context: Context) -> &Keybindings {
+        match context {
+            Context::Map => &self.map,
+            Context::Menu => &self.menu,
+            Context::Dialog => &self.dialog,
+        }
+    }
+
+    fn context_mut(&mut self, context: Context) -> &mut Keybindings {
+        match context {
+            Context::Map => &mut self.map,
+            Context::Menu => &mut self.menu,
+            Context::Dialog => &mut self.dialog,
+        }
+    }
+}
+
+// Names of the keys bound to each action in a single context
+#[derive(Clone, Default, serde::Serialize, serde::Deserialize)]
+#[allow(non_snake_case)]
+pub struct Keybindings {
+    #[serde(default)]
+    pub Right: Vec<String>,
+    #[serde(default)]
+    pub Left: Vec<String>,
+    #[serde(default)]
+    pub Up: Vec<String>,
+    #[serde(default)]
+    pub Down: Vec<String>,
+    #[serde(default)]
+    pub UpLeft: Vec<String>,
+    #[serde(default)]
+    pub UpRight: Vec<String>,
+    #[serde(default)]
+    pub DownLeft: Vec<String>,
+    #[serde(default)]
+    pub DownRight: Vec<String>,
+    #[serde(default)]
+    pub JumpLeft: Vec<String>,
+    #[serde(default)]
+    pub JumpRight: Vec<String>,
+    #[serde(default)]
+    pub JumpTop: Vec<String>,
+    #[serde(default)]
+    pub JumpBottom: Vec<String>,
+    #[serde(default)]
+    pub CenterView: Vec<String>,
+    #[serde(default)]
+    pub ZoomIn: Vec<String>,
+    #[serde(default)]
+    pub ZoomOut: Vec<String>,
+    #[serde(default)]
+    pub Select: Vec<String>,
+    #[serde(default)]
+    pub Cancel: Vec<String>,
+    #[serde(default)]
+    pub Menu: Vec<String>,
+    #[serde(default)]
+    pub ToggleDangerZone: Vec<String>,
+    #[serde(default)]
+    pub NextUnit: Vec<String>,
+    #[serde(default)]
+    pub FastModifier: Vec<String>,
+    #[serde(default)]
+    pub ReloadMap: Vec<String>,
+    #[serde(default)]
+    pub NextMap: Vec<String>,
+    #[serde(default)]
+    pub ToggleMute: Vec<String>,
+    #[serde(default)]
+    pub ToggleDebugOverlay: Vec<String>,
+    #[serde(default)]
+    pub ToggleGrid: Vec<String>,
+    #[serde(default)]
+    pub Screenshot: Vec<String>,
+    #[serde(default)]
+    pub ExportMap: Vec<String>,
+    #[serde(default)]
+    pub CopyPosition: Vec<String>,
+    #[serde(default)]
+    pub ToggleEditor: Vec<String>,
+    #[serde(default)]
+    pub SetBookmark1: Vec<String>,
+    #[serde(default)]
+    pub SetBookmark2: Vec<String>,
+    #[serde(default)]
+    pub SetBookmark3: Vec<String>,
+    #[serde(default)]
+    pub GoToBookmark1: Vec<String>,
+    #[serde(default)]
+    pub GoToBookmark2: Vec<String>,
+    #[serde(default)]
+    pub GoToBookmark3: Vec<String>,
+}
+
+impl Keybindings {
+    pub fn keys(&self, action: Action) -> &[String] {
+        match action {
+            Action::Right => &self.Right,
+            Action::Left => &self.Left,
+            Action::Up => &self.Up,
+            Action::Down => &self.Down,
+            Action::UpLeft => &self.UpLeft,
+            Action::UpRight => &self.UpRight,
+            Action::DownLeft => &self.DownLeft,
+            Action::DownRight => &self.DownRight,
+            Action::JumpLeft => &self.JumpLeft,
+            Action::JumpRight => &self.JumpRight,
+            Action::JumpTop => &self.JumpTop,
+            Action::JumpBottom => &self.JumpBottom,
+            Action::CenterView => &self.CenterView,
+            Action::ZoomIn => &self.ZoomIn,
+            Action::ZoomOut => &self.ZoomOut,
+            Action::Select => &self.Select,
+            Action::Cancel => &self.Cancel,
+            Action::Menu => &self.Menu,
+            Action::ToggleDangerZone => &self.ToggleDangerZone,
+            Action::NextUnit => &self.NextUnit,
+            Action::FastModifier => &self.FastModifier,
+            Action::ReloadMap => &self.ReloadMap,
+            Action::NextMap => &self.NextMap,
+            Action::ToggleMute => &self.ToggleMute,
+            Action::ToggleDebugOverlay => &self.ToggleDebugOverlay,
+            Action::ToggleGrid => &self.ToggleGrid,
+            Action::Screenshot => &self.Screenshot,
+            Action::ExportMap => &self.ExportMap,
+            Action::CopyPosition => &self.CopyPosition,
+            Action::ToggleEditor => &self.ToggleEditor,
+            Action::SetBookmark1 => &self.SetBookmark1,
+            Action::SetBookmark2 => &self.SetBookmark2,
+            Action::SetBookmark3 => &self.SetBookmark3,
+            Action::GoToBookmark1 => &self.GoToBookmark1,
+            Action::GoToBookmark2 => &self.GoToBookmark2,
+            Action::GoToBookmark3 => &self.GoToBookmark3,
+        }
+    }
+
+    fn keys_mut(&mut self, action: Action) -> &mut Vec<String> {
+        match action {
+            Action::Right => &mut self.Right,
+            Action::Left => &mut self.Left,
+            Action::Up => &mut self.Up,
+            Action::Down => &mut self.Down,
+            Action::UpLeft => &mut self.UpLeft,
+            Action::UpRight => &mut self.UpRight,
+            Action::DownLeft => &mut self.DownLeft,
+            Action::DownRight => &mut self.DownRight,
+            Action::JumpLeft => &mut self.JumpLeft,
+            Action::JumpRight => &mut self.JumpRight,
+            Action::JumpTop => &mut self.JumpTop,
+            Action::JumpBottom => &mut self.JumpBottom,
+            Action::CenterView => &mut self.CenterView,
+            Action::ZoomIn => &mut self.ZoomIn,
+            Action::ZoomOut => &mut self.ZoomOut,
+            Action::Select => &mut self.Select,
+            Action::Cancel => &mut self.Cancel,
+            Action::Menu => &mut self.Menu,
+            Action::ToggleDangerZone => &mut self.ToggleDangerZone,
+            Action::NextUnit => &mut self.NextUnit,
+            Action::FastModifier => &mut self.FastModifier,
+            Action::ReloadMap => &mut self.ReloadMap,
+            Action::NextMap => &mut self.NextMap,
+            Action::ToggleMute => &mut self.ToggleMute,
+            Action::ToggleDebugOverlay => &mut self.ToggleDebugOverlay,
+            Action::ToggleGrid => &mut self.ToggleGrid,
+            Action::Screenshot => &mut self.Screenshot,
+            Action::ExportMap => &mut self.ExportMap,
+            Action::CopyPosition => &mut self.CopyPosition,
+            Action::ToggleEditor => &mut self.ToggleEditor,
+            Action::SetBookmark1 => &mut self.SetBookmark1,
+            Action::SetBookmark2 => &mut self.SetBookmark2,
+            Action::SetBookmark3 => &mut self.SetBookmark3,
+            Action::GoToBookmark1 => &mut self.GoToBookmark1,
+            Action::GoToBookmark2 => &mut self.GoToBookmark2,
+            Action::GoToBookmark3 => &mut self.GoToBookmark3,
+        }
+    }
+}
+
+// Table for looking up which action an input is bound to
+pub struct Bindings<I> {
+    keybindings: KeybindingsFile,
+    tables: HashMap<Context, HashMap<I, Action>>,
+}
+
+impl<I: Eq + std::hash::Hash + Clone + ToString> Bindings<I> {
+    // Builds the table from keybindings, using to_input to convert key names into inputs
+    pub fn new<F: Fn(String) -> I>(keybindings: KeybindingsFile, to_input: F) -> Bindings<I> {
+        let mut tables = HashMap::new();
+        for &context in Context::ALL.iter() {
+            let mut table = HashMap::new();
+            for &action in Action::ALL.iter() {
+                for k in keybindings.context(context).keys(action) {
+                    table.insert(to_input(k.clone()), action);
+                }
+            }
+            tables.insert(context, table);
+        }
+        Bindings {
+            keybindings,
+            tables,
+        }
+    }
+
+    // Gets the action an input is bound to, checking each context from the top of the stack down
+    // and then falling back to the map context
+    pub fn get(&self, contexts: &[Context], input: &I) -> Option<Action> {
+        contexts
+            .iter()
+            .rev()
+            .chain(std::iter::once(&Context::Map))
+            .find_map(|c| self.tables.get(c)?.get(input).copied())
+    }
+
+    // Gets the name of the first key bound to an action, checking contexts in the same order as get.
+    // Keys that a context higher up the stack binds to something else are skipped, since pressing
+    // them wouldn't trigger the action. None if no key triggers it
+    pub fn first_key(&self, contexts: &[Context], action: Action) -> Option<&str> {
+        let stack: Vec<_> = contexts
+            .iter()
+            .rev()
+            .copied()
+            .chain(std::iter::once(Context::Map))
+            .collect();
+        let shadowed = |key: &String, above: &[Context]| {
+            above.iter().any(|&context| {
+                let keybindings = self.keybindings.context(context);
+                Action::ALL
+                    .iter()
+                    .any(|&other| other != action && keybindings.keys(other).contains(key))
+            })
+        };
+        stack.iter().enumerate().find_map(|(i, &context)| {
+            let keys = self.keybindings.context(context).keys(action);
+            keys.iter()
+                .find(|key| !shadowed(key, &stack[..i]))
+                .map(String::as_str)
+        })
+    }
+
+    // Makes an input the only one bound to an action within a context
+    pub fn rebind(&mut self, context: Context, action: Action, input: I) {
+        let name = input.to_string();
+        let keybindings = self.keybindings.context_mut(context);
+        for &a in Action::ALL.iter() {
+            keybindings.keys_mut(a).retain(|k| *k != name);
+        }
+        *keybindings.keys_mut(action) = vec![name];
+        if let Some(table) = self.tables.get_mut(&context) {
+            table.retain(|_, a| *a != action);
+            table.insert(input, action);
+        }
+    }
+
+    // Gets the names of the keys bound to each action
+    pub fn keybindings(&self) -> &KeybindingsFile {
+        &self.keybindings
+    }
+}
+
+// Gets how a key is shown to the player, which is shorter than its name for keys with a symbol
+pub fn key_label(name: &str) -> &str {
+    match name {
+        "ArrowLeft" => "\u{2190}",
+        "ArrowUp" => "\u{2191}",
+        "ArrowRight" => "\u{2192}",
+        "ArrowDown" => "\u{2193}",
+        " " => "Space",
+        "Escape" => "Esc",
+        _ => name,
+    }
+}
+
+// Retrieves the bindings saved by the player, falling back to the keybindings file for the locale
+// and then to having no bindings
+pub async fn load<P: Platform>(
+    platform: &P,
+    manifest: &fetch::Manifest,
+    locale: &str,
+) -> Bindings<P::InputType> {
+    Bindings::new(
+        load_keybindings(platform, manifest, locale).await,
+        P::string_to_input,
+    )
+}
+
+async fn load_keybindings<P: Platform>(
+    platform: &P,
+    manifest: &fetch::Manifest,
+    locale: &str,
+) -> KeybindingsFile {
+    if let Some(stored) = platform.retrieve(STORAGE_KEY) {
+        match serde_json::from_str(stored.as_str()) {
+            Ok(k) => return k,
+            Err(e) => log::warn::<P>(format!("Ignoring saved keybindings: {}", e).as_str()),
+        }
+    }
+    let path = platform.keybindings_path(locale);
+    let mut file = match fetch::fetch_with_retry(platform, manifest, path.as_str()).await {
+        Ok(f) => f,
+        Err(e) => {
+            log::warn::<P>(format!("Failed to load keybindings: {}", e).as_str());
+            return KeybindingsFile::default();
+        }
+    };
+    let decoded = serde_json::from_reader(&mut file);
+    if let Err(e) = file.finish() {
+        log::warn::<P>(format!("Failed to load keybindings: {}", e).as_str());
+        return KeybindingsFile::default();
+    }
+    decoded.unwrap_or_else(|e| {
+        log::warn::<P>(format!("Failed to load keybindings: invalid {}: {}", path, e).as_str());
+        KeybindingsFile::default()
+    })
+}
+
+// Saves the bindings so that they're used by later games
+pub fn save<P: Platform>(platform: &P, bindings: &Bindings<P::InputType>) {
+    match serde_json::to_string(bindings.keybindings()) {
+        Ok(serialized) => platform.store(STORAGE_KEY, serialized.as_str()),
+        Err(e) => log::warn::<P>(format!("Failed to save keybindings: {}", e).as_str()),
+    }
+}
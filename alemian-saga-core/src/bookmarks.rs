@@ -0,0 +1,48 @@
+use std::collections::HashMap;
+
+use crate::game::ViewPosition;
+use crate::{log, Platform};
+
+// Key under which bookmarks are stored by the platform
+pub const STORAGE_KEY: &str = "bookmarks";
+// Number of bookmarks that each map has
+pub const COUNT: usize = 3;
+
+// Bookmarks of a single map by number, starting from 0 (None for bookmarks that haven't been set)
+pub type MapBookmarks = [Option<ViewPosition>; COUNT];
+
+// Bookmarks of each map by path relative to the language directory
+pub type Bookmarks = HashMap<String, MapBookmarks>;
+
+// Retrieves the bookmarks of every map (none if nothing has been saved)
+pub fn load<P: Platform>(platform: &P) -> Bookmarks {
+    let stored = match platform.retrieve(STORAGE_KEY) {
+        Some(s) => s,
+        None => return Bookmarks::new(),
+    };
+    serde_json::from_str(stored.as_str()).unwrap_or_else(|e| {
+        log::warn::<P>(format!("Ignoring saved bookmarks: {}", e).as_str());
+        Bookmarks::new()
+    })
+}
+
+pub fn save<P: Platform>(platform: &P, bookmarks: &Bookmarks) {
+    match serde_json::to_string(bookmarks) {
+        Ok(serialized) => platform.store(STORAGE_KEY, serialized.as_str()),
+        Err(e) => log::warn::<P>(format!("Failed to save bookmarks: {}", e).as_str()),
+    }
+}
+
+// Gets one of a map's bookmarks (None if it hasn't been set)
+pub fn get<P: Platform>(platform: &P, map: &str, index: usize) -> Option<ViewPosition> {
+    *load(platform).get(map)?.get(index)?
+}
+
+// Sets one of a map's bookmarks, keeping the other bookmarks of every map
+pub fn set<P: Platform>(platform: &P, map: &str, index: usize, position: ViewPosition) {
+    let mut bookmarks = load(platform);
+    if let Some(bookmark) = bookmarks.entry(map.to_owned()).or_default().get_mut(index) {
+        *bookmark = Some(position);
+    }
+    save(platform, &bookmarks);
+}
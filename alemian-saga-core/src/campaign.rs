@@ -0,0 +1,115 @@
+use crate::{fetch, log, Error, Platform};
+
+// Path of the campaign file relative to the language directory
+pub const CAMPAIGN_PATH: &str = "campaign.json";
+// Key under which the map the player is on is stored by the platform
+pub const STORAGE_KEY: &str = "campaign_progress";
+// Map played when there's no campaign file
+const DEFAULT_MAP: &str = "map.map";
+
+// Serialized format for the campaign file
+#[derive(Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct CampaignFile {
+    // Paths of the maps relative to the language directory, in the order they're played
+    pub maps: Vec<String>,
+}
+
+// Maps that are played one after another, along with how far the player has gotten
+pub struct Campaign {
+    // Never empty
+    maps: Vec<String>,
+    chapter: usize,
+}
+
+impl Campaign {
+    // Starts a campaign at the first map (falling back to the default map if there are no maps)
+    pub fn new(maps: Vec<String>) -> Campaign {
+        let maps = if maps.is_empty() {
+            vec![DEFAULT_MAP.to_owned()]
+        } else {
+            maps
+        };
+        Campaign { maps, chapter: 0 }
+    }
+
+    // Gets the path of the map being played relative to the language directory
+    pub fn current(&self) -> &str {
+        self.maps[self.chapter].as_str()
+    }
+
+    // Gets the index of the map being played
+    pub fn chapter(&self) -> usize {
+        self.chapter
+    }
+
+    pub fn len(&self) -> usize {
+        self.maps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.maps.is_empty()
+    }
+
+    // Moves on to the next map, returning false if the last map was being played
+    pub fn advance(&mut self) -> bool {
+        if self.chapter + 1 < self.maps.len() {
+            self.chapter += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    // Goes back to the first map
+    pub fn restart(&mut self) {
+        self.chapter = 0;
+    }
+
+    // Moves to a map of the campaign, returning false if the campaign doesn't have it
+    pub fn go_to(&mut self, map: &str) -> bool {
+        match self.maps.iter().position(|m| m == map) {
+            Some(chapter) => {
+                self.chapter = chapter;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+// Retrieves the campaign file for a language and picks up at the map the player was last on. Plays
+// just the default map if there's no campaign file
+pub async fn load<P: Platform>(
+    platform: &P,
+    manifest: &fetch::Manifest,
+    language: &str,
+) -> Campaign {
+    let path = format!("{}/{}", language, CAMPAIGN_PATH);
+    let fetched = fetch::fetch_with_retry(platform, manifest, path.as_str()).await;
+    let decoded = fetched.and_then(|mut file| {
+        let decoded = serde_json::from_reader(&mut file);
+        file.finish().map(|_| decoded)
+    });
+    let campaign_file = match decoded {
+        Ok(decoded) => decoded.unwrap_or_else(|e| {
+            log::warn::<P>(format!("Playing a single map: invalid {}: {}", path, e).as_str());
+            CampaignFile::default()
+        }),
+        Err(Error::MissingAsset(_)) => CampaignFile::default(),
+        Err(e) => {
+            log::warn::<P>(format!("Playing a single map: {}", e).as_str());
+            CampaignFile::default()
+        }
+    };
+    let mut campaign = Campaign::new(campaign_file.maps);
+    // The map is saved rather than its index so that progress survives maps being added
+    if let Some(saved) = platform.retrieve(STORAGE_KEY) {
+        campaign.go_to(saved.as_str());
+    }
+    campaign
+}
+
+// Saves which map the player is on so that the campaign picks up there next time
+pub fn save<P: Platform>(platform: &P, campaign: &Campaign) {
+    platform.store(STORAGE_KEY, campaign.current());
+}
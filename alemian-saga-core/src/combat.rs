@@ -0,0 +1,118 @@
+use crate::serialization::{TileType, Unit};
+use crate::Vector;
+
+// How much faster than its opponent a unit has to be to strike twice
+pub const DOUBLE_STRIKE_SPEED: u32 = 4;
+
+// What one side of a fight is going to do
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Strikes {
+    // Hp that each of the unit's strikes takes from its opponent
+    pub damage: u32,
+    // Number of times the unit strikes (0 if it can't reach its opponent)
+    pub hits: u32,
+    // Defense that the tile the unit is standing on adds (negative for tiles that leave it exposed)
+    pub terrain_defense: i32,
+}
+
+// What both sides of a fight are going to do, which is shown before an attack is confirmed and
+// then played out by resolve
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Forecast {
+    pub attacker: Strikes,
+    pub defender: Strikes,
+}
+
+// What happened when one unit attacked another
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Outcome {
+    // Hp the attacker took from the defender
+    pub damage_dealt: u32,
+    // Hp the defender took back from the attacker
+    pub damage_taken: u32,
+    pub defender_killed: bool,
+    pub attacker_killed: bool,
+}
+
+// Gets how much hp a strike from the attacker takes from the defender, with the defense of the
+// defender's tile added to its own
+pub fn damage(attacker: &Unit, defender: &Unit, terrain_defense: i32) -> u32 {
+    let defense = (defender.defense as i64 + terrain_defense as i64).max(0);
+    (attacker.strength as i64 - defense).max(0) as u32
+}
+
+// Gets how many times a unit strikes its opponent when it can reach it
+fn hits(unit: &Unit, opponent: &Unit) -> u32 {
+    if unit.speed >= opponent.speed.saturating_add(DOUBLE_STRIKE_SPEED) {
+        2
+    } else {
+        1
+    }
+}
+
+// Works out what each side of a fight will do, given the tiles they're standing on and the offset
+// from the attacker to the defender. Returns None if the attacker's weapon can't reach the defender.
+// The defender only strikes back if its weapon can reach the attacker
+pub fn forecast(
+    attacker: &Unit,
+    attacker_tile: &TileType,
+    defender: &Unit,
+    defender_tile: &TileType,
+    offset: Vector<i64>,
+) -> Option<Forecast> {
+    if !attacker.weapon_range().contains(offset) {
+        return None;
+    }
+    let back = Vector {
+        x: -offset.x,
+        y: -offset.y,
+    };
+    let defender_hits = if defender.weapon_range().contains(back) {
+        hits(defender, attacker)
+    } else {
+        0
+    };
+    Some(Forecast {
+        attacker: Strikes {
+            damage: damage(attacker, defender, defender_tile.defense),
+            hits: hits(attacker, defender),
+            terrain_defense: attacker_tile.defense,
+        },
+        defender: Strikes {
+            damage: damage(defender, attacker, attacker_tile.defense),
+            hits: defender_hits,
+            terrain_defense: defender_tile.defense,
+        },
+    })
+}
+
+// Takes a strike's damage from a unit, returning how much hp it actually lost
+fn strike(damage: u32, target: &mut Unit) -> u32 {
+    let lost = damage.min(target.hp);
+    target.hp -= lost;
+    lost
+}
+
+// Plays out a forecast fight. The two sides take turns striking, the attacker first, until both
+// have run out of strikes or one of them dies
+pub fn resolve(attacker: &mut Unit, defender: &mut Unit, forecast: &Forecast) -> Outcome {
+    let mut outcome = Outcome::default();
+    let mut attacker_hits = forecast.attacker.hits;
+    let mut defender_hits = forecast.defender.hits;
+    while attacker_hits + defender_hits > 0 && attacker.hp > 0 && defender.hp > 0 {
+        if attacker_hits > 0 {
+            attacker_hits -= 1;
+            outcome.damage_dealt += strike(forecast.attacker.damage, defender);
+            if defender.hp == 0 {
+                break;
+            }
+        }
+        if defender_hits > 0 {
+            defender_hits -= 1;
+            outcome.damage_taken += strike(forecast.defender.damage, attacker);
+        }
+    }
+    outcome.defender_killed = defender.hp == 0;
+    outcome.attacker_killed = attacker.hp == 0;
+    outcome
+}
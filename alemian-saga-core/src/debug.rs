@@ -0,0 +1,87 @@
+use std::cell::Cell;
+use std::collections::VecDeque;
+
+use crate::Platform;
+
+// Length of time that frames are counted over to work out frames per second
+const FPS_WINDOW_NS: u64 = 1_000_000_000;
+// Shortest time between log lines about events that didn't fit in the event queue
+const OVERFLOW_LOG_INTERVAL_NS: u64 = 10_000_000_000;
+
+// Counts what's drawn each frame so that it can be shown on the debug overlay
+pub struct FrameStats<P: Platform> {
+    // Draw calls made since the last frame ended
+    draw_calls: Cell<u32>,
+    // Draw calls made during the last frame
+    last_draw_calls: u32,
+    // When each frame that ended within the last FPS_WINDOW_NS ended, oldest first
+    frame_ends: VecDeque<P::Instant>,
+    // Number of overflowed events that have been logged so far, and when they last were
+    logged_overflows: u64,
+    last_overflow_log: Option<P::Instant>,
+}
+
+impl<P: Platform> Default for FrameStats<P> {
+    fn default() -> FrameStats<P> {
+        FrameStats {
+            draw_calls: Cell::new(0),
+            last_draw_calls: 0,
+            frame_ends: VecDeque::new(),
+            logged_overflows: 0,
+            last_overflow_log: None,
+        }
+    }
+}
+
+impl<P: Platform> FrameStats<P> {
+    // Counts a call to one of the platform's drawing functions
+    pub fn count_draw(&self) {
+        self.draw_calls.set(self.draw_calls.get() + 1);
+    }
+
+    // Ends the current frame, returning false (and leaving the stats alone) if nothing was drawn
+    // since the last one ended
+    pub fn end_frame(&mut self, now: P::Instant) -> bool {
+        let draw_calls = self.draw_calls.replace(0);
+        if draw_calls == 0 {
+            return false;
+        }
+        self.last_draw_calls = draw_calls;
+        self.frame_ends.push_back(now);
+        let window = P::nanoseconds(FPS_WINDOW_NS);
+        while let Some(&end) = self.frame_ends.front() {
+            if P::duration_between(end, now) <= window {
+                break;
+            }
+            self.frame_ends.pop_front();
+        }
+        true
+    }
+
+    // Gets the number of frames that ended within the last second
+    pub fn fps(&self) -> usize {
+        self.frame_ends.len()
+    }
+
+    // Gets the number of draw calls made during the last frame
+    pub fn draw_calls(&self) -> u32 {
+        self.last_draw_calls
+    }
+
+    // Gets how many events have overflowed the event queue since they were last logged (out of
+    // total so far), or None if there aren't any or they were logged too recently
+    pub fn overflows_to_log(&mut self, total: u64, now: P::Instant) -> Option<u64> {
+        if total <= self.logged_overflows {
+            return None;
+        }
+        if let Some(last) = self.last_overflow_log {
+            if P::duration_between(last, now) < P::nanoseconds(OVERFLOW_LOG_INTERVAL_NS) {
+                return None;
+            }
+        }
+        let new = total - self.logged_overflows;
+        self.logged_overflows = total;
+        self.last_overflow_log = Some(now);
+        Some(new)
+    }
+}
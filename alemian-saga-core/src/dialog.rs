@@ -0,0 +1,98 @@
+use std::collections::VecDeque;
+
+use crate::{serialization, style, text, Platform};
+
+// Messages waiting to be shown in the dialog box along with how far the player has read
+pub struct Dialog {
+    // Starts with the message being shown
+    messages: VecDeque<serialization::Message>,
+    // Page of the current message that's being shown
+    page: usize,
+}
+
+impl Dialog {
+    pub fn new() -> Dialog {
+        Dialog {
+            messages: VecDeque::new(),
+            page: 0,
+        }
+    }
+
+    // Adds a message to be shown after the ones already waiting
+    pub fn push(&mut self, message: serialization::Message) {
+        self.messages.push_back(message);
+    }
+
+    // Gets the message being shown (None if the dialog box is closed)
+    pub fn current(&self) -> Option<&serialization::Message> {
+        self.messages.front()
+    }
+
+    // Gets the page of the current message being shown, given how many pages it takes up
+    pub fn page(&self, page_count: usize) -> usize {
+        self.page.min(page_count.saturating_sub(1))
+    }
+
+    // Moves on to the next page, or the next message after the last page
+    pub fn advance(&mut self, page_count: usize) {
+        if self.page + 1 < page_count {
+            self.page += 1;
+        } else {
+            self.messages.pop_front();
+            self.page = 0;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+}
+
+impl Default for Dialog {
+    fn default() -> Dialog {
+        Dialog::new()
+    }
+}
+
+// Splits text into lines that fit within max_width, breaking between words. Newlines always start
+// a new line, and words too wide to fit (or text in languages that aren't written with spaces) are
+// broken between grapheme clusters
+pub fn wrap_text<P: Platform>(
+    platform: &P,
+    text: &str,
+    max_width: P::ScreenDistance,
+    style: &style::TextStyle,
+) -> Vec<String> {
+    let fits = |line: &str| platform.measure_text(line, style) <= max_width;
+    let mut lines = Vec::new();
+    for paragraph in text.split('\n') {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let candidate = if line.is_empty() {
+                word.to_owned()
+            } else {
+                format!("{} {}", line, word)
+            };
+            if fits(candidate.as_str()) {
+                line = candidate;
+                continue;
+            }
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            for grapheme in text::graphemes(word) {
+                let candidate = format!("{}{}", line, grapheme);
+                // Each line gets at least one grapheme even if it's too wide on its own
+                if line.is_empty() || fits(candidate.as_str()) {
+                    line = candidate;
+                } else {
+                    lines.push(std::mem::replace(&mut line, grapheme.to_owned()));
+                }
+            }
+        }
+        if !line.is_empty() {
+            lines.push(line);
+        }
+    }
+    lines
+}
@@ -0,0 +1,94 @@
+use ndarray::Array2;
+
+use crate::game::MapDistance;
+use crate::{pathfinding, Vector};
+
+// Widths of the square brushes that tiles can be painted with
+pub const BRUSH_SIZES: [MapDistance; 3] = [1, 3, 5];
+
+// How the editor paints tiles
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tool {
+    // Paints a square of tiles this many tiles wide centered on the cursor
+    Brush(MapDistance),
+    // Paints every tile joined to the one under the cursor by tiles of the same type
+    Fill,
+}
+
+// Tiles changed by a single paint, as the position and previous type of each
+pub type Stroke = Vec<(Vector<MapDistance>, u32)>;
+
+// Map editor state, while tiles are being painted onto the map
+pub struct Editor {
+    // Index into tile_types of the type that tiles are painted with
+    pub tile_type: u32,
+    pub tool: Tool,
+    // Each stroke painted so far, starting from the first, so that they can be undone one at a time
+    pub undo: Vec<Stroke>,
+}
+
+impl Default for Editor {
+    fn default() -> Editor {
+        Editor {
+            tile_type: 0,
+            tool: Tool::Brush(1),
+            undo: Vec::new(),
+        }
+    }
+}
+
+// Gets the tiles under a square brush of a given width centered on a position, leaving out any that
+// are off the edge of the map
+pub fn brush_area(
+    center: Vector<MapDistance>,
+    width: MapDistance,
+    map_size: Vector<MapDistance>,
+) -> impl Iterator<Item = Vector<MapDistance>> {
+    let reach = width / 2;
+    let left = center.x.saturating_sub(reach);
+    let top = center.y.saturating_sub(reach);
+    let right = center
+        .x
+        .saturating_add(reach)
+        .min(map_size.x.saturating_sub(1));
+    let bottom = center
+        .y
+        .saturating_add(reach)
+        .min(map_size.y.saturating_sub(1));
+    (top..=bottom).flat_map(move |y| (left..=right).map(move |x| Vector { x, y }))
+}
+
+// Gets the tiles that filling from a position with a tile type would change: the position and every
+// tile joined to it by tiles of its type, not counting diagonals. Nothing changes if the position
+// already has that type. The region is grown from a list of tiles to visit rather than by
+// recursing, so that filling a huge region can't overflow the stack
+pub fn flood_fill(
+    map: &Array2<u32>,
+    start: Vector<MapDistance>,
+    tile_type: u32,
+) -> Vec<Vector<MapDistance>> {
+    let (rows, columns) = map.dim();
+    let map_size = Vector {
+        x: columns as MapDistance,
+        y: rows as MapDistance,
+    };
+    let index = |p: Vector<MapDistance>| [p.y as usize, p.x as usize];
+    let replaced = match map.get(index(start)) {
+        Some(&t) if t != tile_type => t,
+        _ => return Vec::new(),
+    };
+    let mut seen = Array2::from_elem(map.dim(), false);
+    seen[index(start)] = true;
+    let mut region = Vec::new();
+    let mut to_visit = vec![start];
+    while let Some(pos) = to_visit.pop() {
+        region.push(pos);
+        for next in pathfinding::neighbors(pos, map_size) {
+            if !seen[index(next)] && map[index(next)] == replaced {
+                seen[index(next)] = true;
+                to_visit.push(next);
+            }
+        }
+    }
+    region
+}
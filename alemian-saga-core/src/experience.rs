@@ -0,0 +1,93 @@
+use crate::combat::Outcome;
+use crate::rng::Rng;
+use crate::serialization::Unit;
+
+// Experience needed to go up a level, which starts over from zero afterwards
+pub const LEVEL_UP_EXP: u32 = 100;
+// Experience for taking part in a fight at all
+const BASE_EXP: u32 = 10;
+// Extra experience for killing the defender
+const KILL_EXP: u32 = 30;
+// Chance of each stat going up on a level up, as a numerator and denominator
+const GROWTH_CHANCE: (u64, u64) = (1, 2);
+
+// Stats that can go up on a level up
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stat {
+    MaxHp,
+    Strength,
+    Defense,
+    Speed,
+}
+
+impl Stat {
+    // In the order they're rolled and listed
+    pub const ALL: [Stat; 4] = [Stat::MaxHp, Stat::Strength, Stat::Defense, Stat::Speed];
+
+    // Gets the string key of the stat's name
+    pub fn key(self) -> &'static str {
+        match self {
+            Stat::MaxHp => "stat.hp",
+            Stat::Strength => "stat.strength",
+            Stat::Defense => "stat.defense",
+            Stat::Speed => "stat.speed",
+        }
+    }
+
+    fn get_mut(self, unit: &mut Unit) -> &mut u32 {
+        match self {
+            Stat::MaxHp => &mut unit.max_hp,
+            Stat::Strength => &mut unit.strength,
+            Stat::Defense => &mut unit.defense,
+            Stat::Speed => &mut unit.speed,
+        }
+    }
+}
+
+// Levels a unit went up by at once, along with how much each stat that went up rose by
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LevelUp {
+    pub levels: u32,
+    pub gains: Vec<(Stat, u32)>,
+}
+
+// Gets the experience the attacker earned in a fight: some for fighting, one for each point of
+// damage dealt and more for a kill. Attackers that died earn nothing
+pub fn earned(outcome: &Outcome) -> u32 {
+    if outcome.attacker_killed {
+        return 0;
+    }
+    let kill = if outcome.defender_killed { KILL_EXP } else { 0 };
+    BASE_EXP + outcome.damage_dealt + kill
+}
+
+// Gives a unit experience, levelling it up for every LEVEL_UP_EXP it reaches. Each stat has a
+// chance of going up by one on each level up (max hp going up heals the unit by as much). Returns
+// None if the unit didn't go up a level, and gives dead units nothing
+pub fn award(unit: &mut Unit, exp: u32, rng: &mut Rng) -> Option<LevelUp> {
+    if unit.hp == 0 {
+        return None;
+    }
+    unit.exp = unit.exp.saturating_add(exp);
+    let mut level_up = LevelUp::default();
+    while unit.exp >= LEVEL_UP_EXP {
+        unit.exp -= LEVEL_UP_EXP;
+        unit.level += 1;
+        level_up.levels += 1;
+        for stat in Stat::ALL {
+            if !rng.chance(GROWTH_CHANCE.0, GROWTH_CHANCE.1) {
+                continue;
+            }
+            *stat.get_mut(unit) += 1;
+            if stat == Stat::MaxHp {
+                unit.hp += 1;
+            }
+            match level_up.gains.iter_mut().find(|(s, _)| *s == stat) {
+                Some((_, gain)) => *gain += 1,
+                None => level_up.gains.push((stat, 1)),
+            }
+        }
+    }
+    level_up.gains.sort();
+    (level_up.levels > 0).then_some(level_up)
+}
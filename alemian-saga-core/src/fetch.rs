@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+use std::io::Read;
+
+use futures::future::{self, Either};
+
+use crate::{log, sha256, Error, Platform};
+
+// Path of the manifest relative to the asset root
+pub const MANIFEST_PATH: &str = "manifest.json";
+
+// Policy describing how failed file retrievals are retried
+#[derive(Clone, Copy)]
+pub struct RetryPolicy {
+    // Maximum number of times a file will be requested
+    pub attempts: u32,
+    // Time to wait before the first retry; each later retry waits twice as long as the last
+    pub initial_backoff_ns: u64,
+    // Time to wait for a single request before giving up on it
+    pub timeout_ns: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            attempts: 4,
+            initial_backoff_ns: 250_000_000,
+            timeout_ns: 10_000_000_000,
+        }
+    }
+}
+
+// Serialized format for the manifest, which lists the hashes that assets should have so that
+// files that were cut short or mangled on the way are caught before they're used
+#[derive(Default, serde::Deserialize)]
+pub struct Manifest {
+    // SHA-256 hash (in hex) of each listed asset by path. Assets that aren't listed aren't checked
+    #[serde(default)]
+    sha256: HashMap<String, String>,
+}
+
+// Retrieves the manifest, falling back to checking nothing if there isn't one
+pub async fn load_manifest<P: Platform>(platform: &P) -> Manifest {
+    let file = match fetch_with_retry(platform, &Manifest::default(), MANIFEST_PATH).await {
+        Ok(f) => f,
+        Err(Error::MissingAsset(_)) => return Manifest::default(),
+        Err(e) => {
+            log::warn::<P>(format!("Not checking assets: {}", e).as_str());
+            return Manifest::default();
+        }
+    };
+    let mut manifest: Manifest = serde_json::from_reader(file).unwrap_or_else(|e| {
+        log::warn::<P>(format!("Not checking assets: invalid {}: {}", MANIFEST_PATH, e).as_str());
+        Manifest::default()
+    });
+    for hash in manifest.sha256.values_mut() {
+        hash.make_ascii_lowercase();
+    }
+    manifest
+}
+
+// A retrieved file that's hashed as it's read, so that it can be checked against the manifest
+// without a second copy of it being kept around
+pub struct Checked<R> {
+    file: R,
+    path: String,
+    // Hash that the manifest lists for the file along with the hash of what's been read so far
+    // (None if the file isn't listed)
+    check: Option<(String, sha256::Sha256)>,
+}
+
+impl<R: Read> Read for Checked<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.file.read(buf)?;
+        if let Some((_, hasher)) = self.check.as_mut() {
+            hasher.update(&buf[..read]);
+        }
+        Ok(read)
+    }
+}
+
+impl<R: Read> Checked<R> {
+    // Reads whatever is left of the file and checks that the whole thing has the hash the manifest
+    // lists for it. Called once the file has been decoded, since a file that doesn't match is
+    // the more useful thing to report than whatever decoding it went wrong
+    pub fn finish(mut self) -> Result<(), Error> {
+        let (expected, mut hasher) = match self.check.take() {
+            Some(c) => c,
+            None => return Ok(()),
+        };
+        let mut buf = [0; 4096];
+        loop {
+            match self.file.read(&mut buf) {
+                Ok(0) => break,
+                Ok(read) => hasher.update(&buf[..read]),
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    return Err(Error::Network {
+                        path: self.path,
+                        source_msg: e.to_string(),
+                    })
+                }
+            }
+        }
+        let actual = sha256::to_hex(&hasher.finish());
+        if actual == expected {
+            Ok(())
+        } else {
+            Err(Error::ChecksumMismatch {
+                path: self.path,
+                expected,
+                actual,
+            })
+        }
+    }
+}
+
+// Retrieves a file, retrying with exponential backoff on network failures and timeouts. Call finish
+// on the file once it's been decoded to check it against the manifest
+pub async fn fetch_with_retry<P: Platform>(
+    platform: &P,
+    manifest: &Manifest,
+    path: &str,
+) -> Result<Checked<P::File>, Error> {
+    let file = fetch_unchecked(platform, path).await?;
+    Ok(Checked {
+        file,
+        path: path.to_owned(),
+        check: manifest
+            .sha256
+            .get(path)
+            .map(|hash| (hash.clone(), sha256::Sha256::default())),
+    })
+}
+
+async fn fetch_unchecked<P: Platform>(platform: &P, path: &str) -> Result<P::File, Error> {
+    let policy = platform.retry_policy();
+    let attempts = policy.attempts.max(1);
+    let mut last_error = (path.to_owned(), String::new());
+    for attempt in 0..attempts {
+        if attempt > 0 {
+            let (failed_path, source_msg) = &last_error;
+            log::debug::<P>(format!("Retrying {}: {}", failed_path, source_msg).as_str());
+            let backoff = policy
+                .initial_backoff_ns
+                .saturating_mul(2u64.saturating_pow(attempt - 1));
+            P::sleep(P::nanoseconds(backoff)).await;
+        }
+        let timeout = P::sleep(P::nanoseconds(policy.timeout_ns));
+        futures::pin_mut!(timeout);
+        match future::select(platform.get_file(path), timeout).await {
+            Either::Left((Ok(file), _)) => return Ok(file),
+            Either::Left((Err(Error::Network { path, source_msg }), _)) => {
+                last_error = (path, source_msg);
+            }
+            Either::Left((Err(e), _)) => return Err(e),
+            Either::Right(_) => last_error.1 = "request timed out".to_owned(),
+        }
+    }
+    let (path, source_msg) = last_error;
+    Err(Error::Network {
+        path,
+        source_msg: format!("{} (gave up after {} attempts)", source_msg, attempts),
+    })
+}
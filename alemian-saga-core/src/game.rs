@@ -0,0 +1,6416 @@
+use futures::{future, stream, StreamExt};
+use ndarray::prelude::*;
+use num_traits::{FromPrimitive, ToPrimitive};
+
+use crate::{
+    bindings, bookmarks, campaign, combat, debug, dialog, editor, experience, fetch, log, menu,
+    net, options, pathfinding, record, rng, roster, save, serialization, settings, sha256, sound,
+    strings, style, text, viewport, Action, Error, Event, Platform, Rectangle, Scalar, Vector,
+};
+
+const CURSOR_IMAGE: &str = "cursor.png";
+const INFO_BAR_IMAGE: &str = "infobar.png";
+// Largest size (in screen units) of a single tile on the minimap
+const MINIMAP_MAX_BLOCK: u32 = 3;
+// Largest fraction of the screen width or height that the minimap may take up
+const MINIMAP_SCREEN_FRACTION: u32 = 4;
+// Colors drawn over hidden and explored tiles when fog of war is enabled
+const FOG_HIDDEN_COLOR: style::Color = style::BLACK;
+const FOG_EXPLORED_COLOR: style::Color = style::Color {
+    alpha: 128,
+    ..style::BLACK
+};
+// String key of the text shown once the game has ended
+const GOODBYE_MESSAGE: &str = "message.goodbye";
+// String key of the text shown once a trigger has finished the map
+const MAP_COMPLETE_MESSAGE: &str = "message.map_complete";
+// String key of the text shown once the last map of a campaign has been finished
+const CAMPAIGN_COMPLETE_MESSAGE: &str = "message.campaign_complete";
+// String key of the text shown on the infobar in place of information about hidden tiles
+const HIDDEN_TILE_NAME: &str = "infobar.hidden";
+// Number of menu rows that would fill the height of the screen
+const MENU_ROWS_PER_SCREEN: u32 = 12;
+// Fraction of the screen height taken up by the dialog box
+const DIALOG_SCREEN_FRACTION: u32 = 4;
+// Number of lines of text the dialog box is divided into, including the speaker's name and half a
+// line of padding above and below
+const DIALOG_ROWS: u32 = 5;
+// Lines of message text shown on each page of the dialog box
+const DIALOG_LINES_PER_PAGE: usize = 3;
+// Time the mouse has to rest before the tooltip is shown
+const TOOLTIP_DELAY_NS: u64 = 500_000_000;
+// Number of tooltips that would fill the height of the screen if stacked
+const TOOLTIP_ROWS_PER_SCREEN: u32 = 16;
+// Longest time between two clicks on the same tile for them to count as a double click
+const DOUBLE_CLICK_NS: u64 = 400_000_000;
+// How many times faster the mouse pans the screen at the very edge of the screen than at the inner
+// boundary of the edge zone
+const EDGE_PAN_MAX_SPEEDUP: f64 = 3.0;
+// Lines of text on the unit panel below the portrait: the name, hp, hp bar and four other stats
+const UNIT_PANEL_LINES: u32 = 7;
+// Lines of text in each column of the battle forecast: the name, hp, damage, hits and terrain
+const FORECAST_LINES: u32 = 5;
+// The battle forecast's width is the screen width divided by this
+const FORECAST_SCREEN_FRACTION: u32 = 2;
+// How long the level up popup stays up if it isn't dismissed
+const LEVEL_UP_POPUP_NS: u64 = 3_000_000_000;
+// Height of each line of the level up popup as a fraction of the screen height
+const LEVEL_UP_ROWS_PER_SCREEN: u32 = 16;
+// How long toasts stay up
+const TOAST_NS: u64 = 2_000_000_000;
+// Number of toasts that would fill the height of the screen if stacked
+const TOAST_ROWS_PER_SCREEN: u32 = 16;
+// The banner's height is the screen height divided by this
+const BANNER_ROWS_PER_SCREEN: u32 = 8;
+// How long the scroll indicator stays up after the screen stops moving, and how long it then takes
+// to fade out
+const SCROLL_INDICATOR_NS: u64 = 1_000_000_000;
+const SCROLL_INDICATOR_FADE_NS: u64 = 500_000_000;
+// The scroll indicator's thickness is the screen height divided by this (but at least 1)
+const SCROLL_INDICATOR_ROWS_PER_SCREEN: u32 = 120;
+// The hint bar's height is the screen height divided by this if the platform can't measure text
+const HINT_BAR_ROWS_PER_SCREEN: u32 = 24;
+// Drawn between hints on the hint bar
+const HINT_SEPARATOR: &str = " \u{b7} ";
+// Hints listed on the hint bar in each context, as the actions whose keys are shown along with the
+// string key of what they do
+const MAP_HINTS: [(&[Action], &str); 4] = [
+    (
+        &[Action::Left, Action::Right, Action::Up, Action::Down],
+        "hint.move",
+    ),
+    (&[Action::Select], "hint.select"),
+    (&[Action::Menu], "hint.menu"),
+    (&[Action::ZoomIn, Action::ZoomOut], "hint.zoom"),
+];
+const MENU_HINTS: [(&[Action], &str); 3] = [
+    (&[Action::Up, Action::Down], "hint.choose"),
+    (&[Action::Select], "hint.select"),
+    (&[Action::Cancel], "hint.back"),
+];
+const DIALOG_HINTS: [(&[Action], &str); 1] = [(&[Action::Select], "hint.next")];
+// Keys for controlling a replay, which are listed on its transport bar
+const TRANSPORT_HINTS: [(&[Action], &str); 3] = [
+    (&[Action::Select], "hint.pause"),
+    (&[Action::Left, Action::Right], "hint.step"),
+    (&[Action::ZoomIn, Action::ZoomOut], "hint.speed"),
+];
+
+// An image along with the path it was loaded from, so that an image that failed to load can be
+// reported by name
+struct Asset<I> {
+    // None if there's no image configured
+    path: Option<String>,
+    // None if it failed to load or there's no image configured
+    image: Option<I>,
+}
+
+impl<I> Asset<I> {
+    // Whether there's an image configured that failed to load
+    fn failed(&self) -> bool {
+        self.path.is_some() && self.image.is_none()
+    }
+}
+
+// Represents a tile in the map
+struct Tile<'a, P: Platform> {
+    image: &'a Asset<P::Image>,
+    info: &'a serialization::TileType,
+}
+
+// A unit on the map along with its images
+struct Unit<I> {
+    info: serialization::Unit,
+    // Shared between units with the same sprite and team once it's been tinted
+    sprite: std::rc::Rc<Asset<I>>,
+    // Path is None if the unit has no portrait
+    portrait: Asset<I>,
+}
+
+// Type used to represent positions and distances on the map (in tiles)
+pub type MapDistance = u32;
+
+// Cheapest cost of reaching each tile that a unit can move to
+type MoveCosts = std::collections::HashMap<Vector<MapDistance>, u32>;
+
+// Time taken for the screen to scroll to a new position
+const SCROLL_DURATION_NS: u64 = 100_000_000;
+
+// Time between frames while the screen is scrolling
+pub const FRAME_INTERVAL_NS: u64 = 16_000_000;
+
+// An in-progress transition of the screen from one position to another
+struct ScrollAnimation<I> {
+    // Offset (in tiles) between where the screen was drawn and screen.top_left when the scroll began
+    from: Vector<f64>,
+    start: I,
+}
+
+// Time taken for a moving unit to go from one tile to the next along its path
+const MOVE_STEP_NS: u64 = 150_000_000;
+
+// A unit gliding along its path to where it's moving, one tile at a time
+struct MoveAnimation<I> {
+    // Index into units
+    unit: usize,
+    // Tiles along the way, starting where the unit was and ending where it's moving to
+    path: Vec<Vector<MapDistance>>,
+    start: I,
+    // Tile that the unit last stepped onto, so that each footstep is only played once
+    footstep: Option<Vector<MapDistance>>,
+}
+
+// Fraction of the way to its target that an attacker lunges in a fight
+const COMBAT_NUDGE_TILES: f64 = 0.25;
+
+// A fight being played out: the attacker lunges toward its target and back, then the damage each
+// side took floats up from its tile while its hp bar drains. The units' hp has already been taken
+// off, but experience and deaths wait until the fight has been seen
+struct CombatAnimation<I> {
+    // Indices into units
+    attacker: usize,
+    target: usize,
+    outcome: combat::Outcome,
+    // Hp that each side had before the fight, which its hp bar drains from
+    attacker_hp: u32,
+    target_hp: u32,
+    start: I,
+}
+
+// Sizes on the screen that only change when the screen is resized or zoomed
+#[derive(Clone, Copy)]
+struct Layout<T> {
+    // Size of the platform's screen that the layout was worked out for
+    screen_size: Vector<T>,
+    // Number of columns and rows of tiles that the layout was worked out for
+    visible_tiles: Vector<MapDistance>,
+    tile_size: Vector<T>,
+    // Part of the screen that tiles are drawn in. It covers the whole screen unless tiles are
+    // scaled by whole numbers of pixels, in which case the rest of the screen is letterboxed
+    viewport: Rectangle<T>,
+    // Width of the zones along the left and right and height of the zones along the top and bottom
+    // of the screen where the mouse pans
+    pan_zone: Vector<f64>,
+    infobar_size: Vector<T>,
+}
+
+// How much of a tile the player can see when fog of war is enabled
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Visibility {
+    // Never been near the cursor
+    Hidden,
+    // Has been near the cursor, but isn't anymore
+    Explored,
+    Visible,
+}
+
+// Reasons for loading a map while the game is running
+enum PendingLoad {
+    // Load the current map again, keeping the view
+    Reload,
+    // Start the next map of the campaign
+    NextMap,
+    // Pick up a saved game
+    Restore(Box<save::Snapshot>),
+}
+
+// Options on the action menu
+#[derive(Clone, Copy)]
+enum MenuAction {
+    EndTurn,
+    Save,
+    Load,
+    Options,
+    // Change an option, or open the list of locales for Setting::Language
+    Setting(Setting),
+    // Switch to the locale with this index into the locales setting
+    Locale(usize),
+    // Save the game in the slot with this index into save::SLOTS
+    SaveTo(usize),
+    // Load the game saved in the slot with this index into save::SLOTS
+    LoadFrom(usize),
+    // Load the latest autosave
+    LoadAutosave,
+    // Finish a unit's move without attacking
+    Wait,
+    // Finish a unit's move by attacking the unit with this index into units
+    Attack(usize),
+    // Paint with the tile type with this index into tile_types
+    Paint(u32),
+    // Paint with a different brush, or switch to filling
+    Tool(editor::Tool),
+    // Save the map with the tiles painted in the editor as a map file
+    SaveMapFile,
+}
+
+// Options that can be changed on the options menu with Left and Right
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Setting {
+    Language,
+    Volume,
+    Smoothing,
+    PanDelay,
+    Grid,
+}
+
+// Stat increases shown after a unit goes up a level
+struct LevelUpPopup<I> {
+    // Heading followed by one line for each stat that went up
+    lines: Vec<String>,
+    shown_at: I,
+}
+
+// Short confirmation shown at the top of the screen until it times out
+struct Toast<I> {
+    text: String,
+    shown_at: I,
+}
+
+// Parts of the screen that events have changed since the last frame, short of the whole screen.
+// Events only record them so that however many arrive before the next frame, they're drawn once
+struct Damage<D> {
+    // Map tiles to draw again
+    tiles: Vec<Vector<MapDistance>>,
+    // Parts of the screen to draw the tiles under again once whatever was on top is taken away
+    regions: Vec<Rectangle<D>>,
+    // Whether everything on top of the map has to be drawn again, e.g. once the cursor has moved
+    overlays: bool,
+    // Whether the whole minimap has to be drawn again, rather than just where it was drawn over
+    minimap: bool,
+    // Things on top of the map that have changed without anything underneath them changing
+    hint_bar: bool,
+    menu: bool,
+    dialog: bool,
+    toast: bool,
+}
+
+impl<D> Default for Damage<D> {
+    fn default() -> Self {
+        Damage {
+            tiles: Vec::new(),
+            regions: Vec::new(),
+            overlays: false,
+            minimap: false,
+            hint_bar: false,
+            menu: false,
+            dialog: false,
+            toast: false,
+        }
+    }
+}
+
+impl<D> Damage<D> {
+    fn is_empty(&self) -> bool {
+        !(self.uncovers()
+            || self.minimap
+            || self.hint_bar
+            || self.menu
+            || self.dialog
+            || self.toast)
+    }
+
+    // Whether anything under the things on top of the map is drawn again, covering them up
+    fn uncovers(&self) -> bool {
+        self.overlays || !self.tiles.is_empty() || !self.regions.is_empty()
+    }
+}
+
+// Message across the middle of the screen that stays up until it's dismissed, e.g. announcing whose
+// turn it is when players take turns
+struct Banner {
+    text: String,
+    // Whether the map is hidden behind the banner so the next player doesn't see the last player's
+    // units
+    hides_map: bool,
+    // Whether taking the banner down connects to the other player's game again
+    reconnect: bool,
+}
+
+// A unit that has been moved but whose action hasn't been chosen yet. The unit itself isn't moved
+// until then, so that the move can be taken back
+#[derive(Clone, Copy)]
+struct PendingMove {
+    // Index into units
+    unit: usize,
+    origin: Vector<MapDistance>,
+    destination: Vector<MapDistance>,
+}
+
+// Tiles that one enemy could reach on its next turn
+struct DangerZone {
+    // Tiles it could move to
+    reach: std::collections::HashSet<Vector<MapDistance>>,
+    // Tiles it could move to or attack
+    tiles: std::collections::HashSet<Vector<MapDistance>>,
+}
+
+// Row of adjacent, same-colored tiles on the minimap
+struct MinimapRun {
+    row: MapDistance,
+    column: MapDistance,
+    length: MapDistance,
+    color: style::Color,
+}
+
+// Gets the color used for a tile type on the minimap
+fn minimap_color(tile_type: &serialization::TileType) -> style::Color {
+    tile_type.minimap_color.unwrap_or_else(|| {
+        // Derive an arbitrary but consistent color from the name (FNV-1a hash)
+        let hash = tile_type.name.bytes().fold(0x811c9dc5u32, |hash, b| {
+            (hash ^ b as u32).wrapping_mul(0x01000193)
+        });
+        let [red, green, blue, _] = hash.to_le_bytes();
+        style::Color::rgb(red, green, blue)
+    })
+}
+
+// Gets the team of one of the players who take turns, by index into the settings' players
+fn player_team(settings: &settings::Settings, player: usize) -> &str {
+    settings
+        .players
+        .get(player)
+        .map_or(serialization::PLAYER_TEAM, |t| t.as_str())
+}
+
+// Gets the indices of a team's units that haven't acted yet, in the order NextUnit goes through
+// them
+fn ready_units<I>(units: &[Unit<I>], team: &str) -> Vec<usize> {
+    (0..units.len())
+        .filter(|&i| {
+            let info = &units[i].info;
+            info.team == team && !info.statuses.contains(&serialization::Status::Acted)
+        })
+        .collect()
+}
+
+// Groups each row of the map into runs of tiles with the same minimap color
+fn minimap_runs(
+    map: &Array2<u32>,
+    tile_types: &[serialization::TileType],
+    visibility: Option<&Array2<Visibility>>,
+) -> Vec<MinimapRun> {
+    let mut runs: Vec<MinimapRun> = Vec::new();
+    for ((r, c), &tile_type) in map.indexed_iter() {
+        let color = match visibility.map(|v| v[[r, c]]) {
+            Some(Visibility::Hidden) => FOG_HIDDEN_COLOR,
+            _ => minimap_color(&tile_types[tile_type as usize]),
+        };
+        match runs.last_mut() {
+            Some(run) if run.row == r as MapDistance && run.color == color => run.length += 1,
+            _ => runs.push(MinimapRun {
+                row: r as MapDistance,
+                column: c as MapDistance,
+                length: 1,
+                color,
+            }),
+        }
+    }
+    runs
+}
+
+// What the caller should do after the game has handled an event
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Redraw {
+    // Nothing changes on screen until the next event
+    Idle,
+    // Something has changed that hasn't been drawn yet, so frame should be called as soon as the
+    // platform is ready for the next frame. Events that arrive before then are handled first so
+    // that several changes are drawn together
+    Changed,
+    // The screen is scrolling, so frame should be called once the platform is ready for the next
+    // frame if no event arrives first
+    NextFrame,
+    // Something is waiting to be drawn, so frame should be called after the given number of
+    // nanoseconds if no event arrives first
+    Wait(u64),
+    // The game is over and won't handle any more events
+    Finished,
+}
+
+impl Redraw {
+    // Gets how long to wait before calling frame (None if it doesn't need to be called)
+    pub fn wait(self) -> Option<u64> {
+        match self {
+            Redraw::Changed | Redraw::NextFrame => Some(FRAME_INTERVAL_NS),
+            Redraw::Wait(ns) => Some(ns),
+            Redraw::Idle | Redraw::Finished => None,
+        }
+    }
+}
+
+// Where the cursor is and how far the screen is zoomed in, e.g. for sharing with other map authors.
+// Written as "x,y@zoom", where zoom is the number of columns of tiles on the screen
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ViewPosition {
+    pub cursor: Vector<MapDistance>,
+    pub zoom: MapDistance,
+}
+
+impl std::fmt::Display for ViewPosition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},{}@{}", self.cursor.x, self.cursor.y, self.zoom)
+    }
+}
+
+// Steps of loading the game that are shown on the loading screen
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoadingPhase {
+    FetchingMap,
+    LoadingImages,
+    // Everything is loaded and the first frame is about to be drawn
+    Starting,
+}
+
+impl LoadingPhase {
+    fn message(self) -> &'static str {
+        match self {
+            LoadingPhase::FetchingMap => "Loading…",
+            LoadingPhase::LoadingImages => "Loading images…",
+            LoadingPhase::Starting => "Starting…",
+        }
+    }
+}
+
+// Covers the screen with a message on a black background
+fn draw_message_screen<P: Platform>(
+    platform: &P,
+    screen_size: Vector<P::ScreenDistance>,
+    text: &str,
+    direction: text::Direction,
+) {
+    // There's nowhere to draw it until the screen has been given a size
+    if !(screen_size.x > 0.into() && screen_size.y > 0.into()) {
+        return;
+    }
+    let screen = Rectangle {
+        top_left: Vector {
+            x: 0.into(),
+            y: 0.into(),
+        },
+        size: screen_size,
+    };
+    platform.fill_rect(&screen, style::BLACK);
+    let position = Vector {
+        x: 0.into(),
+        y: screen_size.y / 2.into(),
+    };
+    platform.draw_text(
+        text,
+        position,
+        screen_size.x,
+        &style::LOADING_SCREEN,
+        direction,
+    );
+}
+
+// Shows how far along loading is, so that the screen isn't left blank before the game can be drawn
+pub fn draw_loading_screen<P: Platform>(platform: &P, phase: LoadingPhase) {
+    // The strings file may not have been loaded yet, so the loading screen isn't translated
+    let direction = text::Direction::LeftToRight;
+    draw_message_screen(
+        platform,
+        platform.get_screen_size(),
+        phase.message(),
+        direction,
+    );
+}
+
+// Struct for holding game state
+pub struct Game<P: Platform> {
+    platform: P,
+    cursor_pos: Vector<MapDistance>,
+    // Index into tile_types of the type of each tile
+    map: Array2<u32>,
+    // Includes an error tile type at the end for tiles whose type doesn't exist
+    tile_types: Vec<serialization::TileType>,
+    // Image for each tile type, in the same order as tile_types
+    images: Vec<Asset<P::Image>>,
+    units: Vec<Unit<P::Image>>,
+    // Language directory that maps are loaded from
+    language: String,
+    // Hashes that files loaded while the game is running are checked against
+    manifest: fetch::Manifest,
+    // Text shown in the player's locale
+    strings: strings::Strings,
+    // Locale that strings was loaded for
+    locale: String,
+    // Settings the player has changed on the options menu
+    options: options::Options,
+    campaign: campaign::Campaign,
+    // Map that should be loaded before the next event is handled
+    pending_load: Option<PendingLoad>,
+    // Locale whose strings file should be loaded before the next event is handled
+    pending_locale: Option<String>,
+    // Number of the current turn on this map, starting from 1. Each player has a go every turn
+    turn: u32,
+    // Index of the player whose turn it is among the players in the settings
+    player: usize,
+    // Music that was last started (None if there's no music)
+    music: Option<String>,
+    sound: sound::SoundSettings,
+    // Signals emitted while handling the current event, whose sounds haven't been played yet
+    signals: Vec<sound::GameSignal>,
+    // Volume that each tile type's ambient sound is playing at, by path
+    ambient_volumes: std::collections::HashMap<String, u32>,
+    // Screen that the ambient volumes were worked out for (None if they need working out again)
+    ambient_screen: Option<Rectangle<MapDistance>>,
+    // Screen that the scroll indicator was last updated for (None if it should be shown again)
+    scroll_indicator_screen: Option<Rectangle<MapDistance>>,
+    // When the screen last moved, or None once the scroll indicator has faded out
+    scroll_indicator_moved_at: Option<P::Instant>,
+    // How opaque the scroll indicator is, from 0 (hidden) to 255
+    scroll_indicator_opacity: u8,
+    // Where the scroll indicator's bars are drawn
+    scroll_indicator_bars: Vec<Rectangle<P::ScreenDistance>>,
+    cursor_image: Asset<P::Image>,
+    infobar_image: Asset<P::Image>,
+    status_icons: std::collections::HashMap<serialization::Status, Asset<P::Image>>,
+    // Paths of images that have been warned about failing to load, so that each is only warned
+    // about once rather than every frame
+    missing_images: std::cell::RefCell<std::collections::HashSet<String>>,
+    screen: Rectangle<MapDistance>,
+    // Worked out on demand since it's needed for most events; None if the screen may have been
+    // resized since it was last worked out
+    layout: std::cell::Cell<Option<Layout<P::ScreenDistance>>>,
+    // Whether the whole screen should be drawn on the next frame
+    needs_redraw: bool,
+    // What should be drawn on the next frame if the whole screen isn't
+    damage: Damage<P::ScreenDistance>,
+    stats: debug::FrameStats<P>,
+    // Whether frame stats are shown on top of the game
+    debug_overlay: bool,
+    // Where the debug overlay was last drawn, or None if it hasn't been drawn since it was shown
+    debug_overlay_position: Option<Rectangle<P::ScreenDistance>>,
+    last_mouse_pan: P::Instant,
+    // Offset (in tiles) of where the screen is currently drawn relative to screen.top_left
+    scroll_offset: Vector<f64>,
+    scroll_animation: Option<ScrollAnimation<P::Instant>>,
+    // Most recent position of the mouse on the screen
+    mouse_pos: Option<Vector<P::MouseDistance>>,
+    // Tile that the mouse was last over, so that the cursor only follows the mouse onto new tiles
+    hover_tile: Option<Vector<MapDistance>>,
+    settings: settings::Settings,
+    background: Option<style::Color>,
+    highlights: std::collections::HashMap<Vector<MapDistance>, style::HighlightKind>,
+    // Position of the unit whose movement and attack ranges are highlighted, if one is selected
+    selected_unit: Option<Vector<MapDistance>>,
+    // Cheapest cost of moving the selected unit to each tile highlighted for movement, worked out
+    // when it was selected so that hovering over tiles doesn't search for paths again
+    move_costs: MoveCosts,
+    // Move waiting for an action to be chosen on the action menu
+    pending_move: Option<PendingMove>,
+    // The pending move's unit on its way to where it's moving, before the action menu is opened
+    move_animation: Option<MoveAnimation<P::Instant>>,
+    combat_animation: Option<CombatAnimation<P::Instant>>,
+    // Indices into units of the player's units that can still act this turn, in a stable order.
+    // Worked out afresh at the start of each turn and whenever units are numbered differently
+    ready_units: Vec<usize>,
+    // Whether the tiles that enemies could attack on their next turn are shaded
+    show_danger_zone: bool,
+    // Whether lines are drawn between tiles for building maps
+    show_grid: bool,
+    // Danger zone of each enemy by index into units. Zones are only worked out while they're shown,
+    // and are dropped when a unit moves somewhere that could change them
+    danger_zones: std::collections::HashMap<usize, DangerZone>,
+    // Tiles in the danger zone of any enemy that isn't hidden by fog, as of when they were last shaded
+    danger_tiles: std::collections::HashSet<Vector<MapDistance>>,
+    // Empty if the minimap is disabled
+    minimap: Vec<MinimapRun>,
+    // None if fog of war is disabled
+    visibility: Option<Array2<Visibility>>,
+    // Position that the visible tiles were last revealed around
+    sight_center: Option<Vector<MapDistance>>,
+    // When the game was suspended, or None if it's running
+    suspended_at: Option<P::Instant>,
+    bindings: bindings::Bindings<P::InputType>,
+    // Contexts that keys are currently bound in, with the innermost last
+    contexts: Vec<bindings::Context>,
+    // Action that the next key pressed will be bound to
+    rebinding: Option<Action>,
+    // Whether the fast move modifier is being held down
+    fast_move: bool,
+    // Whether keyboard input is going to the game; the cursor is hidden while it isn't
+    focused: bool,
+    // Point on the screen that the map has been dragged along with so far, or None if the map
+    // isn't being dragged
+    drag_anchor: Option<Vector<f64>>,
+    // When and where the map was last clicked, or None if the click already finished a double
+    // click
+    last_click: Option<(P::Instant, Vector<MapDistance>)>,
+    menu: Option<menu::Menu<MenuAction>>,
+    // Triggers that haven't been used up yet
+    triggers: Vec<serialization::Trigger>,
+    // Messages shown when the map started, kept for saving the map from the editor
+    intro_dialog: Vec<serialization::Message>,
+    // None unless the map editor is on
+    editor: Option<editor::Editor>,
+    // Messages waiting to be shown in the dialog box, starting with the one being shown
+    dialog: dialog::Dialog,
+    // Whether a trigger has finished the map
+    map_complete: bool,
+    // Whether the game is over (either the map is complete or the player quit)
+    finished: bool,
+    // When the mouse stopped moving, or None if no tooltip is waiting to be shown
+    mouse_rest_start: Option<P::Instant>,
+    // Where the tooltip was drawn, or None if it isn't showing
+    tooltip: Option<Rectangle<P::ScreenDistance>>,
+    // Where the unit panel was drawn, or None if it isn't showing
+    unit_panel: Option<Rectangle<P::ScreenDistance>>,
+    // Where the battle forecast was drawn, or None if it isn't showing
+    forecast_panel: Option<Rectangle<P::ScreenDistance>>,
+    level_up_popup: Option<LevelUpPopup<P::Instant>>,
+    toast: Option<Toast<P::Instant>>,
+    banner: Option<Banner>,
+    // Where the replay being watched is up to (None unless the game is a replay)
+    transport: Option<record::Transport>,
+    // Moment that a replay has set the game's clock to (None to use the platform's)
+    clock: Option<P::Instant>,
+    // Autosave being written (None between autosaves)
+    autosave: Option<save::Autosave>,
+    // The game as it was at the end of the latest turn, if it's waiting to be autosaved until
+    // enough time has passed since the last autosave
+    pending_autosave: Option<Box<save::Snapshot>>,
+    // When the last autosave was started
+    last_autosave: Option<P::Instant>,
+    // Game being played against someone on another machine (None if everyone plays on this one)
+    remote: Option<net::Session>,
+    // Seed that rng was started with
+    seed: u64,
+    // Every random decision is made with this rather than by asking the platform, so that games
+    // can be replayed
+    rng: rng::Rng,
+}
+
+fn multiply_frac<T: Scalar + From<u32>>(x: T, num: u32, den: u32) -> T {
+    x * num.into() / den.into()
+}
+
+// Gets how much of a hp bar of a given width is filled in. Worked out in floating point so that
+// large hp values can't overflow, and rounded so that the bar doesn't look empty until the last hp
+// is lost
+fn hp_bar_fill<T: Scalar + From<u32> + ToPrimitive + FromPrimitive>(
+    width: T,
+    hp: u32,
+    max_hp: u32,
+) -> T {
+    if hp == 0 || max_hp == 0 {
+        return 0.into();
+    }
+    let width_f64 = width.to_f64().unwrap_or(0.0);
+    let filled = width_f64 * hp.min(max_hp) as f64 / max_hp as f64;
+    let filled = filled.round().max(1.0).min(width_f64);
+    T::from_f64(filled).unwrap_or(width)
+}
+
+impl<P: Platform> Game<P> {
+    // Gets the layout for the current zoom level, working it out again if it's stale
+    fn layout(&self) -> Layout<P::ScreenDistance> {
+        match self.layout.get() {
+            Some(layout) if layout.visible_tiles == self.screen.size => layout,
+            _ => {
+                let layout = self.get_layout();
+                self.layout.set(Some(layout));
+                layout
+            }
+        }
+    }
+
+    fn get_layout(&self) -> Layout<P::ScreenDistance> {
+        let screen_size = self.platform.get_screen_size();
+        let stretched = || {
+            let whole_screen = Rectangle {
+                top_left: Vector {
+                    x: 0.into(),
+                    y: 0.into(),
+                },
+                size: screen_size,
+            };
+            let visible_tiles = Vector {
+                x: self.screen.width().max(1),
+                y: self.screen.height().max(1),
+            };
+            (screen_size.piecewise_divide(visible_tiles), whole_screen)
+        };
+        let (tile_size, viewport) = match self.settings.scale_mode {
+            settings::ScaleMode::Stretch => stretched(),
+            settings::ScaleMode::Integer => {
+                integer_viewport(screen_size, self.screen.size).unwrap_or_else(stretched)
+            }
+        };
+        let zone = |tile: P::ScreenDistance, screen: P::ScreenDistance| {
+            let tile = tile.to_f64().unwrap_or(0.0);
+            let screen = screen.to_f64().unwrap_or(0.0);
+            (tile * self.settings.mouse_pan_zone).min(screen / 4.0)
+        };
+        let fraction = self.settings.infobar_screen_fraction.max(1);
+        let infobar_height = screen_size.y / fraction.into();
+        Layout {
+            screen_size,
+            visible_tiles: self.screen.size,
+            tile_size,
+            viewport,
+            pan_zone: Vector {
+                x: zone(tile_size.x, screen_size.x),
+                y: zone(tile_size.y, screen_size.y),
+            },
+            infobar_size: Vector {
+                x: infobar_height * self.settings.infobar_aspect_ratio.into(),
+                y: infobar_height,
+            },
+        }
+    }
+
+    // The screen has no area while e.g. the canvas is hidden or hasn't been laid out yet. Nothing
+    // can be drawn until it does, and the Redraw sent when it's resized draws the first frame
+    fn screen_ready(&self) -> bool {
+        let size = self.layout().screen_size;
+        size.x > 0.into() && size.y > 0.into()
+    }
+
+    fn get_tile_size(&self) -> Vector<P::ScreenDistance> {
+        self.layout().tile_size
+    }
+
+    fn get_tile(&self, pos: Vector<MapDistance>) -> Tile<'_, P> {
+        let tile_type = self.map[[pos.y as usize, pos.x as usize]] as usize;
+        Tile {
+            image: &self.images[tile_type],
+            info: &self.tile_types[tile_type],
+        }
+    }
+
+    // Gets which tiles are drawn and where
+    fn visible_region(&self) -> viewport::VisibleRegion {
+        let tile_size = self.get_tile_size().lossy_cast::<f64>();
+        viewport::VisibleRegion::new(
+            self.screen,
+            self.scroll_offset,
+            self.get_map_size(),
+            tile_size.unwrap_or(Vector { x: 0.0, y: 0.0 }),
+        )
+    }
+
+    // Gets the location on the screen of a map position, or None if it can't be represented
+    fn get_screen_pos(&self, pos: Vector<MapDistance>) -> Option<Rectangle<P::ScreenDistance>> {
+        self.get_region_screen_pos(&self.visible_region(), pos)
+    }
+
+    // Same as get_screen_pos, but with the visible region already worked out
+    fn get_region_screen_pos(
+        &self,
+        region: &viewport::VisibleRegion,
+        pos: Vector<MapDistance>,
+    ) -> Option<Rectangle<P::ScreenDistance>> {
+        let layout = self.layout();
+        let origin = layout.viewport.top_left.lossy_cast::<f64>()?;
+        let offset = region.tile_offset(pos);
+        Some(Rectangle {
+            top_left: Vector {
+                x: P::ScreenDistance::from_f64(origin.x + offset.x)?,
+                y: P::ScreenDistance::from_f64(origin.y + offset.y)?,
+            },
+            size: layout.tile_size,
+        })
+    }
+
+    // Gets the scroll offset at a given moment along with whether the scroll has finished
+    fn scroll_offset_at(&self, now: P::Instant) -> (Vector<f64>, bool) {
+        match &self.scroll_animation {
+            Some(animation) => {
+                let elapsed = P::to_nanoseconds(P::duration_between(animation.start, now));
+                let progress = (elapsed as f64 / SCROLL_DURATION_NS as f64).min(1.0);
+                // Ease out so the scroll starts quickly and settles gently
+                let remaining = (1.0 - progress) * (1.0 - progress);
+                let offset = Vector {
+                    x: animation.from.x * remaining,
+                    y: animation.from.y * remaining,
+                };
+                (offset, progress >= 1.0)
+            }
+            None => (Vector { x: 0.0, y: 0.0 }, true),
+        }
+    }
+
+    // Gets the current moment, which stands still while the game is suspended
+    fn now(&self) -> P::Instant {
+        self.suspended_at.unwrap_or_else(|| self.clock_now())
+    }
+
+    // Gets the current moment by the game's clock: the platform's, unless a replay is being played
+    // by the recording's
+    fn clock_now(&self) -> P::Instant {
+        self.clock.unwrap_or_else(P::now)
+    }
+
+    // Sets the moment that the game's clock shows, so that a replay plays out by the recording's
+    // timing however quickly it's watched (None to go back to the platform's clock)
+    pub fn set_clock(&mut self, now: Option<P::Instant>) {
+        self.clock = now;
+    }
+
+    // Advances the scroll animation to the current moment
+    fn update_scroll(&mut self) {
+        let (offset, finished) = self.scroll_offset_at(self.now());
+        self.scroll_offset = offset;
+        if finished {
+            self.scroll_animation = None;
+        }
+    }
+
+    // Jumps to the end of any in-progress scroll
+    fn finish_scroll(&mut self) {
+        self.scroll_animation = None;
+        self.scroll_offset = Vector { x: 0.0, y: 0.0 };
+    }
+
+    // Checks whether the whole screen is drawn on every frame because something on it is moving
+    fn is_animating(&self) -> bool {
+        self.scroll_animation.is_some()
+            || self.move_animation.is_some()
+            || self.combat_animation.is_some()
+    }
+
+    // Checks whether a unit is drawn on its own rather than along with its tile, because it's
+    // moving or fighting
+    fn is_unit_animated(&self, unit: usize) -> bool {
+        let fighting = |a: &CombatAnimation<_>| a.attacker == unit || a.target == unit;
+        self.move_animation.as_ref().is_some_and(|a| a.unit == unit)
+            || self.combat_animation.as_ref().is_some_and(fighting)
+    }
+
+    // Moves the screen to a new position, scrolling smoothly from wherever it is currently drawn
+    fn pan(&mut self, top_left: Vector<MapDistance>) {
+        let now = self.now();
+        let (offset, _) = self.scroll_offset_at(now);
+        self.scroll_animation = Some(ScrollAnimation {
+            from: Vector {
+                x: offset.x + top_left.x as f64 - self.screen.left() as f64,
+                y: offset.y + top_left.y as f64 - self.screen.top() as f64,
+            },
+            start: now,
+        });
+        self.screen.top_left = top_left;
+    }
+
+    // Gets the minimum time between pans caused by the mouse
+    fn mouse_pan_delay(&self) -> P::Duration {
+        P::nanoseconds(self.settings.mouse_pan_delay_ms.saturating_mul(1_000_000))
+    }
+
+    // Gets where the screen should pan to while the mouse is in the zone along an edge of the
+    // screen, along with how long to wait between pans; the deeper into the zone the mouse is, the
+    // shorter the wait
+    fn get_edge_pan(
+        &self,
+        mouse_pos: Vector<P::MouseDistance>,
+    ) -> Option<(Vector<MapDistance>, P::Duration)> {
+        let pos = mouse_pos.cast::<P::ScreenDistance>().lossy_cast::<f64>()?;
+        let layout = self.layout();
+        let screen_size = layout.screen_size.lossy_cast::<f64>()?;
+        let zone = layout.pan_zone;
+        // Goes from 0 at the inner boundary of the zone to 1 at the edge of the screen (or past it,
+        // or in a zone with no size, where the distance can't be divided by the zone)
+        let depth = |distance_from_edge: f64, zone: f64| {
+            if zone > 0.0 {
+                1.0 - (distance_from_edge / zone).clamp(0.0, 1.0)
+            } else {
+                1.0
+            }
+        };
+        let map_size = self.get_map_size();
+        let mut top_left = self.screen.top_left;
+        let depth = if pos.y < zone.y && self.screen.top() > 0 {
+            top_left.y -= 1;
+            depth(pos.y, zone.y)
+        } else if pos.y > screen_size.y - zone.y && self.screen.bottom() < map_size.y {
+            top_left.y += 1;
+            depth(screen_size.y - pos.y, zone.y)
+        } else if pos.x < zone.x && self.screen.left() > 0 {
+            top_left.x -= 1;
+            depth(pos.x, zone.x)
+        } else if pos.x > screen_size.x - zone.x && self.screen.right() < map_size.x {
+            top_left.x += 1;
+            depth(screen_size.x - pos.x, zone.x)
+        } else {
+            return None;
+        };
+        let speedup = 1.0 + (EDGE_PAN_MAX_SPEEDUP - 1.0) * depth.max(0.0);
+        let delay_ns = self.settings.mouse_pan_delay_ms as f64 * 1_000_000.0 / speedup;
+        Some((top_left, P::nanoseconds(delay_ns as u64)))
+    }
+
+    // Stops time-based logic until the game is resumed
+    fn suspend(&mut self) {
+        if self.suspended_at.is_none() {
+            self.suspended_at = Some(self.clock_now());
+        }
+        // Keys and buttons let go of while the game is in the background may never be reported
+        self.fast_move = false;
+        self.drag_anchor = None;
+    }
+
+    // Hides the cursor while keyboard input is going elsewhere, so that it's clear that keys won't
+    // move it, and shows it again once input comes back
+    fn set_focus(&mut self, focused: bool) {
+        if focused == self.focused {
+            return;
+        }
+        self.focused = focused;
+        // The modifier may be let go of while another element has focus
+        self.fast_move = false;
+        self.redraw_tiles(std::iter::once(self.cursor_pos));
+    }
+
+    // Picks time-based logic back up from where it was suspended and redraws the whole screen
+    fn resume(&mut self) {
+        let suspended_at = match self.suspended_at.take() {
+            Some(t) => t,
+            None => return,
+        };
+        let now = self.clock_now();
+        let (offset, finished) = self.scroll_offset_at(suspended_at);
+        if finished {
+            self.finish_scroll();
+        } else {
+            self.scroll_animation = Some(ScrollAnimation {
+                from: offset,
+                start: now,
+            });
+        }
+        if P::duration_between(self.last_mouse_pan, suspended_at) <= self.mouse_pan_delay() {
+            self.last_mouse_pan = now;
+        }
+        if self.mouse_rest_start.is_some() {
+            self.mouse_rest_start = Some(now);
+        }
+        self.redraw();
+    }
+
+    // Gets the cursor position relative to the top left of the screen (clamped to be non-negative)
+    fn get_cursor_pos_on_screen(&self) -> Vector<MapDistance> {
+        Vector {
+            x: self.cursor_pos.x.saturating_sub(self.screen.left()),
+            y: self.cursor_pos.y.saturating_sub(self.screen.top()),
+        }
+    }
+
+    // Fewest rows and columns that may be visible at once
+    fn min_screen_size(&self) -> Vector<MapDistance> {
+        let max = self.max_screen_size();
+        let min = self.settings.min_visible_tiles.max(1);
+        Vector {
+            x: min.min(max.x),
+            y: min.min(max.y),
+        }
+    }
+
+    // Most rows and columns that may be visible at once
+    fn max_screen_size(&self) -> Vector<MapDistance> {
+        let map_size = self.get_map_size();
+        match self.settings.max_visible_tiles {
+            Some(max) => Vector {
+                x: map_size.x.min(max.max(1)),
+                y: map_size.y.min(max.max(1)),
+            },
+            None => map_size,
+        }
+    }
+
+    // Shrinks the screen by the zoom step, keeping the cursor near the middle of the screen
+    fn zoom_in(&mut self) {
+        self.finish_scroll();
+        for _ in 0..self.settings.zoom_step.max(1) {
+            self.zoom_in_once();
+        }
+    }
+
+    // Grows the screen by the zoom step, keeping the cursor near the middle of the screen
+    fn zoom_out(&mut self) {
+        self.finish_scroll();
+        for _ in 0..self.settings.zoom_step.max(1) {
+            self.zoom_out_once();
+        }
+    }
+
+    // Shrinks the screen by a tile, keeping the cursor near the middle of the screen
+    fn zoom_in_once(&mut self) {
+        let tile_size = self.get_tile_size();
+        let min_size = self.min_screen_size();
+        let cursor_pos_on_screen = self.get_cursor_pos_on_screen();
+        let size = self.screen.size;
+        if size.y > min_size.y && (tile_size.x >= tile_size.y || size.x <= min_size.x) {
+            self.screen.size.y -= 1;
+            if cursor_pos_on_screen.y > self.screen.height() / 2 {
+                self.screen.top_left.y += 1;
+            }
+        }
+        if size.x > min_size.x && (tile_size.y >= tile_size.x || size.y <= min_size.y) {
+            self.screen.size.x -= 1;
+            if cursor_pos_on_screen.x > self.screen.width() / 2 {
+                self.screen.top_left.x += 1;
+            }
+        }
+    }
+
+    // Grows the screen by a tile, keeping the cursor near the middle of the screen
+    fn zoom_out_once(&mut self) {
+        let tile_size = self.get_tile_size();
+        let map_size = self.get_map_size();
+        let max_size = self.max_screen_size();
+        let cursor_pos_on_screen = self.get_cursor_pos_on_screen();
+        let size = self.screen.size;
+        if size.y < max_size.y && (tile_size.y >= tile_size.x || size.x >= max_size.x) {
+            self.screen.size.y += 1;
+            if self.screen.bottom() > map_size.y
+                || self.screen.top() > 0 && cursor_pos_on_screen.y < self.screen.height() / 2
+            {
+                self.screen.top_left.y -= 1;
+            }
+        }
+        if size.x < max_size.x && (tile_size.x >= tile_size.y || size.y >= max_size.y) {
+            self.screen.size.x += 1;
+            if self.screen.right() > map_size.x
+                || self.screen.left() > 0 && cursor_pos_on_screen.x < size.x / 2
+            {
+                self.screen.top_left.x -= 1;
+            }
+        }
+    }
+
+    // Zooms while keeping the map position under the mouse in the same place on the screen
+    fn zoom_at(&mut self, mouse_pos: Vector<P::MouseDistance>, zoom: fn(&mut Self)) {
+        let anchor = match self.get_fractional_map_pos(mouse_pos) {
+            Some(a) => a,
+            None => return zoom(self),
+        };
+        zoom(self);
+        let (screen_pos, tile_size) = match (
+            self.get_viewport_pos(mouse_pos),
+            self.get_tile_size().lossy_cast::<f64>(),
+        ) {
+            (Some(p), Some(t)) => (p, t),
+            _ => return,
+        };
+        let tiles = match screen_pos.finite_piecewise_divide(tile_size) {
+            Some(t) => t,
+            None => return,
+        };
+        let map_size = self.get_map_size();
+        let max_left = (map_size.x - self.screen.width()) as f64;
+        let max_top = (map_size.y - self.screen.height()) as f64;
+        let left = (anchor.x - tiles.x).round();
+        let top = (anchor.y - tiles.y).round();
+        self.screen.top_left = Vector {
+            x: left.max(0.0).min(max_left) as MapDistance,
+            y: top.max(0.0).min(max_top) as MapDistance,
+        };
+        // Keep the cursor under the mouse since the tile it was on may have left the screen
+        if let Some(p) = self.get_map_pos(mouse_pos) {
+            if p.x < map_size.x && p.y < map_size.y {
+                self.cursor_pos = p;
+            }
+        }
+    }
+
+    // Gets where a point on the screen is relative to the top left corner of the viewport (negative
+    // or past the viewport's size if it's in the letterbox)
+    fn get_viewport_pos(&self, pos: Vector<P::MouseDistance>) -> Option<Vector<f64>> {
+        let screen_pos = pos.cast::<P::ScreenDistance>().lossy_cast::<f64>()?;
+        let origin = self.layout().viewport.top_left.lossy_cast::<f64>()?;
+        Some(Vector {
+            x: screen_pos.x - origin.x,
+            y: screen_pos.y - origin.y,
+        })
+    }
+
+    // Gets the map position (including the fraction of a tile) under a point on the screen
+    fn get_fractional_map_pos(&self, pos: Vector<P::MouseDistance>) -> Option<Vector<f64>> {
+        let screen_pos = self.get_viewport_pos(pos)?;
+        let tile_size = self.get_tile_size().lossy_cast::<f64>()?;
+        let tiles = screen_pos.finite_piecewise_divide(tile_size)?;
+        Some(Vector {
+            x: self.screen.left() as f64 + tiles.x,
+            y: self.screen.top() as f64 + tiles.y,
+        })
+    }
+
+    // Gets the parts of the viewport that the visible tiles don't cover
+    fn get_uncovered_regions(&self) -> Vec<Rectangle<P::ScreenDistance>> {
+        let viewport = self.layout().viewport;
+        let region = self.visible_region();
+        let (origin, size) = match (
+            viewport.top_left.lossy_cast::<f64>(),
+            viewport.size.lossy_cast::<f64>(),
+        ) {
+            (Some(o), Some(s)) if !region.is_empty() => (o, s),
+            _ => return vec![viewport],
+        };
+        let bounds = region.bounds();
+        // Tiles can hang off the viewport mid-scroll
+        let corner = |x: f64, y: f64| {
+            Some(Vector {
+                x: P::ScreenDistance::from_f64(origin.x + x.max(0.0).min(size.x))?,
+                y: P::ScreenDistance::from_f64(origin.y + y.max(0.0).min(size.y))?,
+            })
+        };
+        match (
+            corner(bounds.left(), bounds.top()),
+            corner(bounds.right(), bounds.bottom()),
+        ) {
+            (Some(top_left), Some(bottom_right)) => {
+                let covered = Rectangle {
+                    top_left,
+                    size: bottom_right - top_left,
+                };
+                surrounding_strips(viewport, covered)
+            }
+            _ => vec![viewport],
+        }
+    }
+
+    // Gets the margins around the viewport that are filled with the letterbox color
+    fn get_letterbox_regions(&self) -> Vec<Rectangle<P::ScreenDistance>> {
+        let layout = self.layout();
+        let whole_screen = Rectangle {
+            top_left: Vector {
+                x: 0.into(),
+                y: 0.into(),
+            },
+            size: layout.screen_size,
+        };
+        surrounding_strips(whole_screen, layout.viewport)
+    }
+
+    fn get_map_size(&self) -> Vector<MapDistance> {
+        let (rows, columns) = self.map.dim();
+        Vector {
+            x: columns as MapDistance,
+            y: rows as MapDistance,
+        }
+    }
+
+    // Gets the map position under a point on the screen (None if it's in the letterbox)
+    fn get_map_pos(&self, pos: Vector<P::MouseDistance>) -> Option<Vector<MapDistance>> {
+        let layout = self.layout();
+        let screen_pos = pos.cast::<P::ScreenDistance>();
+        let viewport = layout.viewport;
+        if screen_pos.x < viewport.left()
+            || screen_pos.y < viewport.top()
+            || screen_pos.x >= viewport.right()
+            || screen_pos.y >= viewport.bottom()
+        {
+            return None;
+        }
+        let pos_in_viewport = (screen_pos - viewport.top_left).lossy_cast::<f64>()?;
+        self.visible_region().tile_at(pos_in_viewport)
+    }
+
+    // Gets the position on the screen closest to a position on the map
+    fn clamp_to_screen(&self, pos: Vector<MapDistance>) -> Vector<MapDistance> {
+        let screen = &self.screen;
+        let right = screen.checked_right().unwrap_or(MapDistance::MAX);
+        let bottom = screen.checked_bottom().unwrap_or(MapDistance::MAX);
+        Vector {
+            x: pos
+                .x
+                .clamp(screen.left(), right.saturating_sub(1).max(screen.left())),
+            y: pos
+                .y
+                .clamp(screen.top(), bottom.saturating_sub(1).max(screen.top())),
+        }
+    }
+
+    fn move_cursor(&mut self, pos: Vector<MapDistance>) {
+        let pos = self.clamp_to_screen(pos);
+        if pos == self.cursor_pos {
+            // Nothing on the screen would change
+            return;
+        }
+        if self.is_animating() || self.needs_redraw {
+            // The whole screen is going to be drawn on the next frame anyway
+            self.cursor_pos = pos;
+            self.redraw();
+            return;
+        }
+        let old_infobar = self.get_infobar_position();
+        self.damage.tiles.push(self.cursor_pos);
+        self.cursor_pos = pos;
+        let revealed = self.update_visibility();
+        self.damage.tiles.extend(revealed.iter().copied());
+        if self.get_infobar_position().left() != old_infobar.left() {
+            // Uncover the tiles that were underneath the infobar before it moved
+            self.damage.regions.push(old_infobar);
+        }
+        self.damage.overlays = true;
+        self.damage.minimap |= !revealed.is_empty();
+        let enemy_revealed = revealed.iter().any(|p| {
+            self.get_unit(*p)
+                .is_some_and(|u| u.info.team != self.current_team())
+        });
+        if self.show_danger_zone && enemy_revealed {
+            self.refresh_danger_zone();
+        }
+    }
+
+    // Moves the cursor by an offset (stopping at the edges of the map), scrolling the screen along
+    // each axis that the cursor leaves it on
+    fn step_cursor(&mut self, dx: i64, dy: i64) {
+        let map_size = self.get_map_size();
+        let step = |pos: MapDistance, offset: i64, size: MapDistance| {
+            (pos as i64 + offset).clamp(0, size as i64 - 1) as MapDistance
+        };
+        let pos = Vector {
+            x: step(self.cursor_pos.x, dx, map_size.x),
+            y: step(self.cursor_pos.y, dy, map_size.y),
+        };
+        if pos == self.cursor_pos {
+            return;
+        }
+        // Scroll by as much as the cursor moved so that it stays in the same place on the screen
+        let scroll = |pos: MapDistance, old: MapDistance, start: MapDistance, length, size| {
+            if pos >= start && pos - start < length {
+                return start;
+            }
+            let max_start = MapDistance::saturating_sub(size, length);
+            let shifted = (start as i64 + pos as i64 - old as i64).clamp(0, max_start as i64);
+            // Make sure the cursor ends up on the screen even if it somehow started off of it
+            (shifted as MapDistance).clamp((pos + 1).saturating_sub(length), pos)
+        };
+        let (old, screen) = (self.cursor_pos, &self.screen);
+        let top_left = Vector {
+            x: scroll(pos.x, old.x, screen.left(), screen.width(), map_size.x),
+            y: scroll(pos.y, old.y, screen.top(), screen.height(), map_size.y),
+        };
+        if top_left == self.screen.top_left {
+            self.move_cursor(pos);
+        } else {
+            self.cursor_pos = pos;
+            self.pan(top_left);
+            self.redraw();
+        }
+    }
+
+    // Moves the cursor to any tile on the map. If it's off the screen, the screen is centered on it
+    // along each axis that it's off by
+    fn go_to(&mut self, pos: Vector<MapDistance>) {
+        let top_left = self.get_top_left_showing(pos);
+        self.show_at(pos, top_left);
+    }
+
+    // Moves the cursor to any tile on the map, scrolling the screen to a given top left first
+    fn show_at(&mut self, pos: Vector<MapDistance>, top_left: Vector<MapDistance>) {
+        if top_left == self.screen.top_left {
+            self.move_cursor(pos);
+        } else {
+            self.cursor_pos = pos;
+            self.pan(top_left);
+            self.redraw();
+        }
+    }
+
+    // Gets where the screen has to be to show a position: where it already is if the position is
+    // on it, or centered on the position along whichever axes it's off of it
+    fn get_top_left_showing(&self, pos: Vector<MapDistance>) -> Vector<MapDistance> {
+        let centered = self.get_centered_top_left(pos);
+        let center = |pos: MapDistance, start: MapDistance, length: MapDistance, centered| {
+            if pos >= start && pos - start < length {
+                start
+            } else {
+                centered
+            }
+        };
+        let screen = &self.screen;
+        Vector {
+            x: center(pos.x, screen.left(), screen.width(), centered.x),
+            y: center(pos.y, screen.top(), screen.height(), centered.y),
+        }
+    }
+
+    // Reveals the tiles around the cursor and fades the ones that are no longer near it,
+    // returning the positions of every tile whose visibility changed
+    fn update_visibility(&mut self) -> Vec<Vector<MapDistance>> {
+        let visibility = match self.visibility.as_mut() {
+            Some(v) => v,
+            None => return Vec::new(),
+        };
+        let center = self.cursor_pos;
+        if self.sight_center == Some(center) {
+            return Vec::new();
+        }
+        let range = self.settings.sight_range;
+        let (rows, columns) = visibility.dim();
+        let nearby = |c: Vector<MapDistance>| {
+            let top = c.y.saturating_sub(range);
+            let bottom = (c.y.saturating_add(range) as usize).min(rows - 1) as MapDistance;
+            let left = c.x.saturating_sub(range);
+            let right = (c.x.saturating_add(range) as usize).min(columns - 1) as MapDistance;
+            (top..=bottom).flat_map(move |y| {
+                (left..=right)
+                    .map(move |x| Vector { x, y })
+                    .filter(move |p| p.x.abs_diff(c.x) + p.y.abs_diff(c.y) <= range)
+            })
+        };
+        let mut changed = Vec::new();
+        if let Some(old_center) = self.sight_center {
+            for p in nearby(old_center) {
+                let v = &mut visibility[[p.y as usize, p.x as usize]];
+                if *v == Visibility::Visible {
+                    *v = Visibility::Explored;
+                    changed.push(p);
+                }
+            }
+        }
+        for p in nearby(center) {
+            let v = &mut visibility[[p.y as usize, p.x as usize]];
+            match *v {
+                Visibility::Visible => {}
+                // Tiles that were only dimmed on this move don't need to change at all
+                Visibility::Explored if changed.contains(&p) => {
+                    changed.retain(|c| *c != p);
+                    *v = Visibility::Visible;
+                }
+                _ => {
+                    *v = Visibility::Visible;
+                    changed.push(p);
+                }
+            }
+        }
+        self.sight_center = Some(center);
+        if !changed.is_empty() && !self.minimap.is_empty() {
+            self.minimap = minimap_runs(&self.map, &self.tile_types, self.visibility.as_ref());
+        }
+        changed
+    }
+
+    // Checks whether a tile is hidden by fog of war
+    fn is_hidden(&self, pos: Vector<MapDistance>) -> bool {
+        match self.visibility.as_ref() {
+            Some(v) => v[[pos.y as usize, pos.x as usize]] == Visibility::Hidden,
+            None => false,
+        }
+    }
+
+    // Checks whether units on a tile can be seen, which they can't through any fog of war
+    fn is_in_sight(&self, pos: Vector<MapDistance>) -> bool {
+        match self.visibility.as_ref() {
+            Some(v) => v[[pos.y as usize, pos.x as usize]] == Visibility::Visible,
+            None => true,
+        }
+    }
+
+    // Gets where a unit is, counting a move that hasn't been committed yet
+    fn get_unit_pos(&self, index: usize) -> Vector<MapDistance> {
+        match self.pending_move {
+            Some(pending) if pending.unit == index => pending.destination,
+            _ => {
+                let info = &self.units[index].info;
+                Vector {
+                    x: info.x,
+                    y: info.y,
+                }
+            }
+        }
+    }
+
+    fn get_unit_index(&self, pos: Vector<MapDistance>) -> Option<usize> {
+        (0..self.units.len()).find(|&i| self.get_unit_pos(i) == pos)
+    }
+
+    fn get_unit(&self, pos: Vector<MapDistance>) -> Option<&Unit<P::Image>> {
+        self.get_unit_index(pos).map(|i| &self.units[i])
+    }
+
+    // Redraws every visible tile that overlaps part of the screen
+    fn redraw_region(&self, region: &Rectangle<P::ScreenDistance>) {
+        let visible = self.visible_region();
+        for map_pos in visible.positions() {
+            if let Some(screen_pos) = self.get_region_screen_pos(&visible, map_pos) {
+                if screen_pos.intersects(region) {
+                    self.draw_tile(map_pos, &screen_pos);
+                }
+            }
+        }
+    }
+
+    // Draws a tile's image, or a placeholder if it failed to load
+    fn draw_terrain(&self, pos: Vector<MapDistance>, screen_pos: &Rectangle<P::ScreenDistance>) {
+        let tile = self.get_tile(pos);
+        if tile.image.failed() {
+            self.draw_placeholder(screen_pos);
+        }
+        self.attempt_draw(tile.image, tile.info.name.as_str(), screen_pos);
+    }
+
+    // Draws a checkerboard in place of an image that failed to load, so that it stands out instead
+    // of leaving a hole that shows whatever was drawn there before
+    fn draw_placeholder(&self, location: &Rectangle<P::ScreenDistance>) {
+        self.fill_rect(location, style::BLACK);
+        let half = location.size / 2.into();
+        let top_left = Rectangle {
+            top_left: location.top_left,
+            size: half,
+        };
+        let bottom_right = Rectangle {
+            top_left: location.top_left + half,
+            size: location.size - half,
+        };
+        self.fill_rect(&top_left, style::MISSING_IMAGE);
+        self.fill_rect(&bottom_right, style::MISSING_IMAGE);
+    }
+
+    // Draws a unit's sprite with its hp bar (showing the given hp) along the bottom of its tile and
+    // an icon for each of its statuses along the top right
+    fn draw_unit(&self, unit: &Unit<P::Image>, screen_pos: &Rectangle<P::ScreenDistance>, hp: u32) {
+        let info = &unit.info;
+        self.attempt_draw(&unit.sprite, info.name.as_str(), screen_pos);
+
+        let tile = screen_pos.size;
+        let one: P::ScreenDistance = 1.into();
+        let bar_height = partial_ord_min(partial_ord_max(tile.y / 8.into(), one), tile.y);
+        let bar = Rectangle {
+            top_left: Vector {
+                x: screen_pos.left(),
+                y: screen_pos.bottom() - bar_height,
+            },
+            size: Vector {
+                x: tile.x,
+                y: bar_height,
+            },
+        };
+        self.draw_hp_bar(&bar, hp, info.max_hp);
+
+        let icon_size = tile / 4.into();
+        let mut icon_left = screen_pos.right();
+        for status in info.statuses.iter() {
+            if icon_left < screen_pos.left() + icon_size.x {
+                // Out of room on the tile
+                break;
+            }
+            icon_left = icon_left - icon_size.x;
+            let icon = Rectangle {
+                top_left: Vector {
+                    x: icon_left,
+                    y: screen_pos.top(),
+                },
+                size: icon_size,
+            };
+            match self.status_icons.get(status) {
+                Some(asset) if asset.image.is_some() => {
+                    self.attempt_draw(asset, "status icon", &icon)
+                }
+                asset => {
+                    self.fill_rect(&icon, style::STATUS_MARKER);
+                    // Warn about an icon that failed to load
+                    if let Some(asset) = asset {
+                        self.attempt_draw(asset, "status icon", &icon);
+                    }
+                }
+            }
+        }
+    }
+
+    // Draws a bar filled in from the left in proportion to the hp a unit has left
+    fn draw_hp_bar(&self, bar: &Rectangle<P::ScreenDistance>, hp: u32, max_hp: u32) {
+        self.fill_rect(bar, style::HP_BAR_EMPTY);
+        let filled = hp_bar_fill(bar.width(), hp, max_hp);
+        if filled > 0.into() {
+            let full = Rectangle {
+                top_left: bar.top_left,
+                size: Vector {
+                    x: filled,
+                    y: bar.height(),
+                },
+            };
+            self.fill_rect(&full, style::hp_bar_color(hp, max_hp));
+        }
+    }
+
+    // Draws a tile's terrain along with any fog or highlight on it, and the grid if it's shown
+    fn draw_tile(&self, pos: Vector<MapDistance>, screen_pos: &Rectangle<P::ScreenDistance>) {
+        self.draw_tile_contents(pos, screen_pos);
+        if self.show_grid {
+            self.draw_grid(pos, screen_pos);
+        }
+        self.draw_scroll_indicator_over(screen_pos);
+    }
+
+    // Finds where the scroll indicator's bars go: along the right edge of the tiles on the screen
+    // if the map is taller than what's visible and along the bottom if it's wider, each as long as
+    // the part of the map that's visible is compared to the whole map. They're kept over the tiles
+    // so that redrawing the tiles redraws them, and stop short of the minimap and the hint bar so
+    // that the minimap doesn't have to be redrawn as they fade
+    fn get_scroll_indicator_bars(&self) -> Vec<Rectangle<P::ScreenDistance>> {
+        let layout = self.layout();
+        let viewport = layout.viewport;
+        let map_size = self.get_map_size();
+        let screen = self.screen;
+        let thickness = partial_ord_max(
+            layout.screen_size.y / SCROLL_INDICATOR_ROWS_PER_SCREEN.into(),
+            1.into(),
+        );
+        // Tiles can fall short of the edges of the viewport when their size is rounded
+        let tiles = layout.tile_size.piecewise_multiply(screen.size);
+        let edge = partial_ord_min(viewport.right(), viewport.left() + tiles.x);
+        let bottom = partial_ord_min(
+            partial_ord_min(viewport.bottom(), viewport.top() + tiles.y),
+            layout.screen_size.y - self.get_hint_bar_height(),
+        );
+        let minimap = self.get_minimap_position().map(|(position, _)| position);
+        let vertical = screen.height() < map_size.y;
+        let horizontal = screen.width() < map_size.x;
+        // Without a minimap in the corner, the bars leave it to each other
+        let (right, lowest) = match minimap {
+            Some(m) => (m.left(), m.top()),
+            None if vertical && horizontal => (edge - thickness, bottom - thickness),
+            None => (edge, bottom),
+        };
+        let mut bars = Vec::new();
+        if vertical && lowest > viewport.top() + thickness {
+            let track = lowest - viewport.top();
+            let start = track * screen.top().into() / map_size.y.into();
+            let end = track * screen.bottom().min(map_size.y).into() / map_size.y.into();
+            bars.push(Rectangle {
+                top_left: Vector {
+                    x: edge - thickness,
+                    y: viewport.top() + start,
+                },
+                size: Vector {
+                    x: thickness,
+                    y: end - start,
+                },
+            });
+        }
+        if horizontal && right > viewport.left() + thickness {
+            let track = right - viewport.left();
+            let start = track * screen.left().into() / map_size.x.into();
+            let end = track * screen.right().min(map_size.x).into() / map_size.x.into();
+            bars.push(Rectangle {
+                top_left: Vector {
+                    x: viewport.left() + start,
+                    y: bottom - thickness,
+                },
+                size: Vector {
+                    x: end - start,
+                    y: thickness,
+                },
+            });
+        }
+        bars
+    }
+
+    // Works out where the scroll indicator goes and how opaque it is, showing it fully again if the
+    // screen has moved since it was last updated. Returns whether its opacity changed
+    fn update_scroll_indicator(&mut self) -> bool {
+        let old_opacity = self.scroll_indicator_opacity;
+        self.scroll_indicator_bars = if self.settings.show_scroll_indicator {
+            self.get_scroll_indicator_bars()
+        } else {
+            Vec::new()
+        };
+        if self.scroll_indicator_bars.is_empty() {
+            self.scroll_indicator_moved_at = None;
+            self.scroll_indicator_opacity = 0;
+        } else if self.scroll_indicator_screen != Some(self.screen) {
+            self.scroll_indicator_moved_at = Some(self.now());
+            self.scroll_indicator_opacity = u8::MAX;
+        } else if let Some(moved_at) = self.scroll_indicator_moved_at {
+            let shown = P::to_nanoseconds(P::duration_between(moved_at, self.now()));
+            let left = (SCROLL_INDICATOR_NS + SCROLL_INDICATOR_FADE_NS).saturating_sub(shown);
+            let fade = left.min(SCROLL_INDICATOR_FADE_NS);
+            self.scroll_indicator_opacity =
+                (fade * u8::MAX as u64 / SCROLL_INDICATOR_FADE_NS) as u8;
+            if left == 0 {
+                self.scroll_indicator_moved_at = None;
+            }
+        }
+        self.scroll_indicator_screen = Some(self.screen);
+        self.scroll_indicator_opacity != old_opacity
+    }
+
+    // Draws the part of the scroll indicator that's over a tile, since drawing the tile covers it
+    // up. Drawing only that part keeps the indicator from getting more opaque wherever it's drawn
+    // over itself
+    fn draw_scroll_indicator_over(&self, screen_pos: &Rectangle<P::ScreenDistance>) {
+        if self.scroll_indicator_opacity == 0 {
+            return;
+        }
+        let alpha = style::SCROLL_INDICATOR.alpha as u32 * self.scroll_indicator_opacity as u32;
+        let color = style::Color {
+            alpha: (alpha / u8::MAX as u32) as u8,
+            ..style::SCROLL_INDICATOR
+        };
+        for bar in self.scroll_indicator_bars.iter() {
+            if let Some(part) = bar.intersection(screen_pos) {
+                self.fill_rect(&part, color);
+            }
+        }
+    }
+
+    fn draw_tile_contents(
+        &self,
+        pos: Vector<MapDistance>,
+        screen_pos: &Rectangle<P::ScreenDistance>,
+    ) {
+        let visibility = self.visibility.as_ref();
+        match visibility.map(|v| v[[pos.y as usize, pos.x as usize]]) {
+            Some(Visibility::Hidden) => {
+                self.fill_rect(screen_pos, FOG_HIDDEN_COLOR);
+                return;
+            }
+            Some(Visibility::Explored) => {
+                self.draw_terrain(pos, screen_pos);
+                self.fill_rect(screen_pos, FOG_EXPLORED_COLOR);
+            }
+            _ => {
+                self.draw_terrain(pos, screen_pos);
+                match self.get_unit_index(pos) {
+                    Some(i) if !self.is_unit_animated(i) => {
+                        let unit = &self.units[i];
+                        self.draw_unit(unit, screen_pos, unit.info.hp);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        // Highlights go on top so that a selected unit's ranges can still be told apart
+        if self.show_danger_zone && self.danger_tiles.contains(&pos) {
+            self.fill_rect(screen_pos, style::DANGER_ZONE);
+        }
+        if let Some(highlight) = self.highlights.get(&pos) {
+            self.fill_rect(screen_pos, highlight.color());
+        }
+    }
+
+    // Draws the grid lines along the right and bottom edges of a tile, along with its column number
+    // if it's along the top of the screen and its row number if it's along the left. All of it is
+    // drawn inside the tile so that redrawing a tile redraws its part of the grid
+    fn draw_grid(&self, pos: Vector<MapDistance>, screen_pos: &Rectangle<P::ScreenDistance>) {
+        let one: P::ScreenDistance = 1.into();
+        let color = self.settings.grid_color;
+        let right_edge = Rectangle {
+            top_left: Vector {
+                x: screen_pos.right() - one,
+                y: screen_pos.top(),
+            },
+            size: Vector {
+                x: one,
+                y: screen_pos.height(),
+            },
+        };
+        // Stops short of the right edge so that the corner isn't drawn twice
+        let bottom_edge = Rectangle {
+            top_left: Vector {
+                x: screen_pos.left(),
+                y: screen_pos.bottom() - one,
+            },
+            size: Vector {
+                x: screen_pos.width() - one,
+                y: one,
+            },
+        };
+        self.fill_rect(&right_edge, color);
+        self.fill_rect(&bottom_edge, color);
+        if !self.settings.grid_labels {
+            return;
+        }
+        let label = style::TextStyle {
+            color,
+            ..style::GRID_LABEL
+        };
+        if pos.y == self.screen.top() {
+            let column = pos.x.to_string();
+            self.draw_text(
+                column.as_str(),
+                screen_pos.top_left,
+                screen_pos.width(),
+                &label,
+            );
+        }
+        if pos.x == self.screen.left() {
+            let row = pos.y.to_string();
+            let label = style::TextStyle {
+                alignment: style::Alignment::Left,
+                ..label
+            };
+            let row_pos = Vector {
+                x: screen_pos.left(),
+                y: screen_pos.top() + screen_pos.height() / 2.into(),
+            };
+            self.draw_text(row.as_str(), row_pos, screen_pos.width(), &label);
+        }
+    }
+
+    fn toggle_grid(&mut self) {
+        self.show_grid = !self.show_grid;
+        self.redraw();
+    }
+
+    // Has specific tiles drawn again on the next frame, along with whatever is on top of them
+    fn redraw_tiles(&mut self, positions: impl Iterator<Item = Vector<MapDistance>>) {
+        if self.is_animating() || self.needs_redraw {
+            // The whole screen is going to be drawn on the next frame anyway
+            return self.redraw();
+        }
+        self.damage.tiles.extend(positions);
+    }
+
+    // Draws what events have changed since the last frame: tiles first and then whatever is on top
+    // of them, in the same order as when the whole screen is drawn
+    fn draw_damage(&mut self) {
+        let damage = std::mem::take(&mut self.damage);
+        if damage.is_empty() || !self.screen_ready() {
+            return;
+        }
+        let uncovers = damage.uncovers();
+        let mut drawn = Vec::new();
+        let mut seen = std::collections::HashSet::new();
+        for pos in damage.tiles.into_iter().filter(|&p| seen.insert(p)) {
+            let screen_pos = self.get_screen_pos(pos);
+            if let Some(screen_pos) = screen_pos.as_ref() {
+                self.draw_tile(pos, screen_pos);
+            }
+            drawn.push(screen_pos);
+        }
+        for region in damage.regions {
+            self.redraw_region(&region);
+            drawn.push(Some(region));
+        }
+        if uncovers {
+            self.draw_cursor();
+            drawn.push(self.get_screen_pos(self.cursor_pos));
+            self.draw_infobar();
+        } else if damage.hint_bar {
+            self.draw_hint_bar();
+        }
+        if damage.minimap {
+            self.draw_minimap();
+        } else {
+            self.restore_minimap(&drawn);
+        }
+        if uncovers {
+            self.update_unit_panel();
+        }
+        if uncovers || damage.menu {
+            self.draw_menu();
+            self.update_forecast();
+        }
+        if uncovers || damage.dialog {
+            self.draw_dialog();
+        }
+        if uncovers {
+            self.draw_level_up_popup();
+        }
+        if uncovers || damage.toast {
+            self.draw_toast();
+        }
+        if uncovers {
+            self.draw_banner();
+            self.draw_transport();
+            if self.tooltip.is_some() {
+                self.show_tooltip();
+            }
+        }
+    }
+
+    // Selects the tile under the cursor, or deselects it if it's already selected. Selecting a unit
+    // shows where it can move and attack instead
+    fn toggle_selection(&mut self) {
+        let pos = self.cursor_pos;
+        if let Some(origin) = self.selected_unit {
+            // Only free tiles are highlighted for movement, besides the unit's own, which it can
+            // stay on if it's able to move at all
+            let in_reach = self.highlights.get(&pos) == Some(&style::HighlightKind::Movement);
+            if in_reach && (pos != origin || self.can_move(origin)) {
+                return self.start_move(origin, pos);
+            }
+        }
+        if self.is_in_sight(pos) && self.get_unit(pos).is_some() {
+            return self.toggle_unit_selection();
+        }
+        if self.highlights.remove(&pos).is_none() {
+            self.highlights.insert(pos, style::HighlightKind::Selection);
+            self.emit(sound::GameSignal::SelectionConfirmed);
+        }
+        self.redraw_tiles(std::iter::once(pos));
+    }
+
+    // Shows the tiles that the unit under the cursor can move to and attack in place of any other
+    // highlights, or hides them if the unit is already selected
+    fn toggle_unit_selection(&mut self) {
+        let pos = self.cursor_pos;
+        let old = std::mem::take(&mut self.highlights);
+        if self.selected_unit.take() != Some(pos) {
+            (self.highlights, self.move_costs) = self.get_unit_ranges(pos);
+            self.selected_unit = Some(pos);
+            self.emit(sound::GameSignal::SelectionConfirmed);
+        }
+        let changed: std::collections::HashSet<_> = old
+            .into_keys()
+            .chain(self.highlights.keys().copied())
+            .collect();
+        self.redraw_tiles(changed.into_iter());
+    }
+
+    // Gets the highlights for the tiles that the unit at a position can move to and attack, along
+    // with the cheapest cost of moving to each tile it can move to or through. Tiles it can move to
+    // are only highlighted for movement, even if it could also attack them
+    fn get_unit_ranges(
+        &self,
+        pos: Vector<MapDistance>,
+    ) -> (
+        std::collections::HashMap<Vector<MapDistance>, style::HighlightKind>,
+        MoveCosts,
+    ) {
+        let unit = match self.get_unit(pos) {
+            Some(u) => &u.info,
+            None => return Default::default(),
+        };
+        let on_team = |p, same: bool| {
+            self.get_unit(p)
+                .is_some_and(|other| (other.info.team == unit.team) == same)
+        };
+        let map_size = self.get_map_size();
+        let moves = pathfinding::moves(
+            pos,
+            unit.movement,
+            map_size,
+            |p| self.get_move_cost(p),
+            |p| self.get_occupant(&unit.team, p),
+            self.settings.pass_through_allies,
+        );
+        let attacks = pathfinding::attack_range(
+            moves.destinations.iter().copied(),
+            &unit.weapon_range(),
+            map_size,
+            |p| on_team(p, true),
+        );
+        let highlights = attacks
+            .into_iter()
+            .map(|p| (p, style::HighlightKind::Attack))
+            .chain(
+                moves
+                    .destinations
+                    .iter()
+                    .map(|&p| (p, style::HighlightKind::Movement)),
+            )
+            .collect();
+        (highlights, moves.costs)
+    }
+
+    // Gets what it costs to enter a tile, whoever is standing on it
+    fn get_move_cost(&self, pos: Vector<MapDistance>) -> Option<u32> {
+        Some(self.get_tile(pos).info.move_cost)
+    }
+
+    // Gets who is standing on a tile, as far as a unit on a team moving through it goes
+    fn get_occupant(&self, team: &str, pos: Vector<MapDistance>) -> pathfinding::Occupant {
+        match self.get_unit(pos) {
+            None => pathfinding::Occupant::Empty,
+            Some(u) if u.info.team == team => pathfinding::Occupant::Ally,
+            Some(_) => pathfinding::Occupant::Enemy,
+        }
+    }
+
+    // Gets the team of the player whose turn it is
+    fn current_team(&self) -> &str {
+        player_team(&self.settings, self.player)
+    }
+
+    // Whether the player whose turn it is plays on the other machine
+    fn is_remote_turn(&self) -> bool {
+        self.remote
+            .as_ref()
+            .is_some_and(|s| s.plays(self.current_team()))
+    }
+
+    // Checks whether the unit at a position is one of the current player's that hasn't acted yet,
+    // and that the player is at this machine
+    fn can_move(&self, pos: Vector<MapDistance>) -> bool {
+        self.get_unit(pos).is_some_and(|u| {
+            !u.info.statuses.contains(&serialization::Status::Acted)
+                && u.info.team == self.current_team()
+        }) && !self.is_remote_turn()
+    }
+
+    // Moves the selected unit without committing to the move, then asks what it should do there. Only
+    // the player's units that haven't acted yet can be moved
+    fn start_move(&mut self, origin: Vector<MapDistance>, destination: Vector<MapDistance>) {
+        let unit = match self.get_unit_index(origin) {
+            Some(u) => u,
+            None => return,
+        };
+        if !self.can_move(origin) {
+            self.emit(sound::GameSignal::Error);
+            return;
+        }
+        // The unit glides along the way it would take rather than jumping straight there
+        let path = pathfinding::path(
+            origin,
+            destination,
+            &self.move_costs,
+            self.get_map_size(),
+            |p| self.get_move_cost(p),
+        );
+        self.pending_move = Some(PendingMove {
+            unit,
+            origin,
+            destination,
+        });
+        if let Some(path) = path.filter(|p| p.len() > 1) {
+            // The whole screen is drawn on each frame until the unit gets there
+            self.move_animation = Some(MoveAnimation {
+                unit,
+                path,
+                start: self.now(),
+                footstep: None,
+            });
+        }
+        self.invalidate_danger_zones(&[origin, destination]);
+        let mut changed = std::mem::take(&mut self.highlights);
+        // Origin and destination are highlighted, so they're drawn again along with the rest
+        changed.insert(origin, style::HighlightKind::Movement);
+        changed.insert(destination, style::HighlightKind::Movement);
+        self.redraw_tiles(changed.into_keys());
+        if self.move_animation.is_none() {
+            self.open_action_menu();
+        }
+    }
+
+    // Gets how far along its path the moving unit is at a given moment, as the index of the last
+    // tile it passed along with the fraction of the way it is to the next one. None if no unit is
+    // moving or it has reached the end of its path
+    fn move_step_at(&self, now: P::Instant) -> Option<(usize, f64)> {
+        let animation = self.move_animation.as_ref()?;
+        let elapsed = P::to_nanoseconds(P::duration_between(animation.start, now));
+        let steps = elapsed as f64 / MOVE_STEP_NS as f64;
+        let step = steps.floor() as usize;
+        (step + 1 < animation.path.len()).then_some((step, steps - step as f64))
+    }
+
+    // Advances the moving unit to the current moment, scrolling the screen to the tile it's headed
+    // to if that's off of it and playing the footstep of that tile as it sets off toward it
+    fn update_movement(&mut self) {
+        let next = match (self.move_animation.as_ref(), self.move_step_at(self.now())) {
+            (Some(animation), Some((step, _))) => animation.path[step + 1],
+            (Some(_), None) => return self.finish_movement(),
+            (None, _) => return,
+        };
+        let stepped = self
+            .move_animation
+            .as_mut()
+            .is_some_and(|a| a.footstep.replace(next) != Some(next));
+        if stepped {
+            if let Some(path) = self.get_tile(next).info.footstep_sound.as_ref() {
+                self.platform.play_sound(path.as_str(), sound::MAX_VOLUME);
+            }
+        }
+        let top_left = self.get_top_left_showing(next);
+        if top_left != self.screen.top_left {
+            self.pan(top_left);
+        }
+    }
+
+    // Puts the moving unit where it's going right away and asks what it should do there
+    fn finish_movement(&mut self) {
+        let animation = match self.move_animation.take() {
+            Some(a) => a,
+            None => return,
+        };
+        self.redraw();
+        if let Some(&destination) = animation.path.last() {
+            self.go_to(destination);
+        }
+        self.open_action_menu();
+    }
+
+    // Gets where the moving unit is drawn, partway from the last tile it passed to the next one
+    fn get_moving_unit_screen_pos(
+        &self,
+        region: &viewport::VisibleRegion,
+    ) -> Option<Rectangle<P::ScreenDistance>> {
+        let path = &self.move_animation.as_ref()?.path;
+        let (from, to, fraction) = match self.move_step_at(self.now()) {
+            Some((step, fraction)) => (path[step], path[step + 1], fraction),
+            None => (*path.last()?, *path.last()?, 0.0),
+        };
+        self.get_screen_pos_between(region, from, to, fraction)
+    }
+
+    // Gets where something is drawn a fraction of the way from one tile to another
+    fn get_screen_pos_between(
+        &self,
+        region: &viewport::VisibleRegion,
+        from: Vector<MapDistance>,
+        to: Vector<MapDistance>,
+        fraction: f64,
+    ) -> Option<Rectangle<P::ScreenDistance>> {
+        let layout = self.layout();
+        let origin = layout.viewport.top_left.lossy_cast::<f64>()?;
+        let from = region.tile_offset(from);
+        let to = region.tile_offset(to);
+        Some(Rectangle {
+            top_left: Vector {
+                x: P::ScreenDistance::from_f64(origin.x + from.x + (to.x - from.x) * fraction)?,
+                y: P::ScreenDistance::from_f64(origin.y + from.y + (to.y - from.y) * fraction)?,
+            },
+            size: layout.tile_size,
+        })
+    }
+
+    // Opens the menu of actions that the unit with a pending move can take. Attacking is only
+    // offered if an enemy is in range
+    fn open_action_menu(&mut self) {
+        let pending = match self.pending_move {
+            Some(p) => p,
+            None => return,
+        };
+        let info = &self.units[pending.unit].info;
+        let map_size = self.get_map_size();
+        let targets = pathfinding::attack_range(
+            [pending.destination],
+            &info.weapon_range(),
+            map_size,
+            |_| false,
+        );
+        let mut targets: Vec<_> = targets
+            .into_iter()
+            .filter(|p| self.is_in_sight(*p))
+            .filter_map(|p| self.get_unit_index(p))
+            .filter(|&i| self.units[i].info.team != info.team)
+            .collect();
+        // Listed in the order the map lists them rather than whatever order they were found in
+        targets.sort_unstable();
+        let mut items: Vec<_> = targets
+            .into_iter()
+            .map(|i| {
+                let name = self.text(self.units[i].info.name.as_str());
+                (
+                    self.format_text("menu.attack", &[name]),
+                    MenuAction::Attack(i),
+                )
+            })
+            .collect();
+        items.push((self.text("menu.wait").to_owned(), MenuAction::Wait));
+        self.show_menu(items);
+    }
+
+    // Commits the pending move, after which the unit has acted for the turn
+    fn commit_move(&mut self) {
+        let pending = match self.pending_move.take() {
+            Some(p) => p,
+            None => return,
+        };
+        let info = &mut self.units[pending.unit].info;
+        info.x = pending.destination.x;
+        info.y = pending.destination.y;
+        if !info.statuses.contains(&serialization::Status::Acted) {
+            info.statuses.push(serialization::Status::Acted);
+        }
+        self.ready_units.retain(|&u| u != pending.unit);
+        self.selected_unit = None;
+        self.redraw_tiles(std::iter::once(pending.destination));
+    }
+
+    // Commits the pending move without doing anything else, then moves on to the next unit
+    fn wait(&mut self) {
+        let pending = match self.pending_move {
+            Some(p) => p,
+            None => return,
+        };
+        self.perform(net::Command::Wait {
+            origin: pending.origin,
+            destination: pending.destination,
+        });
+        self.snap_to_ready_unit();
+    }
+
+    // Carries out a command from the player whose turn it is, which is sent to the other player's
+    // game first if the player is at this machine. Either way, it's carried out here the same way
+    fn perform(&mut self, command: net::Command) {
+        if !self.is_remote_turn() && self.remote.is_some() {
+            let state_hash = self.shared_state_hash();
+            if let Some(session) = self.remote.as_mut() {
+                session.send::<P>(command, state_hash);
+            }
+        }
+        match command {
+            net::Command::Wait {
+                origin,
+                destination,
+            } => {
+                self.stage_move(origin, destination);
+                self.commit_move();
+            }
+            net::Command::Attack {
+                origin,
+                destination,
+                target,
+            } => {
+                self.stage_move(origin, destination);
+                if let Some(target) = self.get_unit_index(target) {
+                    self.resolve_attack(target);
+                }
+            }
+            net::Command::EndTurn => self.end_turn(),
+        }
+    }
+
+    // Makes a move from the other player's game the pending move, as though it had been made here.
+    // Moves made here are already pending
+    fn stage_move(&mut self, origin: Vector<MapDistance>, destination: Vector<MapDistance>) {
+        if self.pending_move.is_some() {
+            return;
+        }
+        let unit = match self.get_unit_index(origin) {
+            Some(u) => u,
+            None => return,
+        };
+        self.pending_move = Some(PendingMove {
+            unit,
+            origin,
+            destination,
+        });
+        self.invalidate_danger_zones(&[origin, destination]);
+        self.redraw_tiles([origin, destination].iter().copied());
+    }
+
+    // Checks that a command from the other player's game is one that its player could have given
+    // here: that it's their turn, and that the unit is theirs, hasn't acted yet, and can reach
+    // where it went and what it attacked. Returns what's wrong with it otherwise
+    fn check_command(&self, command: &net::Command) -> Result<(), String> {
+        if !self.is_remote_turn() {
+            return Err("it isn't the other player's turn".to_owned());
+        }
+        let (origin, destination, target) = match *command {
+            net::Command::EndTurn => return Ok(()),
+            net::Command::Wait {
+                origin,
+                destination,
+            } => (origin, destination, None),
+            net::Command::Attack {
+                origin,
+                destination,
+                target,
+            } => (origin, destination, Some(target)),
+        };
+        let at = |p: Vector<MapDistance>| format!("({}, {})", p.x, p.y);
+        let unit = match self.get_unit(origin) {
+            Some(u) if u.info.team == self.current_team() => &u.info,
+            _ => return Err(format!("they have no unit at {}", at(origin))),
+        };
+        if unit.statuses.contains(&serialization::Status::Acted) {
+            return Err(format!("the unit at {} has already acted", at(origin)));
+        }
+        let (ranges, _) = self.get_unit_ranges(origin);
+        if ranges.get(&destination) != Some(&style::HighlightKind::Movement) {
+            let msg = format!("the unit at {} can't reach {}", at(origin), at(destination));
+            return Err(msg);
+        }
+        let target_pos = match target {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+        let target = match self.get_unit(target_pos) {
+            Some(u) if u.info.team != unit.team => &u.info,
+            _ => return Err(format!("there's no enemy at {}", at(target_pos))),
+        };
+        let offset = Vector {
+            x: target_pos.x as i64 - destination.x as i64,
+            y: target_pos.y as i64 - destination.y as i64,
+        };
+        let tile = self.get_tile(destination).info;
+        let target_tile = self.get_tile(target_pos).info;
+        match combat::forecast(unit, tile, target, target_tile, offset) {
+            Some(_) => Ok(()),
+            None => Err(format!(
+                "{} is out of range from {}",
+                at(target_pos),
+                at(destination)
+            )),
+        }
+    }
+
+    // Handles a message from the other player's game. Commands are carried out unless they're out
+    // of order, were given in a different state or aren't ones its player could have given
+    fn handle_remote_message(&mut self, bytes: &[u8]) {
+        let session = match self.remote.as_mut() {
+            Some(s) => s,
+            None => return log::warn::<P>("Ignoring a message from another game"),
+        };
+        let message = match net::decode(session.url(), bytes) {
+            Ok(m) => m,
+            Err(e) => return log::error::<P>(e.to_string().as_str()),
+        };
+        let received = match session.receive::<P>(message) {
+            Some(r) => r,
+            None => return,
+        };
+        // The last fight has to be settled for the game to be in the state the other game sees
+        self.finish_combat();
+        let (command, state_hash) = match received {
+            net::Received::Command {
+                command,
+                state_hash,
+            } => (command, state_hash),
+            net::Received::StateWanted => {
+                let snapshot = self.snapshot();
+                if let Some(session) = self.remote.as_ref() {
+                    session.send_state::<P>(snapshot);
+                }
+                return;
+            }
+            net::Received::State(snapshot) => {
+                // What this player sees is kept, and the fog is worked out again from the units
+                let snapshot = save::Snapshot {
+                    cursor: self.cursor_pos,
+                    fog: None,
+                    ..*snapshot
+                };
+                self.pending_load = Some(PendingLoad::Restore(Box::new(snapshot)));
+                return;
+            }
+        };
+        let checked = if state_hash != self.shared_state_hash() {
+            Err("the games were in different states when it was given".to_owned())
+        } else {
+            self.check_command(&command)
+        };
+        if let Err(problem) = checked {
+            let msg = format!("Refusing {:?} from the other player: {}", command, problem);
+            log::error::<P>(msg.as_str());
+            // The games no longer agree on what's happened, so every later command would be
+            // refused too. Dropping the connection has this game pick up from where the other is
+            // once the player reconnects
+            if let Some(session) = self.remote.as_mut() {
+                session.fall_out_of_step();
+            }
+            return self.lose_connection();
+        }
+        if let Some(session) = self.remote.as_mut() {
+            session.accept();
+        }
+        self.perform(command);
+    }
+
+    // Connects to the other player's game, if there is one, showing that the connection was lost
+    // if it can't
+    fn connect_remote(&mut self) {
+        let session = match self.remote.as_mut() {
+            Some(s) => s,
+            None => return,
+        };
+        if let Err(e) = session.connect(&self.platform) {
+            log::error::<P>(e.to_string().as_str());
+            self.lose_connection();
+        }
+    }
+
+    // Shows that the connection to the other player's game was lost until the player asks to
+    // connect again. The game carries on meanwhile, and commands given here are sent once the games
+    // are connected again
+    fn lose_connection(&mut self) {
+        match self.remote.as_mut() {
+            Some(session) => session.disconnect(),
+            None => return,
+        }
+        let hides_map = self.banner.as_ref().is_some_and(|b| b.hides_map);
+        self.show_banner(Banner {
+            text: self.text("banner.disconnected").to_owned(),
+            hides_map,
+            reconnect: true,
+        });
+        self.redraw();
+    }
+
+    // Takes the messages that arrive from the other player's game since it was last connected to,
+    // for the loop that waits for events to wait for them as well
+    pub fn take_incoming(&mut self) -> Option<net::Incoming> {
+        self.remote.as_mut()?.take_incoming()
+    }
+
+    // Moves the cursor to whichever of the player's units that can still act is nearest to it (the
+    // first of them in order if several are as near), e.g. once a unit has finished acting. The
+    // cursor stays where it is if every unit has acted
+    fn snap_to_ready_unit(&mut self) {
+        let from = self.cursor_pos;
+        let nearest = self
+            .ready_units
+            .iter()
+            .map(|&u| self.get_unit_pos(u))
+            .min_by_key(|p| p.manhattan_distance(from));
+        if let Some(pos) = nearest {
+            self.go_to(pos);
+        }
+    }
+
+    // Moves the cursor to the unit after the one under it among those that can still act, going
+    // back to the first after the last, and centers the screen on it
+    fn next_ready_unit(&mut self) {
+        let current = self
+            .get_unit_index(self.cursor_pos)
+            .and_then(|unit| self.ready_units.iter().position(|&u| u == unit));
+        let next = match current {
+            Some(i) => self.ready_units.get(i + 1).or(self.ready_units.first()),
+            None => self.ready_units.first(),
+        };
+        let pos = match next {
+            Some(&u) => self.get_unit_pos(u),
+            None => {
+                self.emit(sound::GameSignal::Error);
+                return;
+            }
+        };
+        let top_left = self.get_centered_top_left(pos);
+        self.show_at(pos, top_left);
+    }
+
+    // Commits the pending move and has the unit attack another from where it ended up. Nothing is
+    // committed if the attacker's weapon can't reach the target from there
+    fn attack(&mut self, target: usize) {
+        let pending = match self.pending_move {
+            Some(p) => p,
+            None => return,
+        };
+        if self.get_forecast(pending.unit, target).is_none() {
+            self.emit(sound::GameSignal::Error);
+            return;
+        }
+        self.perform(net::Command::Attack {
+            origin: pending.origin,
+            destination: pending.destination,
+            target: self.get_unit_pos(target),
+        });
+    }
+
+    // Commits the pending move and plays out the attack. The attacker earns experience if it
+    // survives, and whoever dies is taken off the map
+    fn resolve_attack(&mut self, target: usize) {
+        let attacker = match self.pending_move {
+            Some(p) => p.unit,
+            None => return,
+        };
+        let forecast = match self.get_forecast(attacker, target) {
+            Some(f) => f,
+            None => return,
+        };
+        self.commit_move();
+        let mut attacker_info = self.units[attacker].info.clone();
+        let mut target_info = self.units[target].info.clone();
+        let outcome = combat::resolve(&mut attacker_info, &mut target_info, &forecast);
+        self.combat_animation = Some(CombatAnimation {
+            attacker,
+            target,
+            outcome,
+            attacker_hp: self.units[attacker].info.hp,
+            target_hp: self.units[target].info.hp,
+            start: self.now(),
+        });
+        self.units[attacker].info = attacker_info;
+        self.units[target].info = target_info;
+        self.redraw();
+        // Fights that aren't animated are settled straight away
+        self.update_combat();
+    }
+
+    // Gets how far through the fight being played out the attacker's lunge and then the damage are
+    // at a given moment (each from 0 to 1). None if no fight is being played out or it's over
+    fn combat_progress_at(&self, now: P::Instant) -> Option<(f64, f64)> {
+        let animation = self.combat_animation.as_ref()?;
+        let elapsed = P::to_nanoseconds(P::duration_between(animation.start, now));
+        let nudge = self.settings.combat_nudge_ms.saturating_mul(1_000_000);
+        let damage = self.settings.combat_damage_ms.saturating_mul(1_000_000);
+        if elapsed >= nudge.saturating_add(damage) {
+            return None;
+        }
+        let progress = |elapsed: u64, duration: u64| match duration {
+            0 => 1.0,
+            _ => (elapsed as f64 / duration as f64).min(1.0),
+        };
+        Some((
+            progress(elapsed, nudge),
+            progress(elapsed.saturating_sub(nudge), damage),
+        ))
+    }
+
+    // Settles the fight being played out once it's over
+    fn update_combat(&mut self) {
+        if self.combat_animation.is_some() && self.combat_progress_at(self.now()).is_none() {
+            self.finish_combat();
+        }
+    }
+
+    // Settles the fight being played out right away. The attacker earns experience if it survived,
+    // and whoever died is taken off the map
+    fn finish_combat(&mut self) {
+        let CombatAnimation {
+            attacker,
+            target,
+            outcome,
+            ..
+        } = match self.combat_animation.take() {
+            Some(a) => a,
+            None => return,
+        };
+        self.award_experience(attacker, &outcome);
+        let mut dead = Vec::new();
+        if outcome.attacker_killed {
+            dead.push(attacker);
+        }
+        if outcome.defender_killed {
+            dead.push(target);
+        }
+        if !dead.is_empty() {
+            // Removing units changes the indices of the ones after them, so no zone can be kept
+            dead.sort_unstable();
+            for &i in dead.iter().rev() {
+                self.units.remove(i);
+            }
+            self.danger_zones.clear();
+            self.ready_units = ready_units(&self.units, self.current_team());
+            if self.show_danger_zone {
+                self.refresh_danger_zone();
+            }
+        }
+        // Damage numbers can float onto other tiles, so everything is drawn again
+        self.redraw();
+        self.snap_to_ready_unit();
+    }
+
+    // Draws the fight being played out: the attacker partway through its lunge, and both sides with
+    // their hp bars partly drained and the damage they took floating up from them
+    fn draw_combat(&self, region: &viewport::VisibleRegion) {
+        let animation = match self.combat_animation.as_ref() {
+            Some(a) => a,
+            None => return,
+        };
+        let (nudge, damage) = self.combat_progress_at(self.now()).unwrap_or((1.0, 1.0));
+        let attacker_pos = self.get_unit_pos(animation.attacker);
+        let target_pos = self.get_unit_pos(animation.target);
+        // Out to the furthest point of the lunge halfway through it, then back again
+        let lunge = COMBAT_NUDGE_TILES * (1.0 - (2.0 * nudge - 1.0).abs());
+        let outcome = &animation.outcome;
+        let sides = [
+            (
+                animation.attacker,
+                self.get_screen_pos_between(region, attacker_pos, target_pos, lunge),
+                animation.attacker_hp,
+                // Only shown if the target struck back
+                Some(outcome.damage_taken).filter(|&d| d > 0),
+            ),
+            (
+                animation.target,
+                self.get_screen_pos_between(region, target_pos, target_pos, 0.0),
+                animation.target_hp,
+                Some(outcome.damage_dealt),
+            ),
+        ];
+        for (unit, screen_pos, hp_before, damage_taken) in sides {
+            let screen_pos = match screen_pos {
+                Some(p) => p,
+                None => continue,
+            };
+            let unit = &self.units[unit];
+            let lost = hp_before.saturating_sub(unit.info.hp) as f64;
+            let hp = hp_before.saturating_sub((lost * damage).round() as u32);
+            self.draw_unit(unit, &screen_pos, hp);
+            match damage_taken {
+                Some(d) if nudge >= 1.0 => self.draw_damage_number(&screen_pos, d, damage),
+                _ => {}
+            }
+        }
+    }
+
+    // Draws damage that a unit took, rising from the middle of its tile by half a tile and fading
+    // away as progress goes from 0 to 1
+    fn draw_damage_number(
+        &self,
+        screen_pos: &Rectangle<P::ScreenDistance>,
+        damage: u32,
+        progress: f64,
+    ) {
+        let height = screen_pos.height().to_f64().unwrap_or(0.0);
+        let top = screen_pos.top().to_f64().unwrap_or(0.0) + height * (0.25 - 0.5 * progress);
+        let top = match P::ScreenDistance::from_f64(top) {
+            Some(t) => t,
+            None => return,
+        };
+        let color = style::DAMAGE_NUMBER.color;
+        let alpha = (color.alpha as f64 * (1.0 - progress)).round() as u8;
+        let style = style::TextStyle {
+            color: style::Color { alpha, ..color },
+            ..style::DAMAGE_NUMBER
+        };
+        let offset = Vector {
+            x: screen_pos.left(),
+            y: top,
+        };
+        self.draw_text(
+            damage.to_string().as_str(),
+            offset,
+            screen_pos.width(),
+            &style,
+        );
+    }
+
+    // Works out what would happen if one unit attacked another from where they are now, counting the
+    // tiles they're standing on. None if the attacker's weapon can't reach the target
+    fn get_forecast(&self, attacker: usize, target: usize) -> Option<combat::Forecast> {
+        let attacker_pos = self.get_unit_pos(attacker);
+        let target_pos = self.get_unit_pos(target);
+        let offset = Vector {
+            x: target_pos.x as i64 - attacker_pos.x as i64,
+            y: target_pos.y as i64 - attacker_pos.y as i64,
+        };
+        combat::forecast(
+            &self.units[attacker].info,
+            self.get_tile(attacker_pos).info,
+            &self.units[target].info,
+            self.get_tile(target_pos).info,
+            offset,
+        )
+    }
+
+    // Gets the unit with a pending move and the enemy picked on the action menu as its target, if
+    // an attack is picked
+    fn get_forecast_units(&self) -> Option<(usize, usize)> {
+        let attacker = self.pending_move?.unit;
+        match self.menu.as_ref()?.selected() {
+            Some(MenuAction::Attack(target)) => Some((attacker, *target)),
+            _ => None,
+        }
+    }
+
+    // Gets where the battle forecast is drawn along with the height of each of its lines: along the
+    // bottom of the screen in the middle. None if there's no attack to forecast
+    fn get_forecast_position(&self) -> Option<(Rectangle<P::ScreenDistance>, P::ScreenDistance)> {
+        self.get_forecast_units()?;
+        let screen_size = self.layout().screen_size;
+        let width = screen_size.x / FORECAST_SCREEN_FRACTION.into();
+        let line_height = self
+            .platform
+            .get_text_height(&style::FORECAST_TEXT)
+            .unwrap_or(width / 16.into());
+        let padding = line_height / 2.into();
+        let bottom = screen_size.y - self.get_hint_bar_height();
+        let height = partial_ord_min(
+            padding + line_height * FORECAST_LINES.into() + padding,
+            bottom,
+        );
+        let position = Rectangle {
+            top_left: Vector {
+                x: (screen_size.x - width) / 2.into(),
+                y: bottom - height,
+            },
+            size: Vector {
+                x: width,
+                y: height,
+            },
+        };
+        Some((position, line_height))
+    }
+
+    // Draws what each side would do in the attack picked on the action menu: the attacker in the
+    // left column and the defender in the right
+    fn draw_forecast(&mut self) {
+        self.forecast_panel = None;
+        let ((attacker, target), (position, line_height)) =
+            match (self.get_forecast_units(), self.get_forecast_position()) {
+                (Some(u), Some(p)) => (u, p),
+                _ => return,
+            };
+        let forecast = match self.get_forecast(attacker, target) {
+            Some(f) => f,
+            None => return,
+        };
+        self.fill_rect(&position, style::FORECAST_BACKGROUND);
+        let padding = line_height / 2.into();
+        let column_width = (position.width() - padding - padding - padding) / 2.into();
+        let columns = [
+            (attacker, forecast.attacker, position.left() + padding),
+            (
+                target,
+                forecast.defender,
+                position.left() + padding + column_width + padding,
+            ),
+        ];
+        for (unit, strikes, left) in columns {
+            let info = &self.units[unit].info;
+            let mut y = position.top() + padding;
+            let name_style = &style::FORECAST_NAME;
+            let name = self.text(info.name.as_str());
+            let name = text::fit(&self.platform, name, column_width, name_style);
+            self.draw_text(
+                name.as_str(),
+                Vector { x: left, y },
+                column_width,
+                name_style,
+            );
+            // Each figure is named on the left of its line with its value lined up on the right
+            let text = &style::FORECAST_TEXT;
+            let figures = [
+                ("stat.hp", info.hp.to_string()),
+                ("forecast.damage", strikes.damage.to_string()),
+                ("forecast.hits", strikes.hits.to_string()),
+                ("forecast.terrain", format!("{:+}", strikes.terrain_defense)),
+            ];
+            for (label, value) in figures {
+                y = y + line_height;
+                let label = self.text(label);
+                self.draw_text(label, Vector { x: left, y }, column_width, text);
+                let value_width = partial_ord_min(
+                    self.platform.measure_text(value.as_str(), text),
+                    column_width,
+                );
+                let value_pos = Vector {
+                    x: left + column_width - value_width,
+                    y,
+                };
+                self.draw_text(value.as_str(), value_pos, value_width, text);
+            }
+        }
+        self.forecast_panel = Some(position);
+    }
+
+    // Draws the battle forecast for the attack picked on the action menu, first uncovering wherever
+    // it was drawn before if there's no longer an attack to forecast
+    fn update_forecast(&mut self) {
+        let position = self.get_forecast_position().map(|(p, _)| p);
+        if let Some(old) = self.forecast_panel.filter(|&old| Some(old) != position) {
+            self.redraw_region(&old);
+            self.draw_cursor();
+            self.draw_infobar();
+            self.restore_minimap(&[Some(old)]);
+            self.update_unit_panel();
+        }
+        self.draw_forecast();
+    }
+
+    // Gives a unit the experience it earned in a fight, saving its progress if it's one of the
+    // player's and showing what went up if it went up a level
+    fn award_experience(&mut self, unit: usize, outcome: &combat::Outcome) {
+        let info = &mut self.units[unit].info;
+        let level_up = experience::award(info, experience::earned(outcome), &mut self.rng);
+        if info.team == serialization::PLAYER_TEAM && info.hp > 0 {
+            roster::record(&self.platform, info);
+        }
+        let level_up = match level_up {
+            Some(l) => l,
+            None => return,
+        };
+        let (name, level) = (info.name.clone(), info.level.to_string());
+        let name = self.text(name.as_str());
+        let mut lines = vec![self.format_text("level_up.heading", &[name, level.as_str()])];
+        lines.extend(level_up.gains.iter().map(|(stat, gain)| {
+            let gain = gain.to_string();
+            self.format_text("stat.gain", &[self.text(stat.key()), gain.as_str()])
+        }));
+        if let Some(old) = self.level_up_popup.take() {
+            // Only one popup is shown at a time
+            let position = self.get_level_up_popup_position(&old.lines);
+            self.uncover(&position);
+        }
+        self.level_up_popup = Some(LevelUpPopup {
+            lines,
+            shown_at: self.now(),
+        });
+    }
+
+    // Takes back the pending move, returning the unit and the cursor to where the unit started and
+    // showing its ranges again so that it can be moved somewhere else
+    fn undo_move(&mut self) {
+        let pending = match self.pending_move.take() {
+            Some(p) => p,
+            None => return,
+        };
+        self.invalidate_danger_zones(&[pending.origin, pending.destination]);
+        (self.highlights, self.move_costs) = self.get_unit_ranges(pending.origin);
+        self.selected_unit = Some(pending.origin);
+        let mut changed: std::collections::HashSet<_> = self.highlights.keys().copied().collect();
+        changed.insert(pending.destination);
+        self.redraw_tiles(changed.into_iter());
+        self.go_to(pending.origin);
+    }
+
+    // Ends the current player's turn, taking back any move that hasn't been committed and letting
+    // every unit act again. When players take turns on the same machine, the next player is
+    // announced with a banner
+    fn end_turn(&mut self) {
+        if let Some(pending) = self.pending_move.take() {
+            self.invalidate_danger_zones(&[pending.origin, pending.destination]);
+        }
+        self.selected_unit = None;
+        self.highlights.clear();
+        for unit in self.units.iter_mut() {
+            unit.info
+                .statuses
+                .retain(|s| *s != serialization::Status::Acted);
+        }
+        let players = self.settings.players.len().max(1);
+        self.player = (self.player + 1) % players;
+        if self.player == 0 {
+            self.turn += 1;
+        }
+        self.ready_units = ready_units(&self.units, self.current_team());
+        if players > 1 {
+            let key = format!("team.{}", self.current_team());
+            let team = self.text(key.as_str()).to_owned();
+            // Players on different machines can't see each other's screens anyway
+            let hides_map =
+                self.visibility.is_some() && self.settings.fog_handoff && self.remote.is_none();
+            self.show_banner(Banner {
+                text: self.format_text("banner.turn", &[team.as_str()]),
+                hides_map,
+                reconnect: false,
+            });
+            if !self.is_remote_turn() {
+                self.snap_to_ready_unit();
+            }
+        }
+        // The other players' units are the enemies now
+        if self.show_danger_zone {
+            self.refresh_danger_zone();
+        }
+        if self.settings.autosave {
+            self.pending_autosave = Some(Box::new(self.snapshot()));
+        }
+        self.redraw();
+    }
+
+    // Gets how long until the game should next be autosaved (None if there's nothing to save)
+    fn autosave_wait(&self) -> Option<u64> {
+        if self.autosave.is_some() {
+            return Some(0);
+        }
+        self.pending_autosave.as_ref()?;
+        let interval = self.settings.autosave_interval_ms.saturating_mul(1_000_000);
+        Some(match self.last_autosave {
+            Some(last) => {
+                interval.saturating_sub(P::to_nanoseconds(P::duration_between(last, self.now())))
+            }
+            None => 0,
+        })
+    }
+
+    // Carries out the next step of the autosave being written, or starts autosaving the end of
+    // the latest turn once enough time has passed since the last autosave. Saves are written a
+    // step at a time between frames so that events are never held up for long
+    fn update_autosave(&mut self) {
+        if let Some(autosave) = self.autosave.as_mut() {
+            if autosave.advance(&self.platform) {
+                self.autosave = None;
+            }
+            return;
+        }
+        if self.autosave_wait() != Some(0) {
+            return;
+        }
+        if let Some(snapshot) = self.pending_autosave.take() {
+            self.autosave = save::Autosave::new(&self.platform, &snapshot);
+            self.last_autosave = Some(self.now());
+        }
+    }
+
+    // Shows or hides the tiles that enemies could attack on their next turn
+    fn toggle_danger_zone(&mut self) {
+        self.show_danger_zone = !self.show_danger_zone;
+        if self.show_danger_zone {
+            self.refresh_danger_zone();
+        } else {
+            let shaded = std::mem::take(&mut self.danger_tiles);
+            self.redraw_tiles(shaded.into_iter());
+        }
+    }
+
+    // Drops the danger zones that a unit arriving at or leaving any of the changed positions could
+    // affect: those that include the position or that the position blocks the way out of
+    fn invalidate_danger_zones(&mut self, changed: &[Vector<MapDistance>]) {
+        let map_size = self.get_map_size();
+        self.danger_zones.retain(|_, zone| {
+            changed.iter().all(|&p| {
+                !zone.tiles.contains(&p)
+                    && pathfinding::neighbors(p, map_size).all(|n| !zone.reach.contains(&n))
+            })
+        });
+        if self.show_danger_zone {
+            self.refresh_danger_zone();
+        }
+    }
+
+    // Works out the danger zones of enemies that don't have one yet, then redraws the tiles whose
+    // shading changed
+    fn refresh_danger_zone(&mut self) {
+        let start = P::now();
+        let enemies: Vec<_> = (0..self.units.len())
+            .filter(|&i| self.units[i].info.team != self.current_team())
+            .collect();
+        let mut worked_out = 0;
+        for &enemy in enemies.iter() {
+            if self.danger_zones.contains_key(&enemy) {
+                continue;
+            }
+            let (ranges, _) = self.get_unit_ranges(self.get_unit_pos(enemy));
+            let reach = ranges
+                .iter()
+                .filter(|(_, kind)| **kind == style::HighlightKind::Movement)
+                .map(|(p, _)| *p)
+                .collect();
+            let tiles = ranges.into_keys().collect();
+            self.danger_zones.insert(enemy, DangerZone { reach, tiles });
+            worked_out += 1;
+        }
+        if worked_out > 0 {
+            let elapsed = P::to_nanoseconds(P::duration_between(start, P::now()));
+            let msg = format!(
+                "Worked out danger zones for {} of {} enemies in {}us",
+                worked_out,
+                enemies.len(),
+                elapsed / 1000
+            );
+            log::debug::<P>(msg.as_str());
+        }
+        // Enemies that haven't been seen yet don't give themselves away
+        let shaded: std::collections::HashSet<_> = enemies
+            .into_iter()
+            .filter(|&enemy| !self.is_hidden(self.get_unit_pos(enemy)))
+            .flat_map(|enemy| self.danger_zones[&enemy].tiles.iter().copied())
+            .collect();
+        let old = std::mem::replace(&mut self.danger_tiles, shaded);
+        let changed: Vec<_> = old
+            .symmetric_difference(&self.danger_tiles)
+            .copied()
+            .collect();
+        if !changed.is_empty() {
+            self.redraw_tiles(changed.into_iter());
+        }
+    }
+
+    // Removes every highlight, only redrawing the tiles that had one
+    fn clear_highlights(&mut self) {
+        self.selected_unit = None;
+        let highlights = std::mem::take(&mut self.highlights);
+        self.redraw_tiles(highlights.into_keys());
+    }
+
+    // Gets where the minimap is drawn along with the size of each tile on it
+    fn get_minimap_position(&self) -> Option<(Rectangle<P::ScreenDistance>, u32)> {
+        if self.minimap.is_empty() {
+            return None;
+        }
+        let map_size = self.get_map_size();
+        let screen_size = self.layout().screen_size;
+        let available = screen_size.lossy_cast::<u32>()? / MINIMAP_SCREEN_FRACTION;
+        let block = (available.x / map_size.x)
+            .min(available.y / map_size.y)
+            .clamp(1, MINIMAP_MAX_BLOCK);
+        let size = Vector {
+            x: (map_size.x * block).into(),
+            y: (map_size.y * block).into(),
+        };
+        let hint_bar_height = self.get_hint_bar_height();
+        if size.x > screen_size.x || size.y + hint_bar_height > screen_size.y {
+            return None;
+        }
+        let position = Rectangle {
+            top_left: Vector {
+                x: screen_size.x - size.x,
+                y: screen_size.y - hint_bar_height - size.y,
+            },
+            size,
+        };
+        Some((position, block))
+    }
+
+    // Draws the minimap in the bottom right corner with the visible part of the map outlined
+    fn draw_minimap(&self) {
+        let (position, block) = match self.get_minimap_position() {
+            Some(p) => p,
+            None => return,
+        };
+        let origin = position.top_left;
+        let block_size: P::ScreenDistance = block.into();
+        for run in self.minimap.iter() {
+            let rect = Rectangle {
+                top_left: origin
+                    + Vector {
+                        x: (run.column * block).into(),
+                        y: (run.row * block).into(),
+                    },
+                size: Vector {
+                    x: (run.length * block).into(),
+                    y: block_size,
+                },
+            };
+            self.fill_rect(&rect, run.color);
+        }
+        let viewport = Rectangle {
+            top_left: origin
+                + Vector {
+                    x: (self.screen.left() * block).into(),
+                    y: (self.screen.top() * block).into(),
+                },
+            size: Vector {
+                x: (self.screen.width() * block).into(),
+                y: (self.screen.height() * block).into(),
+            },
+        };
+        let one: P::ScreenDistance = 1.into();
+        let edges = [
+            (
+                viewport.top_left,
+                Vector {
+                    x: viewport.width(),
+                    y: one,
+                },
+            ),
+            (
+                viewport.top_left,
+                Vector {
+                    x: one,
+                    y: viewport.height(),
+                },
+            ),
+            (
+                Vector {
+                    x: viewport.left(),
+                    y: viewport.bottom() - one,
+                },
+                Vector {
+                    x: viewport.width(),
+                    y: one,
+                },
+            ),
+            (
+                Vector {
+                    x: viewport.right() - one,
+                    y: viewport.top(),
+                },
+                Vector {
+                    x: one,
+                    y: viewport.height(),
+                },
+            ),
+        ];
+        for (top_left, size) in edges.iter() {
+            let edge = Rectangle {
+                top_left: *top_left,
+                size: *size,
+            };
+            self.fill_rect(&edge, style::WHITE);
+        }
+    }
+
+    // Redraws the minimap if something was drawn over part of it
+    fn restore_minimap(&self, drawn: &[Option<Rectangle<P::ScreenDistance>>]) {
+        if let Some((position, _)) = self.get_minimap_position() {
+            if drawn.iter().flatten().any(|r| r.intersects(&position)) {
+                self.draw_minimap();
+            }
+        }
+    }
+
+    // Finds the map tile under a point on the minimap
+    fn get_minimap_tile(&self, pos: Vector<P::MouseDistance>) -> Option<Vector<MapDistance>> {
+        let (position, block) = self.get_minimap_position()?;
+        let screen_pos = pos.cast::<P::ScreenDistance>();
+        if screen_pos.x < position.left()
+            || screen_pos.y < position.top()
+            || screen_pos.x >= position.right()
+            || screen_pos.y >= position.bottom()
+        {
+            return None;
+        }
+        let offset = (screen_pos - position.top_left).lossy_cast::<u32>()?;
+        Some(offset / block)
+    }
+
+    // Centers the screen on a tile and moves the cursor there
+    fn jump_to(&mut self, pos: Vector<MapDistance>) {
+        let top_left = self.get_centered_top_left(pos);
+        self.cursor_pos = pos;
+        if top_left != self.screen.top_left {
+            self.pan(top_left);
+        }
+        self.redraw();
+    }
+
+    // Gets where the top left of the screen would be if it were centered on a tile, as far as the
+    // edges of the map allow
+    fn get_centered_top_left(&self, pos: Vector<MapDistance>) -> Vector<MapDistance> {
+        let map_size = self.get_map_size();
+        let size = self.screen.size;
+        Vector {
+            x: pos.x.saturating_sub(size.x / 2).min(map_size.x - size.x),
+            y: pos.y.saturating_sub(size.y / 2).min(map_size.y - size.y),
+        }
+    }
+
+    // Pans the screen by however many whole tiles the map has been dragged since it was last panned,
+    // leaving the rest of the drag for later
+    fn drag(&mut self, mouse_pos: Vector<P::MouseDistance>) {
+        self.mouse_pos = Some(mouse_pos);
+        let (anchor, pos, tile_size) = match (
+            self.drag_anchor,
+            mouse_pos.cast::<P::ScreenDistance>().lossy_cast::<f64>(),
+            self.get_tile_size().lossy_cast::<f64>(),
+        ) {
+            (Some(a), Some(p), Some(t)) if t.x > 0.0 && t.y > 0.0 => (a, p, t),
+            _ => return,
+        };
+        // The map moves along with the mouse, so dragging left shows more of the right side
+        let tiles = Vector {
+            x: ((anchor.x - pos.x) / tile_size.x).trunc(),
+            y: ((anchor.y - pos.y) / tile_size.y).trunc(),
+        };
+        if tiles.x == 0.0 && tiles.y == 0.0 {
+            return;
+        }
+        self.drag_anchor = Some(Vector {
+            x: anchor.x - tiles.x * tile_size.x,
+            y: anchor.y - tiles.y * tile_size.y,
+        });
+        self.scroll_by(tiles.x as i64, tiles.y as i64);
+    }
+
+    // Moves the screen by a number of columns and rows without moving past the edges of the map
+    fn scroll_by(&mut self, dx: i64, dy: i64) {
+        let map_size = self.get_map_size();
+        let shift = |start: MapDistance, offset: i64, length, size| {
+            let max_start = MapDistance::saturating_sub(size, length);
+            (start as i64 + offset).clamp(0, max_start as i64) as MapDistance
+        };
+        let screen = &self.screen;
+        let top_left = Vector {
+            x: shift(screen.left(), dx, screen.width(), map_size.x),
+            y: shift(screen.top(), dy, screen.height(), map_size.y),
+        };
+        if top_left != self.screen.top_left {
+            self.pan(top_left);
+            self.redraw();
+        }
+    }
+
+    // Moves the cursor to a tile that was clicked on, centering the screen on it if it was double
+    // clicked
+    fn click_tile(&mut self, pos: Vector<MapDistance>) {
+        let now = self.now();
+        let double_click = match self.last_click {
+            Some((time, tile)) => {
+                tile == pos && P::duration_between(time, now) <= P::nanoseconds(DOUBLE_CLICK_NS)
+            }
+            None => false,
+        };
+        // A third click starts a new double click rather than finishing another one
+        self.last_click = if double_click { None } else { Some((now, pos)) };
+        self.move_cursor(pos);
+        if double_click {
+            self.center_on(pos);
+        }
+    }
+
+    // The game draws through these rather than the platform's functions so that the draw calls are
+    // counted for the debug overlay, and so that nothing is drawn before the screen has a size.
+    // Images that failed to load are skipped with a warning the first time, where what names the
+    // thing that will be missing from the screen
+    fn attempt_draw(
+        &self,
+        asset: &Asset<P::Image>,
+        what: &str,
+        location: &Rectangle<P::ScreenDistance>,
+    ) {
+        match asset.image.as_ref() {
+            Some(_) if !self.screen_ready() => (),
+            Some(image) => {
+                self.stats.count_draw();
+                self.platform.draw(image, location);
+            }
+            None => {
+                let path = match asset.path.as_ref() {
+                    Some(p) => p,
+                    None => return,
+                };
+                if self.missing_images.borrow_mut().insert(path.clone()) {
+                    let msg = format!("{} failed to load; {} will be drawn without it", path, what);
+                    log::warn::<P>(msg.as_str());
+                }
+            }
+        }
+    }
+
+    fn fill_rect(&self, rect: &Rectangle<P::ScreenDistance>, color: style::Color) {
+        if !self.screen_ready() {
+            return;
+        }
+        self.stats.count_draw();
+        self.platform.fill_rect(rect, color);
+    }
+
+    fn draw_text(
+        &self,
+        text: &str,
+        offset: Vector<P::ScreenDistance>,
+        max_width: P::ScreenDistance,
+        style: &style::TextStyle,
+    ) {
+        if !self.screen_ready() {
+            return;
+        }
+        self.stats.count_draw();
+        let direction = self.strings.direction();
+        self.platform
+            .draw_text(text, offset, max_width, style, direction);
+    }
+
+    fn clear(&self) {
+        if !self.screen_ready() {
+            return;
+        }
+        self.stats.count_draw();
+        self.platform.clear();
+    }
+
+    fn draw_cursor(&self) {
+        if !self.focused {
+            return;
+        }
+        if let Some(cursor_pos_on_screen) = self.get_screen_pos(self.cursor_pos) {
+            self.attempt_draw(&self.cursor_image, "cursor", &cursor_pos_on_screen);
+            self.draw_path_cost(&cursor_pos_on_screen);
+        }
+    }
+
+    // Labels the bottom of the cursor's tile with what it would cost the selected unit to move
+    // there and how much of its movement would be left, if it can move there. The label is inside
+    // the tile, so it's covered up whenever the tile is redrawn without the cursor
+    fn draw_path_cost(&self, screen_pos: &Rectangle<P::ScreenDistance>) {
+        let pos = self.cursor_pos;
+        if self.highlights.get(&pos) != Some(&style::HighlightKind::Movement) {
+            return;
+        }
+        let (origin, cost) = match (self.selected_unit, self.move_costs.get(&pos)) {
+            (Some(origin), Some(&cost)) if origin != pos => (origin, cost),
+            _ => return,
+        };
+        let movement = match self.get_unit(origin) {
+            Some(unit) => unit.info.movement,
+            None => return,
+        };
+        let cost_text = cost.to_string();
+        let left_text = movement.saturating_sub(cost).to_string();
+        let text = self.format_text(
+            "cursor.path_cost",
+            &[cost_text.as_str(), left_text.as_str()],
+        );
+        let height = screen_pos.height() / 3.into();
+        let label = Rectangle {
+            top_left: Vector {
+                x: screen_pos.left(),
+                y: screen_pos.bottom() - height,
+            },
+            size: Vector {
+                x: screen_pos.width(),
+                y: height,
+            },
+        };
+        self.fill_rect(&label, style::PATH_COST_BACKGROUND);
+        self.draw_text(
+            text.as_str(),
+            label.top_left,
+            label.width(),
+            &style::PATH_COST_TEXT,
+        );
+    }
+
+    // Finds where the hint bar is drawn: along the bottom of the screen. None if it's turned off or
+    // it wouldn't fit below the infobar
+    fn get_hint_bar_position(&self) -> Option<Rectangle<P::ScreenDistance>> {
+        if !self.settings.show_input_hints {
+            return None;
+        }
+        let layout = self.layout();
+        let screen_size = layout.screen_size;
+        let height = match self.platform.get_text_height(&style::HINT_TEXT) {
+            Some(text_height) => text_height + text_height / 2.into(),
+            None => screen_size.y / HINT_BAR_ROWS_PER_SCREEN.into(),
+        };
+        if layout.infobar_size.y + height > screen_size.y {
+            return None;
+        }
+        Some(Rectangle {
+            top_left: Vector {
+                x: 0.into(),
+                y: screen_size.y - height,
+            },
+            size: Vector {
+                x: screen_size.x,
+                y: height,
+            },
+        })
+    }
+
+    // Gets how much of the bottom of the screen the hint bar takes up, which panels along the bottom
+    // of the screen are drawn above
+    fn get_hint_bar_height(&self) -> P::ScreenDistance {
+        self.get_hint_bar_position()
+            .map_or(0.into(), |position| position.height())
+    }
+
+    // Gets as many of the hints for the current context as fit in a width, each listing the keys
+    // bound to its actions followed by what they do. Hints whose actions have no keys are left out
+    fn get_hints(&self, width: P::ScreenDistance) -> String {
+        let hints: &[(&[Action], &str)] = match self.contexts.last() {
+            Some(bindings::Context::Menu) => &MENU_HINTS,
+            Some(bindings::Context::Dialog) => &DIALOG_HINTS,
+            Some(bindings::Context::Map) | None => &MAP_HINTS,
+        };
+        self.join_hints(String::new(), hints, &self.contexts, width)
+    }
+
+    // Adds as many hints as fit within a width to the end of some text, listing the keys bound to
+    // their actions in some contexts
+    fn join_hints(
+        &self,
+        mut text: String,
+        hints: &[(&[Action], &str)],
+        contexts: &[bindings::Context],
+        width: P::ScreenDistance,
+    ) -> String {
+        for &(actions, key) in hints {
+            let keys: String = actions
+                .iter()
+                .filter_map(|&action| self.bindings.first_key(contexts, action))
+                .map(bindings::key_label)
+                .collect();
+            if keys.is_empty() {
+                continue;
+            }
+            let hint = format!("{} {}", keys, self.text(key));
+            let joined = if text.is_empty() {
+                hint
+            } else {
+                format!("{}{}{}", text, HINT_SEPARATOR, hint)
+            };
+            // Hints that don't fit are left off entirely rather than cut short
+            if self
+                .platform
+                .measure_text(joined.as_str(), &style::HINT_TEXT)
+                > width
+            {
+                break;
+            }
+            text = joined;
+        }
+        text
+    }
+
+    // Draws the bar listing the keys for what can be done in the current context
+    fn draw_hint_bar(&self) {
+        let position = match self.get_hint_bar_position() {
+            Some(p) => p,
+            None => return,
+        };
+        self.fill_rect(&position, style::HINT_BACKGROUND);
+        let padding = position.height() / 4.into();
+        let width = position.width() - padding - padding;
+        let text = self.get_hints(width);
+        let text_pos = Vector {
+            x: position.left() + padding,
+            y: position.top() + padding,
+        };
+        self.draw_text(text.as_str(), text_pos, width, &style::HINT_TEXT);
+    }
+
+    // Finds where the infobar is drawn: the top left corner of the screen unless the cursor is
+    // underneath it, in which case the top right corner
+    fn get_infobar_position(&self) -> Rectangle<P::ScreenDistance> {
+        let layout = self.layout();
+        let size = layout.infobar_size;
+        let top_left = Rectangle {
+            top_left: Vector {
+                x: 0.into(),
+                y: 0.into(),
+            },
+            size,
+        };
+        match self.get_screen_pos(self.cursor_pos) {
+            Some(cursor) if cursor.intersects(&top_left) => Rectangle {
+                top_left: Vector {
+                    x: layout.screen_size.x - size.x,
+                    y: 0.into(),
+                },
+                size,
+            },
+            _ => top_left,
+        }
+    }
+
+    fn draw_infobar(&self) {
+        // Anything that uncovers the infobar may have uncovered the hint bar too
+        self.draw_hint_bar();
+        let position = self.get_infobar_position();
+        let size = position.size;
+        let height = size.y;
+        self.attempt_draw(&self.infobar_image, "infobar", &position);
+        let origin = position.top_left;
+        let offset_scalar = size.y / 4.into();
+        let offset = origin
+            + Vector {
+                x: offset_scalar,
+                y: offset_scalar,
+            };
+        let tile = self.get_tile(self.cursor_pos);
+        let info = &tile.info;
+        let hidden = self.is_hidden(self.cursor_pos);
+        let name_width = multiply_frac(height, 9, 4);
+        let label = &style::INFOBAR_LABEL;
+        let value = &style::INFOBAR_VALUE;
+        let name = if hidden {
+            HIDDEN_TILE_NAME
+        } else {
+            info.name.as_str()
+        };
+        let name = text::fit(&self.platform, self.text(name), name_width, label);
+        self.draw_text(name.as_str(), offset, name_width, label);
+        let coordinates = format!("({}, {})", self.cursor_pos.x, self.cursor_pos.y);
+        let coordinates_pos = origin
+            + Vector {
+                x: multiply_frac(height, 11, 4),
+                y: offset_scalar,
+            };
+        self.draw_text(coordinates.as_str(), coordinates_pos, height, value);
+
+        // Leave out the second line if there's nothing to show or if it wouldn't fit in the bottom
+        // of the infobar
+        if hidden {
+            return;
+        }
+        let stat_y = multiply_frac(height, 5, 8);
+        if let Some(text_height) = self.platform.get_text_height(value) {
+            if text_height > height - stat_y {
+                return;
+            }
+        }
+        let stat_y = origin.y + stat_y;
+
+        // Point out a tile whose image is missing, since the placeholder drawn for it doesn't say
+        // which image it was meant to be
+        if tile.image.failed() {
+            let path = tile.image.path.as_deref().unwrap_or_default();
+            let text = self.format_text("infobar.missing_image", &[path]);
+            let warning = &style::INFOBAR_WARNING;
+            let warning_pos = Vector {
+                x: offset.x,
+                y: stat_y,
+            };
+            let warning_width = size.x - offset_scalar - offset_scalar;
+            let text = text::fit(&self.platform, text.as_str(), warning_width, warning);
+            self.draw_text(text.as_str(), warning_pos, warning_width, warning);
+            return;
+        }
+
+        let stat_width = height * 13.into() / 16.into();
+        let move_pos = Vector {
+            x: origin.x + multiply_frac(height, 3, 4),
+            y: stat_y,
+        };
+        let defense_pos = Vector {
+            x: origin.x + multiply_frac(height, 15, 8),
+            y: stat_y,
+        };
+        let evade_pos = Vector {
+            x: origin.x + height * 3.into(),
+            y: stat_y,
+        };
+        self.draw_text(
+            info.move_cost.to_string().as_str(),
+            move_pos,
+            stat_width,
+            value,
+        );
+        self.draw_text(
+            info.defense.to_string().as_str(),
+            defense_pos,
+            stat_width,
+            value,
+        );
+        self.draw_text(
+            info.evade.to_string().as_str(),
+            evade_pos,
+            stat_width,
+            value,
+        );
+    }
+
+    // Gets the padding around the edges of a unit panel of a given width and the height of each of
+    // its lines
+    fn get_unit_panel_spacing(
+        &self,
+        width: P::ScreenDistance,
+    ) -> (P::ScreenDistance, P::ScreenDistance) {
+        let line_height = self
+            .platform
+            .get_text_height(&style::UNIT_PANEL_TEXT)
+            .unwrap_or(width / 8.into());
+        (width / 16.into(), line_height)
+    }
+
+    // Finds where the unit panel is drawn: along the right edge of the screen below the infobar,
+    // unless the cursor is underneath it, in which case along the left edge. None if there's no
+    // unit under the cursor to show or the panel wouldn't fit on the screen
+    fn get_unit_panel_position(&self) -> Option<Rectangle<P::ScreenDistance>> {
+        if !self.settings.show_unit_panel || !self.is_in_sight(self.cursor_pos) {
+            return None;
+        }
+        let unit = self.get_unit(self.cursor_pos)?;
+        let layout = self.layout();
+        let screen_size = layout.screen_size;
+        let fraction = self.settings.unit_panel_screen_fraction.max(1);
+        let width = screen_size.x / fraction.into();
+        let (padding, line_height) = self.get_unit_panel_spacing(width);
+        let mut height = padding + line_height * UNIT_PANEL_LINES.into() + padding;
+        if unit.portrait.path.is_some() {
+            // The portrait is square and spans the width of the panel inside the padding
+            height = height + width - padding;
+        }
+        let top = layout.infobar_size.y;
+        if top + height + self.get_hint_bar_height() > screen_size.y {
+            return None;
+        }
+        let size = Vector {
+            x: width,
+            y: height,
+        };
+        let right = Rectangle {
+            top_left: Vector {
+                x: screen_size.x - width,
+                y: top,
+            },
+            size,
+        };
+        match self.get_screen_pos(self.cursor_pos) {
+            Some(cursor) if cursor.intersects(&right) => Some(Rectangle {
+                top_left: Vector {
+                    x: 0.into(),
+                    y: top,
+                },
+                size,
+            }),
+            _ => Some(right),
+        }
+    }
+
+    // Draws the portrait, name, hp and stats of the unit under the cursor, if there is one
+    fn draw_unit_panel(&mut self) {
+        self.unit_panel = None;
+        let (position, unit) = match (
+            self.get_unit_panel_position(),
+            self.get_unit(self.cursor_pos),
+        ) {
+            (Some(p), Some(u)) => (p, u),
+            _ => return,
+        };
+        self.fill_rect(&position, style::UNIT_PANEL_BACKGROUND);
+        let (padding, line_height) = self.get_unit_panel_spacing(position.width());
+        let left = position.left() + padding;
+        let inner_width = position.width() - padding - padding;
+        let mut y = position.top() + padding;
+        if unit.portrait.path.is_some() {
+            let portrait = Rectangle {
+                top_left: Vector { x: left, y },
+                size: Vector {
+                    x: inner_width,
+                    y: inner_width,
+                },
+            };
+            if unit.portrait.failed() {
+                self.draw_placeholder(&portrait);
+            }
+            let what = format!("{}'s portrait", unit.info.name);
+            self.attempt_draw(&unit.portrait, what.as_str(), &portrait);
+            y = y + inner_width + padding;
+        }
+
+        let name_style = &style::UNIT_PANEL_NAME;
+        let name = text::fit(
+            &self.platform,
+            self.text(unit.info.name.as_str()),
+            inner_width,
+            name_style,
+        );
+        self.draw_text(
+            name.as_str(),
+            Vector { x: left, y },
+            inner_width,
+            name_style,
+        );
+        y = y + line_height;
+
+        // Each stat is named on the left of its line with its value lined up on the right
+        let text = &style::UNIT_PANEL_TEXT;
+        let draw_stat = |label: &str, value: String, y: P::ScreenDistance| {
+            let label = self.text(label);
+            self.draw_text(label, Vector { x: left, y }, inner_width, text);
+            let value_width = partial_ord_min(
+                self.platform.measure_text(value.as_str(), text),
+                inner_width,
+            );
+            let value_pos = Vector {
+                x: left + inner_width - value_width,
+                y,
+            };
+            self.draw_text(value.as_str(), value_pos, value_width, text);
+        };
+        let info = &unit.info;
+        draw_stat("stat.hp", format!("{}/{}", info.hp, info.max_hp), y);
+        y = y + line_height;
+
+        let bar = Rectangle {
+            top_left: Vector {
+                x: left,
+                y: y + line_height / 4.into(),
+            },
+            size: Vector {
+                x: inner_width,
+                y: line_height / 2.into(),
+            },
+        };
+        self.draw_hp_bar(&bar, info.hp, info.max_hp);
+        y = y + line_height;
+
+        let stats = [
+            ("stat.strength", info.strength),
+            ("stat.defense", info.defense),
+            ("stat.speed", info.speed),
+            ("stat.move", info.movement),
+        ];
+        for (label, value) in stats {
+            draw_stat(label, value.to_string(), y);
+            y = y + line_height;
+        }
+        self.unit_panel = Some(position);
+    }
+
+    // Draws the unit panel for the unit under the cursor, first uncovering wherever it was drawn
+    // before if it has moved or there's no longer a unit to show
+    fn update_unit_panel(&mut self) {
+        let position = self.get_unit_panel_position();
+        if let Some(old) = self.unit_panel.filter(|&old| Some(old) != position) {
+            self.redraw_region(&old);
+            self.draw_cursor();
+            self.draw_infobar();
+            self.restore_minimap(&[Some(old)]);
+        }
+        self.draw_unit_panel();
+    }
+
+    // Blanks the screen and shows a message in the middle of it
+    fn draw_message(&mut self, text: &str) {
+        // The message covers the whole screen, so there's no point drawing the screen behind it
+        self.needs_redraw = false;
+        let screen_size = self.layout().screen_size;
+        draw_message_screen(&self.platform, screen_size, text, self.strings.direction());
+    }
+
+    // Gets the text for a string key in the current locale
+    fn text<'a>(&'a self, key: &'a str) -> &'a str {
+        self.strings.get::<P>(key)
+    }
+
+    // Gets the text for a string key in the current locale, filled in with arguments
+    fn format_text(&self, key: &str, args: &[&str]) -> String {
+        self.strings.format::<P>(key, args)
+    }
+
+    // Draws the final frame shown once the game has ended
+    fn draw_goodbye(&mut self) {
+        self.draw_message(self.text(GOODBYE_MESSAGE).to_owned().as_str());
+    }
+
+    // Waits for the next key to be pressed so that it can be bound to an action
+    fn start_rebind(&mut self, action: Action) {
+        self.rebinding = Some(action);
+        self.redraw();
+    }
+
+    // Gets the event that a key triggers, or binds the key if the game is waiting for one. Number
+    // keys pick a tile type instead while the map editor is on
+    fn handle_key(&mut self, key: P::InputType) -> Option<Event<P::MouseDistance, P::InputType>> {
+        match self.rebinding.take() {
+            Some(action) => {
+                self.rebind(action, key);
+                self.redraw();
+                None
+            }
+            None => {
+                if let Some(tile_type) = self.editor_tile_key(&key) {
+                    self.choose_tile_type(tile_type);
+                    return None;
+                }
+                match self.bindings.get(&self.contexts, &key)? {
+                    Action::FastModifier => {
+                        self.fast_move = true;
+                        None
+                    }
+                    action => action.event(),
+                }
+            }
+        }
+    }
+
+    // Stops moving the cursor quickly once the fast move modifier is let go of
+    fn handle_release(&mut self, key: P::InputType) {
+        if self.bindings.get(&self.contexts, &key) == Some(Action::FastModifier) {
+            self.fast_move = false;
+        }
+    }
+
+    // Gets how many tiles the cursor moves at a time
+    fn move_stride(&self) -> i64 {
+        if self.fast_move {
+            self.settings.fast_move_stride.max(1).into()
+        } else {
+            1
+        }
+    }
+
+    // Makes a key the only one bound to an action and saves the new bindings
+    fn rebind(&mut self, action: Action, key: P::InputType) {
+        self.bindings.rebind(bindings::Context::Map, action, key);
+        bindings::save(&self.platform, &self.bindings);
+    }
+
+    // Has the whole screen drawn on the next frame, so that several changes made before then are
+    // only drawn once
+    fn redraw(&mut self) {
+        // Pull the cursor along if the screen has moved away from it
+        self.cursor_pos = self.clamp_to_screen(self.cursor_pos);
+        self.update_visibility();
+        self.needs_redraw = true;
+    }
+
+    // Draws the whole screen
+    fn draw_screen(&mut self) {
+        self.needs_redraw = false;
+        self.damage = Damage::default();
+        if let Some(action) = self.rebinding {
+            // The prompt stands in for the whole screen until a key is pressed
+            let action = format!("{:?}", action);
+            let text = self.format_text("message.rebind", &[action.as_str()]);
+            return self.draw_message(text.as_str());
+        }
+        if !self.screen_ready() {
+            return;
+        }
+        self.cursor_pos = self.clamp_to_screen(self.cursor_pos);
+        self.update_scroll();
+        self.update_visibility();
+        self.update_ambient_sounds();
+        self.update_scroll_indicator();
+        let uncovered = self.get_uncovered_regions();
+        if !uncovered.is_empty() {
+            // Get rid of whatever was drawn outside of the map last frame
+            match self.background {
+                Some(color) => {
+                    for region in uncovered.iter() {
+                        self.fill_rect(region, color);
+                    }
+                }
+                None => self.clear(),
+            }
+        }
+        let region = self.visible_region();
+        // Tiles make up most of what's drawn, so they're sent to the screen together
+        self.platform.begin_batch();
+        for map_pos in region.positions() {
+            if let Some(screen_pos) = self.get_region_screen_pos(&region, map_pos) {
+                self.draw_tile(map_pos, &screen_pos);
+            }
+        }
+        self.platform.end_batch();
+        if let Some(animation) = self.move_animation.as_ref() {
+            if let Some(screen_pos) = self.get_moving_unit_screen_pos(&region) {
+                let unit = &self.units[animation.unit];
+                self.draw_unit(unit, &screen_pos, unit.info.hp);
+            }
+        }
+        self.draw_combat(&region);
+        // The letterbox is drawn over the tiles since they can hang off the viewport mid-scroll
+        for region in self.get_letterbox_regions() {
+            self.fill_rect(&region, self.settings.letterbox_color);
+        }
+        self.draw_cursor();
+        self.draw_infobar();
+        self.draw_minimap();
+        self.draw_unit_panel();
+        self.draw_menu();
+        self.draw_forecast();
+        self.draw_dialog();
+        self.draw_level_up_popup();
+        self.draw_toast();
+        self.draw_banner();
+        self.draw_transport();
+        if self.tooltip.is_some() {
+            self.show_tooltip();
+        }
+    }
+
+    // Fires the triggers on the tile under the cursor in the order they were declared, using up
+    // the ones that only fire once
+    fn fire_triggers(&mut self) {
+        let pos = self.cursor_pos;
+        let mut fired = Vec::new();
+        self.triggers.retain(|t| {
+            if t.x != pos.x || t.y != pos.y {
+                return true;
+            }
+            fired.push(t.action.clone());
+            t.repeat
+        });
+        let mut messages = Vec::new();
+        for action in fired {
+            match action {
+                serialization::TriggerAction::Message(message) => messages.push(message),
+                serialization::TriggerAction::EndMap => self.map_complete = true,
+            }
+        }
+        self.show_map_dialog(messages);
+    }
+
+    // Queues up messages from a map, whose speakers and text are string keys
+    fn show_map_dialog(&mut self, messages: Vec<serialization::Message>) {
+        let messages = messages
+            .iter()
+            .map(|m| serialization::Message {
+                speaker: m.speaker.as_deref().map(|s| self.text(s).to_owned()),
+                text: self.text(m.text.as_str()).to_owned(),
+            })
+            .collect();
+        self.show_dialog(messages);
+    }
+
+    // Queues up messages to be shown in the dialog box, opening it if it isn't already open
+    fn show_dialog(&mut self, messages: Vec<serialization::Message>) {
+        let was_showing = !self.dialog.is_empty();
+        for message in messages {
+            self.dialog.push(message);
+        }
+        if !was_showing && !self.dialog.is_empty() {
+            self.contexts.push(bindings::Context::Dialog);
+            self.damage.hint_bar = true;
+            self.damage.dialog = true;
+        }
+    }
+
+    // Starts the music for a map, leaving it playing if the map uses the same music as before.
+    // Music failing to play isn't worth interrupting the game over, so it's only logged
+    fn switch_music(&mut self, music: Option<String>) {
+        if music == self.music {
+            return;
+        }
+        if let Err(e) = self.platform.play_music(music.as_deref()) {
+            log::warn::<P>(e.to_string().as_str());
+        }
+        self.music = music;
+    }
+
+    // Queues up a signal to be played once the current event has been handled. Each signal is only
+    // played once per event so that e.g. moving several tiles at once makes a single sound
+    fn emit(&mut self, signal: sound::GameSignal) {
+        if !self.signals.contains(&signal) {
+            self.signals.push(signal);
+        }
+    }
+
+    // Sets how loud each tile type's ambient sound is from the fraction of the screen that tiles of
+    // that type cover, stopping the ones that are no longer on it. Counting tiles is only done
+    // once the screen has been panned or zoomed (or the map replaced) since the last count
+    fn update_ambient_sounds(&mut self) {
+        if self.ambient_screen == Some(self.screen) {
+            return;
+        }
+        self.ambient_screen = Some(self.screen);
+        let map_size = self.get_map_size();
+        let screen = &self.screen;
+        let right = screen
+            .checked_right()
+            .map_or(map_size.x, |r| r.min(map_size.x));
+        let bottom = screen
+            .checked_bottom()
+            .map_or(map_size.y, |b| b.min(map_size.y));
+        let mut counts = std::collections::HashMap::<&str, u64>::new();
+        let mut total = 0u64;
+        for y in screen.top()..bottom {
+            for x in screen.left()..right {
+                total += 1;
+                if let Some(path) = self.get_tile(Vector { x, y }).info.ambient_sound.as_ref() {
+                    *counts.entry(path.as_str()).or_default() += 1;
+                }
+            }
+        }
+        // Rounded up so that a sound is never silent while its tiles are on the screen
+        let volumes: std::collections::HashMap<_, _> = counts
+            .into_iter()
+            .map(|(path, count)| {
+                let volume = (count * sound::MAX_VOLUME as u64).div_ceil(total);
+                (path.to_owned(), volume as u32)
+            })
+            .collect();
+        for path in self.ambient_volumes.keys() {
+            if !volumes.contains_key(path) {
+                self.platform.set_ambient_sound(path.as_str(), 0);
+            }
+        }
+        for (path, &volume) in volumes.iter() {
+            if self.ambient_volumes.get(path) != Some(&volume) {
+                self.platform.set_ambient_sound(path.as_str(), volume);
+            }
+        }
+        self.ambient_volumes = volumes;
+    }
+
+    // Plays the sounds configured for the signals emitted since this was last called
+    fn play_signals(&mut self) {
+        for signal in std::mem::take(&mut self.signals) {
+            if let Some(path) = self.settings.sounds.get(&signal) {
+                self.platform.play_sound(path.as_str(), sound::MAX_VOLUME);
+            }
+        }
+    }
+
+    // Shows or hides the debug overlay
+    fn toggle_debug_overlay(&mut self) {
+        self.debug_overlay = !self.debug_overlay;
+        self.debug_overlay_position = None;
+        self.redraw();
+    }
+
+    // Finds where the debug overlay is drawn: the top corner of the screen on the other side from
+    // the infobar
+    fn get_debug_overlay_position(&self, lines: &[String]) -> Rectangle<P::ScreenDistance> {
+        let layout = self.layout();
+        let style = &style::DEBUG_OVERLAY_TEXT;
+        let line_height = self
+            .platform
+            .get_text_height(style)
+            .unwrap_or(layout.infobar_size.y / 4.into());
+        let padding = line_height / 2.into();
+        let text_width = lines
+            .iter()
+            .map(|l| self.platform.measure_text(l.as_str(), style))
+            .fold(0.into(), partial_ord_max);
+        let size = Vector {
+            x: text_width + padding + padding,
+            y: line_height * (lines.len() as u32).into() + padding + padding,
+        };
+        let infobar_on_left = self.get_infobar_position().left() == 0.into();
+        let x = if infobar_on_left && layout.screen_size.x > size.x {
+            layout.screen_size.x - size.x
+        } else {
+            0.into()
+        };
+        Rectangle {
+            top_left: Vector { x, y: 0.into() },
+            size,
+        }
+    }
+
+    // Draws the frame stats on top of the game. Drawn straight to the platform so that the
+    // overlay's own draw calls aren't counted
+    fn draw_debug_overlay(&mut self) {
+        let lines = [
+            format!("FPS: {}", self.stats.fps()),
+            format!("Draw calls: {}", self.stats.draw_calls()),
+            format!("Queue overflows: {}", self.platform.event_queue_overflows()),
+            format!(
+                "Screen: ({}, {}) {}x{}",
+                self.screen.left(),
+                self.screen.top(),
+                self.screen.width(),
+                self.screen.height()
+            ),
+        ];
+        let position = self.get_debug_overlay_position(&lines);
+        if self.debug_overlay_position.is_some_and(|p| p != position) {
+            // Uncover wherever the overlay was drawn before; it's drawn in its new position once
+            // the whole screen has been drawn again
+            self.debug_overlay_position = None;
+            self.redraw();
+            return;
+        }
+        self.debug_overlay_position = Some(position);
+        let style = &style::DEBUG_OVERLAY_TEXT;
+        self.platform
+            .fill_rect(&position, style::DEBUG_OVERLAY_BACKGROUND);
+        let line_height = position.height() / (lines.len() as u32 + 1).into();
+        let padding = line_height / 2.into();
+        let max_width = position.width() - padding - padding;
+        for (i, line) in lines.iter().enumerate() {
+            let offset = Vector {
+                x: position.left() + padding,
+                y: position.top() + padding + line_height * (i as u32).into(),
+            };
+            // Stats are always shown in English
+            let direction = text::Direction::LeftToRight;
+            self.platform
+                .draw_text(line.as_str(), offset, max_width, style, direction);
+        }
+    }
+
+    // Ends the frame if anything was drawn since the last one, showing its stats on the debug
+    // overlay if it's enabled
+    fn finish_frame(&mut self) {
+        let now = P::now();
+        let overflows = self.platform.event_queue_overflows();
+        if let Some(new) = self.stats.overflows_to_log(overflows, now) {
+            log::warn::<P>(
+                format!(
+                    "{} events didn't fit in the event queue ({} in total)",
+                    new, overflows
+                )
+                .as_str(),
+            );
+        }
+        if self.stats.end_frame(now) && self.debug_overlay && !self.finished {
+            self.draw_debug_overlay();
+        }
+    }
+
+    // Silences sound or brings it back, remembering the choice for later games
+    fn toggle_mute(&mut self) {
+        self.sound.muted = !self.sound.muted;
+        self.platform.set_volume(self.sound.effective_volume());
+        sound::save(&self.platform, &self.sound);
+    }
+
+    // Switches to a newly loaded map, clamping the screen and cursor to fit within it
+    fn replace_map(&mut self, loaded: LoadedMap<P>) {
+        self.finish_scroll();
+        self.map = loaded.map;
+        self.tile_types = loaded.tile_types;
+        self.images = loaded.images;
+        self.units = loaded.units;
+        self.level_up_popup = None;
+        self.forecast_panel = None;
+        // The whole screen is drawn again below, so nothing needs to be uncovered
+        self.unit_panel = None;
+        // Anything still missing after a reload is warned about again
+        self.missing_images.borrow_mut().clear();
+        self.background = loaded.background;
+        self.triggers = loaded.triggers;
+        self.intro_dialog = loaded.intro_dialog;
+        // Tiles painted on the old map can't be undone on the new one
+        self.editor = None;
+        self.switch_music(loaded.music);
+        self.map_complete = false;
+        self.highlights.clear();
+        self.selected_unit = None;
+        // Units are numbered afresh on the new map
+        self.danger_zones.clear();
+        self.danger_tiles.clear();
+        self.move_animation = None;
+        self.combat_animation = None;
+        self.ready_units = ready_units(&self.units, self.current_team());
+        // The new map's tiles may have different sounds
+        self.ambient_screen = None;
+        self.scroll_indicator_screen = None;
+        if self.pending_move.take().is_some() {
+            // The action menu was for a unit that no longer exists
+            self.menu = None;
+            self.contexts.retain(|c| *c != bindings::Context::Menu);
+        }
+        self.hover_tile = None;
+        self.last_click = None;
+        self.sight_center = None;
+        self.visibility = if loaded.fog {
+            Some(Array2::from_elem(self.map.dim(), Visibility::Hidden))
+        } else {
+            None
+        };
+
+        let map_size = self.get_map_size();
+        let min = self.min_screen_size();
+        let max = self.max_screen_size();
+        let size = Vector {
+            x: self.screen.width().max(min.x).min(max.x),
+            y: self.screen.height().max(min.y).min(max.y),
+        };
+        self.screen = Rectangle {
+            top_left: Vector {
+                x: self.screen.left().min(map_size.x - size.x),
+                y: self.screen.top().min(map_size.y - size.y),
+            },
+            size,
+        };
+        self.cursor_pos = self.clamp_to_screen(Vector {
+            x: self.cursor_pos.x.min(map_size.x - 1),
+            y: self.cursor_pos.y.min(map_size.y - 1),
+        });
+
+        self.update_visibility();
+        if self.settings.show_minimap {
+            self.minimap = minimap_runs(&self.map, &self.tile_types, self.visibility.as_ref());
+        }
+        self.redraw();
+        if self.show_danger_zone {
+            self.refresh_danger_zone();
+        }
+    }
+
+    // Gets everything needed to pick the game back up where it is now
+    pub fn snapshot(&self) -> save::Snapshot {
+        save::Snapshot {
+            version: save::VERSION,
+            saved_at: self.platform.timestamp(),
+            map: self.campaign.current().to_owned(),
+            turn: self.turn,
+            player: self.player,
+            units: self.units.iter().map(|u| u.info.clone()).collect(),
+            triggers: self.triggers.clone(),
+            fog: self.visibility.clone(),
+            cursor: self.cursor_pos,
+            rng: self.rng.clone(),
+        }
+    }
+
+    // Switches to the map a game was saved on and picks up where the save left off
+    fn restore(&mut self, loaded: LoadedMap<P>, snapshot: save::Snapshot) {
+        if !self.campaign.go_to(snapshot.map.as_str()) {
+            log::warn::<P>(format!("{} is not part of the campaign", snapshot.map).as_str());
+        }
+        campaign::save(&self.platform, &self.campaign);
+        // Progress made after the game was saved is forgotten along with everything else
+        let mut roster = roster::load(&self.platform);
+        for info in snapshot.units.iter() {
+            if info.team == serialization::PLAYER_TEAM {
+                roster.insert(info.name.clone(), roster::Progress::of(info));
+            }
+        }
+        roster::save(&self.platform, &roster);
+
+        // The player is needed to tell which units are ready
+        self.player = snapshot.player;
+        self.take_banner();
+        self.replace_map(loaded);
+        self.turn = snapshot.turn;
+        self.rng = snapshot.rng;
+        let map_size = self.get_map_size();
+        self.cursor_pos = Vector {
+            x: snapshot.cursor.x.min(map_size.x - 1),
+            y: snapshot.cursor.y.min(map_size.y - 1),
+        };
+        self.screen.top_left = self.get_centered_top_left(self.cursor_pos);
+        match (self.visibility.as_mut(), snapshot.fog) {
+            (Some(visibility), Some(fog)) if fog.dim() == visibility.dim() => {
+                *visibility = fog;
+                // The fog was saved with the tiles around the cursor already revealed
+                self.sight_center = Some(self.cursor_pos);
+            }
+            _ => {
+                self.update_visibility();
+            }
+        }
+        if !self.minimap.is_empty() {
+            self.minimap = minimap_runs(&self.map, &self.tile_types, self.visibility.as_ref());
+        }
+        self.redraw();
+        if self.show_danger_zone {
+            self.refresh_danger_zone();
+        }
+    }
+
+    // Shows that the map is over once a trigger has finished it and the player has read any
+    // remaining messages, returning whether the game should stop. A load of another map that's
+    // still waiting gives way to the next one
+    fn finish_map(&mut self) -> bool {
+        let advancing = matches!(self.pending_load, Some(PendingLoad::NextMap));
+        if !self.map_complete || !self.dialog.is_empty() || advancing {
+            return self.finished;
+        }
+        if self.campaign.advance() {
+            campaign::save(&self.platform, &self.campaign);
+            self.pending_load = Some(PendingLoad::NextMap);
+            return false;
+        }
+        let message = if self.campaign.len() > 1 {
+            CAMPAIGN_COMPLETE_MESSAGE
+        } else {
+            MAP_COMPLETE_MESSAGE
+        };
+        let message = self.text(message).to_owned();
+        // Start over the next time the game is played
+        self.campaign.restart();
+        campaign::save(&self.platform, &self.campaign);
+        roster::save(&self.platform, &roster::Roster::new());
+        self.draw_message(message.as_str());
+        self.finished = true;
+        true
+    }
+
+    // Gets whether the screen needs to be redrawn again once the current frame is over
+    fn redraw_hint(&self) -> Redraw {
+        if self.finished {
+            Redraw::Finished
+        } else if self.suspended_at.is_some() {
+            Redraw::Idle
+        } else if self.needs_redraw || !self.damage.is_empty() {
+            Redraw::Changed
+        } else if self.is_animating() {
+            Redraw::NextFrame
+        } else {
+            // Wait for whichever of the tooltip, the next autosave and the popup's, toast's and
+            // scroll indicator's timeouts comes first
+            let now = self.now();
+            let wait = |start, delay: u64| {
+                delay.saturating_sub(P::to_nanoseconds(P::duration_between(start, now)))
+            };
+            let tooltip = self.mouse_rest_start.map(|s| wait(s, TOOLTIP_DELAY_NS));
+            let popup = self
+                .level_up_popup
+                .as_ref()
+                .map(|p| wait(p.shown_at, LEVEL_UP_POPUP_NS));
+            let toast = self.toast.as_ref().map(|t| wait(t.shown_at, TOAST_NS));
+            let autosave = self.autosave_wait();
+            let indicator = self
+                .scroll_indicator_moved_at
+                .map(|m| wait(m, SCROLL_INDICATOR_NS));
+            if indicator == Some(0) {
+                // The scroll indicator is fading out
+                return Redraw::NextFrame;
+            }
+            match tooltip
+                .into_iter()
+                .chain(popup)
+                .chain(toast)
+                .chain(autosave)
+                .chain(indicator)
+                .min()
+            {
+                Some(ns) => Redraw::Wait(ns),
+                None => Redraw::Idle,
+            }
+        }
+    }
+
+    // Gets the tooltip text for the tile under the mouse along with where it's drawn
+    fn get_tooltip(&self) -> Option<(String, Rectangle<P::ScreenDistance>)> {
+        let mouse_pos = self.mouse_pos?;
+        let map_pos = self.get_map_pos(mouse_pos)?;
+        let map_size = self.get_map_size();
+        if map_pos.x >= map_size.x || map_pos.y >= map_size.y {
+            return None;
+        }
+        let text = if self.is_hidden(map_pos) {
+            HIDDEN_TILE_NAME
+        } else {
+            self.get_tile(map_pos).info.name.as_str()
+        };
+        let text = self.text(text).to_owned();
+        let screen_size = self.layout().screen_size;
+        let height = screen_size.y / TOOLTIP_ROWS_PER_SCREEN.into();
+        let padding = height / 2.into();
+        let text_width = self
+            .platform
+            .measure_text(text.as_str(), &style::TOOLTIP_TEXT);
+        let size = Vector {
+            x: partial_ord_min(text_width + padding + padding, screen_size.x),
+            y: height,
+        };
+        // Just below and to the right of the mouse, but kept on the screen
+        let mouse_pos = mouse_pos.cast::<P::ScreenDistance>();
+        let max = screen_size - size;
+        let top_left = Vector {
+            x: partial_ord_min(mouse_pos.x + padding, max.x),
+            y: partial_ord_min(mouse_pos.y + padding, max.y),
+        };
+        Some((text, Rectangle { top_left, size }))
+    }
+
+    // Draws the tooltip for the tile under the mouse, as long as nothing else is open
+    fn show_tooltip(&mut self) {
+        let only_map = self.contexts == [bindings::Context::Map];
+        self.tooltip = match self.get_tooltip().filter(|_| only_map) {
+            Some((text, position)) => {
+                self.fill_rect(&position, style::TOOLTIP_BACKGROUND);
+                let padding = position.height() / 2.into();
+                let text_pos = Vector {
+                    x: position.left() + padding,
+                    y: position.top() + position.height() / 4.into(),
+                };
+                let max_width = position.width() - padding - padding;
+                self.draw_text(text.as_str(), text_pos, max_width, &style::TOOLTIP_TEXT);
+                Some(position)
+            }
+            None => None,
+        };
+    }
+
+    // Forgets where the mouse was once it's left the screen, so that its last position doesn't keep
+    // a tooltip up or count as being in the zone along an edge. Coming back is treated as a fresh
+    // hover
+    fn forget_mouse(&mut self) {
+        self.mouse_pos = None;
+        self.hover_tile = None;
+        self.drag_anchor = None;
+        self.hide_tooltip();
+    }
+
+    // Stops waiting to show the tooltip and uncovers whatever was underneath it
+    fn hide_tooltip(&mut self) {
+        self.mouse_rest_start = None;
+        let position = match self.tooltip.take() {
+            Some(p) => p,
+            None => return,
+        };
+        self.uncover(&position);
+    }
+
+    // Gets where the dialog box is drawn
+    fn get_dialog_position(&self) -> Rectangle<P::ScreenDistance> {
+        let screen_size = self.layout().screen_size;
+        let height = screen_size.y / DIALOG_SCREEN_FRACTION.into();
+        Rectangle {
+            top_left: Vector {
+                x: 0.into(),
+                y: screen_size.y - self.get_hint_bar_height() - height,
+            },
+            size: Vector {
+                x: screen_size.x,
+                y: height,
+            },
+        }
+    }
+
+    // Gets the height of each line of the dialog box along with the space on either side of the text
+    fn get_dialog_spacing(&self) -> (P::ScreenDistance, P::ScreenDistance) {
+        let line_height = self.get_dialog_position().height() / DIALOG_ROWS.into();
+        (line_height, line_height / 2.into())
+    }
+
+    // Splits a message into the pages of lines that it's shown on, based on the current screen
+    // size. Every message has at least one page, even if it's empty
+    fn get_dialog_pages(&self, message: &serialization::Message) -> Vec<Vec<String>> {
+        let (_, padding) = self.get_dialog_spacing();
+        let width = self.get_dialog_position().width() - padding - padding;
+        let lines = dialog::wrap_text(
+            &self.platform,
+            message.text.as_str(),
+            width,
+            &style::DIALOG_TEXT,
+        );
+        let mut pages: Vec<_> = lines
+            .chunks(DIALOG_LINES_PER_PAGE)
+            .map(|c| c.to_vec())
+            .collect();
+        if pages.is_empty() {
+            pages.push(Vec::new());
+        }
+        pages
+    }
+
+    // Draws the dialog box (if a message is being shown) on top of everything else
+    fn draw_dialog(&self) {
+        let message = match self.dialog.current() {
+            Some(m) => m,
+            None => return,
+        };
+        let position = self.get_dialog_position();
+        self.fill_rect(&position, style::DIALOG_BACKGROUND);
+        let (line_height, padding) = self.get_dialog_spacing();
+        let max_width = position.width() - padding - padding;
+        let mut text_pos = position.top_left
+            + Vector {
+                x: padding,
+                y: padding,
+            };
+        if let Some(speaker) = message.speaker.as_ref() {
+            let speaker = text::fit(&self.platform, speaker, max_width, &style::DIALOG_SPEAKER);
+            self.draw_text(
+                speaker.as_str(),
+                text_pos,
+                max_width,
+                &style::DIALOG_SPEAKER,
+            );
+        }
+        let pages = self.get_dialog_pages(message);
+        let page = &pages[self.dialog.page(pages.len())];
+        for line in page.iter() {
+            text_pos.y = text_pos.y + line_height;
+            self.draw_text(line.as_str(), text_pos, max_width, &style::DIALOG_TEXT);
+        }
+    }
+
+    // Moves on to the next page, closing the dialog box after the last page of the last message
+    fn advance_dialog(&mut self) {
+        let page_count = match self.dialog.current() {
+            Some(m) => self.get_dialog_pages(m).len(),
+            None => return,
+        };
+        self.dialog.advance(page_count);
+        if !self.dialog.is_empty() {
+            self.damage.dialog = true;
+            return;
+        }
+        self.contexts.retain(|c| *c != bindings::Context::Dialog);
+        let position = self.get_dialog_position();
+        self.uncover(&position);
+    }
+
+    // Has everything that was underneath part of the screen drawn on the next frame after something
+    // on top of it is taken away
+    fn uncover(&mut self, region: &Rectangle<P::ScreenDistance>) {
+        self.damage.regions.push(*region);
+    }
+
+    // Gets where the level up popup is drawn with some lines on it: the middle of the screen
+    fn get_level_up_popup_position(&self, lines: &[String]) -> Rectangle<P::ScreenDistance> {
+        let screen_size = self.layout().screen_size;
+        let line_height = screen_size.y / LEVEL_UP_ROWS_PER_SCREEN.into();
+        let padding = line_height / 2.into();
+        let text_width = lines
+            .iter()
+            .enumerate()
+            .map(|(i, l)| {
+                let style = if i == 0 {
+                    &style::LEVEL_UP_TITLE
+                } else {
+                    &style::LEVEL_UP_TEXT
+                };
+                self.platform.measure_text(l.as_str(), style)
+            })
+            .fold(0.into(), partial_ord_max);
+        let size = Vector {
+            x: partial_ord_min(text_width + padding + padding, screen_size.x),
+            y: partial_ord_min(
+                line_height * (lines.len() as u32).into() + padding + padding,
+                screen_size.y,
+            ),
+        };
+        Rectangle {
+            top_left: (screen_size - size) / 2.into(),
+            size,
+        }
+    }
+
+    // Draws the level up popup (if one is up) on top of everything else
+    fn draw_level_up_popup(&self) {
+        let lines = match self.level_up_popup.as_ref() {
+            Some(p) => &p.lines,
+            None => return,
+        };
+        let position = self.get_level_up_popup_position(lines);
+        self.fill_rect(&position, style::LEVEL_UP_BACKGROUND);
+        let line_height = self.layout().screen_size.y / LEVEL_UP_ROWS_PER_SCREEN.into();
+        let padding = line_height / 2.into();
+        let max_width = position.width() - padding - padding;
+        for (i, line) in lines.iter().enumerate() {
+            let style = if i == 0 {
+                &style::LEVEL_UP_TITLE
+            } else {
+                &style::LEVEL_UP_TEXT
+            };
+            let text_pos = Vector {
+                x: position.left() + padding,
+                y: position.top() + padding + line_height * (i as u32).into(),
+            };
+            self.draw_text(line.as_str(), text_pos, max_width, style);
+        }
+    }
+
+    // Takes down the level up popup, uncovering whatever was underneath it
+    fn dismiss_level_up_popup(&mut self) {
+        if let Some(popup) = self.level_up_popup.take() {
+            let position = self.get_level_up_popup_position(&popup.lines);
+            self.uncover(&position);
+        }
+    }
+
+    // Gets where a toast is drawn with some text on it: the top middle of the screen
+    fn get_toast_position(&self, text: &str) -> Rectangle<P::ScreenDistance> {
+        let screen_size = self.layout().screen_size;
+        let height = screen_size.y / TOAST_ROWS_PER_SCREEN.into();
+        let padding = height / 2.into();
+        let text_width = self.platform.measure_text(text, &style::TOAST_TEXT);
+        let size = Vector {
+            x: partial_ord_min(text_width + padding + padding, screen_size.x),
+            y: height,
+        };
+        Rectangle {
+            top_left: Vector {
+                x: (screen_size.x - size.x) / 2.into(),
+                y: padding,
+            },
+            size,
+        }
+    }
+
+    fn draw_toast(&self) {
+        let text = match self.toast.as_ref() {
+            Some(t) => t.text.as_str(),
+            None => return,
+        };
+        let position = self.get_toast_position(text);
+        self.fill_rect(&position, style::TOAST_BACKGROUND);
+        let padding = position.height() / 2.into();
+        let text_pos = Vector {
+            x: position.left() + padding,
+            y: position.top() + position.height() / 4.into(),
+        };
+        let max_width = position.width() - padding - padding;
+        self.draw_text(text, text_pos, max_width, &style::TOAST_TEXT);
+    }
+
+    // Shows a toast with the text for a string key in place of any toast that's already up
+    fn show_toast(&mut self, key: &str) {
+        self.show_toast_text(self.text(key).to_owned());
+    }
+
+    fn show_toast_text(&mut self, text: String) {
+        self.dismiss_toast();
+        self.toast = Some(Toast {
+            text,
+            shown_at: self.now(),
+        });
+        self.damage.toast = true;
+    }
+
+    // Takes down the toast, uncovering whatever was underneath it
+    fn dismiss_toast(&mut self) {
+        if let Some(toast) = self.toast.take() {
+            let position = self.get_toast_position(toast.text.as_str());
+            self.uncover(&position);
+        }
+    }
+
+    // Puts up a banner in place of any that's already up. It's dismissed like a message
+    fn show_banner(&mut self, banner: Banner) {
+        if self.banner.is_none() && self.dialog.is_empty() {
+            self.contexts.push(bindings::Context::Dialog);
+        }
+        self.banner = Some(banner);
+    }
+
+    // Gets where the banner is drawn: a band across the middle of the screen
+    fn get_banner_position(&self) -> Rectangle<P::ScreenDistance> {
+        let screen_size = self.layout().screen_size;
+        let height = screen_size.y / BANNER_ROWS_PER_SCREEN.into();
+        Rectangle {
+            top_left: Vector {
+                x: 0.into(),
+                y: (screen_size.y - height) / 2.into(),
+            },
+            size: Vector {
+                x: screen_size.x,
+                y: height,
+            },
+        }
+    }
+
+    // Draws the banner (if one is up) on top of everything else, hiding the whole screen
+    // behind it if the map shouldn't be seen
+    fn draw_banner(&self) {
+        let banner = match self.banner.as_ref() {
+            Some(b) => b,
+            None => return,
+        };
+        if banner.hides_map {
+            let screen = Rectangle {
+                top_left: Vector {
+                    x: 0.into(),
+                    y: 0.into(),
+                },
+                size: self.layout().screen_size,
+            };
+            self.fill_rect(&screen, style::HANDOFF_BACKGROUND);
+        }
+        let position = self.get_banner_position();
+        self.fill_rect(&position, style::BANNER_BACKGROUND);
+        let text_pos = Vector {
+            x: position.left(),
+            y: position.top() + position.height() / 4.into(),
+        };
+        self.draw_text(
+            banner.text.as_str(),
+            text_pos,
+            position.width(),
+            &style::BANNER_TEXT,
+        );
+    }
+
+    // Finds where a replay's transport bar is drawn: along the top of the screen
+    fn get_transport_position(&self) -> Rectangle<P::ScreenDistance> {
+        let screen_size = self.layout().screen_size;
+        let height = match self.platform.get_text_height(&style::TRANSPORT_TEXT) {
+            Some(text_height) => text_height + text_height / 2.into(),
+            None => screen_size.y / HINT_BAR_ROWS_PER_SCREEN.into(),
+        };
+        Rectangle {
+            top_left: Vector {
+                x: 0.into(),
+                y: 0.into(),
+            },
+            size: Vector {
+                x: screen_size.x,
+                y: height,
+            },
+        }
+    }
+
+    // Draws the bar showing how far a replay has got, how fast it's going and the keys that
+    // control it, on top of everything else
+    fn draw_transport(&self) {
+        let transport = match self.transport.as_ref() {
+            Some(t) => t,
+            None => return,
+        };
+        let position = self.get_transport_position();
+        self.fill_rect(&position, style::TRANSPORT_BACKGROUND);
+        let padding = position.height() / 4.into();
+        let width = position.width() - padding - padding;
+        let (done, len) = (transport.position.to_string(), transport.len.to_string());
+        let status = if transport.position == transport.len {
+            self.strings
+                .format::<P>("replay.over", &[done.as_str(), len.as_str()])
+        } else if transport.paused {
+            self.strings
+                .format::<P>("replay.paused", &[done.as_str(), len.as_str()])
+        } else {
+            let speed = transport.speed.to_string();
+            self.strings.format::<P>(
+                "replay.playing",
+                &[done.as_str(), len.as_str(), speed.as_str()],
+            )
+        };
+        let text = self.join_hints(status, &TRANSPORT_HINTS, &[bindings::Context::Map], width);
+        let text_pos = Vector {
+            x: position.left() + padding,
+            y: position.top() + padding,
+        };
+        self.draw_text(text.as_str(), text_pos, width, &style::TRANSPORT_TEXT);
+    }
+
+    // Shows where the replay being watched is up to on its transport bar
+    pub fn show_transport(&mut self, transport: record::Transport) {
+        self.transport = Some(transport);
+        self.draw_transport();
+    }
+
+    // Removes the banner without uncovering what was underneath it
+    fn take_banner(&mut self) -> Option<Banner> {
+        let banner = self.banner.take();
+        if banner.is_some() && self.dialog.is_empty() {
+            self.contexts.retain(|c| *c != bindings::Context::Dialog);
+        }
+        banner
+    }
+
+    // Takes down the banner, showing the map again if it was hidden
+    fn dismiss_banner(&mut self) {
+        if let Some(banner) = self.take_banner() {
+            if banner.hides_map {
+                self.redraw();
+            } else {
+                self.uncover(&self.get_banner_position());
+                self.damage.hint_bar = true;
+            }
+            if banner.reconnect {
+                self.connect_remote();
+            }
+        }
+    }
+
+    // Gets the name an image is exported under, which is made unique by when it was exported, e.g.
+    // "screenshot-2021-03-04-05-06-07"
+    fn export_name(&self, kind: &str) -> String {
+        match self.platform.timestamp() {
+            Some(t) => {
+                let minute = save::format_timestamp(t).replace([' ', ':'], "-");
+                format!("{}-{}-{:02}", kind, minute, t % 60)
+            }
+            None => kind.to_owned(),
+        }
+    }
+
+    // Saves an image of what's on the screen. Changes waiting for the next frame are drawn first so
+    // that the image matches what the player sees, but toasts are left out of it
+    fn take_screenshot(&mut self) {
+        if self.needs_redraw || self.is_animating() {
+            self.draw_screen();
+        }
+        self.dismiss_toast();
+        self.draw_damage();
+        self.platform
+            .export_frame(self.export_name("screenshot").as_str());
+        self.show_toast("toast.screenshot");
+    }
+
+    // Saves an image of the whole map with every tile drawn at the size set in the settings,
+    // however far the screen is zoomed in. Tiles are drawn as the player sees them, so fog of war
+    // still hides what's underneath it
+    fn export_map(&mut self) {
+        let tile_size = self.settings.export_tile_size;
+        let map_size = self.get_map_size();
+        let size = Vector {
+            x: (map_size.x * tile_size).into(),
+            y: (map_size.y * tile_size).into(),
+        };
+        if !self.platform.begin_offscreen(size) {
+            log::warn::<P>("This platform can't export the whole map");
+            return;
+        }
+        for y in 0..map_size.y {
+            for x in 0..map_size.x {
+                let screen_pos = Rectangle {
+                    top_left: Vector {
+                        x: (x * tile_size).into(),
+                        y: (y * tile_size).into(),
+                    },
+                    size: Vector {
+                        x: tile_size.into(),
+                        y: tile_size.into(),
+                    },
+                };
+                self.draw_tile_contents(Vector { x, y }, &screen_pos);
+            }
+        }
+        self.platform
+            .export_offscreen(self.export_name("map").as_str());
+        self.show_toast("toast.map_exported");
+    }
+
+    // Puts where the cursor is and how far the screen is zoomed on the clipboard
+    fn copy_position(&mut self) {
+        let position = self.view_position().to_string();
+        self.platform.set_clipboard(position.as_str());
+        self.show_toast("toast.position_copied");
+    }
+
+    // Remembers where the cursor is and how far the screen is zoomed as one of the map's bookmarks
+    fn set_bookmark(&mut self, index: usize) {
+        let position = self.view_position();
+        bookmarks::set(&self.platform, self.campaign.current(), index, position);
+        let number = (index + 1).to_string();
+        let text = self.format_text("toast.bookmark_set", &[number.as_str()]);
+        self.show_toast_text(text);
+    }
+
+    // Zooms back to where one of the map's bookmarks was set and centers the screen on it.
+    // Bookmarks set before the map shrank are clamped to fit it
+    fn go_to_bookmark(&mut self, index: usize) {
+        let position = match bookmarks::get(&self.platform, self.campaign.current(), index) {
+            Some(p) => p,
+            None => return self.emit(sound::GameSignal::Error),
+        };
+        self.finish_scroll();
+        self.zoom_to(position.zoom);
+        let map_size = self.get_map_size();
+        self.cursor_pos = Vector {
+            x: position.cursor.x.min(map_size.x - 1),
+            y: position.cursor.y.min(map_size.y - 1),
+        };
+        self.center_on(self.cursor_pos);
+        self.redraw();
+    }
+
+    // Starts or stops painting tiles onto the map. Anything that was selected is deselected first,
+    // since Select and Cancel paint and undo while the editor is on
+    fn toggle_editor(&mut self) {
+        if self.editor.take().is_some() {
+            self.show_toast("toast.editor_off");
+            return;
+        }
+        if self.selected_unit.is_some() || !self.highlights.is_empty() {
+            self.clear_highlights();
+        }
+        self.editor = Some(editor::Editor::default());
+        self.show_toast("toast.editor_on");
+    }
+
+    // Gets the tile type that a number key picks while the editor is on and nothing is open on top
+    // of the map: 1 to 9 for the first nine types and 0 for the tenth (None for other keys and for
+    // numbers without a tile type)
+    fn editor_tile_key(&self, key: &P::InputType) -> Option<u32> {
+        if self.editor.is_none() || self.contexts.last() != Some(&bindings::Context::Map) {
+            return None;
+        }
+        let digit = match key.to_string().as_str() {
+            "0" => 10,
+            k if k.len() == 1 => k.parse::<u32>().ok()?,
+            _ => return None,
+        };
+        let tile_type = digit.checked_sub(1)?;
+        // The error tile type at the end can't be painted with
+        if (tile_type as usize) < self.tile_types.len() - 1 {
+            Some(tile_type)
+        } else {
+            None
+        }
+    }
+
+    // Picks the tile type that the editor paints with, showing its name
+    fn choose_tile_type(&mut self, tile_type: u32) {
+        if let Some(editor) = self.editor.as_mut() {
+            editor.tile_type = tile_type;
+            let name = self.tile_types[tile_type as usize].name.clone();
+            self.show_toast(name.as_str());
+        }
+    }
+
+    fn choose_tool(&mut self, tool: editor::Tool) {
+        if let Some(editor) = self.editor.as_mut() {
+            editor.tool = tool;
+        }
+    }
+
+    // Opens a menu of the tile types that can be painted with, followed by the tools that they can
+    // be painted with and saving the map
+    fn open_palette(&mut self) {
+        if self.menu.is_some() {
+            return;
+        }
+        let error_tile = self.tile_types.len() - 1;
+        let mut items: Vec<_> = self.tile_types[..error_tile]
+            .iter()
+            .enumerate()
+            .map(|(i, t)| {
+                (
+                    self.text(t.name.as_str()).to_owned(),
+                    MenuAction::Paint(i as u32),
+                )
+            })
+            .collect();
+        for &width in editor::BRUSH_SIZES.iter() {
+            let text = width.to_string();
+            let label = self.format_text("menu.brush", &[text.as_str(), text.as_str()]);
+            items.push((label, MenuAction::Tool(editor::Tool::Brush(width))));
+        }
+        items.extend(self.label_items(&[
+            ("menu.fill", MenuAction::Tool(editor::Tool::Fill)),
+            ("menu.save_map", MenuAction::SaveMapFile),
+        ]));
+        self.show_menu(items);
+    }
+
+    // Paints the tiles under the editor's tool as one stroke, which is undone all at once
+    fn paint_tile(&mut self) {
+        let (tile_type, tool) = match self.editor.as_ref() {
+            Some(editor) => (editor.tile_type, editor.tool),
+            None => return,
+        };
+        let positions: Vec<_> = match tool {
+            editor::Tool::Brush(width) => {
+                editor::brush_area(self.cursor_pos, width, self.get_map_size())
+                    .filter(|p| self.map[[p.y as usize, p.x as usize]] != tile_type)
+                    .collect()
+            }
+            editor::Tool::Fill => editor::flood_fill(&self.map, self.cursor_pos, tile_type),
+        };
+        if positions.is_empty() {
+            return;
+        }
+        let changes: Vec<_> = positions.into_iter().map(|p| (p, tile_type)).collect();
+        let stroke = self.set_tile_types(&changes);
+        if let Some(editor) = self.editor.as_mut() {
+            editor.undo.push(stroke);
+        }
+    }
+
+    // Takes back the last stroke painted in the editor
+    fn undo_paint(&mut self) {
+        match self.editor.as_mut().and_then(|e| e.undo.pop()) {
+            Some(stroke) => {
+                self.set_tile_types(&stroke);
+            }
+            None => self.emit(sound::GameSignal::Error),
+        }
+    }
+
+    // Changes the types of tiles, returning their old types. Only the changed tiles that are on the
+    // screen are redrawn, along with the minimap, and danger zones that the tiles' move costs could
+    // change are worked out again along with the ambient sounds
+    fn set_tile_types(&mut self, changes: &[(Vector<MapDistance>, u32)]) -> editor::Stroke {
+        let stroke = changes
+            .iter()
+            .map(|&(pos, tile_type)| {
+                let tile = &mut self.map[[pos.y as usize, pos.x as usize]];
+                (pos, std::mem::replace(tile, tile_type))
+            })
+            .collect();
+        if !self.minimap.is_empty() {
+            self.minimap = minimap_runs(&self.map, &self.tile_types, self.visibility.as_ref());
+        }
+        let region = self.visible_region();
+        let positions: Vec<_> = changes.iter().map(|&(pos, _)| pos).collect();
+        self.redraw_tiles(positions.iter().copied().filter(|&p| region.contains(p)));
+        self.damage.minimap = true;
+        self.invalidate_danger_zones(&positions);
+        self.ambient_screen = None;
+        self.update_ambient_sounds();
+        stroke
+    }
+
+    // Saves the map as a map file with the tiles painted in the editor. Units and triggers are
+    // saved as they are now, so units that have moved are saved where they are
+    fn save_map_file(&mut self) {
+        let error_tile = self.tile_types.len() - 1;
+        let map_file = serialization::Map {
+            tile_types: self.tile_types[..error_tile].to_vec(),
+            // Tiles whose type didn't exist still point past the end of the tile types
+            map: self.map.clone(),
+            background: self.background,
+            fog: self.visibility.is_some(),
+            triggers: self.triggers.clone(),
+            intro_dialog: self.intro_dialog.clone(),
+            music: self.music.clone(),
+            units: self.units.iter().map(|u| u.info.clone()).collect(),
+        };
+        match serialization::write_map(&map_file) {
+            Ok(bytes) => {
+                let name = format!("{}.map", self.export_name("map"));
+                self.platform.export_file(name.as_str(), &bytes);
+                self.show_toast("toast.map_saved");
+            }
+            Err(e) => log::warn::<P>(format!("Failed to save the map file: {}", e).as_str()),
+        }
+    }
+
+    // Handles an event while a unit is moving or fighting, returning false if it should be handled
+    // as usual. Cancelling skips to the end, and nothing else the player does is handled until it
+    // has finished
+    fn handle_animation_event(&mut self, event: &Event<P::MouseDistance, P::InputType>) -> bool {
+        // The animation may have finished since the last frame was drawn
+        self.update_movement();
+        self.update_combat();
+        if self.move_animation.is_none() && self.combat_animation.is_none() {
+            return false;
+        }
+        match event {
+            Event::Cancel => {
+                self.finish_movement();
+                self.finish_combat();
+            }
+            Event::Redraw
+            | Event::Quit
+            | Event::Suspend
+            | Event::Resume
+            | Event::FocusLost
+            | Event::FocusGained
+            | Event::Input(_)
+            | Event::Release(_)
+            | Event::DragEnd
+            | Event::MouseLeave
+            | Event::ToggleMute
+            | Event::ToggleDebugOverlay
+            | Event::Screenshot => return false,
+            _ => {}
+        }
+        true
+    }
+
+    // Handles an event while the level up popup is up, returning false if it should be handled as
+    // usual. Only selecting or cancelling does anything to the popup, which goes away on its own
+    // otherwise
+    fn handle_level_up_event(&mut self, event: &Event<P::MouseDistance, P::InputType>) -> bool {
+        if self.level_up_popup.is_none() {
+            return false;
+        }
+        match event {
+            Event::Select | Event::Cancel => self.dismiss_level_up_popup(),
+            Event::Redraw
+            | Event::Quit
+            | Event::Suspend
+            | Event::Resume
+            | Event::FocusLost
+            | Event::FocusGained
+            | Event::Input(_)
+            | Event::Release(_)
+            | Event::DragEnd
+            | Event::MouseLeave
+            | Event::StartRebind(_)
+            | Event::ReloadAssets
+            | Event::NextMap
+            | Event::ToggleMute
+            | Event::ToggleDebugOverlay
+            | Event::ToggleGrid
+            | Event::Screenshot
+            | Event::ExportMap
+            | Event::CopyPosition => return false,
+            // Keep the popup from being drawn over until it's gone
+            _ => {}
+        }
+        true
+    }
+
+    // Handles an event while the banner is up, returning false if it should be handled as usual.
+    // Only Select takes the banner down, so that the next player can't act by accident
+    fn handle_banner_event(&mut self, event: &Event<P::MouseDistance, P::InputType>) -> bool {
+        if self.banner.is_none() {
+            return false;
+        }
+        match event {
+            Event::Select => self.dismiss_banner(),
+            Event::Redraw
+            | Event::Quit
+            | Event::Suspend
+            | Event::Resume
+            | Event::FocusLost
+            | Event::FocusGained
+            | Event::Input(_)
+            | Event::Release(_)
+            | Event::DragEnd
+            | Event::MouseLeave
+            | Event::StartRebind(_)
+            | Event::ReloadAssets
+            | Event::NextMap
+            | Event::ToggleMute
+            | Event::ToggleDebugOverlay
+            | Event::ToggleGrid
+            | Event::Screenshot
+            | Event::CopyPosition => return false,
+            // Exporting the whole map would give away what the banner is hiding
+            _ => {}
+        }
+        true
+    }
+
+    // Handles an event while a message is shown, returning false if it should be handled as usual
+    fn handle_dialog_event(&mut self, event: &Event<P::MouseDistance, P::InputType>) -> bool {
+        if self.dialog.is_empty() {
+            return false;
+        }
+        match event {
+            Event::Select | Event::Cancel => self.advance_dialog(),
+            Event::Redraw
+            | Event::Quit
+            | Event::Suspend
+            | Event::Resume
+            | Event::FocusLost
+            | Event::FocusGained
+            | Event::Input(_)
+            | Event::Release(_)
+            | Event::DragEnd
+            | Event::MouseLeave
+            | Event::StartRebind(_)
+            | Event::ReloadAssets
+            | Event::NextMap
+            | Event::ToggleMute
+            | Event::ToggleDebugOverlay
+            | Event::ToggleGrid
+            | Event::Screenshot
+            | Event::ExportMap
+            | Event::CopyPosition => return false,
+            // Keep the player from doing anything else until they've read the message
+            _ => {}
+        }
+        true
+    }
+
+    // Opens the action menu for the tile under the cursor
+    fn open_menu(&mut self) {
+        if self.menu.is_some() {
+            return;
+        }
+        let items = [
+            ("menu.end_turn", MenuAction::EndTurn),
+            ("menu.save", MenuAction::Save),
+            ("menu.load", MenuAction::Load),
+            ("menu.options", MenuAction::Options),
+        ];
+        self.show_menu(self.label_items(&items));
+    }
+
+    // Looks up the text for menu items labelled with string keys
+    fn label_items(&self, items: &[(&str, MenuAction)]) -> Vec<(String, MenuAction)> {
+        items
+            .iter()
+            .map(|&(key, action)| (self.text(key).to_owned(), action))
+            .collect()
+    }
+
+    // Opens a menu of options, each labelled with its current value
+    fn open_options_menu(&mut self) {
+        let items = [
+            Setting::Language,
+            Setting::Volume,
+            Setting::Smoothing,
+            Setting::PanDelay,
+            Setting::Grid,
+        ]
+        .iter()
+        .map(|&setting| (self.setting_label(setting), MenuAction::Setting(setting)))
+        .collect();
+        self.show_menu(items);
+    }
+
+    // Gets the name of a locale in its own language if the current strings file names it, or its
+    // code if not
+    fn locale_name<'a>(&'a self, locale: &'a str) -> &'a str {
+        let key = format!("locale.{}", locale);
+        self.strings.lookup(key.as_str()).unwrap_or(locale)
+    }
+
+    // Gets the label of an option on the options menu, which shows its current value
+    fn setting_label(&self, setting: Setting) -> String {
+        let on_off = |on| self.text(if on { "option.on" } else { "option.off" });
+        match setting {
+            Setting::Language => {
+                self.format_text("option.language", &[self.locale_name(self.locale.as_str())])
+            }
+            Setting::Volume => {
+                let volume = self.sound.volume.min(sound::MAX_VOLUME).to_string();
+                self.format_text("option.volume", &[volume.as_str()])
+            }
+            Setting::Smoothing => {
+                self.format_text("option.smoothing", &[on_off(self.settings.image_smoothing)])
+            }
+            Setting::PanDelay => {
+                let delay = self.settings.mouse_pan_delay_ms.to_string();
+                self.format_text("option.pan_delay", &[delay.as_str()])
+            }
+            Setting::Grid => self.format_text("option.grid", &[on_off(self.settings.show_grid)]),
+        }
+    }
+
+    // Changes an option to its next value (or its previous one if forward is false), applying it
+    // straight away and saving it for later games
+    fn adjust_setting(&mut self, setting: Setting, forward: bool) {
+        match setting {
+            Setting::Language => {
+                let count = self.settings.locales.len();
+                if count == 0 {
+                    return;
+                }
+                let current = self.settings.locales.iter().position(|l| *l == self.locale);
+                let index = match (current, forward) {
+                    (Some(i), true) => (i + 1) % count,
+                    (Some(i), false) => (i + count - 1) % count,
+                    (None, _) => 0,
+                };
+                // The menu is relabelled once the new strings file has loaded
+                return self.switch_locale(index);
+            }
+            Setting::Volume => {
+                let volume = self.sound.volume.min(sound::MAX_VOLUME);
+                self.sound.volume = if forward {
+                    (volume + options::VOLUME_STEP).min(sound::MAX_VOLUME)
+                } else {
+                    volume.saturating_sub(options::VOLUME_STEP)
+                };
+                self.platform.set_volume(self.sound.effective_volume());
+                sound::save(&self.platform, &self.sound);
+            }
+            Setting::Smoothing => {
+                let smoothing = !self.settings.image_smoothing;
+                self.settings.image_smoothing = smoothing;
+                self.options.image_smoothing = Some(smoothing);
+                self.platform.set_smoothing(smoothing);
+                // Everything on the screen was scaled the old way
+                self.redraw();
+            }
+            Setting::PanDelay => {
+                let delay = self.settings.mouse_pan_delay_ms;
+                let delay = if forward {
+                    delay.saturating_add(options::PAN_DELAY_STEP_MS)
+                } else {
+                    delay.saturating_sub(options::PAN_DELAY_STEP_MS)
+                };
+                let delay = delay.clamp(options::MIN_PAN_DELAY_MS, options::MAX_PAN_DELAY_MS);
+                self.settings.mouse_pan_delay_ms = delay;
+                self.options.mouse_pan_delay_ms = Some(delay);
+            }
+            Setting::Grid => {
+                let show_grid = !self.settings.show_grid;
+                self.settings.show_grid = show_grid;
+                self.options.show_grid = Some(show_grid);
+                if self.show_grid != show_grid {
+                    self.toggle_grid();
+                }
+            }
+        }
+        options::save(&self.platform, &self.options);
+        self.relabel_options_menu();
+    }
+
+    // Updates the labels on the options menu (if it's open) to show the current value of each option
+    fn relabel_options_menu(&mut self) {
+        let mut menu = match self.menu.take() {
+            Some(m) => m,
+            None => return,
+        };
+        let (old_position, _) = self.get_menu_position(&menu);
+        menu.relabel(|action| match action {
+            MenuAction::Setting(setting) => Some(self.setting_label(*setting)),
+            _ => None,
+        });
+        let (position, _) = self.get_menu_position(&menu);
+        self.menu = Some(menu);
+        if position == old_position {
+            self.damage.menu = true;
+        } else {
+            // Whatever the old menu covered has to be uncovered
+            self.redraw();
+        }
+    }
+
+    // Opens a menu listing the locales that the game's text can be switched to
+    fn open_language_menu(&mut self) {
+        let items = self
+            .settings
+            .locales
+            .iter()
+            .enumerate()
+            .map(|(i, locale)| (self.locale_name(locale).to_owned(), MenuAction::Locale(i)))
+            .collect();
+        self.show_menu(items);
+    }
+
+    // Starts switching the game's text to another locale, which is remembered for later games
+    fn switch_locale(&mut self, index: usize) {
+        if let Some(locale) = self.settings.locales.get(index) {
+            self.pending_locale = Some(locale.clone());
+            self.options.locale = Some(locale.clone());
+            options::save(&self.platform, &self.options);
+        }
+    }
+
+    // Opens a menu listing the save slots along with when each was saved, followed by the latest
+    // autosave if it's a menu for loading
+    fn open_slot_menu(&mut self, action: fn(usize) -> MenuAction, with_autosave: bool) {
+        let mut items: Vec<_> = save::SLOTS
+            .iter()
+            .enumerate()
+            .map(|(i, slot)| {
+                let label = save::describe(&self.platform, &self.strings, slot);
+                (label, action(i))
+            })
+            .collect();
+        if with_autosave {
+            let label = save::describe_autosave(&self.platform, &self.strings);
+            items.push((label, MenuAction::LoadAutosave));
+        }
+        self.show_menu(items);
+    }
+
+    // Saves the game in a slot, replacing whatever was saved there before
+    fn save_to(&mut self, slot: usize) {
+        save::store(&self.platform, save::SLOTS[slot], &self.snapshot());
+    }
+
+    // Starts loading the game saved in a slot. Nothing is loaded if the slot is empty, and saves
+    // that can't be loaded are explained in the dialog box
+    fn load_from(&mut self, slot: usize) {
+        let retrieved = save::retrieve(&self.platform, save::SLOTS[slot]);
+        self.start_restore(retrieved);
+    }
+
+    // Starts loading the latest autosave that's intact
+    fn load_autosave(&mut self) {
+        let retrieved = save::retrieve_autosave(&self.platform);
+        self.start_restore(retrieved);
+    }
+
+    // Starts loading a save that was read, unless there wasn't one or it can't be loaded
+    fn start_restore(&mut self, retrieved: Result<Option<save::Snapshot>, Error>) {
+        match retrieved {
+            Ok(Some(snapshot)) => {
+                self.pending_load = Some(PendingLoad::Restore(Box::new(snapshot)))
+            }
+            Ok(None) => self.emit(sound::GameSignal::Error),
+            Err(e) => self.show_load_error(e),
+        }
+    }
+
+    fn show_load_error(&mut self, e: Error) {
+        let text = self.format_text("error.load_save", &[e.to_string().as_str()]);
+        log::error::<P>(text.as_str());
+        self.emit(sound::GameSignal::Error);
+        self.show_dialog(vec![serialization::Message {
+            speaker: None,
+            text,
+        }]);
+    }
+
+    fn show_menu(&mut self, items: Vec<(String, MenuAction)>) {
+        self.menu = Some(menu::Menu::new(items));
+        self.contexts.push(bindings::Context::Menu);
+        self.damage.hint_bar = true;
+        self.damage.menu = true;
+        self.emit(sound::GameSignal::MenuOpened);
+    }
+
+    // Gets where a menu is drawn along with the height of each of its rows
+    fn get_menu_position<T>(
+        &self,
+        menu: &menu::Menu<T>,
+    ) -> (Rectangle<P::ScreenDistance>, P::ScreenDistance) {
+        let screen_size = self.layout().screen_size;
+        let row_height = screen_size.y / MENU_ROWS_PER_SCREEN.into();
+        let padding = row_height / 2.into();
+        let text_width = menu
+            .labels()
+            .map(|l| self.platform.measure_text(l, &style::MENU_ITEM))
+            .fold(0.into(), partial_ord_max);
+        let available = screen_size.y - self.get_hint_bar_height();
+        let size = Vector {
+            x: partial_ord_min(text_width + padding + padding, screen_size.x),
+            y: partial_ord_min(row_height * (menu.len() as u32).into(), available),
+        };
+        let position = Rectangle {
+            top_left: Vector {
+                x: screen_size.x - size.x,
+                y: (available - size.y) / 2.into(),
+            },
+            size,
+        };
+        (position, row_height)
+    }
+
+    // Draws the menu (if one is open) on top of everything else
+    fn draw_menu(&self) {
+        let menu = match self.menu.as_ref() {
+            Some(m) => m,
+            None => return,
+        };
+        let (position, row_height) = self.get_menu_position(menu);
+        self.fill_rect(&position, style::MENU_BACKGROUND);
+        let padding = row_height / 2.into();
+        let mut row_top = position.top();
+        for (i, label) in menu.labels().enumerate() {
+            if i == menu.selected_index() {
+                let row = Rectangle {
+                    top_left: Vector {
+                        x: position.left(),
+                        y: row_top,
+                    },
+                    size: Vector {
+                        x: position.width(),
+                        y: row_height,
+                    },
+                };
+                self.fill_rect(&row, style::MENU_HIGHLIGHT);
+            }
+            let text_pos = Vector {
+                x: position.left() + padding,
+                y: row_top + row_height / 4.into(),
+            };
+            let max_width = position.width() - padding - padding;
+            self.draw_text(label, text_pos, max_width, &style::MENU_ITEM);
+            row_top = row_top + row_height;
+        }
+    }
+
+    // Closes the menu and uncovers whatever was underneath it
+    fn close_menu(&mut self) {
+        let menu = match self.menu.take() {
+            Some(m) => m,
+            None => return,
+        };
+        self.contexts.retain(|c| *c != bindings::Context::Menu);
+        let (position, _) = self.get_menu_position(&menu);
+        self.uncover(&position);
+    }
+
+    // Handles an event while the menu is open, returning false if it should be handled as usual
+    fn handle_menu_event(&mut self, event: &Event<P::MouseDistance, P::InputType>) -> bool {
+        let menu = match self.menu.as_mut() {
+            Some(m) => m,
+            None => return false,
+        };
+        match event {
+            Event::Up => {
+                menu.previous();
+                self.damage.menu = true;
+            }
+            Event::Left | Event::Right => {
+                if let Some(&MenuAction::Setting(setting)) = menu.selected() {
+                    self.adjust_setting(setting, matches!(event, Event::Right));
+                }
+            }
+            Event::Down => {
+                menu.next();
+                self.damage.menu = true;
+            }
+            Event::Select => {
+                let action = menu.selected().copied();
+                if let Some(MenuAction::Setting(setting)) = action {
+                    // Options other than the language are changed in place, keeping the menu open
+                    if setting != Setting::Language {
+                        self.adjust_setting(setting, true);
+                        return true;
+                    }
+                }
+                self.close_menu();
+                if action.is_some() {
+                    self.emit(sound::GameSignal::SelectionConfirmed);
+                }
+                match action {
+                    Some(MenuAction::EndTurn) if self.is_remote_turn() => {
+                        self.emit(sound::GameSignal::Error)
+                    }
+                    Some(MenuAction::EndTurn) => self.perform(net::Command::EndTurn),
+                    Some(MenuAction::Save) => self.open_slot_menu(MenuAction::SaveTo, false),
+                    Some(MenuAction::Load) => self.open_slot_menu(MenuAction::LoadFrom, true),
+                    Some(MenuAction::SaveTo(slot)) => self.save_to(slot),
+                    Some(MenuAction::LoadFrom(slot)) => self.load_from(slot),
+                    Some(MenuAction::LoadAutosave) => self.load_autosave(),
+                    Some(MenuAction::Options) => self.open_options_menu(),
+                    Some(MenuAction::Setting(_)) => self.open_language_menu(),
+                    Some(MenuAction::Locale(locale)) => self.switch_locale(locale),
+                    Some(MenuAction::Wait) => self.wait(),
+                    Some(MenuAction::Attack(target)) => self.attack(target),
+                    Some(MenuAction::Paint(tile_type)) => self.choose_tile_type(tile_type),
+                    Some(MenuAction::Tool(tool)) => self.choose_tool(tool),
+                    Some(MenuAction::SaveMapFile) => self.save_map_file(),
+                    None => {}
+                }
+            }
+            Event::Cancel => {
+                self.close_menu();
+                self.undo_move();
+            }
+            Event::Redraw
+            | Event::Quit
+            | Event::Suspend
+            | Event::Resume
+            | Event::FocusLost
+            | Event::FocusGained
+            | Event::Input(_)
+            | Event::Release(_)
+            | Event::DragEnd
+            | Event::MouseLeave
+            | Event::StartRebind(_)
+            | Event::ReloadAssets
+            | Event::NextMap
+            | Event::ToggleMute
+            | Event::ToggleDebugOverlay
+            | Event::ToggleGrid
+            | Event::Screenshot
+            | Event::ExportMap
+            | Event::CopyPosition => return false,
+            // Leave the map alone while the menu is open
+            _ => {}
+        }
+        true
+    }
+}
+
+// Works out the largest whole number of pixels that tiles can be while fitting the visible tiles on
+// the screen, along with the viewport they're drawn in, centered on the screen (None if the screen
+// size can't be worked with)
+fn integer_viewport<T: Scalar + ToPrimitive + FromPrimitive>(
+    screen_size: Vector<T>,
+    visible_tiles: Vector<MapDistance>,
+) -> Option<(Vector<T>, Rectangle<T>)> {
+    let screen = screen_size.lossy_cast::<f64>()?;
+    let tile = |screen: f64, tiles: MapDistance| (screen / tiles.max(1) as f64).floor().max(1.0);
+    let margin = |screen: f64, viewport: f64| ((screen - viewport) / 2.0).floor().max(0.0);
+    let tile_size = Vector {
+        x: tile(screen.x, visible_tiles.x),
+        y: tile(screen.y, visible_tiles.y),
+    };
+    let size = Vector {
+        x: tile_size.x * visible_tiles.x as f64,
+        y: tile_size.y * visible_tiles.y as f64,
+    };
+    let from_f64 = |v: Vector<f64>| {
+        Some(Vector {
+            x: T::from_f64(v.x)?,
+            y: T::from_f64(v.y)?,
+        })
+    };
+    let viewport = Rectangle {
+        top_left: from_f64(Vector {
+            x: margin(screen.x, size.x),
+            y: margin(screen.y, size.y),
+        })?,
+        size: from_f64(size)?,
+    };
+    Some((from_f64(tile_size)?, viewport))
+}
+
+// Gets the strips of an area around a rectangle inside it, leaving out any that are empty
+fn surrounding_strips<T: Scalar + num_traits::Zero>(
+    area: Rectangle<T>,
+    inner: Rectangle<T>,
+) -> Vec<Rectangle<T>> {
+    let zero = T::zero();
+    let (top, bottom) = (inner.top(), inner.bottom());
+    let strips = [
+        (area.top_left, area.width(), top - area.top()),
+        (
+            Vector {
+                x: area.left(),
+                y: bottom,
+            },
+            area.width(),
+            area.bottom() - bottom,
+        ),
+        (
+            Vector {
+                x: area.left(),
+                y: top,
+            },
+            inner.left() - area.left(),
+            bottom - top,
+        ),
+        (
+            Vector {
+                x: inner.right(),
+                y: top,
+            },
+            area.right() - inner.right(),
+            bottom - top,
+        ),
+    ];
+    strips
+        .iter()
+        .filter(|(_, width, height)| *width > zero && *height > zero)
+        .map(|(top_left, width, height)| Rectangle {
+            top_left: *top_left,
+            size: Vector {
+                x: *width,
+                y: *height,
+            },
+        })
+        .collect()
+}
+
+fn partial_ord_min<T: std::cmp::PartialOrd>(a: T, b: T) -> T {
+    if b < a {
+        b
+    } else {
+        a
+    }
+}
+
+fn partial_ord_max<T: std::cmp::PartialOrd>(a: T, b: T) -> T {
+    if b > a {
+        b
+    } else {
+        a
+    }
+}
+
+// Checks a map file for problems that would prevent the game from running
+fn validate_map(map_file: &serialization::Map) -> Result<(), Error> {
+    let mut problems = vec![];
+    let (rows, columns) = map_file.map.dim();
+    if rows == 0 || columns == 0 {
+        problems.push(format!("map has dimensions {}x{}", columns, rows));
+    }
+    if rows > MapDistance::MAX as usize || columns > MapDistance::MAX as usize {
+        problems.push(format!("map dimensions {}x{} are too large", columns, rows));
+    }
+    if map_file.tile_types.is_empty() {
+        problems.push("map has no tile types".to_owned());
+    }
+    for unit in map_file.units.iter() {
+        if unit.x as usize >= columns || unit.y as usize >= rows {
+            problems.push(format!(
+                "unit {} at ({}, {}) is outside the map",
+                unit.name, unit.x, unit.y
+            ));
+        }
+    }
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::InvalidMap(problems))
+    }
+}
+
+// Map file along with the images for its tile types, ready for a game to use
+pub struct LoadedMap<P: Platform> {
+    // Index into tile_types of the type of each tile
+    map: Array2<u32>,
+    // Includes an error tile type at the end for tiles whose type doesn't exist
+    tile_types: Vec<serialization::TileType>,
+    // Image for each tile type, in the same order as tile_types
+    images: Vec<Asset<P::Image>>,
+    units: Vec<Unit<P::Image>>,
+    background: Option<style::Color>,
+    fog: bool,
+    triggers: Vec<serialization::Trigger>,
+    intro_dialog: Vec<serialization::Message>,
+    music: Option<String>,
+}
+
+// Gets the path of the campaign's current map
+fn map_path(language: &str, campaign: &campaign::Campaign) -> String {
+    format!("{}/{}", language, campaign.current())
+}
+
+// Retrieves and checks a map file, then loads the images for its tile types
+pub async fn load_map<P: Platform>(
+    platform: &P,
+    manifest: &fetch::Manifest,
+    path: &str,
+) -> Result<LoadedMap<P>, Error> {
+    let map_file = fetch_campaign_map(platform, manifest, path).await?;
+    Ok(load_map_images(platform, map_file).await)
+}
+
+// Retrieves a map of the campaign, with the player's units picking up whatever they've earned on
+// earlier maps
+async fn fetch_campaign_map<P: Platform>(
+    platform: &P,
+    manifest: &fetch::Manifest,
+    path: &str,
+) -> Result<serialization::Map, Error> {
+    let mut map_file = fetch_map(platform, manifest, path).await?;
+    let roster = roster::load(platform);
+    for info in map_file.units.iter_mut() {
+        if info.team == serialization::PLAYER_TEAM {
+            if let Some(progress) = roster.get(&info.name) {
+                progress.apply(info);
+            }
+        }
+    }
+    Ok(map_file)
+}
+
+// Hashes a snapshot of a game, e.g. to check whether two games have ended up in the same state
+fn hash_snapshot<P: Platform>(snapshot: &save::Snapshot) -> String {
+    let mut hasher = sha256::Sha256::default();
+    match rmp_serde::to_vec_named(snapshot) {
+        Ok(bytes) => hasher.update(bytes.as_slice()),
+        Err(e) => log::error::<P>(format!("Failed to encode the game state: {}", e).as_str()),
+    }
+    sha256::to_hex(&hasher.finish())
+}
+
+// Retrieves the map that a game was saved on, with its units and triggers as they were when it was
+// saved
+async fn load_saved_map<P: Platform>(
+    platform: &P,
+    manifest: &fetch::Manifest,
+    language: &str,
+    snapshot: &save::Snapshot,
+) -> Result<LoadedMap<P>, Error> {
+    let path = format!("{}/{}", language, snapshot.map);
+    let mut map_file = fetch_map(platform, manifest, path.as_str()).await?;
+    map_file.units = snapshot.units.clone();
+    map_file.triggers = snapshot.triggers.clone();
+    // The map was already introduced when the game was saved
+    map_file.intro_dialog.clear();
+    validate_map(&map_file)?;
+    Ok(load_map_images(platform, map_file).await)
+}
+
+// Retrieves a map file and checks it for problems
+async fn fetch_map<P: Platform>(
+    platform: &P,
+    manifest: &fetch::Manifest,
+    path: &str,
+) -> Result<serialization::Map, Error> {
+    let mut file = fetch::fetch_with_retry(platform, manifest, path).await?;
+    let decoded = rmp_serde::decode::from_read(&mut file);
+    file.finish()?;
+    let map_file: serialization::Map = decoded.map_err(|e| Error::Decode {
+        path: path.to_owned(),
+        source_msg: e.to_string(),
+    })?;
+    validate_map(&map_file)?;
+    Ok(map_file)
+}
+
+// Loads the images for a map file's tile types
+async fn load_map_images<P: Platform>(platform: &P, map_file: serialization::Map) -> LoadedMap<P> {
+    // Point tiles whose type doesn't exist at the error tile type
+    let mut tile_types = map_file.tile_types;
+    let error_tile = tile_types.len() as u32;
+    tile_types.push(serialization::TileType {
+        image: "".to_owned(),
+        name: "ERROR".to_owned(),
+        defense: 0,
+        evade: 0,
+        move_cost: 1,
+        minimap_color: None,
+        ambient_sound: None,
+        footstep_sound: None,
+    });
+    let mut invalid_tiles = log::Repeated::<P>::new(log::Level::Warn);
+    let map = map_file.map.mapv(|i| {
+        if i < error_tile {
+            i
+        } else {
+            let problem = format!("tile type {} does not exist", i);
+            invalid_tiles.log(Error::InvalidMap(vec![problem]).to_string().as_str());
+            error_tile
+        }
+    });
+    invalid_tiles.finish();
+
+    // Load the image for each tile type followed by the sprite and portrait of each unit. Tile
+    // types and units without an image are left blank rather than requesting an empty path
+    let tile_paths = tile_types[..error_tile as usize]
+        .iter()
+        .map(|t| Some(t.image.clone()));
+    let unit_paths = map_file
+        .units
+        .iter()
+        .flat_map(|u| [Some(u.image.clone()), u.portrait.clone()]);
+    let paths = tile_paths
+        .chain(unit_paths)
+        .map(|p| p.filter(|p| !p.is_empty()));
+    let mut images = load_assets(platform, paths).await;
+    let mut unit_images = images.split_off(error_tile as usize).into_iter();
+    images.push(Asset {
+        path: None,
+        image: None,
+    });
+    let units = map_file
+        .units
+        .into_iter()
+        .map_while(|info| {
+            Some(Unit {
+                info,
+                sprite: std::rc::Rc::new(unit_images.next()?),
+                portrait: unit_images.next()?,
+            })
+        })
+        .collect();
+
+    LoadedMap {
+        map,
+        tile_types,
+        images,
+        units,
+        background: map_file.background,
+        fog: map_file.fog,
+        triggers: map_file.triggers,
+        intro_dialog: map_file.intro_dialog,
+        music: map_file.music,
+    }
+}
+
+// Loads images several at a time, keeping them in the same order as their paths (None for no image)
+async fn load_assets<P: Platform>(
+    platform: &P,
+    paths: impl Iterator<Item = Option<String>>,
+) -> Vec<Asset<P::Image>> {
+    let load_limit = platform.image_load_limit().unwrap_or(usize::MAX).max(1);
+    stream::iter(paths)
+        .map(|path| {
+            let image = path.as_deref().map(|p| platform.get_image(p));
+            async move {
+                let image = match image {
+                    Some(i) => i.await,
+                    None => None,
+                };
+                Asset { path, image }
+            }
+        })
+        .buffered(load_limit)
+        .collect()
+        .await
+}
+
+// Swaps each unit's sprite for a copy tinted with its team's color, tinting each sprite with each
+// color only once. Sprites are left as they are if the team has no color or the platform can't tint
+async fn tint_unit_sprites<P: Platform>(
+    platform: &P,
+    units: &mut [Unit<P::Image>],
+    team_colors: &std::collections::HashMap<String, style::Color>,
+) {
+    let mut tinted = std::collections::HashMap::new();
+    for unit in units.iter_mut() {
+        let (path, color) = match (unit.sprite.path.as_ref(), team_colors.get(&unit.info.team)) {
+            (Some(path), Some(&color)) => (path.clone(), color),
+            _ => continue,
+        };
+        let key = (path, color);
+        if let Some(sprite) = tinted.get(&key) {
+            unit.sprite = std::rc::Rc::clone(sprite);
+            continue;
+        }
+        let future = unit
+            .sprite
+            .image
+            .as_ref()
+            .and_then(|i| platform.tint(i, color));
+        let image = match future {
+            Some(f) => f.await,
+            None => None,
+        };
+        if let Some(image) = image {
+            unit.sprite = std::rc::Rc::new(Asset {
+                path: Some(key.0.clone()),
+                image: Some(image),
+            });
+        }
+        // Sprites that couldn't be tinted are remembered too, so that they aren't tried again
+        tinted.insert(key, std::rc::Rc::clone(&unit.sprite));
+    }
+}
+
+impl<P: Platform> Game<P> {
+    // Loads the map and everything else needed to start the game, then draws the first frame
+    pub async fn load(platform: P, language: &str, locale: &str) -> Result<Game<P>, Error> {
+        let seed = platform.random();
+        Game::load_seeded(platform, language, locale, seed).await
+    }
+
+    // Same as load, but starts the random number generator with a particular seed (e.g. to replay
+    // a game)
+    pub async fn load_seeded(
+        platform: P,
+        language: &str,
+        locale: &str,
+        seed: u64,
+    ) -> Result<Game<P>, Error> {
+        Game::load_seeded_at(platform, language, locale, seed, None).await
+    }
+
+    // Same as load_seeded, but starts on a particular map of the campaign (if it's part of it)
+    // rather than the one the player was last on
+    pub async fn load_seeded_at(
+        platform: P,
+        language: &str,
+        locale: &str,
+        seed: u64,
+        map: Option<&str>,
+    ) -> Result<Game<P>, Error> {
+        let last_mouse_pan = P::now();
+
+        let cursor_future = platform.get_image(CURSOR_IMAGE);
+        let info_future = platform.get_image(INFO_BAR_IMAGE);
+        let manifest = fetch::load_manifest(&platform).await;
+        let options = options::load(&platform);
+        let locale = options.locale.as_deref().unwrap_or(locale);
+        let map_future = async {
+            let mut campaign = campaign::load(&platform, &manifest, language).await;
+            if let Some(map) = map {
+                if !campaign.go_to(map) {
+                    log::warn::<P>(format!("{} is not part of the campaign", map).as_str());
+                }
+            }
+            let path = map_path(language, &campaign);
+            let loaded = match fetch_campaign_map(&platform, &manifest, path.as_str()).await {
+                Ok(map_file) => {
+                    draw_loading_screen(&platform, LoadingPhase::LoadingImages);
+                    let loaded = load_map_images(&platform, map_file).await;
+                    draw_loading_screen(&platform, LoadingPhase::Starting);
+                    Ok(loaded)
+                }
+                Err(e) => Err(e),
+            };
+            (campaign, loaded)
+        };
+        let ((campaign, loaded), mut settings, bindings, strings) = future::join4(
+            map_future,
+            settings::load(&platform, &manifest),
+            bindings::load(&platform, &manifest, locale),
+            strings::load(&platform, &manifest, locale),
+        )
+        .await;
+        options.apply(&mut settings);
+        platform.set_smoothing(settings.image_smoothing);
+        log::set_verbosity(platform.log_level().unwrap_or(settings.log_level));
+        let sound = sound::load(&platform);
+        let mut loaded = match loaded {
+            Ok(l) => l,
+            Err(e) => {
+                // Leave the error up in place of the loading screen
+                let text = strings.format::<P>("error.start", &[e.to_string().as_str()]);
+                let screen_size = platform.get_screen_size();
+                draw_message_screen(&platform, screen_size, text.as_str(), strings.direction());
+                return Err(e);
+            }
+        };
+        tint_unit_sprites(&platform, &mut loaded.units, &settings.team_colors).await;
+        let (statuses, icon_paths): (Vec<_>, Vec<_>) = settings
+            .status_icons
+            .iter()
+            .map(|(&status, path)| (status, Some(path.clone())))
+            .unzip();
+        let icons = load_assets(&platform, icon_paths.into_iter()).await;
+        let status_icons = statuses.into_iter().zip(icons).collect();
+        let map = loaded.map;
+        let ready_units = ready_units(&loaded.units, player_team(&settings, 0));
+
+        let (rows, columns) = map.dim();
+        let map_size = Vector {
+            x: columns as MapDistance,
+            y: rows as MapDistance,
+        };
+
+        let remote = settings
+            .remote_url
+            .clone()
+            .map(|url| net::Session::new(url, settings.remote_teams.clone()));
+        // Both games of a session make the same random decisions as long as they carry out the
+        // same commands, so they have to start from the same seed
+        let seed = settings.remote_url.as_deref().map_or(seed, net::seed);
+        let mut game = Game {
+            platform,
+            cursor_pos: Vector { x: 0, y: 0 },
+            map,
+            tile_types: loaded.tile_types,
+            images: loaded.images,
+            units: loaded.units,
+            language: language.to_owned(),
+            manifest,
+            strings,
+            locale: locale.to_owned(),
+            options,
+            campaign,
+            pending_load: None,
+            pending_locale: None,
+            turn: 1,
+            player: 0,
+            music: None,
+            sound,
+            signals: Vec::new(),
+            ambient_volumes: std::collections::HashMap::new(),
+            ambient_screen: None,
+            scroll_indicator_screen: None,
+            scroll_indicator_moved_at: None,
+            scroll_indicator_opacity: 0,
+            scroll_indicator_bars: Vec::new(),
+            cursor_image: Asset {
+                path: Some(CURSOR_IMAGE.to_owned()),
+                image: cursor_future.await,
+            },
+            infobar_image: Asset {
+                path: Some(INFO_BAR_IMAGE.to_owned()),
+                image: info_future.await,
+            },
+            status_icons,
+            missing_images: std::cell::RefCell::new(std::collections::HashSet::new()),
+            screen: Rectangle {
+                top_left: Vector { x: 0, y: 0 },
+                size: map_size,
+            },
+            layout: std::cell::Cell::new(None),
+            needs_redraw: false,
+            damage: Damage::default(),
+            stats: debug::FrameStats::default(),
+            debug_overlay: false,
+            debug_overlay_position: None,
+            last_mouse_pan,
+            scroll_offset: Vector { x: 0.0, y: 0.0 },
+            scroll_animation: None,
+            mouse_pos: None,
+            background: loaded.background,
+            highlights: std::collections::HashMap::new(),
+            selected_unit: None,
+            move_costs: MoveCosts::new(),
+            pending_move: None,
+            move_animation: None,
+            combat_animation: None,
+            ready_units,
+            show_danger_zone: false,
+            show_grid: settings.show_grid,
+            danger_zones: std::collections::HashMap::new(),
+            danger_tiles: std::collections::HashSet::new(),
+            minimap: Vec::new(),
+            visibility: None,
+            sight_center: None,
+            suspended_at: None,
+            bindings,
+            contexts: vec![bindings::Context::Map],
+            rebinding: None,
+            fast_move: false,
+            focused: true,
+            drag_anchor: None,
+            hover_tile: None,
+            last_click: None,
+            menu: None,
+            triggers: loaded.triggers,
+            intro_dialog: loaded.intro_dialog.clone(),
+            editor: None,
+            dialog: dialog::Dialog::new(),
+            map_complete: false,
+            finished: false,
+            mouse_rest_start: None,
+            tooltip: None,
+            unit_panel: None,
+            forecast_panel: None,
+            level_up_popup: None,
+            toast: None,
+            banner: None,
+            transport: None,
+            clock: None,
+            autosave: None,
+            pending_autosave: None,
+            last_autosave: None,
+            remote,
+            seed,
+            rng: rng::Rng::new(seed),
+            settings,
+        };
+        if loaded.fog {
+            game.visibility = Some(Array2::from_elem(game.map.dim(), Visibility::Hidden));
+            game.update_visibility();
+        }
+        if game.settings.show_minimap {
+            game.minimap = minimap_runs(&game.map, &game.tile_types, game.visibility.as_ref());
+        }
+        game.screen.size = game.max_screen_size();
+        game.platform.set_volume(game.sound.effective_volume());
+        game.switch_music(loaded.music);
+        game.connect_remote();
+
+        game.draw_screen();
+        game.show_map_dialog(loaded.intro_dialog);
+        game.finish_frame();
+        Ok(game)
+    }
+
+    // Gets the seed that the game's random number generator was started with
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // Gets the path of the map being played relative to the language directory
+    pub fn current_map(&self) -> &str {
+        self.campaign.current()
+    }
+
+    // Hashes everything that a saved game would keep besides when it was saved, so that two games
+    // can be checked for having ended up in the same state
+    pub fn state_hash(&self) -> String {
+        hash_snapshot::<P>(&save::Snapshot {
+            saved_at: None,
+            ..self.snapshot()
+        })
+    }
+
+    // Hashes the part of the game's state that both games of a remote session share, which leaves
+    // out what each player sees
+    fn shared_state_hash(&self) -> String {
+        hash_snapshot::<P>(&save::Snapshot {
+            saved_at: None,
+            fog: None,
+            cursor: Vector { x: 0, y: 0 },
+            ..self.snapshot()
+        })
+    }
+    // Gets the action that a key is bound to while moving the cursor around the map
+    pub fn map_action(&self, key: &P::InputType) -> Option<Action> {
+        self.bindings.get(&[bindings::Context::Map], key)
+    }
+
+    // Ends the game, handing back the platform it was played on (e.g. to start it again)
+    pub fn into_platform(self) -> P {
+        self.platform
+    }
+
+    // Gets the random number generator that the game's random decisions are made with
+    pub fn rng(&mut self) -> &mut rng::Rng {
+        &mut self.rng
+    }
+
+    // Gets the position of the cursor on the map
+    pub fn cursor_pos(&self) -> Vector<MapDistance> {
+        self.cursor_pos
+    }
+
+    // Gets the part of the map that's currently visible (in tiles)
+    pub fn visible_rect(&self) -> Rectangle<MapDistance> {
+        self.screen
+    }
+
+    // Gets how big each tile is drawn on the screen
+    pub fn tile_size(&self) -> Vector<P::ScreenDistance> {
+        self.get_tile_size()
+    }
+
+    // Gets the number of columns and rows in the map
+    pub fn map_size(&self) -> Vector<MapDistance> {
+        self.get_map_size()
+    }
+
+    // Gets where the cursor is and how far the screen is zoomed in
+    pub fn view_position(&self) -> ViewPosition {
+        ViewPosition {
+            cursor: self.cursor_pos,
+            zoom: self.screen.width(),
+        }
+    }
+
+    // Moves the cursor to a position and zooms to it without scrolling, with the screen centered on
+    // the cursor. Positions are clamped to the map and zoom limits, since they may have been made
+    // for a map of a different size
+    pub fn show_position(&mut self, position: ViewPosition) {
+        self.finish_scroll();
+        let map_size = self.get_map_size();
+        self.cursor_pos = Vector {
+            x: position.cursor.x.min(map_size.x - 1),
+            y: position.cursor.y.min(map_size.y - 1),
+        };
+        self.zoom_to(position.zoom);
+        self.screen.top_left = self.get_centered_top_left(self.cursor_pos);
+        self.redraw();
+    }
+
+    // Zooms until a number of columns are on the screen, as far as the zoom limits allow. Zooming a
+    // tile at a time keeps the screen the same shape as zooming with the controls
+    fn zoom_to(&mut self, zoom: MapDistance) {
+        while self.screen.width() < zoom {
+            let size = self.screen.size;
+            self.zoom_out_once();
+            if self.screen.size == size {
+                break;
+            }
+        }
+        while self.screen.width() > zoom {
+            let size = self.screen.size;
+            self.zoom_in_once();
+            if self.screen.size == size {
+                break;
+            }
+        }
+    }
+
+    // Scrolls the screen so that a tile is as close to its center as the edges of the map allow
+    pub fn center_on(&mut self, pos: Vector<MapDistance>) {
+        let top_left = self.get_centered_top_left(pos);
+        if top_left != self.screen.top_left {
+            self.pan(top_left);
+            self.redraw();
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    // Whether a map needs to be loaded before the next event is handled (see load_pending_map)
+    pub fn load_pending(&self) -> bool {
+        self.pending_load.is_some() || self.pending_locale.is_some()
+    }
+
+    // Loads the strings file or map that events have asked for, if any. Reloading the current map
+    // keeps the view as close to the same as the new map allows; if that fails, the old map keeps
+    // running and the error is shown in the dialog box. Failing to load the next map of the
+    // campaign ends the game
+    pub async fn load_pending_map(&mut self) -> Redraw {
+        // What's being loaded is only forgotten once it has loaded, in case the load is cut short
+        // by a newer one (see run)
+        if let Some(locale) = self.pending_locale.clone() {
+            self.strings = strings::load(&self.platform, &self.manifest, locale.as_str()).await;
+            self.pending_locale = None;
+            self.locale = locale;
+            self.relabel_options_menu();
+            self.redraw();
+            self.finish_frame();
+        }
+        if let Some(PendingLoad::Reload) = self.pending_load {
+            // Assets may have been changed along with the hashes listed for them
+            self.manifest = fetch::load_manifest(&self.platform).await;
+        }
+        let loaded = match self.pending_load.as_ref() {
+            Some(PendingLoad::Restore(snapshot)) => {
+                let language = self.language.as_str();
+                load_saved_map(&self.platform, &self.manifest, language, snapshot).await
+            }
+            Some(PendingLoad::Reload | PendingLoad::NextMap) => {
+                let path = map_path(self.language.as_str(), &self.campaign);
+                load_map(&self.platform, &self.manifest, path.as_str()).await
+            }
+            None => return self.redraw_hint(),
+        };
+        let loaded = match loaded {
+            Ok(mut loaded) => {
+                let team_colors = &self.settings.team_colors;
+                tint_unit_sprites(&self.platform, &mut loaded.units, team_colors).await;
+                Ok(loaded)
+            }
+            Err(e) => Err(e),
+        };
+        let pending_load = match self.pending_load.take() {
+            Some(p) => p,
+            None => return self.redraw_hint(),
+        };
+        match (loaded, pending_load) {
+            (Ok(loaded), PendingLoad::Reload) => self.replace_map(loaded),
+            (Ok(loaded), PendingLoad::NextMap) => {
+                let intro_dialog = loaded.intro_dialog.clone();
+                self.cursor_pos = Vector { x: 0, y: 0 };
+                self.screen = Rectangle {
+                    top_left: Vector { x: 0, y: 0 },
+                    size: Vector {
+                        x: MapDistance::MAX,
+                        y: MapDistance::MAX,
+                    },
+                };
+                self.turn = 1;
+                self.player = 0;
+                self.take_banner();
+                self.replace_map(loaded);
+                self.show_map_dialog(intro_dialog);
+            }
+            (Ok(loaded), PendingLoad::Restore(snapshot)) => self.restore(loaded, *snapshot),
+            (Err(e), PendingLoad::Reload) => {
+                let text = self.format_text("error.reload_map", &[e.to_string().as_str()]);
+                log::error::<P>(text.as_str());
+                self.emit(sound::GameSignal::Error);
+                self.show_dialog(vec![serialization::Message {
+                    speaker: None,
+                    text,
+                }]);
+            }
+            (Err(e), PendingLoad::Restore(_)) => self.show_load_error(e),
+            (Err(e), PendingLoad::NextMap) => {
+                let text = self.format_text("error.next_map", &[e.to_string().as_str()]);
+                log::error::<P>(text.as_str());
+                self.emit(sound::GameSignal::Error);
+                self.draw_message(text.as_str());
+                self.finished = true;
+            }
+        }
+        self.play_signals();
+        self.finish_frame();
+        self.redraw_hint()
+    }
+
+    // Waits until the platform is ready for the next frame
+    pub fn request_frame(&self) -> P::FrameFuture {
+        self.platform.request_frame()
+    }
+
+    // Draws the next frame of an in-progress scroll or of changes made by events, along with the
+    // tooltip once the mouse has rested for long enough
+    pub fn frame(&mut self) -> Redraw {
+        self.update_autosave();
+        self.draw_frame();
+        self.finish_frame();
+        self.redraw_hint()
+    }
+
+    fn draw_frame(&mut self) {
+        if self.finished || self.suspended_at.is_some() {
+            return;
+        }
+        self.update_movement();
+        self.update_combat();
+        if self.needs_redraw {
+            // Waiting events are handled before the mouse is considered to have rested
+            self.draw_screen();
+            return;
+        }
+        if !self.damage.is_empty() && !self.is_animating() {
+            // Likewise for changes made by events that only part of the screen is drawn for
+            self.draw_damage();
+            return;
+        }
+        if self.is_animating() {
+            self.draw_screen();
+        } else if self.update_scroll_indicator() {
+            // Only the tiles under the indicator are redrawn as it fades
+            for bar in self.scroll_indicator_bars.clone() {
+                self.uncover(&bar);
+            }
+        }
+        if let Some(popup) = self.level_up_popup.as_ref() {
+            let shown = P::duration_between(popup.shown_at, self.now());
+            if shown >= P::nanoseconds(LEVEL_UP_POPUP_NS) {
+                self.dismiss_level_up_popup();
+            }
+        }
+        if let Some(toast) = self.toast.as_ref() {
+            if P::duration_between(toast.shown_at, self.now()) >= P::nanoseconds(TOAST_NS) {
+                self.dismiss_toast();
+            }
+        }
+        self.draw_damage();
+        if let Some(start) = self.mouse_rest_start {
+            let rested = P::duration_between(start, self.now());
+            if rested >= P::nanoseconds(TOOLTIP_DELAY_NS) {
+                self.mouse_rest_start = None;
+                self.show_tooltip();
+            }
+        }
+    }
+
+    // Updates the game in response to a single event
+    pub fn handle_event(&mut self, event: Event<P::MouseDistance, P::InputType>) -> Redraw {
+        self.handle_event_internal(event);
+        self.play_signals();
+        // Drawing the debug overlay may ask for another redraw, so this comes before the hint
+        self.finish_frame();
+        self.redraw_hint()
+    }
+
+    fn handle_event_internal(&mut self, event: Event<P::MouseDistance, P::InputType>) {
+        if self.finished {
+            return;
+        }
+        match event {
+            Event::Redraw | Event::Suspend | Event::Resume | Event::Release(_) => {}
+            _ => self.hide_tooltip(),
+        }
+        let e = match event {
+            Event::Input(key) => match self.handle_key(key) {
+                Some(e) => e,
+                None => return,
+            },
+            Event::Release(key) => {
+                self.handle_release(key);
+                return;
+            }
+            e => e,
+        };
+        // The other player's game goes on whatever is showing on this one
+        match e {
+            Event::Remote(message) => {
+                self.handle_remote_message(message.as_slice());
+                self.finish_map();
+                return;
+            }
+            Event::ConnectionLost => return self.lose_connection(),
+            _ => {}
+        }
+        if self.handle_animation_event(&e)
+            || self.handle_banner_event(&e)
+            || self.handle_level_up_event(&e)
+            || self.handle_dialog_event(&e)
+            || self.handle_menu_event(&e)
+        {
+            self.finish_map();
+            return;
+        }
+        let map_size = self.get_map_size();
+        let last_column = map_size.x - 1;
+        let last_row = map_size.y - 1;
+        let old_cursor_pos = self.cursor_pos;
+        let stride = self.move_stride();
+        match e {
+            Event::Right => self.step_cursor(stride, 0),
+            Event::Left => self.step_cursor(-stride, 0),
+            Event::Up => self.step_cursor(0, -stride),
+            Event::Down => self.step_cursor(0, stride),
+            Event::UpLeft => self.step_cursor(-stride, -stride),
+            Event::UpRight => self.step_cursor(stride, -stride),
+            Event::DownLeft => self.step_cursor(-stride, stride),
+            Event::DownRight => self.step_cursor(stride, stride),
+            Event::JumpLeft => self.go_to(Vector {
+                x: 0,
+                y: self.cursor_pos.y,
+            }),
+            Event::JumpRight => self.go_to(Vector {
+                x: last_column,
+                y: self.cursor_pos.y,
+            }),
+            Event::JumpTop => self.go_to(Vector {
+                x: self.cursor_pos.x,
+                y: 0,
+            }),
+            Event::JumpBottom => self.go_to(Vector {
+                x: self.cursor_pos.x,
+                y: last_row,
+            }),
+            Event::ZoomIn => {
+                self.zoom_in();
+                self.redraw();
+            }
+            Event::ZoomOut => {
+                self.zoom_out();
+                self.redraw();
+            }
+            Event::ZoomInAt(mouse_pos) => {
+                self.mouse_pos = Some(mouse_pos);
+                self.zoom_at(mouse_pos, Game::zoom_in);
+                self.redraw();
+            }
+            Event::ZoomOutAt(mouse_pos) => {
+                self.mouse_pos = Some(mouse_pos);
+                self.zoom_at(mouse_pos, Game::zoom_out);
+                self.redraw();
+            }
+            Event::ScrollHorizontal(columns) => self.scroll_by(columns.into(), 0),
+            Event::ScrollVertical(rows) => self.scroll_by(0, rows.into()),
+            Event::MouseMove(mouse_pos) if self.drag_anchor.is_some() => self.drag(mouse_pos),
+            Event::MouseMove(mouse_pos) => {
+                self.mouse_pos = Some(mouse_pos);
+                let time = self.now();
+                self.mouse_rest_start = Some(time);
+                let pan = match self.get_edge_pan(mouse_pos) {
+                    Some((top_left, delay))
+                        if P::duration_between(self.last_mouse_pan, time) > delay =>
+                    {
+                        self.pan(top_left);
+                        true
+                    }
+                    _ => false,
+                };
+                let hover_tile = self
+                    .get_map_pos(mouse_pos)
+                    .filter(|p| p.x <= last_column && p.y <= last_row);
+                if pan {
+                    // Leave the cursor alone so that panning doesn't fight with the keyboard; the
+                    // redraw only moves it if the screen moved away from it
+                    self.last_mouse_pan = time;
+                    self.redraw();
+                } else if let Some(p) = hover_tile.filter(|&p| Some(p) != self.hover_tile) {
+                    self.move_cursor(p);
+                }
+                self.hover_tile = hover_tile;
+            }
+            Event::Click(mouse_pos) => {
+                self.mouse_pos = Some(mouse_pos);
+                if let Some(p) = self.get_minimap_tile(mouse_pos) {
+                    self.jump_to(p);
+                } else if let Some(p) = self.get_map_pos(mouse_pos) {
+                    if p.x <= last_column && p.y <= last_row {
+                        self.click_tile(p);
+                    }
+                }
+            }
+            Event::DragStart(mouse_pos) => {
+                self.mouse_pos = Some(mouse_pos);
+                self.drag_anchor = mouse_pos.cast::<P::ScreenDistance>().lossy_cast();
+            }
+            Event::DragEnd => self.drag_anchor = None,
+            Event::MouseLeave => self.forget_mouse(),
+            Event::CenterView => self.center_on(self.cursor_pos),
+            // Loading is asynchronous, so it's left to whoever is handling events
+            // A load that's already waiting fetches everything again anyway, and is kept so that
+            // e.g. the campaign doesn't go back to being on the map it just advanced from
+            Event::ReloadAssets => {
+                self.pending_load.get_or_insert(PendingLoad::Reload);
+            }
+            Event::NextMap => self.map_complete = true,
+            Event::ToggleMute => self.toggle_mute(),
+            Event::ToggleDebugOverlay => self.toggle_debug_overlay(),
+            Event::ToggleDangerZone => self.toggle_danger_zone(),
+            Event::NextUnit => self.next_ready_unit(),
+            Event::ToggleGrid => self.toggle_grid(),
+            Event::Screenshot => self.take_screenshot(),
+            Event::ExportMap => self.export_map(),
+            Event::CopyPosition => self.copy_position(),
+            Event::SetBookmark(index) => self.set_bookmark(index),
+            Event::GoToBookmark(index) => self.go_to_bookmark(index),
+            // Handled before the menus and popups could hold them up
+            Event::Remote(_) | Event::ConnectionLost => {}
+            Event::Redraw => {
+                // Redraws are sent when the screen is resized
+                self.layout.set(None);
+                self.redraw();
+            }
+            Event::ToggleEditor => self.toggle_editor(),
+            Event::Select if self.editor.is_some() => self.paint_tile(),
+            Event::Cancel if self.editor.is_some() => self.undo_paint(),
+            Event::Menu if self.editor.is_some() => self.open_palette(),
+            Event::Select => self.toggle_selection(),
+            Event::Cancel => self.clear_highlights(),
+            Event::Menu => self.open_menu(),
+            Event::Suspend => self.suspend(),
+            Event::Resume => self.resume(),
+            Event::FocusLost => self.set_focus(false),
+            Event::FocusGained => self.set_focus(true),
+            Event::Input(_) | Event::Release(_) => {}
+            Event::StartRebind(action) => self.start_rebind(action),
+            Event::Quit => {
+                self.draw_goodbye();
+                self.finished = true;
+                return;
+            }
+        }
+        if self.cursor_pos != old_cursor_pos {
+            self.emit(sound::GameSignal::CursorMoved);
+            self.fire_triggers();
+        }
+        self.finish_map();
+    }
+}
@@ -0,0 +1,241 @@
+use crate::Scalar;
+
+// Represents a vector
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub struct Vector<T> {
+    pub x: T,
+    pub y: T,
+}
+
+impl<T: Scalar + num_traits::ToPrimitive> Vector<T> {
+    pub fn lossy_cast<U: num_traits::NumCast>(self) -> Option<Vector<U>> {
+        Some(Vector {
+            x: U::from(self.x)?,
+            y: U::from(self.y)?,
+        })
+    }
+
+    // Casts each component (truncating any fraction), or returns None if either one is out of
+    // range. Unlike lossy_cast, negative fractions aren't rounded up to zero in unsigned types
+    pub fn try_cast<U: num_traits::NumCast>(self) -> Option<Vector<U>> {
+        let negative = self.x.to_f64()? < 0.0 || self.y.to_f64()? < 0.0;
+        if negative && U::from(-1i8).is_none() {
+            return None;
+        }
+        self.lossy_cast()
+    }
+}
+
+impl<T: Scalar> Vector<T> {
+    pub fn piecewise_divide<U: Scalar + Into<T>>(self, rhs: Vector<U>) -> Vector<T> {
+        Vector {
+            x: self.x / rhs.x.into(),
+            y: self.y / rhs.y.into(),
+        }
+    }
+    pub fn piecewise_multiply<U: Scalar + Into<T>>(self, rhs: Vector<U>) -> Vector<T> {
+        Vector {
+            x: self.x * rhs.x.into(),
+            y: self.y * rhs.y.into(),
+        }
+    }
+    // Same as piecewise_divide, but returns None instead of dividing by zero
+    pub fn checked_piecewise_divide<U: Scalar + Into<T>>(self, rhs: Vector<U>) -> Option<Vector<T>>
+    where
+        T: num_traits::Zero,
+    {
+        if rhs.x.into().is_zero() || rhs.y.into().is_zero() {
+            None
+        } else {
+            Some(self.piecewise_divide(rhs))
+        }
+    }
+    // Gets how many steps it takes to get from one position to another, not counting diagonals
+    pub fn manhattan_distance(self, other: Vector<T>) -> T {
+        let difference = |a: T, b: T| if a > b { a - b } else { b - a };
+        difference(self.x, other.x) + difference(self.y, other.y)
+    }
+    pub fn cast<U: Scalar + From<T>>(self) -> Vector<U> {
+        Vector {
+            x: self.x.into(),
+            y: self.y.into(),
+        }
+    }
+}
+
+impl Vector<f64> {
+    // Same as piecewise_divide, but returns None rather than an infinite or NaN component, which
+    // zero-sized or NaN divisors (e.g. the tiles of an empty screen) would give
+    pub fn finite_piecewise_divide(self, rhs: Vector<f64>) -> Option<Vector<f64>> {
+        let quotient = self.piecewise_divide(rhs);
+        if quotient.x.is_finite() && quotient.y.is_finite() {
+            Some(quotient)
+        } else {
+            None
+        }
+    }
+}
+
+impl<T: num_traits::CheckedAdd> Vector<T> {
+    // Adds two vectors, or returns None if either component overflows
+    pub fn checked_add(&self, other: &Self) -> Option<Self> {
+        Some(Vector {
+            x: self.x.checked_add(&other.x)?,
+            y: self.y.checked_add(&other.y)?,
+        })
+    }
+}
+
+impl<T: std::ops::Add<Output = T>> std::ops::Add for Vector<T> {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        Self {
+            x: self.x + other.x,
+            y: self.y + other.y,
+        }
+    }
+}
+
+impl<T: std::ops::Sub<Output = T>> std::ops::Sub for Vector<T> {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self {
+        Self {
+            x: self.x - other.x,
+            y: self.y - other.y,
+        }
+    }
+}
+
+impl<T: std::ops::Div<Output = T> + Copy> std::ops::Div<T> for Vector<T> {
+    type Output = Self;
+    fn div(self, rhs: T) -> Self::Output {
+        Self {
+            x: self.x / rhs,
+            y: self.y / rhs,
+        }
+    }
+}
+
+// Represents a rectangle
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rectangle<T> {
+    pub top_left: Vector<T>,
+    pub size: Vector<T>,
+}
+
+impl<T: Scalar> Rectangle<T> {
+    pub fn top(&self) -> T {
+        self.top_left.y
+    }
+    pub fn left(&self) -> T {
+        self.top_left.x
+    }
+    pub fn width(&self) -> T {
+        self.size.x
+    }
+    pub fn height(&self) -> T {
+        self.size.y
+    }
+    pub fn bottom(&self) -> T {
+        self.top() + self.height()
+    }
+    pub fn right(&self) -> T {
+        self.left() + self.width()
+    }
+    pub fn intersects(&self, other: &Rectangle<T>) -> bool {
+        self.left() < other.right()
+            && other.left() < self.right()
+            && self.top() < other.bottom()
+            && other.top() < self.bottom()
+    }
+    // Gets the part of the rectangle that's also part of another one (None if they don't overlap)
+    pub fn intersection(&self, other: &Rectangle<T>) -> Option<Rectangle<T>> {
+        if !self.intersects(other) {
+            return None;
+        }
+        let max = |a: T, b: T| if b > a { b } else { a };
+        let min = |a: T, b: T| if b < a { b } else { a };
+        let top_left = Vector {
+            x: max(self.left(), other.left()),
+            y: max(self.top(), other.top()),
+        };
+        let bottom_right = Vector {
+            x: min(self.right(), other.right()),
+            y: min(self.bottom(), other.bottom()),
+        };
+        Some(Rectangle {
+            top_left,
+            size: bottom_right - top_left,
+        })
+    }
+}
+
+impl<T: Scalar + num_traits::CheckedAdd> Rectangle<T> {
+    // Same as bottom, but returns None instead of overflowing
+    pub fn checked_bottom(&self) -> Option<T> {
+        self.top().checked_add(&self.height())
+    }
+    // Same as right, but returns None instead of overflowing
+    pub fn checked_right(&self) -> Option<T> {
+        self.left().checked_add(&self.width())
+    }
+}
+
+// Tiles that a weapon can reach, as offsets from the tile its wielder is standing on
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum RangePattern {
+    // Every tile at least min and at most max steps away, not counting diagonals. A bow that can't
+    // hit adjacent tiles has a min of 2
+    MinMax { min: u32, max: u32 },
+    // Exactly these offsets, which don't have to be symmetric
+    Fixed(Vec<Vector<i64>>),
+}
+
+impl RangePattern {
+    pub fn contains(&self, offset: Vector<i64>) -> bool {
+        match self {
+            RangePattern::MinMax { min, max } => {
+                let distance = offset.x.unsigned_abs() + offset.y.unsigned_abs();
+                distance >= *min as u64 && distance <= *max as u64
+            }
+            RangePattern::Fixed(offsets) => offsets.contains(&offset),
+        }
+    }
+
+    // Gets every offset that the pattern reaches
+    pub fn offsets(&self) -> Vec<Vector<i64>> {
+        match self {
+            RangePattern::MinMax { min, max } => {
+                let (min, max) = (*min as i64, *max as i64);
+                let mut offsets = Vec::new();
+                for y in -max..=max {
+                    let width = max - y.abs();
+                    for x in -width..=width {
+                        if x.abs() + y.abs() >= min {
+                            offsets.push(Vector { x, y });
+                        }
+                    }
+                }
+                offsets
+            }
+            RangePattern::Fixed(offsets) => offsets.clone(),
+        }
+    }
+}
+
+// Moves a position by an offset, or returns None if that leaves a map of a given size
+pub fn offset_within(
+    pos: Vector<u32>,
+    offset: Vector<i64>,
+    map_size: Vector<u32>,
+) -> Option<Vector<u32>> {
+    let x = pos.x as i64 + offset.x;
+    let y = pos.y as i64 + offset.y;
+    if x < 0 || y < 0 || x >= map_size.x as i64 || y >= map_size.y as i64 {
+        return None;
+    }
+    Some(Vector {
+        x: x as u32,
+        y: y as u32,
+    })
+}
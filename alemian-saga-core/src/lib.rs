@@ -1,14 +1,45 @@
 #![cfg_attr(feature = "strict", deny(warnings))]
 
-mod detail;
+pub mod bindings;
+pub mod bookmarks;
+pub mod campaign;
+pub mod combat;
+pub mod debug;
+pub mod dialog;
+pub mod editor;
+pub mod experience;
+pub mod fetch;
+pub mod game;
+pub mod geometry;
+pub mod log;
+pub mod menu;
+pub mod net;
+pub mod options;
+pub mod pathfinding;
+pub mod queue;
+pub mod record;
+pub mod rng;
+pub mod roster;
+pub mod save;
 pub mod serialization;
+pub mod settings;
+pub mod sha256;
+pub mod sound;
+pub mod strings;
+pub mod style;
+pub mod text;
+pub mod viewport;
 
 use std::{cmp, ops};
 
 use async_trait::async_trait;
+use futures::channel::mpsc;
+use futures::{future, StreamExt};
 use num_traits::FromPrimitive;
 
-use detail::Rectangle;
+pub use geometry::{Rectangle, Vector};
+
+const DEFAULT_IMAGE_LOAD_LIMIT: usize = 16;
 
 // A trait that should be implemented by all primitive numberic types
 pub trait Scalar:
@@ -33,12 +64,12 @@ impl<T> Scalar for T where
 
 // Trait used for abstracting away logic that is specific to a particular platform
 #[async_trait(?Send)]
-pub trait Platform {
+pub trait Platform: Sized {
     // Type used to represent images
     type Image;
 
     // Type used to represent user input (keyboard or button)
-    type InputType: Eq + std::hash::Hash;
+    type InputType: Eq + std::hash::Hash + Clone + ToString;
 
     // Type used to represent distance in mouse events (should be the same ScreenDistance
     type MouseDistance: Scalar;
@@ -48,6 +79,7 @@ pub trait Platform {
         + From<u32>
         + From<Self::MouseDistance>
         + num_traits::ToPrimitive
+        + num_traits::Zero
         + FromPrimitive;
 
     // Future type returned by get_image
@@ -62,6 +94,12 @@ pub trait Platform {
     // Type used to represent lengths of time
     type Duration: cmp::PartialOrd;
 
+    // Future type returned by sleep
+    type SleepFuture: std::future::Future<Output = ()>;
+
+    // Future type returned by request_frame
+    type FrameFuture: std::future::Future<Output = ()>;
+
     // Draw an image to the screen
     fn draw_primitive(
         &self,
@@ -72,16 +110,42 @@ pub trait Platform {
         height: Self::ScreenDistance,
     );
 
-    // Renders text to the screen
+    // Starts holding back images drawn with draw_primitive so that end_batch can send them to the
+    // screen all at once, for platforms where each draw has a cost of its own (e.g. crossing from
+    // wasm into JavaScript). Everything still ends up drawn in the order it was asked for.
+    // Platforms that don't batch draw right away
+    fn begin_batch(&self) {}
+
+    // Draws every image held back since begin_batch
+    fn end_batch(&self) {}
+
+    // Fill a rectangle on the screen with a solid color
+    fn fill_rect_primitive(
+        &self,
+        left: Self::ScreenDistance,
+        top: Self::ScreenDistance,
+        width: Self::ScreenDistance,
+        height: Self::ScreenDistance,
+        color: style::Color,
+    );
+
+    // Erase everything that has been drawn to the screen
+    fn clear(&self);
+
+    // Renders text to the screen, aligned within the max_width wide space to the right of x. The
+    // style's alignment has already been mirrored for right-to-left text (see text::align); the
+    // direction is what mixed-direction text is ordered by
     fn draw_text_primitive(
         &self,
         text: &str,
         x: Self::ScreenDistance,
         y: Self::ScreenDistance,
         max_width: Self::ScreenDistance,
+        style: &style::TextStyle,
+        direction: text::Direction,
     );
 
-    // Converts a Sring into an InputType
+    // Converts a Sring into an InputType (the reverse of to_string)
     fn string_to_input(input: String) -> Self::InputType;
 
     // Get the width of the game screen
@@ -90,24 +154,157 @@ pub trait Platform {
     // Get the height of the game screen
     fn get_height(&self) -> Self::ScreenDistance;
 
+    // Get the width that a piece of text would take up if rendered
+    fn measure_text(&self, text: &str, style: &style::TextStyle) -> Self::ScreenDistance;
+
+    // Get the height of a line of rendered text (None if the platform can't tell)
+    fn get_text_height(&self, _style: &style::TextStyle) -> Option<Self::ScreenDistance> {
+        None
+    }
+
     // Retrieve an image from a specified file path
-    fn get_image(path: &str) -> Self::ImageFuture;
+    fn get_image(&self, path: &str) -> Self::ImageFuture;
+
+    // Creates a copy of an image with a color laid over its opaque parts, e.g. to show which team a
+    // unit is on (None if the platform can't tint images). The game tints each image with each
+    // color at most once, so this doesn't need to be fast
+    fn tint(&self, _img: &Self::Image, _color: style::Color) -> Option<Self::ImageFuture> {
+        None
+    }
 
     // Retrieve a file from a specified file path
-    async fn get_file(&self, path: &str) -> Result<Self::File, String>;
+    async fn get_file(&self, path: &str) -> Result<Self::File, Error>;
+
+    // Log a message (typically to stdout or the equivalent). Messages are usually logged through
+    // the log module, which puts their level in front
+    fn log(msg: &str);
 
-    // Log a message (typically to stdout or the equivalent)
-    fn log(path: &str);
+    // Log an error message (typically to stderr or the equivalent)
+    fn log_error(msg: &str) {
+        Self::log(msg);
+    }
 
     // Gets the current moment in time
     fn now() -> Self::Instant;
 
     // Converts an integer value in nanoseconds into a Duration object
-    fn nanoseconds(ns: usize) -> Self::Duration;
+    fn nanoseconds(ns: u64) -> Self::Duration;
+
+    // Converts a Duration object into an integer value in nanoseconds
+    fn to_nanoseconds(duration: Self::Duration) -> u64;
 
     // Gets the amount of time between two moments
     fn duration_between(fist: Self::Instant, second: Self::Instant) -> Self::Duration;
 
+    // Gets the moment that comes an amount of time after another
+    fn instant_after(instant: Self::Instant, duration: Self::Duration) -> Self::Instant;
+
+    // Creates a future that completes once the specified amount of time has passed
+    fn sleep(duration: Self::Duration) -> Self::SleepFuture;
+
+    // Waits until it's time to draw the next frame (e.g. the display's next refresh)
+    fn request_frame(&self) -> Self::FrameFuture;
+
+    // Sets whether scaled images are smoothed (as opposed to using nearest-neighbor scaling)
+    fn set_smoothing(&self, _enabled: bool) {}
+
+    // Gets the maximum number of images that may be loaded at once (None for no limit)
+    fn image_load_limit(&self) -> Option<usize> {
+        Some(DEFAULT_IMAGE_LOAD_LIMIT)
+    }
+
+    // Gets the path of the keybindings file for a locale
+    fn keybindings_path(&self, locale: &str) -> String {
+        bindings::default_path(locale)
+    }
+
+    // Gets the least serious level of message to log, overriding the settings file (None leaves it
+    // up to the settings file)
+    fn log_level(&self) -> Option<log::Level> {
+        None
+    }
+
+    // Gets a random number to seed the game's random number generator with. Only called once per
+    // game; every random decision after that comes from the game's own generator
+    fn random(&self) -> u64;
+
+    // Gets the policy used for retrying failed file retrievals
+    fn retry_policy(&self) -> fetch::RetryPolicy {
+        fetch::RetryPolicy::default()
+    }
+
+    // Saves a value under a key so that it can be retrieved in later sessions
+    fn store(&self, _key: &str, _value: &str) {}
+
+    // Retrieves a value saved with store (None if it's missing or the platform can't store values)
+    fn retrieve(&self, _key: &str) -> Option<String> {
+        None
+    }
+
+    // Gets the current date and time in seconds since the Unix epoch (None if the platform can't
+    // tell)
+    fn timestamp(&self) -> Option<u64> {
+        None
+    }
+
+    // Starts looping a music file in place of whatever music was playing (None stops the music)
+    fn play_music(&self, _path: Option<&str>) -> Result<(), Error> {
+        Ok(())
+    }
+
+    // Plays a short sound effect over the music, at a volume from 0 to sound::MAX_VOLUME that's
+    // scaled by the one passed to set_volume. Sound effects aren't important enough to report
+    // failures for
+    fn play_sound(&self, _path: &str, _volume: u32) {}
+
+    // Loops a sound over the music alongside any others, starting it if it isn't already playing,
+    // at a volume from 0 to sound::MAX_VOLUME that's scaled by the one passed to set_volume. A
+    // volume of 0 stops it. Like sound effects, failures aren't reported
+    fn set_ambient_sound(&self, _path: &str, _volume: u32) {}
+
+    // Sets how loud sound is played, from 0 (silent) to sound::MAX_VOLUME
+    fn set_volume(&self, _volume: u32) {}
+
+    // Puts text on the clipboard so that the player can paste it elsewhere. Platforms without a
+    // clipboard ignore this
+    fn set_clipboard(&self, _text: &str) {}
+
+    // Opens a connection to another player's game through a server at a URL (e.g. a WebSocket
+    // server that passes messages between the two games). The connection is usually still being
+    // opened when this returns, and a connection that fails to open ends its incoming messages
+    // like one that's lost
+    fn connect(&self, url: &str) -> Result<net::MessageStream, Error> {
+        Err(Error::Network {
+            path: url.to_owned(),
+            source_msg: "This platform can't connect to other games".to_owned(),
+        })
+    }
+
+    // Gets the number of events that haven't fit in the event queue so far, e.g. because input
+    // arrived faster than the game could handle it
+    fn event_queue_overflows(&self) -> u64 {
+        0
+    }
+
+    // Saves what's currently on the screen as an image under a name without an extension, e.g. by
+    // downloading it as a PNG. Platforms that can't save images ignore this
+    fn export_frame(&self, _name: &str) {}
+
+    // Sends everything drawn from now on to an offscreen surface of a given size instead of the
+    // screen, so that more can be drawn than fits on the screen. Returns false if the platform
+    // can't draw offscreen, in which case drawing still goes to the screen
+    fn begin_offscreen(&self, _size: Vector<Self::ScreenDistance>) -> bool {
+        false
+    }
+
+    // Saves what was drawn on the offscreen surface as an image the same way export_frame does,
+    // then goes back to drawing on the screen
+    fn export_offscreen(&self, _name: &str) {}
+
+    // Saves a file under a name that includes its extension, e.g. by downloading it. Platforms that
+    // can't save files ignore this
+    fn export_file(&self, _name: &str, _contents: &[u8]) {}
+
     // Gets the size of the screen
     fn get_screen_size(&self) -> Vector<Self::ScreenDistance> {
         Vector {
@@ -128,6 +325,11 @@ pub trait Platform {
         );
     }
 
+    // Fill a rectangle on the screen with a solid color
+    fn fill_rect(&self, rect: &Rectangle<Self::ScreenDistance>, color: style::Color) {
+        self.fill_rect_primitive(rect.left(), rect.top(), rect.width(), rect.height(), color);
+    }
+
     // Attempt to draw an image
     fn attempt_draw(&self, img: Option<&Self::Image>, location: &Rectangle<Self::ScreenDistance>) {
         if let Some(i) = img {
@@ -135,73 +337,549 @@ pub trait Platform {
         }
     }
 
-    // Adds keybinds to a keybinding map
-    fn add_bindings(
-        map: &mut std::collections::HashMap<Self::InputType, Event<Self::MouseDistance>>,
-        keys: Vec<String>,
-        event: Event<Self::MouseDistance>,
-    ) {
-        for k in keys.into_iter() {
-            map.insert(Self::string_to_input(k), event);
-        }
-    }
-
-    // Retrieves a keybinding map describing the what keys map to what actions
-    async fn get_keybindings(
-        &self,
-        locale: &str,
-    ) -> Option<std::collections::HashMap<Self::InputType, Event<Self::MouseDistance>>> {
-        let mut ret = std::collections::HashMap::new();
-        let keybindings_path = format!("keybindings/{}.json", locale);
-        let bindings_file = self.get_file(keybindings_path.as_str()).await.ok()?;
-        let bindings: detail::Keybindings = serde_json::from_reader(bindings_file).ok()?;
-        Self::add_bindings(&mut ret, bindings.Right, Event::Right);
-        Self::add_bindings(&mut ret, bindings.Left, Event::Left);
-        Self::add_bindings(&mut ret, bindings.Up, Event::Up);
-        Self::add_bindings(&mut ret, bindings.Down, Event::Down);
-        Self::add_bindings(&mut ret, bindings.ZoomIn, Event::ZoomIn);
-        Self::add_bindings(&mut ret, bindings.ZoomOut, Event::ZoomOut);
-        Some(ret)
-    }
-
-    // Renders text to the screen
+    // Renders text to the screen in a locale's reading direction
     fn draw_text(
         &self,
         text: &str,
         offset: Vector<Self::ScreenDistance>,
         max_width: Self::ScreenDistance,
+        style: &style::TextStyle,
+        direction: text::Direction,
     ) {
-        self.draw_text_primitive(text, offset.x, offset.y, max_width);
+        let style = style::TextStyle {
+            alignment: text::align(style.alignment, direction),
+            ..*style
+        };
+        self.draw_text_primitive(text, offset.x, offset.y, max_width, &style, direction);
     }
 }
 
-// Represents a vector
-#[derive(Clone, Copy)]
-pub struct Vector<T> {
-    pub x: T,
-    pub y: T,
-}
-
 // Type used to represent user input events
-#[derive(Clone, Copy)]
-pub enum Event<P: Scalar> {
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub enum Event<P: Scalar, I> {
     Right,
     Left,
     Up,
     Down,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
+    // Move the cursor to the edge of the map along its row or column
+    JumpLeft,
+    JumpRight,
+    JumpTop,
+    JumpBottom,
+    // Scroll the screen so that the cursor is in the middle
+    CenterView,
     ZoomIn,
     ZoomOut,
+    // Zoom while keeping the map under a point on the screen (typically the mouse) in place
+    ZoomInAt(Vector<P>),
+    ZoomOutAt(Vector<P>),
+    // Scroll the screen by a number of columns or rows, with negative numbers going toward the top
+    // left, e.g. for horizontal mouse wheels and trackpad swipes
+    ScrollHorizontal(i32),
+    ScrollVertical(i32),
     MouseMove(Vector<P>),
+    // The mouse left the game's screen
+    MouseLeave,
+    Click(Vector<P>),
+    // The button used for dragging the map around was pressed at a point on the screen
+    DragStart(Vector<P>),
+    // The button used for dragging the map around was let go of
+    DragEnd,
     Redraw,
+    // Toggle whether the tile under the cursor is selected
+    Select,
+    // Deselect all tiles
+    Cancel,
+    // Open the action menu for the tile under the cursor
+    Menu,
+    // Shade or unshade every tile that an enemy could attack on its next turn
+    ToggleDangerZone,
+    // Move the cursor to the next of the player's units that hasn't acted yet this turn, centering
+    // the screen on it
+    NextUnit,
+    // End the game
+    Quit,
+    // Stop time-based logic, e.g. because the game is no longer visible
+    Suspend,
+    // Continue after a suspend
+    Resume,
+    // Keyboard input stopped going to the game, e.g. because something else on the page was
+    // clicked
+    FocusLost,
+    // Keyboard input started going to the game again
+    FocusGained,
+    // A key or button was pressed; the game decides which action it's bound to
+    Input(I),
+    // A key or button was let go of (only needed for keys that are held down, like modifiers)
+    Release(I),
+    // Bind the next key that's pressed to an action
+    StartRebind(Action),
+    // Load the map and its images again, e.g. after they've been edited
+    ReloadAssets,
+    // Skip to the next map of the campaign as if the current one had been finished
+    NextMap,
+    // Silence sound, or bring it back if it's already silenced
+    ToggleMute,
+    // Show or hide frame stats on top of the game
+    ToggleDebugOverlay,
+    // Show or hide lines between tiles along with row and column numbers, for building maps
+    ToggleGrid,
+    // Save an image of what's on the screen
+    Screenshot,
+    // Save an image of the whole map, however much of it is on the screen
+    ExportMap,
+    // Put where the cursor is and how far the screen is zoomed on the clipboard
+    CopyPosition,
+    // Start or stop painting tiles onto the map
+    ToggleEditor,
+    // Remember where the cursor is and how far the screen is zoomed as one of the map's bookmarks
+    // (by number, starting from 0)
+    SetBookmark(usize),
+    // Go back to where one of the map's bookmarks was set
+    GoToBookmark(usize),
+    // A message arrived from the other player's game
+    Remote(Vec<u8>),
+    // The connection to the other player's game was lost
+    ConnectionLost,
 }
 
+// Game actions that keys can be bound to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Action {
+    Right,
+    Left,
+    Up,
+    Down,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
+    JumpLeft,
+    JumpRight,
+    JumpTop,
+    JumpBottom,
+    CenterView,
+    ZoomIn,
+    ZoomOut,
+    Select,
+    Cancel,
+    Menu,
+    ToggleDangerZone,
+    NextUnit,
+    // Makes direction keys move the cursor several tiles at a time while it's held
+    FastModifier,
+    ReloadMap,
+    NextMap,
+    ToggleMute,
+    ToggleDebugOverlay,
+    ToggleGrid,
+    Screenshot,
+    ExportMap,
+    CopyPosition,
+    ToggleEditor,
+    SetBookmark1,
+    SetBookmark2,
+    SetBookmark3,
+    GoToBookmark1,
+    GoToBookmark2,
+    GoToBookmark3,
+}
+
+impl Action {
+    pub const ALL: [Action; 36] = [
+        Action::Right,
+        Action::Left,
+        Action::Up,
+        Action::Down,
+        Action::UpLeft,
+        Action::UpRight,
+        Action::DownLeft,
+        Action::DownRight,
+        Action::JumpLeft,
+        Action::JumpRight,
+        Action::JumpTop,
+        Action::JumpBottom,
+        Action::CenterView,
+        Action::ZoomIn,
+        Action::ZoomOut,
+        Action::Select,
+        Action::Cancel,
+        Action::Menu,
+        Action::ToggleDangerZone,
+        Action::NextUnit,
+        Action::FastModifier,
+        Action::ReloadMap,
+        Action::NextMap,
+        Action::ToggleMute,
+        Action::ToggleDebugOverlay,
+        Action::ToggleGrid,
+        Action::Screenshot,
+        Action::ExportMap,
+        Action::CopyPosition,
+        Action::ToggleEditor,
+        Action::SetBookmark1,
+        Action::SetBookmark2,
+        Action::SetBookmark3,
+        Action::GoToBookmark1,
+        Action::GoToBookmark2,
+        Action::GoToBookmark3,
+    ];
+
+    // Gets the event that's triggered by the action (None for modifiers, which don't trigger
+    // anything on their own)
+    pub fn event<P: Scalar, I>(self) -> Option<Event<P, I>> {
+        Some(match self {
+            Action::Right => Event::Right,
+            Action::Left => Event::Left,
+            Action::Up => Event::Up,
+            Action::Down => Event::Down,
+            Action::UpLeft => Event::UpLeft,
+            Action::UpRight => Event::UpRight,
+            Action::DownLeft => Event::DownLeft,
+            Action::DownRight => Event::DownRight,
+            Action::JumpLeft => Event::JumpLeft,
+            Action::JumpRight => Event::JumpRight,
+            Action::JumpTop => Event::JumpTop,
+            Action::JumpBottom => Event::JumpBottom,
+            Action::CenterView => Event::CenterView,
+            Action::ZoomIn => Event::ZoomIn,
+            Action::ZoomOut => Event::ZoomOut,
+            Action::Select => Event::Select,
+            Action::Cancel => Event::Cancel,
+            Action::Menu => Event::Menu,
+            Action::ToggleDangerZone => Event::ToggleDangerZone,
+            Action::NextUnit => Event::NextUnit,
+            Action::FastModifier => return None,
+            Action::ReloadMap => Event::ReloadAssets,
+            Action::NextMap => Event::NextMap,
+            Action::ToggleMute => Event::ToggleMute,
+            Action::ToggleDebugOverlay => Event::ToggleDebugOverlay,
+            Action::ToggleGrid => Event::ToggleGrid,
+            Action::Screenshot => Event::Screenshot,
+            Action::ExportMap => Event::ExportMap,
+            Action::CopyPosition => Event::CopyPosition,
+            Action::ToggleEditor => Event::ToggleEditor,
+            Action::SetBookmark1 => Event::SetBookmark(0),
+            Action::SetBookmark2 => Event::SetBookmark(1),
+            Action::SetBookmark3 => Event::SetBookmark(2),
+            Action::GoToBookmark1 => Event::GoToBookmark(0),
+            Action::GoToBookmark2 => Event::GoToBookmark(1),
+            Action::GoToBookmark3 => Event::GoToBookmark(2),
+        })
+    }
+}
+
+// Error type for failures that prevent the game from running
+#[derive(Debug)]
+pub enum Error {
+    // A file could not be retrieved
+    Network {
+        path: String,
+        source_msg: String,
+    },
+    // A file was retrieved but could not be parsed
+    Decode {
+        path: String,
+        source_msg: String,
+    },
+    // A file does not exist
+    MissingAsset(String),
+    // A map file was parsed but its contents don't make sense
+    InvalidMap(Vec<String>),
+    // A sound file could not be played
+    Playback {
+        path: String,
+        source_msg: String,
+    },
+    // A saved game was made by a version of the game that saves in a different format
+    SaveVersion {
+        found: u32,
+        expected: u32,
+    },
+    // A file doesn't have the hash that the manifest lists for it
+    ChecksumMismatch {
+        path: String,
+        expected: String,
+        actual: String,
+    },
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Network { path, source_msg } => {
+                write!(f, "Failed to retrieve {}: {}", path, source_msg)
+            }
+            Error::Decode { source_msg, .. } => write!(f, "{}", source_msg),
+            Error::MissingAsset(path) => write!(f, "Error: Missing file {}", path),
+            Error::InvalidMap(problems) => {
+                write!(f, "Error: Invalid map file: {}", problems.join("; "))
+            }
+            Error::Playback { path, source_msg } => {
+                write!(f, "Failed to play {}: {}", path, source_msg)
+            }
+            Error::SaveVersion { found, expected } => write!(
+                f,
+                "Error: Saved game is version {}, but only version {} can be loaded",
+                found, expected
+            ),
+            Error::ChecksumMismatch {
+                path,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Error: {} is corrupt: expected SHA-256 {}, but got {}",
+                path, expected, actual
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
 // Entry point for starting game logic
 pub async fn run<P: Platform>(
     platform: P,
-    mut event_queue: futures::channel::mpsc::Receiver<Event<P::MouseDistance>>,
+    event_queue: mpsc::Receiver<Event<P::MouseDistance, P::InputType>>,
+    language: &str,
+    locale: &str,
+) {
+    run_internal(platform, event_queue, language, locale, None, None).await;
+}
+
+// Same as run, but starts with the cursor and zoom at a position (e.g. from a link that another
+// player shared) instead of where the map starts them, once the map has loaded
+pub async fn run_at<P: Platform>(
+    platform: P,
+    event_queue: mpsc::Receiver<Event<P::MouseDistance, P::InputType>>,
+    language: &str,
+    locale: &str,
+    start: Option<game::ViewPosition>,
+) {
+    run_internal(platform, event_queue, language, locale, start, None).await;
+}
+
+// Same as run, but also records every event that the game handles so that it can be replayed
+pub async fn run_recorded<P: Platform>(
+    platform: P,
+    event_queue: mpsc::Receiver<Event<P::MouseDistance, P::InputType>>,
+    language: &str,
+    locale: &str,
+    recorder: &mut record::Recorder<P>,
+) {
+    run_internal(
+        platform,
+        event_queue,
+        language,
+        locale,
+        None,
+        Some(recorder),
+    )
+    .await;
+}
+
+// Lets someone watch a recorded game with controls for pausing, stepping through it and speeding
+// it up (see record::watch), returning the game once they're done unless it failed to load
+pub async fn run_replay<P: Platform>(
+    platform: P,
+    event_queue: mpsc::Receiver<Event<P::MouseDistance, P::InputType>>,
+    language: &str,
+    locale: &str,
+    recording: record::Recording<P>,
+) -> Option<game::Game<P>> {
+    let watched = record::watch(recording, platform, event_queue, language, locale).await;
+    let game = watched
+        .map_err(|e| log::error::<P>(e.to_string().as_str()))
+        .ok();
+    log::info::<P>("closing");
+    game
+}
+
+async fn run_internal<P: Platform>(
+    platform: P,
+    mut event_queue: mpsc::Receiver<Event<P::MouseDistance, P::InputType>>,
     language: &str,
+    locale: &str,
+    start: Option<game::ViewPosition>,
+    mut recorder: Option<&mut record::Recorder<P>>,
 ) {
-    if let Err(e) = detail::run_internal(platform, &mut event_queue, language).await {
-        P::log(e.msg.as_str());
+    game::draw_loading_screen(&platform, game::LoadingPhase::FetchingMap);
+    let mut game = match game::Game::load(platform, language, locale).await {
+        Ok(g) => g,
+        Err(e) => {
+            log::error::<P>(e.to_string().as_str());
+            return;
+        }
+    };
+    if let Some(r) = recorder.as_mut() {
+        r.set_seed(game.seed());
+        r.set_map(game.current_map());
+    }
+    // Messages from the other player's game, if there is one
+    let mut remote = game.take_incoming();
+    let mut redraw = game::Redraw::Idle;
+    if let Some(position) = start {
+        game.show_position(position);
+        redraw = game.frame();
+    }
+    let mut pending = None;
+    while redraw != game::Redraw::Finished {
+        let next = match redraw {
+            game::Redraw::Changed | game::Redraw::NextFrame => {
+                let frame_future = game.request_frame();
+                let event_future = next_event(&mut event_queue, &mut pending, &mut remote);
+                futures::pin_mut!(frame_future, event_future);
+                // If the frame is ready at the same time as an event, changes are drawn first but
+                // frames of a scroll give way to the event
+                let event = if redraw == game::Redraw::Changed {
+                    match future::select(frame_future, event_future).await {
+                        future::Either::Left(_) => None,
+                        future::Either::Right((e, _)) => Some(e),
+                    }
+                } else {
+                    match future::select(event_future, frame_future).await {
+                        future::Either::Left((e, _)) => Some(e),
+                        future::Either::Right(_) => None,
+                    }
+                };
+                match event {
+                    Some(e) => e,
+                    None => {
+                        redraw = game.frame();
+                        continue;
+                    }
+                }
+            }
+            _ => match redraw.wait() {
+                Some(wait) => {
+                    // Wake up for the next frame if no event arrives first
+                    let event_future = next_event(&mut event_queue, &mut pending, &mut remote);
+                    let frame_future = P::sleep(P::nanoseconds(wait));
+                    futures::pin_mut!(event_future, frame_future);
+                    match future::select(event_future, frame_future).await {
+                        future::Either::Left((e, _)) => e,
+                        future::Either::Right(_) => {
+                            redraw = game.frame();
+                            continue;
+                        }
+                    }
+                }
+                None => next_event(&mut event_queue, &mut pending, &mut remote).await,
+            },
+        };
+        match next {
+            Some(e) => {
+                if let Some(r) = recorder.as_mut() {
+                    r.record(&e);
+                }
+                redraw = game.handle_event(e);
+                if game.load_pending() {
+                    redraw = load(&mut game, &mut event_queue, &mut pending, &mut recorder).await;
+                }
+                // The game connects again after the connection is lost
+                if let Some(incoming) = game.take_incoming() {
+                    remote = Some(incoming);
+                }
+            }
+            None => break,
+        }
+    }
+    if let Some(r) = recorder.as_mut() {
+        r.set_state_hash(game.state_hash());
+    }
+    log::info::<P>("closing");
+}
+
+// Loads whatever the game is waiting to load while watching for events. An event that asks for a
+// load of its own (reloading again or skipping to the next map) drops the load in progress, along
+// with every fetch it's waiting on, so nothing it was fetching can end up in the map loaded after
+// it. The load is started afresh once the event has been handled. Any other event is held in
+// pending until the load is over
+async fn load<P: Platform>(
+    game: &mut game::Game<P>,
+    event_queue: &mut mpsc::Receiver<Event<P::MouseDistance, P::InputType>>,
+    pending: &mut Option<Event<P::MouseDistance, P::InputType>>,
+    recorder: &mut Option<&mut record::Recorder<P>>,
+) -> game::Redraw {
+    loop {
+        if pending.is_some() {
+            // There's nowhere to hold another event, so the queue is left alone until it's over
+            return game.load_pending_map().await;
+        }
+        let newer = {
+            let load = game.load_pending_map();
+            futures::pin_mut!(load);
+            match future::select(load, event_queue.next()).await {
+                future::Either::Left((redraw, _)) => return redraw,
+                future::Either::Right((Some(e), _)) if restarts_load(&e) => e,
+                future::Either::Right((e, load)) => {
+                    *pending = e;
+                    return load.await;
+                }
+            }
+        };
+        if let Some(r) = recorder.as_mut() {
+            r.record(&newer);
+        }
+        let redraw = game.handle_event(newer);
+        if !game.load_pending() {
+            return redraw;
+        }
+    }
+}
+
+// Whether an event asks for a map to be loaded, so that it cuts short any load in progress rather
+// than waiting for it
+fn restarts_load<M: Scalar, I>(event: &Event<M, I>) -> bool {
+    matches!(event, Event::ReloadAssets | Event::NextMap)
+}
+
+// Retrieves the next event to process. Consecutive mouse movements that are already queued are
+// coalesced into the most recent one; the first non-movement event after them is held in pending
+async fn next_event<M: Scalar, I>(
+    event_queue: &mut mpsc::Receiver<Event<M, I>>,
+    pending: &mut Option<Event<M, I>>,
+    remote: &mut Option<net::Incoming>,
+) -> Option<Event<M, I>> {
+    let mut event = match pending.take() {
+        Some(e) => e,
+        None => next_input(event_queue, remote).await?,
+    };
+    if let Event::MouseMove(_) = event {
+        while let Ok(next) = event_queue.try_recv() {
+            if let Event::MouseMove(_) = next {
+                event = next;
+            } else {
+                *pending = Some(next);
+                break;
+            }
+        }
+    }
+    Some(event)
+}
+
+// Waits for whichever comes first out of an event from the player and a message from the other
+// player's game (if there is one). The end of the other game's messages comes as ConnectionLost,
+// after which they aren't waited for until the game connects again
+async fn next_input<M: Scalar, I>(
+    event_queue: &mut mpsc::Receiver<Event<M, I>>,
+    remote: &mut Option<net::Incoming>,
+) -> Option<Event<M, I>> {
+    let incoming = match remote.as_mut() {
+        Some(i) => i,
+        None => return event_queue.next().await,
+    };
+    match future::select(event_queue.next(), incoming.next()).await {
+        future::Either::Left((e, _)) => e,
+        future::Either::Right((Some(message), _)) => Some(Event::Remote(message)),
+        future::Either::Right((None, _)) => {
+            *remote = None;
+            Some(Event::ConnectionLost)
+        }
     }
 }
@@ -0,0 +1,126 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::Platform;
+
+// Number of times a repeated message is logged before the rest are suppressed
+pub const MAX_REPEATS: u32 = 3;
+
+// How serious a logged message is, from most to least serious
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    // Something the player asked for couldn't be done
+    Error,
+    // Something went wrong, but the game carried on without it
+    Warn,
+    Info,
+    // Only useful when working on the game
+    Debug,
+}
+
+impl Level {
+    pub const ALL: [Level; 4] = [Level::Error, Level::Warn, Level::Info, Level::Debug];
+
+    // Gets the name put in front of messages logged at this level
+    pub fn name(self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+        }
+    }
+
+    // Finds the level with a name, ignoring case (None if there isn't one)
+    pub fn parse(name: &str) -> Option<Level> {
+        Level::ALL
+            .iter()
+            .copied()
+            .find(|l| l.name().eq_ignore_ascii_case(name))
+    }
+}
+
+// Least serious level that's logged
+static VERBOSITY: AtomicU8 = AtomicU8::new(Level::Info as u8);
+
+// Sets the least serious level that's logged; anything less serious is dropped
+pub fn set_verbosity(level: Level) {
+    VERBOSITY.store(level as u8, Ordering::Relaxed);
+}
+
+// Gets the least serious level that's logged
+pub fn verbosity() -> Level {
+    let verbosity = VERBOSITY.load(Ordering::Relaxed);
+    Level::ALL
+        .iter()
+        .copied()
+        .find(|&l| l as u8 == verbosity)
+        .unwrap_or(Level::Info)
+}
+
+// Logs a message with its level in front, routing errors to the platform's error log
+pub fn log<P: Platform>(level: Level, msg: &str) {
+    if level > verbosity() {
+        return;
+    }
+    let line = format!("{}: {}", level.name(), msg);
+    match level {
+        Level::Error => P::log_error(line.as_str()),
+        _ => P::log(line.as_str()),
+    }
+}
+
+pub fn error<P: Platform>(msg: &str) {
+    log::<P>(Level::Error, msg);
+}
+
+pub fn warn<P: Platform>(msg: &str) {
+    log::<P>(Level::Warn, msg);
+}
+
+pub fn info<P: Platform>(msg: &str) {
+    log::<P>(Level::Info, msg);
+}
+
+pub fn debug<P: Platform>(msg: &str) {
+    log::<P>(Level::Debug, msg);
+}
+
+// Logs a message that may come up many times in a row (e.g. once for each tile of a map) only the
+// first MAX_REPEATS times, so that it doesn't drown out everything else
+pub struct Repeated<P: Platform> {
+    level: Level,
+    logged: u32,
+    suppressed: u32,
+    platform: std::marker::PhantomData<P>,
+}
+
+impl<P: Platform> Repeated<P> {
+    pub fn new(level: Level) -> Repeated<P> {
+        Repeated {
+            level,
+            logged: 0,
+            suppressed: 0,
+            platform: std::marker::PhantomData,
+        }
+    }
+
+    pub fn log(&mut self, msg: &str) {
+        if self.logged < MAX_REPEATS {
+            self.logged += 1;
+            log::<P>(self.level, msg);
+        } else {
+            self.suppressed += 1;
+        }
+    }
+
+    // Logs how many messages were suppressed, if any were
+    pub fn finish(self) {
+        if self.suppressed > 0 {
+            let msg = format!("{} more like this were suppressed", self.suppressed);
+            log::<P>(self.level, msg.as_str());
+        }
+    }
+}
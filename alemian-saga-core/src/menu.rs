@@ -0,0 +1,58 @@
+// Vertical list of labeled options, one of which is highlighted
+pub struct Menu<T> {
+    items: Vec<(String, T)>,
+    // Index of the highlighted item (always 0 for an empty menu)
+    selected: usize,
+}
+
+impl<T> Menu<T> {
+    // Creates a menu with the first item highlighted
+    pub fn new(items: Vec<(String, T)>) -> Menu<T> {
+        Menu { items, selected: 0 }
+    }
+
+    // Highlights the item below the current one, wrapping around to the top
+    pub fn next(&mut self) {
+        if !self.items.is_empty() {
+            self.selected = (self.selected + 1) % self.items.len();
+        }
+    }
+
+    // Highlights the item above the current one, wrapping around to the bottom
+    pub fn previous(&mut self) {
+        if !self.items.is_empty() {
+            self.selected = (self.selected + self.items.len() - 1) % self.items.len();
+        }
+    }
+
+    // Gets the value of the highlighted item (None if the menu is empty)
+    pub fn selected(&self) -> Option<&T> {
+        self.items.get(self.selected).map(|(_, value)| value)
+    }
+
+    pub fn selected_index(&self) -> usize {
+        self.selected
+    }
+
+    // Replaces the label of each item that label gives a new one for, keeping the same item
+    // highlighted
+    pub fn relabel(&mut self, label: impl Fn(&T) -> Option<String>) {
+        for (old, value) in self.items.iter_mut() {
+            if let Some(new) = label(value) {
+                *old = new;
+            }
+        }
+    }
+
+    pub fn labels(&self) -> impl Iterator<Item = &str> {
+        self.items.iter().map(|(label, _)| label.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
@@ -0,0 +1,279 @@
+use crate::game::MapDistance;
+use crate::{log, save, sha256, Error, Platform, Vector};
+
+// Messages that arrive from the other player's game, ending once the connection is lost
+pub type Incoming = std::pin::Pin<Box<dyn futures::Stream<Item = Vec<u8>>>>;
+
+// Sends a message to the other game
+pub type Sender = Box<dyn Fn(&[u8])>;
+
+// Connection to another player's game, as made by Platform::connect
+pub struct MessageStream {
+    pub incoming: Incoming,
+    // Messages sent before the connection is open are held until it is, and those sent after it's
+    // lost are dropped
+    pub send: Sender,
+}
+
+// Something a player did on their turn that can't be taken back. Units are picked out by where
+// they stand, since both games number them the same way but positions are easier to check
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Command {
+    // The unit at origin moved to destination and waited there
+    Wait {
+        origin: Vector<MapDistance>,
+        destination: Vector<MapDistance>,
+    },
+    // The unit at origin moved to destination and attacked the unit at target from there
+    Attack {
+        origin: Vector<MapDistance>,
+        destination: Vector<MapDistance>,
+        target: Vector<MapDistance>,
+    },
+    EndTurn,
+}
+
+// What the games send each other. Only the game of the player whose turn it is sends commands;
+// the other applies them as they arrive
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Message {
+    // Sent on connecting, with how many commands have arrived so far, so that any that were lost
+    // along with the last connection are sent again. The other game answers with a Welcome, or
+    // with its State if resync is set because this game fell out of step with it
+    Hello {
+        received: u32,
+        resync: bool,
+    },
+    // Answer to a Hello with how many commands have arrived, for the same reason
+    Welcome {
+        received: u32,
+    },
+    // A game's commands are numbered in the order they were given, starting from 0. The hash of
+    // the game's state before the command (see Game::shared_state_hash) is sent along with it, so
+    // that the other game can tell if it has fallen out of step
+    Command {
+        seq: u32,
+        command: Command,
+        state_hash: String,
+    },
+    // Answer to a Hello asking to resync: the game as it is now, for the other game to pick up
+    // from, along with how many commands have been sent and received
+    State {
+        snapshot: Box<save::Snapshot>,
+        sent: u32,
+        received: u32,
+    },
+}
+
+// What a game has to do about a message from the other game
+#[derive(Debug, PartialEq)]
+pub enum Received {
+    // Carry out the command, unless the other game was in a different state when it was given
+    Command {
+        command: Command,
+        state_hash: String,
+    },
+    // Send the other game the state of this one (see Session::send_state)
+    StateWanted,
+    // Pick up from where the other game is, since this one fell out of step with it
+    State(Box<save::Snapshot>),
+}
+
+// Seed for the random number generators of both games in a session, which is decided by the address
+// they meet at so that they make the same random decisions
+pub fn seed(url: &str) -> u64 {
+    let mut hasher = sha256::Sha256::default();
+    hasher.update(url.as_bytes());
+    let hash = hasher.finish();
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&hash[..8]);
+    u64::from_le_bytes(bytes)
+}
+
+// Encodes a message as MessagePack with field names, so that either game can tell if the other
+// sent fields it doesn't know about
+pub fn encode(message: &Message) -> Result<Vec<u8>, rmp_serde::encode::Error> {
+    rmp_serde::to_vec_named(message)
+}
+
+pub fn decode(url: &str, bytes: &[u8]) -> Result<Message, Error> {
+    rmp_serde::from_slice(bytes).map_err(|e| Error::Decode {
+        path: url.to_owned(),
+        source_msg: e.to_string(),
+    })
+}
+
+// Game played against someone on another machine. Every command sent is kept so that any lost
+// along with a connection can be sent again once the games reconnect
+pub struct Session {
+    url: String,
+    // Teams played by the other player
+    teams: Vec<String>,
+    // None while disconnected
+    send: Option<Sender>,
+    // Messages from the latest connection, until the loop that waits for events takes them
+    incoming: Option<Incoming>,
+    sent: Vec<Message>,
+    // Number of commands from the other game that have arrived, which is also the number the next
+    // one should have
+    received: u32,
+    // Whether this game has fallen out of step with the other, and should pick up from where the
+    // other game is once they reconnect
+    resync: bool,
+}
+
+impl Session {
+    pub fn new(url: String, teams: Vec<String>) -> Session {
+        Session {
+            url,
+            teams,
+            send: None,
+            incoming: None,
+            sent: Vec::new(),
+            received: 0,
+            resync: false,
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        self.url.as_str()
+    }
+
+    // Whether a team is played on the other machine
+    pub fn plays(&self, team: &str) -> bool {
+        self.teams.iter().any(|t| t == team)
+    }
+
+    // Connects to the other game (again, if the connection was lost) and greets it
+    pub fn connect<P: Platform>(&mut self, platform: &P) -> Result<(), Error> {
+        let stream = platform.connect(self.url.as_str())?;
+        self.send = Some(stream.send);
+        self.incoming = Some(stream.incoming);
+        self.send_message::<P>(&Message::Hello {
+            received: self.received,
+            resync: self.resync,
+        });
+        Ok(())
+    }
+
+    // Forgets the connection after it's lost. Commands given until the games reconnect are sent
+    // once they do
+    pub fn disconnect(&mut self) {
+        self.send = None;
+        self.incoming = None;
+    }
+
+    // Takes the messages from the latest connection, if they haven't been taken already
+    pub fn take_incoming(&mut self) -> Option<Incoming> {
+        self.incoming.take()
+    }
+
+    // Sends a command to the other game along with the hash of this game's state before the
+    // command was carried out
+    pub fn send<P: Platform>(&mut self, command: Command, state_hash: String) {
+        let message = Message::Command {
+            seq: self.sent.len() as u32,
+            command,
+            state_hash,
+        };
+        self.send_message::<P>(&message);
+        self.sent.push(message);
+    }
+
+    // Sends the state of this game for the other game to pick up from
+    pub fn send_state<P: Platform>(&self, snapshot: save::Snapshot) {
+        self.send_message::<P>(&Message::State {
+            snapshot: Box::new(snapshot),
+            sent: self.sent.len() as u32,
+            received: self.received,
+        });
+    }
+
+    // Notes that this game has fallen out of step with the other, so that it asks for the other
+    // game's state once they reconnect
+    pub fn fall_out_of_step(&mut self) {
+        self.resync = true;
+    }
+
+    fn send_message<P: Platform>(&self, message: &Message) {
+        let send = match self.send.as_ref() {
+            Some(s) => s,
+            None => return,
+        };
+        match encode(message) {
+            Ok(bytes) => send(bytes.as_slice()),
+            Err(e) => log::error::<P>(format!("Failed to encode {:?}: {}", message, e).as_str()),
+        }
+    }
+
+    // Handles a message from the other game, returning what the game has to do about it. A command
+    // is only returned if it's the one that comes next, and it's only counted as having arrived
+    // once it's accepted. Commands that were already carried out are ignored, since they're sent
+    // again after a reconnect, and commands that skip ahead are refused. While waiting for the
+    // other game's state, everything but the state is ignored, since it's already part of it
+    pub fn receive<P: Platform>(&mut self, message: Message) -> Option<Received> {
+        // Messages still on their way over a connection that was dropped are sent again after
+        // the games reconnect
+        self.send.as_ref()?;
+        match message {
+            Message::Hello { resync: true, .. } => Some(Received::StateWanted),
+            Message::State {
+                snapshot,
+                sent,
+                received,
+            } if self.resync => {
+                // Commands given here that the other game hasn't received were given in a state
+                // that's being left behind, so they're dropped
+                self.sent.truncate(received as usize);
+                self.received = sent;
+                self.resync = false;
+                Some(Received::State(snapshot))
+            }
+            _ if self.resync => None,
+            Message::Hello { received, .. } => {
+                self.send_message::<P>(&Message::Welcome {
+                    received: self.received,
+                });
+                self.resend::<P>(received);
+                None
+            }
+            Message::Welcome { received } => {
+                self.resend::<P>(received);
+                None
+            }
+            Message::State { .. } => {
+                log::warn::<P>(format!("Ignoring a state from {}", self.url).as_str());
+                None
+            }
+            Message::Command { seq, .. } if seq < self.received => None,
+            Message::Command { seq, .. } if seq > self.received => {
+                let msg = format!(
+                    "Refusing command {} from {}: expected command {}",
+                    seq, self.url, self.received
+                );
+                log::error::<P>(msg.as_str());
+                None
+            }
+            Message::Command {
+                command,
+                state_hash,
+                ..
+            } => Some(Received::Command {
+                command,
+                state_hash,
+            }),
+        }
+    }
+
+    // Counts the command that was just received as having arrived, once it's been carried out
+    pub fn accept(&mut self) {
+        self.received += 1;
+    }
+
+    // Sends again the commands after the first few that the other game has received
+    fn resend<P: Platform>(&self, received: u32) {
+        for message in self.sent.iter().skip(received as usize) {
+            self.send_message::<P>(message);
+        }
+    }
+}
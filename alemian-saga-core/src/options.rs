@@ -0,0 +1,60 @@
+use crate::settings::Settings;
+use crate::{log, Platform};
+
+// Key under which the options chosen on the options menu are stored by the platform
+pub const STORAGE_KEY: &str = "options";
+// Amount that each press of Left or Right changes the volume by
+pub const VOLUME_STEP: u32 = 10;
+// Amount that each press of Left or Right changes the edge pan delay by, in milliseconds
+pub const PAN_DELAY_STEP_MS: u64 = 25;
+// Shortest and longest edge pan delays that can be chosen, in milliseconds
+pub const MIN_PAN_DELAY_MS: u64 = 25;
+pub const MAX_PAN_DELAY_MS: u64 = 500;
+
+// Serialized format for the settings the player has changed on the options menu, which override
+// the ones in the settings file (None for ones that haven't been changed). Volume is saved with the
+// rest of the sound settings instead
+#[derive(Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Options {
+    pub image_smoothing: Option<bool>,
+    pub mouse_pan_delay_ms: Option<u64>,
+    pub show_grid: Option<bool>,
+    // Locale that the game's text is shown in, in place of the one the game was started with
+    pub locale: Option<String>,
+}
+
+impl Options {
+    // Replaces the settings from the settings file with the ones the player has chosen
+    pub fn apply(&self, settings: &mut Settings) {
+        if let Some(smoothing) = self.image_smoothing {
+            settings.image_smoothing = smoothing;
+        }
+        if let Some(delay) = self.mouse_pan_delay_ms {
+            settings.mouse_pan_delay_ms = delay;
+        }
+        if let Some(show_grid) = self.show_grid {
+            settings.show_grid = show_grid;
+        }
+    }
+}
+
+// Retrieves the options saved by the player (none if nothing has been saved)
+pub fn load<P: Platform>(platform: &P) -> Options {
+    let stored = match platform.retrieve(STORAGE_KEY) {
+        Some(s) => s,
+        None => return Options::default(),
+    };
+    serde_json::from_str(stored.as_str()).unwrap_or_else(|e| {
+        log::warn::<P>(format!("Ignoring saved options: {}", e).as_str());
+        Options::default()
+    })
+}
+
+// Saves the options so that they're used by later games
+pub fn save<P: Platform>(platform: &P, options: &Options) {
+    match serde_json::to_string(options) {
+        Ok(serialized) => platform.store(STORAGE_KEY, serialized.as_str()),
+        Err(e) => log::warn::<P>(format!("Failed to save options: {}", e).as_str()),
+    }
+}
@@ -0,0 +1,152 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use crate::game::MapDistance;
+use crate::geometry::{self, RangePattern};
+use crate::Vector;
+
+// Steps from a tile to each of the tiles next to it
+static NEIGHBOR_OFFSETS: [(i64, i64); 4] = [(0, -1), (-1, 0), (1, 0), (0, 1)];
+
+// Gets the tiles next to a position (not counting diagonals) that are on a map of a given size
+pub fn neighbors(
+    pos: Vector<MapDistance>,
+    map_size: Vector<MapDistance>,
+) -> impl Iterator<Item = Vector<MapDistance>> {
+    NEIGHBOR_OFFSETS.iter().filter_map(move |&(dx, dy)| {
+        let x = pos.x as i64 + dx;
+        let y = pos.y as i64 + dy;
+        let on_map = x >= 0 && y >= 0 && x < map_size.x as i64 && y < map_size.y as i64;
+        on_map.then_some(Vector {
+            x: x as MapDistance,
+            y: y as MapDistance,
+        })
+    })
+}
+
+// Finds every tile that can be reached from start without spending more than budget, along with
+// the cheapest cost of reaching each. cost gives what it takes to enter a tile, or None if it can't
+// be entered at all
+pub fn reachable(
+    start: Vector<MapDistance>,
+    budget: u32,
+    map_size: Vector<MapDistance>,
+    cost: impl Fn(Vector<MapDistance>) -> Option<u32>,
+) -> HashMap<Vector<MapDistance>, u32> {
+    let mut best = HashMap::from([(start, 0u32)]);
+    let mut frontier = BinaryHeap::from([Reverse((0, start.y, start.x))]);
+    while let Some(Reverse((spent, y, x))) = frontier.pop() {
+        let pos = Vector { x, y };
+        if best.get(&pos).is_some_and(|&b| b < spent) {
+            // Already reached more cheaply some other way
+            continue;
+        }
+        for next in neighbors(pos, map_size) {
+            let total = match cost(next) {
+                Some(c) => spent.saturating_add(c),
+                None => continue,
+            };
+            if total <= budget && best.get(&next).is_none_or(|&b| total < b) {
+                best.insert(next, total);
+                frontier.push(Reverse((total, next.y, next.x)));
+            }
+        }
+    }
+    best
+}
+
+// Who is standing on a tile, as far as a unit moving through it goes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Occupant {
+    Empty,
+    // A unit on the same team (including the one that's moving)
+    Ally,
+    Enemy,
+}
+
+// Where a unit can move, worked out by moves
+pub struct Moves {
+    // Cheapest cost of reaching each tile that the unit can move to or through
+    pub costs: HashMap<Vector<MapDistance>, u32>,
+    // Tiles that the unit can end its move on, which always include the one it started on
+    pub destinations: HashSet<Vector<MapDistance>>,
+}
+
+// Finds where a unit can move from start without spending more than budget. Enemies can't be moved
+// through, allies can only be moved through if pass_through_allies is set, and no unit can stop on
+// another's tile. occupant says who is on each tile, and cost gives what it takes to enter a tile
+// (or None if it can't be entered whoever is on it)
+pub fn moves(
+    start: Vector<MapDistance>,
+    budget: u32,
+    map_size: Vector<MapDistance>,
+    cost: impl Fn(Vector<MapDistance>) -> Option<u32>,
+    occupant: impl Fn(Vector<MapDistance>) -> Occupant,
+    pass_through_allies: bool,
+) -> Moves {
+    let costs = reachable(start, budget, map_size, |p| match occupant(p) {
+        Occupant::Empty => cost(p),
+        Occupant::Ally if pass_through_allies => cost(p),
+        _ => None,
+    });
+    let destinations = costs
+        .keys()
+        .copied()
+        .filter(|&p| p == start || occupant(p) == Occupant::Empty)
+        .collect();
+    Moves {
+        costs,
+        destinations,
+    }
+}
+
+// Works out the cheapest path from start to destination out of the costs that reachable found from
+// start, given the same cost of entering each tile. The path begins at start and ends at
+// destination, or is None if destination wasn't reached
+pub fn path(
+    start: Vector<MapDistance>,
+    destination: Vector<MapDistance>,
+    costs: &HashMap<Vector<MapDistance>, u32>,
+    map_size: Vector<MapDistance>,
+    cost: impl Fn(Vector<MapDistance>) -> Option<u32>,
+) -> Option<Vec<Vector<MapDistance>>> {
+    let mut path = vec![destination];
+    let mut pos = destination;
+    while pos != start {
+        // The tile before this one is whichever neighbor it was reached from, and tiles already on
+        // the path are skipped in case entering some of them is free
+        let before = costs.get(&pos)?.checked_sub(cost(pos)?)?;
+        pos = neighbors(pos, map_size)
+            .find(|n| costs.get(n) == Some(&before) && !path.contains(n))?;
+        path.push(pos);
+    }
+    path.reverse();
+    Some(path)
+}
+
+// Finds every tile on the map that a range pattern reaches from any of the origins, apart from the
+// excluded ones. Origins are only included if they're in range of another origin
+pub fn attack_range(
+    origins: impl IntoIterator<Item = Vector<MapDistance>>,
+    pattern: &RangePattern,
+    map_size: Vector<MapDistance>,
+    excluded: impl Fn(Vector<MapDistance>) -> bool,
+) -> HashSet<Vector<MapDistance>> {
+    let offsets: Vec<_> = pattern
+        .offsets()
+        .into_iter()
+        .filter(|&offset| offset != Vector { x: 0, y: 0 })
+        .collect();
+    let mut tiles = HashSet::new();
+    for origin in origins {
+        for &offset in offsets.iter() {
+            match geometry::offset_within(origin, offset, map_size) {
+                Some(pos) if !excluded(pos) => {
+                    tiles.insert(pos);
+                }
+                _ => {}
+            }
+        }
+    }
+    tiles
+}
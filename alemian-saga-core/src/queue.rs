@@ -0,0 +1,105 @@
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use futures::channel::mpsc;
+use futures::SinkExt;
+
+use crate::{Event, Scalar};
+
+// Sends events to the game without blocking while preserving the order they were sent in.
+// Events that don't fit in the queue are held in a backlog that is forwarded by a single future,
+// and every event sent while the backlog is non-empty joins the end of the backlog
+pub struct OrderedSender<M: Scalar, I> {
+    sender: mpsc::Sender<Event<M, I>>,
+    // The front of the backlog is the event currently being forwarded
+    backlog: Rc<RefCell<VecDeque<Event<M, I>>>>,
+    // Number of events that didn't fit in the queue and had to wait in the backlog
+    overflows: Rc<Cell<u64>>,
+}
+
+// Creates a queue that holds capacity events (plus one per sender) before events are backlogged,
+// along with an ordered sender for it
+pub fn channel<M: Scalar, I: Clone>(
+    capacity: usize,
+) -> (OrderedSender<M, I>, mpsc::Receiver<Event<M, I>>) {
+    let (sender, receiver) = mpsc::channel(capacity);
+    (OrderedSender::new(sender), receiver)
+}
+
+impl<M: Scalar, I> Clone for OrderedSender<M, I> {
+    fn clone(&self) -> Self {
+        OrderedSender {
+            sender: self.sender.clone(),
+            backlog: self.backlog.clone(),
+            overflows: self.overflows.clone(),
+        }
+    }
+}
+
+impl<M: Scalar, I: Clone> OrderedSender<M, I> {
+    pub fn new(sender: mpsc::Sender<Event<M, I>>) -> OrderedSender<M, I> {
+        OrderedSender {
+            sender,
+            backlog: Rc::new(RefCell::new(VecDeque::new())),
+            overflows: Rc::new(Cell::new(0)),
+        }
+    }
+
+    // Gets the number of events sent so far that didn't fit in the queue, counting every clone of
+    // the sender
+    pub fn overflows(&self) -> u64 {
+        self.overflows.get()
+    }
+
+    // Sends an event. If the queue is full, a future is returned which must be run to completion
+    // (e.g. by spawning it) to forward the backlog
+    pub fn send(&mut self, event: Event<M, I>) -> Option<impl std::future::Future<Output = ()>> {
+        {
+            let mut backlog = self.backlog.borrow_mut();
+            if !backlog.is_empty() {
+                self.overflows.set(self.overflows.get() + 1);
+                // Only the latest mouse position matters, but never replace the event in flight
+                let coalesce = backlog.len() > 1
+                    && matches!(event, Event::MouseMove(_))
+                    && matches!(backlog.back(), Some(Event::MouseMove(_)));
+                if coalesce {
+                    backlog.pop_back();
+                }
+                backlog.push_back(event);
+                return None;
+            }
+            match self.sender.try_send(event) {
+                Err(e) if e.is_full() => {
+                    self.overflows.set(self.overflows.get() + 1);
+                    backlog.push_back(e.into_inner());
+                }
+                _ => return None,
+            }
+        }
+        Some(Self::forward_backlog(self.clone()))
+    }
+
+    // Closes the queue for every sender. The game stops once it's handled the events already in
+    // the queue, and the backlog and anything sent later are dropped
+    pub fn close(&mut self) {
+        self.backlog.borrow_mut().clear();
+        self.sender.close_channel();
+    }
+
+    async fn forward_backlog(mut self) {
+        loop {
+            let front = self.backlog.borrow().front().cloned();
+            match front {
+                Some(event) => {
+                    if self.sender.send(event).await.is_err() {
+                        self.backlog.borrow_mut().clear();
+                        break;
+                    }
+                    self.backlog.borrow_mut().pop_front();
+                }
+                None => break,
+            }
+        }
+    }
+}
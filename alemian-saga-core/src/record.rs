@@ -0,0 +1,397 @@
+use futures::channel::mpsc;
+use futures::{future, StreamExt};
+
+use crate::{game, log, Error, Event, Platform, Scalar};
+
+// Speeds that a replay can be watched at, as multiples of how quickly the game was played
+pub const SPEEDS: [u64; 3] = [1, 2, 4];
+
+// An event along with when it happened
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct RecordedEvent<M: Scalar, I> {
+    // Time since the recording started
+    pub time_ns: u64,
+    pub event: Event<M, I>,
+}
+
+// Everything needed to play a game again exactly as it happened
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct Session<M: Scalar, I> {
+    // Seed that the game's random number generator was started with
+    pub seed: u64,
+    // Map that the game started on, relative to the language directory (None to start on the map
+    // that the player was last on, as recordings made before this was kept did)
+    #[serde(default)]
+    pub map: Option<String>,
+    pub events: Vec<RecordedEvent<M, I>>,
+    // Hash of the game's state once the last event was handled (see Game::state_hash), so that a
+    // replay can be checked for ending up in the same state
+    #[serde(default)]
+    pub state_hash: Option<String>,
+}
+
+pub type Recording<P> = Session<<P as Platform>::MouseDistance, <P as Platform>::InputType>;
+
+// How quickly recorded events are fed back to the game
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pacing {
+    // Wait between events for as long as the player did
+    Original,
+    // Handle each event as soon as the previous one is done
+    Fast,
+}
+
+// Keeps track of the events handled by a game
+pub struct Recorder<P: Platform> {
+    // When the first event was recorded
+    start: Option<P::Instant>,
+    recording: Recording<P>,
+}
+
+impl<P: Platform> Recorder<P> {
+    pub fn new() -> Recorder<P> {
+        Recorder {
+            start: None,
+            recording: Session {
+                seed: 0,
+                map: None,
+                events: Vec::new(),
+                state_hash: None,
+            },
+        }
+    }
+
+    // Records the seed the game was started with
+    pub fn set_seed(&mut self, seed: u64) {
+        self.recording.seed = seed;
+    }
+
+    // Records the map the game started on
+    pub fn set_map(&mut self, map: &str) {
+        self.recording.map = Some(map.to_owned());
+    }
+
+    // Records the state the game was left in once it's over
+    pub fn set_state_hash(&mut self, state_hash: String) {
+        self.recording.state_hash = Some(state_hash);
+    }
+
+    pub fn record(&mut self, event: &Event<P::MouseDistance, P::InputType>) {
+        let now = P::now();
+        let start = *self.start.get_or_insert(now);
+        self.recording.events.push(RecordedEvent {
+            time_ns: P::to_nanoseconds(P::duration_between(start, now)),
+            event: event.clone(),
+        });
+    }
+
+    pub fn events(&self) -> &[RecordedEvent<P::MouseDistance, P::InputType>] {
+        &self.recording.events
+    }
+
+    pub fn recording(&self) -> &Recording<P> {
+        &self.recording
+    }
+
+    pub fn into_recording(self) -> Recording<P> {
+        self.recording
+    }
+}
+
+impl<P: Platform> Default for Recorder<P> {
+    fn default() -> Recorder<P> {
+        Recorder::new()
+    }
+}
+
+// Saves a recording under a key so that it can be loaded later
+pub fn save<P: Platform>(platform: &P, key: &str, recording: &Recording<P>)
+where
+    P::MouseDistance: serde::Serialize,
+    P::InputType: serde::Serialize,
+{
+    match serde_json::to_string(recording) {
+        Ok(serialized) => platform.store(key, serialized.as_str()),
+        Err(e) => log::error::<P>(format!("Failed to save recording: {}", e).as_str()),
+    }
+}
+
+// Loads a recording saved with save (None if it's missing or can't be parsed)
+pub fn load<P: Platform>(platform: &P, key: &str) -> Option<Recording<P>>
+where
+    P::MouseDistance: serde::de::DeserializeOwned,
+    P::InputType: serde::de::DeserializeOwned,
+{
+    let stored = platform.retrieve(key)?;
+    serde_json::from_str(stored.as_str())
+        .map_err(|e| log::error::<P>(format!("Failed to load recording: {}", e).as_str()))
+        .ok()
+}
+
+// Starts a new game with the recorded seed on the recorded map
+async fn start<P: Platform>(
+    recording: &Recording<P>,
+    platform: P,
+    language: &str,
+    locale: &str,
+) -> Result<game::Game<P>, Error> {
+    game::draw_loading_screen(&platform, game::LoadingPhase::FetchingMap);
+    let map = recording.map.as_deref();
+    game::Game::load_seeded_at(platform, language, locale, recording.seed, map).await
+}
+
+// Starts a new game with the recorded seed and feeds it the recorded events, returning the game
+// once they've all been handled
+pub async fn replay<P: Platform>(
+    recording: Recording<P>,
+    platform: P,
+    language: &str,
+    locale: &str,
+    pacing: Pacing,
+) -> Result<game::Game<P>, Error> {
+    let mut game = start(&recording, platform, language, locale).await?;
+    let start = P::now();
+    let mut redraw = game::Redraw::Idle;
+    for recorded in recording.events {
+        if redraw == game::Redraw::Finished {
+            break;
+        }
+        if pacing == Pacing::Original {
+            // Keep drawing any frames that come up before the event is due
+            loop {
+                let elapsed = P::to_nanoseconds(P::duration_between(start, P::now()));
+                let remaining = recorded.time_ns.saturating_sub(elapsed);
+                if remaining == 0 {
+                    break;
+                }
+                match redraw.wait() {
+                    Some(wait) if wait <= remaining => {
+                        P::sleep(P::nanoseconds(wait)).await;
+                        redraw = game.frame();
+                    }
+                    _ => P::sleep(P::nanoseconds(remaining)).await,
+                }
+            }
+        }
+        redraw = game.handle_event(recorded.event);
+        if game.load_pending() {
+            redraw = game.load_pending_map().await;
+        }
+        // Platforms that are always ready for the next frame draw changes before the next event
+        if redraw == game::Redraw::Changed {
+            game.request_frame().await;
+            redraw = game.frame();
+        }
+    }
+    Ok(game)
+}
+
+// Where a replay being watched is up to, as shown on its transport bar
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Transport {
+    // Number of recorded events that have been handled
+    pub position: usize,
+    pub len: usize,
+    pub paused: bool,
+    // One of SPEEDS
+    pub speed: u64,
+}
+
+// What the viewer of a replay can do with it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Control {
+    TogglePause,
+    // Pause and handle just the next event
+    Step,
+    // Pause and go back to before the last event that was handled
+    StepBack,
+    Faster,
+    Slower,
+    Redraw,
+    Quit,
+}
+
+// Picks out what the viewer of a replay wants to do from their input, going by the keys bound to
+// moving the cursor around the map
+fn control<P: Platform>(
+    game: &game::Game<P>,
+    event: Event<P::MouseDistance, P::InputType>,
+) -> Option<Control> {
+    let event = match event {
+        Event::Input(key) => game.map_action(&key)?.event()?,
+        e => e,
+    };
+    match event {
+        Event::Select | Event::Click(_) => Some(Control::TogglePause),
+        Event::Right => Some(Control::Step),
+        Event::Left => Some(Control::StepBack),
+        Event::ZoomIn => Some(Control::Faster),
+        Event::ZoomOut => Some(Control::Slower),
+        Event::Redraw => Some(Control::Redraw),
+        Event::Quit => Some(Control::Quit),
+        _ => None,
+    }
+}
+
+// Handles the next recorded event at the moment it was recorded (by the clock of a replay that
+// started at origin), checking the game's state against the recorded one if it was the last
+async fn step<P: Platform>(
+    game: &mut game::Game<P>,
+    recording: &Recording<P>,
+    transport: &mut Transport,
+    origin: P::Instant,
+) -> game::Redraw {
+    let recorded = &recording.events[transport.position];
+    game.set_clock(Some(P::instant_after(
+        origin,
+        P::nanoseconds(recorded.time_ns),
+    )));
+    transport.position += 1;
+    let mut redraw = game.handle_event(recorded.event.clone());
+    if game.load_pending() {
+        redraw = game.load_pending_map().await;
+    }
+    if transport.position == transport.len {
+        match recording.state_hash.as_ref() {
+            Some(hash) if *hash != game.state_hash() => {
+                log::warn::<P>("The replay ended in a different state than the recorded game")
+            }
+            _ => {}
+        }
+    }
+    redraw
+}
+
+// Gets how far into a recording the next event to handle comes, which is where playback stands
+// while it's paused between events
+fn next_time<M: Scalar, I>(recording: &Session<M, I>, transport: &Transport) -> Option<u64> {
+    recording
+        .events
+        .get(transport.position)
+        .map(|next| next.time_ns)
+}
+
+// Lets someone watch a recorded game, rendered just as it was when it was played, while pausing,
+// stepping through it an event at a time or speeding it up. The game's clock follows the recording
+// rather than the platform's, so that animations keep pace with the events however quickly they're
+// played. Games can't be wound back, so stepping back starts the game over and handles every event
+// up to the one before. Returns the game once the viewer quits
+pub async fn watch<P: Platform>(
+    recording: Recording<P>,
+    platform: P,
+    mut event_queue: mpsc::Receiver<Event<P::MouseDistance, P::InputType>>,
+    language: &str,
+    locale: &str,
+) -> Result<game::Game<P>, Error> {
+    let mut game = start(&recording, platform, language, locale).await?;
+    let mut transport = Transport {
+        position: 0,
+        len: recording.events.len(),
+        paused: false,
+        speed: SPEEDS[0],
+    };
+    game.show_transport(transport);
+    let mut shown = transport;
+    // Moment on the platform's clock that the recording's times are counted from
+    let origin = P::now();
+    // Time into the recording that playback has reached
+    let mut played_ns = 0;
+    let mut last = origin;
+    let mut redraw = game::Redraw::Idle;
+    // Whether the wait for the next frame is over
+    let mut frame_due = false;
+    loop {
+        let now = P::now();
+        if !transport.paused {
+            played_ns += P::to_nanoseconds(P::duration_between(last, now)) * transport.speed;
+        }
+        last = now;
+        while !transport.paused
+            && next_time(&recording, &transport).is_some_and(|time| time <= played_ns)
+        {
+            redraw = step(&mut game, &recording, &mut transport, origin).await;
+        }
+        game.set_clock(Some(P::instant_after(origin, P::nanoseconds(played_ns))));
+        if redraw == game::Redraw::Changed {
+            game.request_frame().await;
+            redraw = game.frame();
+        } else if frame_due {
+            redraw = game.frame();
+        }
+        frame_due = false;
+        if transport != shown {
+            game.show_transport(transport);
+            shown = transport;
+        }
+
+        // Wait for the viewer, the next frame or the next event to come due, whichever is first
+        let due = match next_time(&recording, &transport) {
+            Some(time) if !transport.paused => {
+                Some(time.saturating_sub(played_ns) / transport.speed)
+            }
+            _ => None,
+        };
+        let wait = match (due, redraw.wait()) {
+            (Some(due), Some(frame)) => Some(due.min(frame)),
+            (due, frame) => due.or(frame),
+        };
+        let event = match wait {
+            Some(wait) => {
+                let event_future = event_queue.next();
+                let sleep_future = P::sleep(P::nanoseconds(wait));
+                futures::pin_mut!(sleep_future);
+                match future::select(event_future, sleep_future).await {
+                    future::Either::Left((e, _)) => e,
+                    future::Either::Right(_) => {
+                        frame_due = redraw.wait().is_some();
+                        continue;
+                    }
+                }
+            }
+            None => event_queue.next().await,
+        };
+        let event = match event {
+            Some(e) => e,
+            None => break,
+        };
+        match control(&game, event) {
+            Some(Control::TogglePause) => transport.paused = !transport.paused,
+            // Stepping plays out whatever the event started until the next event comes
+            Some(Control::Step) => {
+                transport.paused = true;
+                if transport.position < transport.len {
+                    redraw = step(&mut game, &recording, &mut transport, origin).await;
+                    played_ns = next_time(&recording, &transport).unwrap_or(played_ns);
+                }
+            }
+            Some(Control::StepBack) => {
+                transport.paused = true;
+                if transport.position > 0 {
+                    let target = transport.position - 1;
+                    game = start(&recording, game.into_platform(), language, locale).await?;
+                    transport.position = 0;
+                    while transport.position < target {
+                        step(&mut game, &recording, &mut transport, origin).await;
+                    }
+                    played_ns = next_time(&recording, &transport).unwrap_or(0);
+                    redraw = game.handle_event(Event::Redraw);
+                    // The new game doesn't know about the transport bar yet
+                    game.show_transport(transport);
+                    shown = transport;
+                }
+            }
+            Some(Control::Faster) => {
+                let faster = SPEEDS.iter().find(|&&s| s > transport.speed);
+                transport.speed = *faster.unwrap_or(&transport.speed);
+            }
+            Some(Control::Slower) => {
+                let slower = SPEEDS.iter().rev().find(|&&s| s < transport.speed);
+                transport.speed = *slower.unwrap_or(&transport.speed);
+            }
+            Some(Control::Redraw) => redraw = game.handle_event(Event::Redraw),
+            Some(Control::Quit) => break,
+            None => {}
+        }
+    }
+    Ok(game)
+}
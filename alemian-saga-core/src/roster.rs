@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+
+use crate::{log, serialization, Platform};
+
+// Key under which the progress of the player's units is stored by the platform
+pub const STORAGE_KEY: &str = "roster";
+
+// Level and stats that one of the player's units has earned, which carry over from map to map
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Progress {
+    pub level: u32,
+    pub exp: u32,
+    pub max_hp: u32,
+    pub strength: u32,
+    pub defense: u32,
+    pub speed: u32,
+}
+
+impl Progress {
+    pub fn of(unit: &serialization::Unit) -> Progress {
+        Progress {
+            level: unit.level,
+            exp: unit.exp,
+            max_hp: unit.max_hp,
+            strength: unit.strength,
+            defense: unit.defense,
+            speed: unit.speed,
+        }
+    }
+
+    // Gives a unit the saved level and stats. The unit keeps however much hp the map says it's
+    // missing
+    pub fn apply(&self, unit: &mut serialization::Unit) {
+        let missing_hp = unit.max_hp.saturating_sub(unit.hp);
+        unit.level = self.level;
+        unit.exp = self.exp;
+        unit.max_hp = self.max_hp;
+        unit.hp = self.max_hp.saturating_sub(missing_hp);
+        unit.strength = self.strength;
+        unit.defense = self.defense;
+        unit.speed = self.speed;
+    }
+}
+
+// Progress of each of the player's units by name
+pub type Roster = HashMap<String, Progress>;
+
+// Retrieves the saved progress of the player's units (none if nothing has been saved)
+pub fn load<P: Platform>(platform: &P) -> Roster {
+    let stored = match platform.retrieve(STORAGE_KEY) {
+        Some(s) => s,
+        None => return Roster::new(),
+    };
+    serde_json::from_str(stored.as_str()).unwrap_or_else(|e| {
+        log::warn::<P>(format!("Ignoring saved unit progress: {}", e).as_str());
+        Roster::new()
+    })
+}
+
+// Saves the progress of the player's units so that it carries over to later maps and games
+pub fn save<P: Platform>(platform: &P, roster: &Roster) {
+    match serde_json::to_string(roster) {
+        Ok(serialized) => platform.store(STORAGE_KEY, serialized.as_str()),
+        Err(e) => log::warn::<P>(format!("Failed to save unit progress: {}", e).as_str()),
+    }
+}
+
+// Saves one unit's current progress along with what's already saved for the others
+pub fn record<P: Platform>(platform: &P, unit: &serialization::Unit) {
+    let mut roster = load(platform);
+    roster.insert(unit.name.clone(), Progress::of(unit));
+    save(platform, &roster);
+}
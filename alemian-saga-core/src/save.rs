@@ -0,0 +1,292 @@
+use crate::game::{MapDistance, Visibility};
+use crate::{log, rng, serialization, sha256, strings, Error, Platform, Vector};
+
+// Format that games are saved in. Saves in any other format are refused rather than loaded wrong
+pub const VERSION: u32 = 1;
+// Names of the slots that games can be saved in
+pub const SLOTS: [&str; 3] = ["1", "2", "3"];
+// Saves are stored by the platform under this followed by the slot name
+const STORAGE_PREFIX: &str = "save_";
+// Slots that the game is saved in automatically after turns. Autosaves take turns between them, so
+// one that's cut short (e.g. by the page closing halfway through) never replaces the last good one
+pub const AUTOSAVE_SLOTS: [&str; 2] = ["autosave_a", "autosave_b"];
+// Key under which the platform stores the name of the slot holding the latest complete autosave
+pub const AUTOSAVE_POINTER_KEY: &str = "autosave_current";
+// Autosaves start with a SHA-256 digest of the rest of the save in hex
+const CHECKSUM_LEN: usize = 64;
+
+// Everything needed to pick a game back up where it was saved
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    // When the game was saved in seconds since the Unix epoch (None if the platform couldn't tell)
+    pub saved_at: Option<u64>,
+    // Path of the map being played relative to the language directory
+    pub map: String,
+    pub turn: u32,
+    // Index of the player whose turn it is among the players in the settings
+    #[serde(default)]
+    pub player: usize,
+    // Every unit left on the map, along with its position, stats and statuses
+    pub units: Vec<serialization::Unit>,
+    // Triggers that haven't been used up yet
+    pub triggers: Vec<serialization::Trigger>,
+    // None if fog of war is disabled
+    pub fog: Option<ndarray::Array2<Visibility>>,
+    pub cursor: Vector<MapDistance>,
+    pub rng: rng::Rng,
+}
+
+// Start of a save, which is read on its own to list the save and to check that the rest can be
+// loaded. Saves are encoded with field names, so the other fields are skipped
+#[derive(serde::Deserialize)]
+struct Header {
+    version: u32,
+    saved_at: Option<u64>,
+}
+
+fn storage_key(slot: &str) -> String {
+    format!("{}{}", STORAGE_PREFIX, slot)
+}
+
+// Text with an odd number of digits is rejected since its last pair of digits is cut short
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+// Saves a game in a slot, replacing whatever was saved there before
+pub fn store<P: Platform>(platform: &P, slot: &str, snapshot: &Snapshot) {
+    match rmp_serde::to_vec_named(snapshot) {
+        Ok(bytes) => platform.store(storage_key(slot).as_str(), sha256::to_hex(&bytes).as_str()),
+        Err(e) => log::warn::<P>(format!("Failed to save game: {}", e).as_str()),
+    }
+}
+
+// Reads the bytes saved in a slot along with the header at the start of them (None if the slot is
+// empty)
+fn read<P: Platform>(platform: &P, slot: &str) -> Result<Option<(Vec<u8>, Header)>, Error> {
+    let key = storage_key(slot);
+    let stored = match platform.retrieve(key.as_str()) {
+        Some(s) => s,
+        None => return Ok(None),
+    };
+    let decode_error = |source_msg: String| Error::Decode {
+        path: key.clone(),
+        source_msg,
+    };
+    let bytes = from_hex(stored.as_str()).ok_or_else(|| decode_error("not hex".to_owned()))?;
+    let header: Header = rmp_serde::from_slice(&bytes).map_err(|e| decode_error(e.to_string()))?;
+    Ok(Some((bytes, header)))
+}
+
+// Loads the game saved in a slot (None if the slot is empty)
+pub fn retrieve<P: Platform>(platform: &P, slot: &str) -> Result<Option<Snapshot>, Error> {
+    let (bytes, header) = match read(platform, slot)? {
+        Some(r) => r,
+        None => return Ok(None),
+    };
+    decode(slot, &bytes, &header).map(Some)
+}
+
+// Decodes a save that was read from a slot, unless it's in another format
+fn decode(slot: &str, bytes: &[u8], header: &Header) -> Result<Snapshot, Error> {
+    if header.version != VERSION {
+        return Err(Error::SaveVersion {
+            found: header.version,
+            expected: VERSION,
+        });
+    }
+    rmp_serde::from_slice(bytes).map_err(|e| Error::Decode {
+        path: storage_key(slot),
+        source_msg: e.to_string(),
+    })
+}
+
+// Reads the autosave in a slot, checking it against its checksum so that one that was only partly
+// written is caught rather than loaded (None if the slot is empty)
+fn read_checked<P: Platform>(platform: &P, slot: &str) -> Result<Option<Snapshot>, Error> {
+    let key = storage_key(slot);
+    let stored = match platform.retrieve(key.as_str()) {
+        Some(s) => s,
+        None => return Ok(None),
+    };
+    let decode_error = |source_msg: &str| Error::Decode {
+        path: key.clone(),
+        source_msg: source_msg.to_owned(),
+    };
+    let (checksum, hex) = match stored.get(..CHECKSUM_LEN) {
+        Some(checksum) => (checksum, &stored[CHECKSUM_LEN..]),
+        None => return Err(decode_error("cut short")),
+    };
+    let bytes = from_hex(hex).ok_or_else(|| decode_error("not hex"))?;
+    let mut hasher = sha256::Sha256::default();
+    hasher.update(&bytes);
+    if sha256::to_hex(&hasher.finish()) != checksum {
+        return Err(decode_error("checksum doesn't match"));
+    }
+    let header: Header = rmp_serde::from_slice(&bytes).map_err(|e| decode_error(&e.to_string()))?;
+    decode(slot, &bytes, &header).map(Some)
+}
+
+// Gets the autosave slots in the order they're loaded from: the one holding the latest complete
+// autosave first
+fn autosave_slots<P: Platform>(platform: &P) -> [&'static str; 2] {
+    let [first, second] = AUTOSAVE_SLOTS;
+    match platform.retrieve(AUTOSAVE_POINTER_KEY) {
+        Some(current) if current == second => [second, first],
+        _ => [first, second],
+    }
+}
+
+// Loads the latest autosave, falling back to the one before it if it's damaged (None if the game
+// hasn't been autosaved)
+pub fn retrieve_autosave<P: Platform>(platform: &P) -> Result<Option<Snapshot>, Error> {
+    let mut error = None;
+    for slot in autosave_slots(platform) {
+        match read_checked(platform, slot) {
+            Ok(Some(snapshot)) => return Ok(Some(snapshot)),
+            Ok(None) => {}
+            Err(e) => {
+                log::warn::<P>(format!("Skipping autosave {}: {}", slot, e).as_str());
+                error.get_or_insert(e);
+            }
+        }
+    }
+    error.map_or(Ok(None), Err)
+}
+
+// Steps of writing an autosave
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum AutosaveStep {
+    Write,
+    // Read the autosave back and check it against its checksum
+    Verify,
+    // Mark the autosave as the latest complete one
+    Flip,
+}
+
+// Autosave that's written a step at a time between events, so that saving never holds up the game
+// for long
+pub struct Autosave {
+    slot: &'static str,
+    // The save as stored, checksum first
+    text: String,
+    step: AutosaveStep,
+}
+
+impl Autosave {
+    // Prepares to autosave a game in the slot that doesn't hold the latest autosave (None if the
+    // game can't be encoded)
+    pub fn new<P: Platform>(platform: &P, snapshot: &Snapshot) -> Option<Autosave> {
+        let bytes = rmp_serde::to_vec_named(snapshot)
+            .map_err(|e| log::warn::<P>(format!("Failed to autosave game: {}", e).as_str()))
+            .ok()?;
+        let mut hasher = sha256::Sha256::default();
+        hasher.update(&bytes);
+        let [_, slot] = autosave_slots(platform);
+        Some(Autosave {
+            slot,
+            text: format!(
+                "{}{}",
+                sha256::to_hex(&hasher.finish()),
+                sha256::to_hex(&bytes)
+            ),
+            step: AutosaveStep::Write,
+        })
+    }
+
+    // Carries out the next step of the autosave, returning whether it's over. An autosave that
+    // doesn't read back intact is given up on, leaving the last one as the latest
+    pub fn advance<P: Platform>(&mut self, platform: &P) -> bool {
+        match self.step {
+            AutosaveStep::Write => {
+                platform.store(storage_key(self.slot).as_str(), self.text.as_str());
+                self.step = AutosaveStep::Verify;
+                false
+            }
+            AutosaveStep::Verify => match read_checked(platform, self.slot) {
+                Ok(Some(_)) => {
+                    self.step = AutosaveStep::Flip;
+                    false
+                }
+                Ok(None) => {
+                    log::warn::<P>(format!("Autosave {} wasn't stored", self.slot).as_str());
+                    true
+                }
+                Err(e) => {
+                    log::warn::<P>(format!("Autosave {} failed: {}", self.slot, e).as_str());
+                    true
+                }
+            },
+            AutosaveStep::Flip => {
+                platform.store(AUTOSAVE_POINTER_KEY, self.slot);
+                true
+            }
+        }
+    }
+}
+
+// Gets the label a slot is listed with: its name followed by when it was saved, or by why it can't
+// be loaded
+pub fn describe<P: Platform>(platform: &P, strings: &strings::Strings, slot: &str) -> String {
+    let status = match read(platform, slot) {
+        Ok(None) => strings.get::<P>("save.empty").to_owned(),
+        Ok(Some((_, header))) if header.version != VERSION => {
+            strings.get::<P>("save.incompatible").to_owned()
+        }
+        Ok(Some((_, header))) => match header.saved_at {
+            Some(t) => format_timestamp(t),
+            None => strings.get::<P>("save.saved").to_owned(),
+        },
+        Err(_) => strings.get::<P>("save.unreadable").to_owned(),
+    };
+    strings.format::<P>("save.slot", &[slot, status.as_str()])
+}
+
+// Gets the label that the latest autosave is listed with: when it was saved, or why it can't be
+// loaded
+pub fn describe_autosave<P: Platform>(platform: &P, strings: &strings::Strings) -> String {
+    let status = match retrieve_autosave(platform) {
+        Ok(None) => strings.get::<P>("save.empty").to_owned(),
+        Ok(Some(snapshot)) => match snapshot.saved_at {
+            Some(t) => format_timestamp(t),
+            None => strings.get::<P>("save.saved").to_owned(),
+        },
+        Err(Error::SaveVersion { .. }) => strings.get::<P>("save.incompatible").to_owned(),
+        Err(_) => strings.get::<P>("save.unreadable").to_owned(),
+    };
+    strings.format::<P>("save.autosave", &[status.as_str()])
+}
+
+// Formats seconds since the Unix epoch as a UTC date and time, e.g. "2021-03-04 05:06"
+pub fn format_timestamp(seconds: u64) -> String {
+    let days = (seconds / 86_400) as i64;
+    let minutes = seconds % 86_400 / 60;
+    // Converts days to a date on the proleptic Gregorian calendar, counting from the start of a
+    // 400-year era beginning in March so that leap days fall at the end of each year
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        minutes / 60,
+        minutes % 60
+    )
+}
@@ -1,11 +1,23 @@
+use crate::geometry::RangePattern;
+
 // Serialized format for metadata about a particular type of tile
-#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct TileType {
     pub image: String,
     pub name: String,
     pub defense: i32,
     pub evade: i32,
     pub move_cost: u32,
+    // Color of the tile on the minimap (derived from the name if unset)
+    #[serde(default)]
+    pub minimap_color: Option<crate::style::Color>,
+    // Path of a sound looped while tiles of this type are on the screen, louder the more of the
+    // screen they cover
+    #[serde(default)]
+    pub ambient_sound: Option<String>,
+    // Path of the sound played as a unit steps onto a tile of this type
+    #[serde(default)]
+    pub footstep_sound: Option<String>,
 }
 
 // Serialized format for maps
@@ -13,4 +25,124 @@ pub struct TileType {
 pub struct Map {
     pub tile_types: Vec<TileType>,
     pub map: ndarray::Array2<u32>,
+    // Color drawn behind the map wherever the tiles don't cover the screen
+    #[serde(default)]
+    pub background: Option<crate::style::Color>,
+    // Whether tiles are hidden until the cursor comes near them
+    #[serde(default)]
+    pub fog: bool,
+    // Events that happen when the cursor enters specific tiles
+    #[serde(default)]
+    pub triggers: Vec<Trigger>,
+    // Messages shown when the map starts
+    #[serde(default)]
+    pub intro_dialog: Vec<Message>,
+    // Path of the music looped while the map is played (None for silence)
+    #[serde(default)]
+    pub music: Option<String>,
+    // Units on the map when it starts
+    #[serde(default)]
+    pub units: Vec<Unit>,
+}
+
+// Encodes a map in the format that map files are stored in
+pub fn write_map(map: &Map) -> Result<Vec<u8>, rmp_serde::encode::Error> {
+    rmp_serde::to_vec(map)
+}
+
+// Serialized format for a unit on the map
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Unit {
+    pub name: String,
+    pub x: u32,
+    pub y: u32,
+    // Image drawn on the unit's tile
+    pub image: String,
+    // Image shown on the unit panel (None for no portrait)
+    #[serde(default)]
+    pub portrait: Option<String>,
+    pub hp: u32,
+    pub max_hp: u32,
+    pub strength: u32,
+    pub defense: u32,
+    pub speed: u32,
+    // Number of tiles the unit can move in a turn
+    pub movement: u32,
+    // Number of tiles away the unit's weapon can reach
+    #[serde(default = "melee_range")]
+    pub range: u32,
+    // Tiles the unit's weapon can reach, in place of range (None for every tile up to range away)
+    #[serde(default)]
+    pub range_pattern: Option<RangePattern>,
+    // Team the unit fights for, which its sprite is tinted with the color of
+    #[serde(default = "player_team")]
+    pub team: String,
+    #[serde(default)]
+    pub statuses: Vec<Status>,
+    #[serde(default = "first_level")]
+    pub level: u32,
+    // Experience earned towards the next level
+    #[serde(default)]
+    pub exp: u32,
+}
+
+impl Unit {
+    // Gets the tiles that the unit's weapon can reach
+    pub fn weapon_range(&self) -> RangePattern {
+        self.range_pattern.clone().unwrap_or(RangePattern::MinMax {
+            min: 1,
+            max: self.range,
+        })
+    }
+}
+
+// Conditions a unit can be in, each shown by an icon on its tile
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum Status {
+    // The unit has already acted this turn
+    Acted,
+}
+
+// Team that the player's units are on
+pub const PLAYER_TEAM: &str = "player";
+
+fn melee_range() -> u32 {
+    1
+}
+
+fn first_level() -> u32 {
+    1
+}
+
+fn player_team() -> String {
+    PLAYER_TEAM.to_owned()
+}
+
+// Serialized format for a message shown in the dialog box
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Message {
+    // Name of whoever is speaking (None for narration)
+    #[serde(default)]
+    pub speaker: Option<String>,
+    pub text: String,
+}
+
+// Serialized format for something that happens when the cursor enters a tile
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Trigger {
+    pub x: u32,
+    pub y: u32,
+    // Whether the trigger fires every time the tile is entered instead of only the first time
+    #[serde(default)]
+    pub repeat: bool,
+    pub action: TriggerAction,
+}
+
+// What happens when a trigger fires
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum TriggerAction {
+    // Show a message in the dialog box
+    Message(Message),
+    // Finish the map
+    EndMap,
 }
@@ -0,0 +1,180 @@
+use crate::{fetch, log, serialization, sound, style, Platform};
+
+// Path of the settings file relative to the asset root
+pub const SETTINGS_PATH: &str = "settings.json";
+
+// Serialized format for user-adjustable game settings
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    // Fewest rows or columns of tiles that may be visible at once (limited by the map size)
+    pub min_visible_tiles: u32,
+    // Most rows or columns of tiles that may be visible at once (no limit besides the map size if
+    // unset)
+    pub max_visible_tiles: Option<u32>,
+    // Number of rows or columns added or removed by each zoom
+    pub zoom_step: u32,
+    // Whether to draw a small overview of the whole map in the corner of the screen
+    pub show_minimap: bool,
+    // Whether to briefly show which part of the map is visible along the right and bottom edges of
+    // the screen after it moves
+    pub show_scroll_indicator: bool,
+    // How many tiles away from the cursor fog of war is cleared
+    pub sight_range: u32,
+    // Whether scaled images are smoothed; pixel art looks better without smoothing
+    pub image_smoothing: bool,
+    // Minimum time in milliseconds between pans caused by holding the mouse at the edge of the
+    // screen, when the mouse is just inside the edge zone (it pans faster closer to the edge)
+    pub mouse_pan_delay_ms: u64,
+    // Width of the zone along the edges of the screen where the mouse pans, in tiles (limited to a
+    // quarter of the screen)
+    pub mouse_pan_zone: f64,
+    // The infobar's height is the screen height divided by this
+    pub infobar_screen_fraction: u32,
+    // The infobar's width is its height multiplied by this
+    pub infobar_aspect_ratio: u32,
+    // Number of tiles the cursor moves at a time while the fast move modifier is held
+    pub fast_move_stride: u32,
+    // Whether to show a panel with information about the unit under the cursor
+    pub show_unit_panel: bool,
+    // The unit panel's width is the screen width divided by this
+    pub unit_panel_screen_fraction: u32,
+    // Path of the icon drawn on a unit's tile for each status (statuses that aren't listed are
+    // shown with a plain marker)
+    pub status_icons: std::collections::HashMap<serialization::Status, String>,
+    // Color that each team's unit sprites are tinted with (teams that aren't listed aren't tinted)
+    pub team_colors: std::collections::HashMap<String, style::Color>,
+    // Path of the sound effect played for each signal (signals that aren't listed are silent)
+    pub sounds: std::collections::HashMap<sound::GameSignal, String>,
+    // Least serious level of message that's logged (the platform may override it)
+    pub log_level: log::Level,
+    // Locales that the player can switch the game's text to
+    pub locales: Vec<String>,
+    // How tiles are scaled to fill the screen
+    pub scale_mode: ScaleMode,
+    // Color of the margins around the tiles when they're scaled by whole numbers of pixels
+    pub letterbox_color: style::Color,
+    // Color of the grid lines and the row and column numbers shown with them
+    pub grid_color: style::Color,
+    // Whether the grid is shown when the game starts
+    pub show_grid: bool,
+    // Whether row and column numbers are shown along the top and left of the screen with the grid
+    pub grid_labels: bool,
+    // Width and height in pixels of each tile in images of the whole map
+    pub export_tile_size: u32,
+    // Whether to show a bar along the bottom of the screen listing the keys for what can be done
+    pub show_input_hints: bool,
+    // Time in milliseconds that an attacker takes to lunge toward its target and back in a fight
+    pub combat_nudge_ms: u64,
+    // Time in milliseconds that the damage from a fight takes to float away while hp bars drain
+    // (fights are settled straight away if this and combat_nudge_ms are both 0)
+    pub combat_damage_ms: u64,
+    // Whether units can move through tiles held by their allies (they can never stop on them, or
+    // move through enemies)
+    pub pass_through_allies: bool,
+    // Teams that take turns in this order; with more than one, players take turns on the same
+    // machine
+    pub players: Vec<String>,
+    // Whether the map is hidden between turns of different players while fog of war is on, so that
+    // they don't see each other's units
+    pub fog_handoff: bool,
+    // Address of the server that passes moves between this game and another player's, e.g. a
+    // WebSocket URL (the game is played on one machine if unset)
+    pub remote_url: Option<String>,
+    // Teams played on the other player's machine, which take their turns there
+    pub remote_teams: Vec<String>,
+    // Whether the game is saved automatically after every turn
+    pub autosave: bool,
+    // Shortest time in milliseconds between autosaves; turns that end sooner after the last one
+    // are saved together once it's up
+    pub autosave_interval_ms: u64,
+}
+
+// How tiles are scaled to fill the screen
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScaleMode {
+    // Tiles stretch to fill the whole screen, which may leave them a fraction of a pixel in size
+    Stretch,
+    // Tiles are the largest whole number of pixels that fits, centered with the rest of the screen
+    // letterboxed. Tiles can't show seams between them this way
+    Integer,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            min_visible_tiles: 4,
+            max_visible_tiles: None,
+            zoom_step: 1,
+            show_minimap: true,
+            show_scroll_indicator: true,
+            sight_range: 3,
+            image_smoothing: true,
+            mouse_pan_delay_ms: 100,
+            mouse_pan_zone: 0.5,
+            infobar_screen_fraction: 15,
+            infobar_aspect_ratio: 4,
+            fast_move_stride: 5,
+            show_unit_panel: true,
+            unit_panel_screen_fraction: 4,
+            status_icons: std::collections::HashMap::new(),
+            team_colors: default_team_colors(),
+            sounds: std::collections::HashMap::new(),
+            log_level: log::Level::Info,
+            locales: vec!["us".to_owned()],
+            scale_mode: ScaleMode::Stretch,
+            letterbox_color: style::BLACK,
+            grid_color: style::Color {
+                alpha: 160,
+                ..style::BLACK
+            },
+            show_grid: false,
+            grid_labels: true,
+            export_tile_size: 32,
+            show_input_hints: true,
+            combat_nudge_ms: 150,
+            combat_damage_ms: 600,
+            pass_through_allies: true,
+            players: vec![serialization::PLAYER_TEAM.to_owned()],
+            fog_handoff: true,
+            remote_url: None,
+            remote_teams: Vec::new(),
+            autosave: true,
+            autosave_interval_ms: 30_000,
+        }
+    }
+}
+
+fn default_team_colors() -> std::collections::HashMap<String, style::Color> {
+    let tint = |color: style::Color| style::Color { alpha: 96, ..color };
+    std::collections::HashMap::from([
+        (
+            serialization::PLAYER_TEAM.to_owned(),
+            tint(style::Color::rgb(40, 100, 255)),
+        ),
+        ("enemy".to_owned(), tint(style::Color::rgb(255, 40, 40))),
+    ])
+}
+
+// Retrieves the settings file, falling back to the default settings if it can't be loaded
+pub async fn load<P: Platform>(platform: &P, manifest: &fetch::Manifest) -> Settings {
+    let mut file = match fetch::fetch_with_retry(platform, manifest, SETTINGS_PATH).await {
+        Ok(f) => f,
+        Err(e) => {
+            log::warn::<P>(format!("Using default settings: {}", e).as_str());
+            return Settings::default();
+        }
+    };
+    let decoded = serde_json::from_reader(&mut file);
+    if let Err(e) = file.finish() {
+        log::warn::<P>(format!("Using default settings: {}", e).as_str());
+        return Settings::default();
+    }
+    decoded.unwrap_or_else(|e| {
+        log::warn::<P>(
+            format!("Using default settings: invalid {}: {}", SETTINGS_PATH, e).as_str(),
+        );
+        Settings::default()
+    })
+}
@@ -0,0 +1,66 @@
+use crate::{log, Platform};
+
+// Key under which the sound settings are stored by the platform
+pub const STORAGE_KEY: &str = "sound";
+// Loudest volume
+pub const MAX_VOLUME: u32 = 100;
+
+// Things that happen in the game that may be accompanied by a sound effect
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum GameSignal {
+    CursorMoved,
+    MenuOpened,
+    SelectionConfirmed,
+    // Something the player asked for couldn't be done
+    Error,
+}
+
+// Serialized format for the player's sound settings
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct SoundSettings {
+    // From 0 (silent) to MAX_VOLUME
+    pub volume: u32,
+    // Silences everything without forgetting the volume
+    pub muted: bool,
+}
+
+impl SoundSettings {
+    // Gets the volume that sound should actually be played at
+    pub fn effective_volume(&self) -> u32 {
+        if self.muted {
+            0
+        } else {
+            self.volume.min(MAX_VOLUME)
+        }
+    }
+}
+
+impl Default for SoundSettings {
+    fn default() -> SoundSettings {
+        SoundSettings {
+            volume: MAX_VOLUME,
+            muted: false,
+        }
+    }
+}
+
+// Retrieves the sound settings saved by the player, falling back to the defaults
+pub fn load<P: Platform>(platform: &P) -> SoundSettings {
+    let stored = match platform.retrieve(STORAGE_KEY) {
+        Some(s) => s,
+        None => return SoundSettings::default(),
+    };
+    serde_json::from_str(stored.as_str()).unwrap_or_else(|e| {
+        log::warn::<P>(format!("Ignoring saved sound settings: {}", e).as_str());
+        SoundSettings::default()
+    })
+}
+
+// Saves the sound settings so that they're used by later games
+pub fn save<P: Platform>(platform: &P, sound: &SoundSettings) {
+    match serde_json::to_string(sound) {
+        Ok(serialized) => platform.store(STORAGE_KEY, serialized.as_str()),
+        Err(e) => log::warn::<P>(format!("Failed to save sound settings: {}", e).as_str()),
+    }
+}
@@ -0,0 +1,176 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{fetch, log, text, Platform};
+
+// Text used for keys that the strings file doesn't have. Keys from map files (tile, unit and
+// speaker names and dialog) aren't built in, since they differ from map to map
+const BUILT_IN: [(&str, &str); 69] = [
+    ("text.direction", "ltr"),
+    ("menu.end_turn", "End Turn"),
+    ("menu.save", "Save"),
+    ("menu.load", "Load"),
+    ("menu.options", "Options"),
+    ("menu.wait", "Wait"),
+    ("menu.attack", "Attack {}"),
+    ("menu.brush", "Brush {}x{}"),
+    ("menu.fill", "Fill"),
+    ("menu.save_map", "Save Map File"),
+    ("option.language", "Language: {}"),
+    ("option.volume", "Volume: {}"),
+    ("option.smoothing", "Image Smoothing: {}"),
+    ("option.pan_delay", "Edge Pan Delay: {} ms"),
+    ("option.grid", "Grid: {}"),
+    ("option.on", "On"),
+    ("option.off", "Off"),
+    ("save.slot", "Slot {}: {}"),
+    ("save.empty", "Empty"),
+    ("save.incompatible", "Incompatible"),
+    ("save.unreadable", "Unreadable"),
+    ("save.saved", "Saved"),
+    ("save.autosave", "Autosave: {}"),
+    ("stat.hp", "HP"),
+    ("stat.strength", "Strength"),
+    ("stat.defense", "Defense"),
+    ("stat.speed", "Speed"),
+    ("stat.move", "Move"),
+    ("stat.gain", "{} +{}"),
+    ("forecast.damage", "Damage"),
+    ("forecast.hits", "Hits"),
+    ("forecast.terrain", "Terrain"),
+    ("level_up.heading", "{} reached level {}"),
+    ("infobar.hidden", "???"),
+    ("infobar.missing_image", "missing: {}"),
+    ("cursor.path_cost", "{} ({} left)"),
+    ("hint.move", "Move"),
+    ("hint.select", "Select"),
+    ("hint.menu", "Menu"),
+    ("hint.zoom", "Zoom"),
+    ("hint.choose", "Choose"),
+    ("hint.back", "Back"),
+    ("hint.next", "Next"),
+    ("hint.pause", "Pause"),
+    ("hint.step", "Step"),
+    ("hint.speed", "Speed"),
+    ("replay.playing", "Event {} of {} at {}x"),
+    ("replay.paused", "Paused at event {} of {}"),
+    ("replay.over", "Replay over: {} of {} events"),
+    ("message.goodbye", "Goodbye"),
+    ("message.map_complete", "Map complete"),
+    ("message.campaign_complete", "Campaign complete"),
+    ("message.rebind", "Press a key for {}"),
+    ("toast.screenshot", "Screenshot saved"),
+    ("toast.map_exported", "Map exported"),
+    ("toast.position_copied", "Position copied"),
+    ("toast.editor_on", "Editor on"),
+    ("toast.editor_off", "Editor off"),
+    ("toast.map_saved", "Map file saved"),
+    ("toast.bookmark_set", "Bookmark {} set"),
+    ("banner.turn", "{} Phase"),
+    ("team.player", "Player"),
+    ("team.enemy", "Enemy"),
+    (
+        "banner.disconnected",
+        "Connection lost: Select to reconnect",
+    ),
+    ("error.start", "{}"),
+    ("error.load_save", "Failed to load saved game: {}"),
+    ("error.reload_map", "Failed to reload map: {}"),
+    ("error.next_map", "Failed to load the next map: {}"),
+    ("locale.us", "English"),
+];
+
+// Gets the path of the strings file for a locale
+pub fn default_path(locale: &str) -> String {
+    format!("strings/{}.json", locale)
+}
+
+// Display text for each string key in the player's locale
+pub struct Strings {
+    table: HashMap<String, String>,
+    // Keys that have been warned about having no text, so that each is only warned about once
+    // rather than every time it's drawn
+    missing: std::cell::RefCell<HashSet<String>>,
+}
+
+impl Default for Strings {
+    fn default() -> Strings {
+        Strings::new(HashMap::new())
+    }
+}
+
+impl Strings {
+    // Uses a strings file's text, falling back to the built in text for keys it doesn't have
+    pub fn new(file: HashMap<String, String>) -> Strings {
+        let mut table: HashMap<_, _> = BUILT_IN
+            .iter()
+            .map(|&(key, text)| (key.to_owned(), text.to_owned()))
+            .collect();
+        table.extend(file);
+        Strings {
+            table,
+            missing: std::cell::RefCell::new(HashSet::new()),
+        }
+    }
+
+    // Gets the text for a key, or None if there isn't any
+    pub fn lookup(&self, key: &str) -> Option<&str> {
+        self.table.get(key).map(String::as_str)
+    }
+
+    // Gets the direction that the locale's text is read in
+    pub fn direction(&self) -> text::Direction {
+        self.lookup("text.direction")
+            .and_then(text::Direction::parse)
+            .unwrap_or(text::Direction::LeftToRight)
+    }
+
+    // Gets the text for a key. Keys without any text are shown as they are, with a warning
+    pub fn get<'a, P: Platform>(&'a self, key: &'a str) -> &'a str {
+        match self.lookup(key) {
+            Some(text) => text,
+            None => {
+                if self.missing.borrow_mut().insert(key.to_owned()) {
+                    log::warn::<P>(format!("No text for string key {}", key).as_str());
+                }
+                key
+            }
+        }
+    }
+
+    // Gets the text for a key with each {} in it replaced by the next argument
+    pub fn format<P: Platform>(&self, key: &str, args: &[&str]) -> String {
+        let mut args = args.iter();
+        let mut text = String::new();
+        for (i, piece) in self.get::<P>(key).split("{}").enumerate() {
+            if i > 0 {
+                text.push_str(args.next().copied().unwrap_or("{}"));
+            }
+            text.push_str(piece);
+        }
+        text
+    }
+}
+
+// Retrieves the strings file for a locale, falling back to the built in text if it can't be loaded
+pub async fn load<P: Platform>(platform: &P, manifest: &fetch::Manifest, locale: &str) -> Strings {
+    let path = default_path(locale);
+    let mut file = match fetch::fetch_with_retry(platform, manifest, path.as_str()).await {
+        Ok(f) => f,
+        Err(e) => {
+            log::warn::<P>(format!("Using built in text: {}", e).as_str());
+            return Strings::default();
+        }
+    };
+    let decoded = serde_json::from_reader(&mut file);
+    if let Err(e) = file.finish() {
+        log::warn::<P>(format!("Using built in text: {}", e).as_str());
+        return Strings::default();
+    }
+    match decoded {
+        Ok(table) => Strings::new(table),
+        Err(e) => {
+            log::warn::<P>(format!("Using built in text: invalid {}: {}", path, e).as_str());
+            Strings::default()
+        }
+    }
+}
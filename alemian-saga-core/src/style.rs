@@ -0,0 +1,340 @@
+// Color in RGBA form
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub struct Color {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    // Opacity, from fully transparent (0) to fully opaque (255)
+    #[serde(default = "opaque")]
+    pub alpha: u8,
+}
+
+fn opaque() -> u8 {
+    u8::MAX
+}
+
+impl Color {
+    // Creates a fully opaque color
+    pub const fn rgb(red: u8, green: u8, blue: u8) -> Color {
+        Color {
+            red,
+            green,
+            blue,
+            alpha: u8::MAX,
+        }
+    }
+
+    // Formats the color as a CSS color
+    pub fn to_css(self) -> String {
+        format!(
+            "rgba({}, {}, {}, {})",
+            self.red,
+            self.green,
+            self.blue,
+            self.alpha as f64 / 255.0
+        )
+    }
+}
+
+pub const BLACK: Color = Color::rgb(0, 0, 0);
+pub const WHITE: Color = Color::rgb(255, 255, 255);
+pub const DARK_RED: Color = Color::rgb(160, 16, 16);
+// Drawn in a checkerboard with black in place of images that failed to load
+pub const MISSING_IMAGE: Color = Color::rgb(255, 0, 255);
+
+// Reasons for tinting a tile
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HighlightKind {
+    // Tiles the player has picked
+    Selection,
+    // Tiles a unit can move to
+    Movement,
+    // Tiles a unit can attack
+    Attack,
+}
+
+impl HighlightKind {
+    // Translucent color drawn over tiles with this highlight
+    pub fn color(self) -> Color {
+        match self {
+            HighlightKind::Selection => Color {
+                alpha: 112,
+                ..Color::rgb(255, 230, 80)
+            },
+            HighlightKind::Movement => Color {
+                alpha: 96,
+                ..Color::rgb(40, 100, 255)
+            },
+            HighlightKind::Attack => Color {
+                alpha: 96,
+                ..Color::rgb(255, 40, 40)
+            },
+        }
+    }
+}
+
+// Horizontal placement of text within the space it's given, for text read left to right
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Alignment {
+    Left,
+    Center,
+    Right,
+}
+
+// Describes how a piece of text should look
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextStyle {
+    // Font size as a multiple of the platform's normal font size
+    pub size: f64,
+    pub color: Color,
+    pub alignment: Alignment,
+    pub bold: bool,
+}
+
+// Names of things shown on the infobar, like the tile name
+pub const INFOBAR_LABEL: TextStyle = TextStyle {
+    size: 1.0,
+    color: BLACK,
+    alignment: Alignment::Left,
+    bold: true,
+};
+
+// Numbers shown on the infobar, like tile stats and coordinates
+pub const INFOBAR_VALUE: TextStyle = TextStyle {
+    size: 0.8,
+    color: BLACK,
+    alignment: Alignment::Center,
+    bold: false,
+};
+
+// Shown on the second line of the infobar instead of the tile stats when the tile's image is missing
+pub const INFOBAR_WARNING: TextStyle = TextStyle {
+    size: 0.8,
+    color: DARK_RED,
+    alignment: Alignment::Left,
+    bold: false,
+};
+
+// Messages shown while the game is starting up
+pub const LOADING_SCREEN: TextStyle = TextStyle {
+    size: 2.0,
+    color: WHITE,
+    alignment: Alignment::Center,
+    bold: false,
+};
+
+// Options listed in a menu
+pub const MENU_ITEM: TextStyle = TextStyle {
+    size: 1.0,
+    color: WHITE,
+    alignment: Alignment::Left,
+    bold: false,
+};
+
+// Panel that menu options are drawn on
+pub const MENU_BACKGROUND: Color = Color::rgb(32, 32, 48);
+// Bar drawn behind the highlighted menu option
+pub const MENU_HIGHLIGHT: Color = Color::rgb(72, 72, 120);
+
+// Text shown in dialog boxes
+pub const DIALOG_TEXT: TextStyle = TextStyle {
+    size: 1.0,
+    color: WHITE,
+    alignment: Alignment::Left,
+    bold: false,
+};
+
+// Name of whoever is speaking in a dialog box
+pub const DIALOG_SPEAKER: TextStyle = TextStyle {
+    bold: true,
+    ..DIALOG_TEXT
+};
+
+// Panel that dialog text is drawn on
+pub const DIALOG_BACKGROUND: Color = Color::rgb(24, 24, 32);
+
+// Name of the tile under the mouse shown in the tooltip
+pub const TOOLTIP_TEXT: TextStyle = TextStyle {
+    size: 0.8,
+    color: WHITE,
+    alignment: Alignment::Left,
+    bold: false,
+};
+
+// Panel that tooltip text is drawn on
+pub const TOOLTIP_BACKGROUND: Color = Color {
+    alpha: 224,
+    ..Color::rgb(16, 16, 16)
+};
+
+// Short confirmation shown at the top of the screen, e.g. after a screenshot is saved
+pub const TOAST_TEXT: TextStyle = TextStyle {
+    size: 0.8,
+    color: WHITE,
+    alignment: Alignment::Center,
+    bold: false,
+};
+
+// Panel that toast text is drawn on
+pub const TOAST_BACKGROUND: Color = TOOLTIP_BACKGROUND;
+
+// Banner announcing whose turn it is, or that the connection to the other player was lost
+pub const BANNER_TEXT: TextStyle = TextStyle {
+    size: 1.0,
+    color: WHITE,
+    alignment: Alignment::Center,
+    bold: true,
+};
+
+// Panel that a banner's text is drawn on
+pub const BANNER_BACKGROUND: Color = Color::rgb(24, 24, 48);
+
+// Covers the whole map between turns so that the next player doesn't see the last player's units
+pub const HANDOFF_BACKGROUND: Color = BLACK;
+
+// Bars along the right and bottom edges of the screen showing which part of the map is visible, at
+// their most opaque
+pub const SCROLL_INDICATOR: Color = Color {
+    alpha: 112,
+    ..WHITE
+};
+
+// Keys listed on the hint bar along with what they do
+pub const HINT_TEXT: TextStyle = TextStyle {
+    size: 0.7,
+    color: WHITE,
+    alignment: Alignment::Left,
+    bold: false,
+};
+
+// Bar along the bottom of the screen that hints are drawn on
+pub const HINT_BACKGROUND: Color = TOOLTIP_BACKGROUND;
+
+// How far a replay has got, along with the keys that control it
+pub const TRANSPORT_TEXT: TextStyle = HINT_TEXT;
+
+// Bar along the top of the screen that a replay's progress is drawn on
+pub const TRANSPORT_BACKGROUND: Color = BANNER_BACKGROUND;
+
+// Name of the unit under the cursor shown on the unit panel
+pub const UNIT_PANEL_NAME: TextStyle = TextStyle {
+    size: 1.0,
+    color: WHITE,
+    alignment: Alignment::Left,
+    bold: true,
+};
+
+// Stat names and values shown on the unit panel
+pub const UNIT_PANEL_TEXT: TextStyle = TextStyle {
+    size: 0.8,
+    color: WHITE,
+    alignment: Alignment::Left,
+    bold: false,
+};
+
+// Panel that information about the unit under the cursor is drawn on
+pub const UNIT_PANEL_BACKGROUND: Color = Color::rgb(24, 24, 32);
+// Names of the units on either side of the battle forecast
+pub const FORECAST_NAME: TextStyle = TextStyle {
+    size: 1.0,
+    color: WHITE,
+    alignment: Alignment::Left,
+    bold: true,
+};
+
+// Figures shown on the battle forecast
+pub const FORECAST_TEXT: TextStyle = TextStyle {
+    size: 0.8,
+    color: WHITE,
+    alignment: Alignment::Left,
+    bold: false,
+};
+
+// Panel comparing the two sides of an attack before it's confirmed
+pub const FORECAST_BACKGROUND: Color = Color::rgb(40, 24, 24);
+
+// Heading of the popup shown when a unit goes up a level
+pub const LEVEL_UP_TITLE: TextStyle = TextStyle {
+    size: 1.0,
+    color: WHITE,
+    alignment: Alignment::Left,
+    bold: true,
+};
+
+// Stat increases listed on the level up popup
+pub const LEVEL_UP_TEXT: TextStyle = TextStyle {
+    size: 0.8,
+    color: WHITE,
+    alignment: Alignment::Left,
+    bold: false,
+};
+
+pub const LEVEL_UP_BACKGROUND: Color = Color::rgb(32, 24, 56);
+// Parts of hp bars standing for the hp a unit has left, depending on how much it has left
+pub const HP_BAR_HEALTHY: Color = Color::rgb(64, 200, 64);
+pub const HP_BAR_WOUNDED: Color = Color::rgb(230, 200, 40);
+pub const HP_BAR_CRITICAL: Color = Color::rgb(220, 40, 40);
+// Part of hp bars standing for the hp a unit has lost
+pub const HP_BAR_EMPTY: Color = Color::rgb(40, 40, 40);
+// Drawn in the corner of a unit's tile for statuses that don't have an icon
+pub const STATUS_MARKER: Color = Color::rgb(200, 200, 200);
+// Row and column numbers shown along the edges of the screen with the grid. The color comes from
+// the settings
+pub const GRID_LABEL: TextStyle = TextStyle {
+    size: 0.6,
+    color: BLACK,
+    alignment: Alignment::Center,
+    bold: false,
+};
+// Label on the cursor's tile with the cost of moving the selected unit there
+pub const PATH_COST_TEXT: TextStyle = TextStyle {
+    size: 0.6,
+    color: WHITE,
+    alignment: Alignment::Center,
+    bold: false,
+};
+pub const PATH_COST_BACKGROUND: Color = TOOLTIP_BACKGROUND;
+// Damage that a unit took in a fight, floating up from its tile. It fades away from this color
+pub const DAMAGE_NUMBER: TextStyle = TextStyle {
+    size: 1.0,
+    color: Color::rgb(255, 230, 80),
+    alignment: Alignment::Center,
+    bold: true,
+};
+// Drawn over tiles that an enemy could attack on its next turn, underneath any highlight
+pub const DANGER_ZONE: Color = Color {
+    alpha: 80,
+    ..Color::rgb(170, 0, 40)
+};
+
+// Gets the color of the filled part of a hp bar: healthy above half hp, wounded above a quarter
+// and critical otherwise
+pub fn hp_bar_color(hp: u32, max_hp: u32) -> Color {
+    let (hp, max_hp) = (hp as u64 * 4, max_hp as u64);
+    if hp > max_hp * 2 {
+        HP_BAR_HEALTHY
+    } else if hp > max_hp {
+        HP_BAR_WOUNDED
+    } else {
+        HP_BAR_CRITICAL
+    }
+}
+
+// Frame stats shown on the debug overlay
+pub const DEBUG_OVERLAY_TEXT: TextStyle = TextStyle {
+    size: 0.8,
+    color: Color::rgb(80, 255, 80),
+    alignment: Alignment::Left,
+    bold: false,
+};
+
+// Panel that the debug overlay is drawn on
+pub const DEBUG_OVERLAY_BACKGROUND: Color = BLACK;
+
+// Errors that prevent the game from continuing
+pub const ERROR_BANNER: TextStyle = TextStyle {
+    size: 1.25,
+    color: DARK_RED,
+    alignment: Alignment::Center,
+    bold: true,
+};
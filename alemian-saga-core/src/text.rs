@@ -0,0 +1,99 @@
+use crate::{style, Platform, Rectangle, Vector};
+use unicode_segmentation::UnicodeSegmentation;
+
+// Direction that a locale's text is read in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    LeftToRight,
+    RightToLeft,
+}
+
+impl Direction {
+    // Gets the direction with a name as written in strings files ("ltr" or "rtl")
+    pub fn parse(name: &str) -> Option<Direction> {
+        match name {
+            "ltr" => Some(Direction::LeftToRight),
+            "rtl" => Some(Direction::RightToLeft),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Direction::LeftToRight => "ltr",
+            Direction::RightToLeft => "rtl",
+        }
+    }
+}
+
+// Gets which side of its space text is placed on. Styles are written for text read left to right,
+// so right-to-left text is mirrored to start from the right edge instead
+pub fn align(alignment: style::Alignment, direction: Direction) -> style::Alignment {
+    match (alignment, direction) {
+        (style::Alignment::Left, Direction::RightToLeft) => style::Alignment::Right,
+        (style::Alignment::Right, Direction::RightToLeft) => style::Alignment::Left,
+        (a, _) => a,
+    }
+}
+
+// Gets where text ends up when drawn in the max_width wide space to the right of offset. Text
+// wider than its space is squeezed to fit it, and the height is 0 if the platform can't tell it
+pub fn layout<P: Platform>(
+    platform: &P,
+    text: &str,
+    offset: Vector<P::ScreenDistance>,
+    max_width: P::ScreenDistance,
+    style: &style::TextStyle,
+    direction: Direction,
+) -> Rectangle<P::ScreenDistance> {
+    let measured = platform.measure_text(text, style);
+    let width = if measured < max_width {
+        measured
+    } else {
+        max_width
+    };
+    let x = match align(style.alignment, direction) {
+        style::Alignment::Left => offset.x,
+        style::Alignment::Center => offset.x + (max_width - width) / 2.into(),
+        style::Alignment::Right => offset.x + max_width - width,
+    };
+    let height = platform.get_text_height(style).unwrap_or_else(|| 0.into());
+    Rectangle {
+        top_left: Vector { x, y: offset.y },
+        size: Vector {
+            x: width,
+            y: height,
+        },
+    }
+}
+
+// Shortens text with an ellipsis until it fits within max_width. Text is only ever cut between
+// grapheme clusters so that accents and the like aren't split from the letters they belong to
+pub fn fit<P: Platform>(
+    platform: &P,
+    text: &str,
+    max_width: P::ScreenDistance,
+    style: &style::TextStyle,
+) -> String {
+    if platform.measure_text(text, style) <= max_width {
+        return text.to_owned();
+    }
+    let mut starts = Vec::new();
+    let mut start = 0;
+    for grapheme in graphemes(text) {
+        starts.push(start);
+        start += grapheme.len();
+    }
+    for &end in starts.iter().rev() {
+        let shortened = format!("{}…", text[..end].trim_end());
+        if platform.measure_text(shortened.as_str(), style) <= max_width {
+            return shortened;
+        }
+    }
+    "…".to_owned()
+}
+
+// Splits text into extended grapheme clusters: the pieces that a reader sees as single characters
+pub fn graphemes(text: &str) -> unicode_segmentation::Graphemes<'_> {
+    text.graphemes(true)
+}
@@ -0,0 +1,121 @@
+use crate::game::MapDistance;
+use crate::{Rectangle, Vector};
+
+// Part of the map that's drawn in the viewport. The screen can show more columns or rows than the
+// map has (along either axis or both) and can hang off the edge of the map mid-scroll, so only the
+// tiles that exist are drawn and the rest of the viewport is left uncovered
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VisibleRegion {
+    // First column and row that are drawn, and the column and row after the last ones. Both are
+    // within the map, and start equals end along an axis where nothing is drawn
+    pub start: Vector<MapDistance>,
+    pub end: Vector<MapDistance>,
+    // Where the top left corner of the start tile is drawn relative to the top left corner of the
+    // viewport (negative when the tile hangs off the viewport mid-scroll)
+    pub offset: Vector<f64>,
+    pub tile_size: Vector<f64>,
+}
+
+impl VisibleRegion {
+    // Works out what's drawn of a map when the screen shows the columns and rows in screen, drawn
+    // scroll_offset tiles away from where it's headed
+    pub fn new(
+        screen: Rectangle<MapDistance>,
+        scroll_offset: Vector<f64>,
+        map_size: Vector<MapDistance>,
+        tile_size: Vector<f64>,
+    ) -> VisibleRegion {
+        // Gets the start, end and offset along one axis
+        let axis = |first: MapDistance, count: MapDistance, scroll: f64, map: MapDistance| {
+            let first = first as f64 - scroll;
+            let map = map as f64;
+            let start = first.floor().max(0.0).min(map);
+            let end = (first + count as f64).ceil().min(map).max(start);
+            (start as MapDistance, end as MapDistance, start - first)
+        };
+        let (start_x, end_x, offset_x) =
+            axis(screen.left(), screen.width(), scroll_offset.x, map_size.x);
+        let (start_y, end_y, offset_y) =
+            axis(screen.top(), screen.height(), scroll_offset.y, map_size.y);
+        VisibleRegion {
+            start: Vector {
+                x: start_x,
+                y: start_y,
+            },
+            end: Vector { x: end_x, y: end_y },
+            offset: Vector {
+                x: offset_x * tile_size.x,
+                y: offset_y * tile_size.y,
+            },
+            tile_size,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start.x >= self.end.x || self.start.y >= self.end.y
+    }
+
+    pub fn contains(&self, pos: Vector<MapDistance>) -> bool {
+        pos.x >= self.start.x && pos.y >= self.start.y && pos.x < self.end.x && pos.y < self.end.y
+    }
+
+    // Gets every map position in the region, a row at a time
+    pub fn positions(&self) -> impl Iterator<Item = Vector<MapDistance>> {
+        let (start, end) = (self.start, self.end);
+        (start.y..end.y).flat_map(move |y| (start.x..end.x).map(move |x| Vector { x, y }))
+    }
+
+    // Gets where the top left corner of a tile is drawn relative to the top left corner of the
+    // viewport. Tiles outside of the region are placed where they would be if the screen were
+    // bigger
+    pub fn tile_offset(&self, pos: Vector<MapDistance>) -> Vector<f64> {
+        Vector {
+            x: self.offset.x + (pos.x as f64 - self.start.x as f64) * self.tile_size.x,
+            y: self.offset.y + (pos.y as f64 - self.start.y as f64) * self.tile_size.y,
+        }
+    }
+
+    // Gets the part of the viewport that tiles are drawn over
+    pub fn bounds(&self) -> Rectangle<f64> {
+        Rectangle {
+            top_left: self.offset,
+            size: Vector {
+                x: (self.end.x - self.start.x) as f64 * self.tile_size.x,
+                y: (self.end.y - self.start.y) as f64 * self.tile_size.y,
+            },
+        }
+    }
+
+    // Gets the tile drawn under a point relative to the top left corner of the viewport, or None
+    // if no tile is drawn there
+    pub fn tile_at(&self, point: Vector<f64>) -> Option<Vector<MapDistance>> {
+        let axis = |point: f64, offset: f64, tile: f64, start: MapDistance, end: MapDistance| {
+            if tile.is_nan() || tile <= 0.0 {
+                return None;
+            }
+            let steps = ((point - offset) / tile).floor();
+            let pos = start as f64 + steps;
+            if steps < 0.0 || pos >= end as f64 {
+                None
+            } else {
+                Some(pos as MapDistance)
+            }
+        };
+        Some(Vector {
+            x: axis(
+                point.x,
+                self.offset.x,
+                self.tile_size.x,
+                self.start.x,
+                self.end.x,
+            )?,
+            y: axis(
+                point.y,
+                self.offset.y,
+                self.tile_size.y,
+                self.start.y,
+                self.end.y,
+            )?,
+        })
+    }
+}
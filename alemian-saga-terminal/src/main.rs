@@ -0,0 +1,618 @@
+#![cfg_attr(feature = "strict", deny(warnings))]
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use crossterm::event::{KeyCode, KeyModifiers, MouseEventKind};
+use crossterm::{cursor, event, style, terminal, QueueableCommand};
+use futures::channel::mpsc;
+use futures::SinkExt;
+
+use alemian_saga_core::style::{Color, TextStyle};
+use alemian_saga_core::{log, text, Event, Vector};
+
+const GLYPH_FILE: &str = "glyphs.json";
+const DEFAULT_ASSET_DIRS: [&str; 2] = ["../public", "../dev_utils/generated-files"];
+const LANGUAGE: &str = "english";
+const LOCALE: &str = "us";
+const EVENT_QUEUE_CAPACITY: usize = 8;
+// Environment variable that overrides EVENT_QUEUE_CAPACITY
+const QUEUE_CAPACITY_VAR: &str = "ALEMIAN_SAGA_QUEUE_CAPACITY";
+// Name the web platform uses for the shift key, which the terminal only reports alongside others
+const SHIFT_KEY: &str = "Shift";
+// Directory (relative to the working directory) that stored values are saved in
+const STORAGE_DIR: &str = ".alemian-saga";
+const STATUS_SEPARATOR: &str = "  ";
+const FILL_SYMBOL: char = '█';
+const BASE64_DIGITS: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const MISSING_GLYPH: Glyph = Glyph {
+    symbol: '?',
+    color: style::Color::Magenta,
+    outline: false,
+};
+
+// Messages logged while the terminal is in use; they're printed once the terminal is restored
+static LOG: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+// Serialized format for an entry in the glyph file
+#[derive(serde::Deserialize)]
+struct GlyphInfo {
+    symbol: char,
+    color: String,
+    #[serde(default)]
+    outline: bool,
+}
+
+// Character and color used to represent an image in the terminal
+#[derive(Clone, Copy)]
+struct Glyph {
+    symbol: char,
+    color: style::Color,
+    // Only draw the border of the image's rectangle (used for the cursor)
+    outline: bool,
+}
+
+impl From<GlyphInfo> for Glyph {
+    fn from(info: GlyphInfo) -> Glyph {
+        Glyph {
+            symbol: info.symbol,
+            color: style::Color::try_from(info.color.as_str()).unwrap_or(style::Color::White),
+            outline: info.outline,
+        }
+    }
+}
+
+// Future that completes once a deadline has passed
+struct Sleep {
+    deadline: std::time::Instant,
+    started: bool,
+}
+
+// Implementation of Future trait for Sleep
+impl std::future::Future for Sleep {
+    type Output = ();
+    fn poll(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Self::Output> {
+        let future = self.get_mut();
+        let now = std::time::Instant::now();
+        if now >= future.deadline {
+            std::task::Poll::Ready(())
+        } else {
+            // Wake the waker from another thread once the deadline has passed
+            if !future.started {
+                future.started = true;
+                let waker = cx.waker().clone();
+                let remaining = future.deadline - now;
+                std::thread::spawn(move || {
+                    std::thread::sleep(remaining);
+                    waker.wake();
+                });
+            }
+            std::task::Poll::Pending
+        }
+    }
+}
+
+type TerminalCell = (char, style::Color);
+
+const BLANK_CELL: TerminalCell = (' ', style::Color::Reset);
+
+// Platform type that renders the game as colored characters in a terminal
+struct Terminal {
+    asset_dirs: Vec<std::path::PathBuf>,
+    // Images mapped to None are intentionally not drawn
+    glyphs: HashMap<String, Option<Glyph>>,
+    // Contents of each cell in the map area, used to only write cells that have changed
+    cells: RefCell<Vec<TerminalCell>>,
+    size: Cell<(u16, u16)>,
+    status: RefCell<Vec<String>>,
+    status_stale: Cell<bool>,
+    // Number of events that didn't fit in the event queue, counted by the input thread
+    overflows: Arc<AtomicU64>,
+}
+
+// Constructor and helper functions for the Terminal type
+impl Terminal {
+    fn new(asset_dirs: Vec<std::path::PathBuf>) -> Result<Terminal, String> {
+        let glyph_file = std::fs::File::open(GLYPH_FILE).map_err(|e| e.to_string())?;
+        let glyph_info: HashMap<String, Option<GlyphInfo>> =
+            serde_json::from_reader(std::io::BufReader::new(glyph_file))
+                .map_err(|e| e.to_string())?;
+        let glyphs = glyph_info
+            .into_iter()
+            .map(|(k, v)| (k, v.map(Glyph::from)))
+            .collect();
+        Ok(Terminal {
+            asset_dirs,
+            glyphs,
+            cells: RefCell::new(Vec::new()),
+            size: Cell::new((0, 0)),
+            status: RefCell::new(Vec::new()),
+            status_stale: Cell::new(false),
+            overflows: Arc::new(AtomicU64::new(0)),
+        })
+    }
+
+    // Gets the size of the terminal, minus the last row which is reserved for the status line
+    fn map_area_size() -> (u16, u16) {
+        let (columns, rows) = terminal::size().unwrap_or((80, 24));
+        (columns, rows.saturating_sub(1))
+    }
+
+    // Resets the cell buffer and clears the terminal if the terminal has been resized
+    fn sync_size(&self, out: &mut impl Write) {
+        let size = Self::map_area_size();
+        if size != self.size.get() {
+            self.size.set(size);
+            *self.cells.borrow_mut() = vec![BLANK_CELL; size.0 as usize * size.1 as usize];
+            let _ = out.queue(terminal::Clear(terminal::ClearType::All));
+        }
+    }
+
+    // Gets the current contents of a cell in the map area
+    fn get_cell(&self, x: u32, y: u32) -> Option<TerminalCell> {
+        let (width, height) = self.size.get();
+        if x >= width.into() || y >= height.into() {
+            return None;
+        }
+        Some(self.cells.borrow()[y as usize * width as usize + x as usize])
+    }
+
+    // Writes a cell to the terminal if its contents have changed
+    fn set_cell(&self, out: &mut impl Write, x: u32, y: u32, cell: TerminalCell) {
+        let (width, height) = self.size.get();
+        if x >= width.into() || y >= height.into() {
+            return;
+        }
+        let index = y as usize * width as usize + x as usize;
+        let mut cells = self.cells.borrow_mut();
+        if cells[index] != cell {
+            cells[index] = cell;
+            let _ = out
+                .queue(cursor::MoveTo(x as u16, y as u16))
+                .and_then(|o| o.queue(style::SetForegroundColor(cell.1)))
+                .and_then(|o| o.queue(style::Print(cell.0)));
+        }
+    }
+
+    // Rewrites the status line at the bottom of the terminal
+    fn draw_status(&self, out: &mut impl Write) {
+        let (width, height) = self.size.get();
+        let text = self.status.borrow().join(STATUS_SEPARATOR);
+        let line: String = text
+            .chars()
+            .chain(std::iter::repeat(' '))
+            .take(width as usize)
+            .collect();
+        let _ = out
+            .queue(cursor::MoveTo(0, height))
+            .and_then(|o| o.queue(style::SetForegroundColor(style::Color::Reset)))
+            .and_then(|o| o.queue(style::Print(line)));
+    }
+}
+
+// Implementation of the Platform trait for the Terminal type
+#[async_trait(?Send)]
+impl alemian_saga_core::Platform for Terminal {
+    type Image = String;
+
+    type InputType = String;
+
+    type MouseDistance = u32;
+
+    type ScreenDistance = u32;
+
+    type ImageFuture = std::future::Ready<Option<Self::Image>>;
+
+    type File = std::fs::File;
+
+    type Instant = std::time::Instant;
+
+    type Duration = std::time::Duration;
+
+    type SleepFuture = Sleep;
+
+    type FrameFuture = Sleep;
+
+    fn now() -> Self::Instant {
+        std::time::Instant::now()
+    }
+
+    fn duration_between(first: Self::Instant, second: Self::Instant) -> Self::Duration {
+        second.saturating_duration_since(first)
+    }
+
+    fn instant_after(instant: Self::Instant, duration: Self::Duration) -> Self::Instant {
+        instant + duration
+    }
+
+    fn nanoseconds(ns: u64) -> Self::Duration {
+        std::time::Duration::from_nanos(ns)
+    }
+
+    fn to_nanoseconds(duration: Self::Duration) -> u64 {
+        duration.as_nanos() as u64
+    }
+
+    fn sleep(duration: Self::Duration) -> Self::SleepFuture {
+        Sleep {
+            deadline: std::time::Instant::now() + duration,
+            started: false,
+        }
+    }
+
+    // Terminals have no refresh to wait for, so frames are just spaced out
+    fn request_frame(&self) -> Self::FrameFuture {
+        Self::sleep(Self::nanoseconds(
+            alemian_saga_core::game::FRAME_INTERVAL_NS,
+        ))
+    }
+
+    fn draw_primitive(&self, image: &Self::Image, left: u32, top: u32, width: u32, height: u32) {
+        let glyph = match self.glyphs.get(image) {
+            Some(Some(g)) => *g,
+            Some(None) => return,
+            None => MISSING_GLYPH,
+        };
+        let mut out = std::io::stdout();
+        self.sync_size(&mut out);
+        let right = left + width;
+        let bottom = top + height;
+        for y in top..bottom {
+            for x in left..right {
+                let on_border = x == left || x + 1 == right || y == top || y + 1 == bottom;
+                if on_border || !glyph.outline {
+                    self.set_cell(&mut out, x, y, (glyph.symbol, glyph.color));
+                }
+            }
+        }
+        // Text drawn after an image belongs to a new status message
+        self.status_stale.set(true);
+        let _ = out.flush();
+    }
+
+    fn fill_rect_primitive(&self, left: u32, top: u32, width: u32, height: u32, color: Color) {
+        let mut out = std::io::stdout();
+        self.sync_size(&mut out);
+        let fill_color = style::Color::Rgb {
+            r: color.red,
+            g: color.green,
+            b: color.blue,
+        };
+        for y in top..top + height {
+            for x in left..left + width {
+                // Translucent fills recolor whatever is already in the cell
+                let symbol = match self.get_cell(x, y) {
+                    Some((symbol, _)) if color.alpha < u8::MAX => symbol,
+                    _ => FILL_SYMBOL,
+                };
+                self.set_cell(&mut out, x, y, (symbol, fill_color));
+            }
+        }
+        let _ = out.flush();
+    }
+
+    fn clear(&self) {
+        let mut out = std::io::stdout();
+        self.sync_size(&mut out);
+        let (width, height) = self.size.get();
+        for y in 0..height.into() {
+            for x in 0..width.into() {
+                self.set_cell(&mut out, x, y, BLANK_CELL);
+            }
+        }
+        let _ = out.flush();
+    }
+
+    // Text is written to the status line rather than the infobar, so it never needs to be shortened
+    fn measure_text(&self, _text: &str, _style: &TextStyle) -> u32 {
+        0
+    }
+
+    // Styles are ignored since the status line is plain text
+    fn draw_text_primitive(
+        &self,
+        text: &str,
+        _x: u32,
+        _y: u32,
+        _max_width: u32,
+        _style: &TextStyle,
+        _direction: text::Direction,
+    ) {
+        let mut out = std::io::stdout();
+        self.sync_size(&mut out);
+        if self.status_stale.replace(false) {
+            self.status.borrow_mut().clear();
+        }
+        self.status.borrow_mut().push(text.to_owned());
+        self.draw_status(&mut out);
+        let _ = out.flush();
+    }
+
+    fn get_width(&self) -> u32 {
+        Self::map_area_size().0.into()
+    }
+
+    fn get_height(&self) -> u32 {
+        Self::map_area_size().1.into()
+    }
+
+    fn get_image(&self, path: &str) -> Self::ImageFuture {
+        std::future::ready(Some(path.to_owned()))
+    }
+
+    fn random(&self) -> u64 {
+        // The standard library seeds its hash maps with randomness from the operating system
+        use std::hash::{BuildHasher, Hasher};
+        std::collections::hash_map::RandomState::new()
+            .build_hasher()
+            .finish()
+    }
+
+    fn store(&self, key: &str, value: &str) {
+        let dir = std::path::Path::new(STORAGE_DIR);
+        if let Err(e) =
+            std::fs::create_dir_all(dir).and_then(|_| std::fs::write(dir.join(key), value))
+        {
+            log::warn::<Terminal>(format!("Failed to save {}: {}", key, e).as_str());
+        }
+    }
+
+    fn retrieve(&self, key: &str) -> Option<String> {
+        std::fs::read_to_string(std::path::Path::new(STORAGE_DIR).join(key)).ok()
+    }
+
+    // Sent as an OSC 52 escape sequence, which terminals that support it copy to the system
+    // clipboard and the rest ignore
+    fn event_queue_overflows(&self) -> u64 {
+        self.overflows.load(Ordering::Relaxed)
+    }
+
+    fn set_clipboard(&self, text: &str) {
+        let mut out = std::io::stdout();
+        let _ = write!(out, "\x1b]52;c;{}\x07", base64(text.as_bytes()));
+        let _ = out.flush();
+    }
+
+    // Terminals can't be saved as images, so the map area's characters are saved as text instead
+    fn export_frame(&self, name: &str) {
+        let (width, _) = self.size.get();
+        let text: String = self
+            .cells
+            .borrow()
+            .chunks(usize::from(width).max(1))
+            .flat_map(|row| row.iter().map(|cell| cell.0).chain(std::iter::once('\n')))
+            .collect();
+        let dir = std::path::Path::new(STORAGE_DIR);
+        let file = format!("{}.txt", name);
+        if let Err(e) =
+            std::fs::create_dir_all(dir).and_then(|_| std::fs::write(dir.join(&file), text))
+        {
+            log::warn::<Terminal>(format!("Failed to save {}: {}", file, e).as_str());
+        }
+    }
+
+    fn export_file(&self, name: &str, contents: &[u8]) {
+        let dir = std::path::Path::new(STORAGE_DIR);
+        if let Err(e) =
+            std::fs::create_dir_all(dir).and_then(|_| std::fs::write(dir.join(name), contents))
+        {
+            log::warn::<Terminal>(format!("Failed to save {}: {}", name, e).as_str());
+        }
+    }
+
+    fn timestamp(&self) -> Option<u64> {
+        let since_epoch = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH);
+        since_epoch.ok().map(|d| d.as_secs())
+    }
+
+    async fn get_file(&self, path: &str) -> Result<Self::File, alemian_saga_core::Error> {
+        for dir in self.asset_dirs.iter() {
+            match std::fs::File::open(dir.join(path)) {
+                Ok(f) => return Ok(f),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    return Err(alemian_saga_core::Error::Network {
+                        path: path.to_owned(),
+                        source_msg: e.to_string(),
+                    })
+                }
+            }
+        }
+        Err(alemian_saga_core::Error::MissingAsset(path.to_owned()))
+    }
+
+    fn string_to_input(input: String) -> Self::InputType {
+        input
+    }
+
+    fn log(msg: &str) {
+        if let Ok(mut log) = LOG.lock() {
+            log.push(msg.to_owned());
+        }
+    }
+}
+
+// Encodes bytes as base64, padded with = to a multiple of four digits
+fn base64(bytes: &[u8]) -> String {
+    let mut text = String::new();
+    for chunk in bytes.chunks(3) {
+        let bits = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |bits, (i, &b)| bits | u32::from(b) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                text.push(BASE64_DIGITS[(bits >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
+
+// Converts a key code into the name used for it in the keybindings file
+fn key_name(code: KeyCode) -> Option<String> {
+    let name = match code {
+        KeyCode::Char(c) => return Some(c.to_string()),
+        KeyCode::F(n) => return Some(format!("F{}", n)),
+        KeyCode::Left => "ArrowLeft",
+        KeyCode::Right => "ArrowRight",
+        KeyCode::Up => "ArrowUp",
+        KeyCode::Down => "ArrowDown",
+        KeyCode::Enter => "Enter",
+        KeyCode::Backspace => "Backspace",
+        KeyCode::Home => "Home",
+        KeyCode::End => "End",
+        KeyCode::PageUp => "PageUp",
+        KeyCode::PageDown => "PageDown",
+        KeyCode::Tab => "Tab",
+        KeyCode::Delete => "Delete",
+        KeyCode::Insert => "Insert",
+        _ => return None,
+    };
+    Some(name.to_owned())
+}
+
+// Sends an event to the game, counting it as an overflow if the queue is full. Blocking on the send
+// until there's room keeps events in order. Returns false once the game has stopped
+fn send(
+    event_queue: &mut mpsc::Sender<Event<u32, String>>,
+    overflows: &AtomicU64,
+    event: Event<u32, String>,
+) -> bool {
+    match event_queue.try_send(event) {
+        Ok(()) => true,
+        Err(e) if e.is_full() => {
+            overflows.fetch_add(1, Ordering::Relaxed);
+            futures::executor::block_on(event_queue.send(e.into_inner())).is_ok()
+        }
+        Err(_) => false,
+    }
+}
+
+// Reads terminal input and forwards it to the game until the player quits
+fn forward_input(mut event_queue: mpsc::Sender<Event<u32, String>>, overflows: Arc<AtomicU64>) {
+    while let Ok(e) = event::read() {
+        let game_events = match e {
+            event::Event::Key(key) => {
+                let ctrl_c =
+                    key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+                if ctrl_c || key.code == KeyCode::Esc {
+                    send(&mut event_queue, &overflows, Event::Quit);
+                    break;
+                }
+                let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+                let code = match key.code {
+                    KeyCode::Char(c) if shift => KeyCode::Char(c.to_ascii_lowercase()),
+                    c => c,
+                };
+                match key_name(code) {
+                    // Act as if shift was pressed just for this key
+                    Some(name) if shift => vec![
+                        Event::Input(SHIFT_KEY.to_owned()),
+                        Event::Input(name),
+                        Event::Release(SHIFT_KEY.to_owned()),
+                    ],
+                    name => name.map(Event::Input).into_iter().collect(),
+                }
+            }
+            event::Event::Mouse(mouse) => {
+                let mouse_pos = Vector {
+                    x: mouse.column.into(),
+                    y: mouse.row.into(),
+                };
+                match mouse.kind {
+                    MouseEventKind::Moved => vec![Event::MouseMove(mouse_pos)],
+                    MouseEventKind::Down(event::MouseButton::Left) => vec![Event::Click(mouse_pos)],
+                    MouseEventKind::Down(_) => vec![Event::DragStart(mouse_pos)],
+                    MouseEventKind::Drag(_) => vec![Event::MouseMove(mouse_pos)],
+                    MouseEventKind::Up(_) => vec![Event::DragEnd],
+                    // Terminals have no horizontal wheel, so shift+wheel and alt+wheel scroll instead
+                    MouseEventKind::ScrollUp if mouse.modifiers.contains(KeyModifiers::SHIFT) => {
+                        vec![Event::ScrollHorizontal(-1)]
+                    }
+                    MouseEventKind::ScrollDown if mouse.modifiers.contains(KeyModifiers::SHIFT) => {
+                        vec![Event::ScrollHorizontal(1)]
+                    }
+                    MouseEventKind::ScrollUp if mouse.modifiers.contains(KeyModifiers::ALT) => {
+                        vec![Event::ScrollVertical(-1)]
+                    }
+                    MouseEventKind::ScrollDown if mouse.modifiers.contains(KeyModifiers::ALT) => {
+                        vec![Event::ScrollVertical(1)]
+                    }
+                    MouseEventKind::ScrollUp => vec![Event::ZoomInAt(mouse_pos)],
+                    MouseEventKind::ScrollDown => vec![Event::ZoomOutAt(mouse_pos)],
+                }
+            }
+            event::Event::Resize(_, _) => vec![Event::Redraw],
+        };
+        for ge in game_events {
+            if !send(&mut event_queue, &overflows, ge) {
+                return;
+            }
+        }
+    }
+}
+
+// Switches the terminal into game mode, runs the game, and restores the terminal afterwards
+fn run_game(platform: Terminal, queue_capacity: usize) -> crossterm::Result<()> {
+    let (sender, receiver) = mpsc::channel(queue_capacity);
+    let overflows = platform.overflows.clone();
+    let mut stdout = std::io::stdout();
+    terminal::enable_raw_mode()?;
+    crossterm::execute!(
+        stdout,
+        terminal::EnterAlternateScreen,
+        event::EnableMouseCapture,
+        cursor::Hide
+    )?;
+    std::thread::spawn(move || forward_input(sender, overflows));
+    futures::executor::block_on(alemian_saga_core::run(platform, receiver, LANGUAGE, LOCALE));
+    crossterm::execute!(
+        stdout,
+        style::ResetColor,
+        cursor::Show,
+        event::DisableMouseCapture,
+        terminal::LeaveAlternateScreen
+    )?;
+    terminal::disable_raw_mode()
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let asset_dirs = if args.is_empty() {
+        DEFAULT_ASSET_DIRS
+            .iter()
+            .map(std::path::PathBuf::from)
+            .collect()
+    } else {
+        args.iter().map(std::path::PathBuf::from).collect()
+    };
+    let queue_capacity = std::env::var(QUEUE_CAPACITY_VAR)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(EVENT_QUEUE_CAPACITY);
+    match Terminal::new(asset_dirs) {
+        Ok(platform) => {
+            if let Err(e) = run_game(platform, queue_capacity) {
+                log::error::<Terminal>(e.to_string().as_str());
+            }
+        }
+        Err(e) => log::error::<Terminal>(format!("Failed to load {}: {}", GLYPH_FILE, e).as_str()),
+    }
+    if let Ok(log) = LOG.lock() {
+        for msg in log.iter() {
+            eprintln!("{}", msg);
+        }
+    }
+}
@@ -1,6 +1,5 @@
 #![cfg_attr(feature = "strict", deny(warnings))]
 #![feature(unboxed_closures)]
-#![feature(fn_traits)]
 
 use std::pin;
 use std::task;
@@ -8,23 +7,68 @@ use std::task;
 use async_trait::async_trait;
 use bytes::Buf;
 use futures::channel::mpsc;
-use futures::SinkExt;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 
-use alemian_saga_core::Platform;
+use alemian_saga_core::style::{Alignment, Color, TextStyle};
+use alemian_saga_core::{log, text};
 
 const HOST: &str = "https://alemiansaga.web.app/";
-const FONT: &str = "1.5rem serif";
+const CANVAS_ID: &str = "g";
+const FONT_FAMILY: &str = "serif";
+const FONT_SIZE_REM: f64 = 1.5;
 const LANGUAGE: &str = "english";
 const LOCALE: &str = "us";
 const EVENT_QUEUE_CAPACITY: usize = 8;
+const FETCH_ATTEMPTS: u32 = 5;
+const FETCH_INITIAL_BACKOFF_NS: u64 = 500_000_000;
+const FETCH_TIMEOUT_NS: u64 = 15_000_000_000;
+// Values of MouseEvent.button for the buttons that drag the map
+const MIDDLE_BUTTON: i16 = 1;
+const RIGHT_BUTTON: i16 = 2;
+// Arguments and body of the JavaScript function that draws a batch of images, each with four
+// numbers in rects for where it goes (left, top, width and height)
+const DRAW_BATCH_ARGS: &str = "context, images, rects";
+const DRAW_BATCH_BODY: &str = "for (let i = 0; i < images.length; i++) { \
+    context.drawImage(images[i], rects[4 * i], rects[4 * i + 1], rects[4 * i + 2], \
+    rects[4 * i + 3]); }";
 
-// Entry Point; Construct WebBrowser object and run game
+// Entry Point; Construct WebBrowser object and run game. Pages embedding the game may pass the id
+// of the canvas to draw on, the URL that assets are retrieved relative to, the path of the
+// keybindings file relative to that URL, and how many events are queued up before the rest wait
+// in a backlog; anything left out falls back to the defaults. Each call starts a separate game,
+// which only listens for input on its own canvas
 #[wasm_bindgen]
-pub extern "C" fn start() {
+pub fn start(
+    canvas_id: Option<String>,
+    host: Option<String>,
+    keybindings_path: Option<String>,
+    queue_capacity: Option<usize>,
+) -> GameHandle {
     enable_stack_trace();
-    wasm_bindgen_futures::spawn_local(run_game());
+    let capacity = queue_capacity.unwrap_or(EVENT_QUEUE_CAPACITY);
+    let (event_queue, receiver) = alemian_saga_core::queue::channel(capacity);
+    let handle = GameHandle {
+        event_queue: event_queue.clone(),
+    };
+    let game = run_game(canvas_id, host, keybindings_path, event_queue, receiver);
+    wasm_bindgen_futures::spawn_local(game);
+    handle
+}
+
+// Handle returned to the page for controlling a game that's been started
+#[wasm_bindgen]
+pub struct GameHandle {
+    event_queue: EventSender,
+}
+
+#[wasm_bindgen]
+impl GameHandle {
+    // Stops the game once it's handled the events already sent to it, which removes its event
+    // listeners
+    pub fn stop(&mut self) {
+        self.event_queue.close();
+    }
 }
 
 #[cfg(feature = "stack-trace")]
@@ -35,14 +79,44 @@ fn enable_stack_trace() {
 #[cfg(not(feature = "stack-trace"))]
 fn enable_stack_trace() {}
 
-async fn run_game() {
-    let (sender, receiver) = mpsc::channel(EVENT_QUEUE_CAPACITY);
-    match WebBrowser::new(HOST, sender).await {
-        Some(p) => alemian_saga_core::run(p, receiver, LANGUAGE).await,
-        None => WebBrowser::log("Failed to initialize game state"),
+async fn run_game(
+    canvas_id: Option<String>,
+    host: Option<String>,
+    keybindings_path: Option<String>,
+    event_queue: EventSender,
+    receiver: mpsc::Receiver<alemian_saga_core::Event<i32, String>>,
+) {
+    let canvas_id = canvas_id.as_deref().unwrap_or(CANVAS_ID);
+    let host = host.as_deref().unwrap_or(HOST);
+    match WebBrowser::new(canvas_id, host, keybindings_path, event_queue) {
+        Some(p) => {
+            let start = start_position();
+            alemian_saga_core::run_at(p, receiver, LANGUAGE, LOCALE, start).await
+        }
+        None => log::error::<WebBrowser>("Failed to initialize game state"),
     }
 }
 
+// Gets the position that a shared link starts the game at from the page's fragment, e.g. "#3,4,8"
+// for the cursor at (3, 4) with 8 columns of tiles on the screen
+fn start_position() -> Option<alemian_saga_core::game::ViewPosition> {
+    let fragment = web_sys::window()?.location().hash().ok()?;
+    parse_position(fragment.strip_prefix('#')?)
+}
+
+// Parses a position written as "x,y,zoom" (None if it's written any other way)
+fn parse_position(text: &str) -> Option<alemian_saga_core::game::ViewPosition> {
+    let mut numbers = text.split(',').map(|n| n.trim().parse().ok());
+    let (x, y, zoom) = (numbers.next()??, numbers.next()??, numbers.next()??);
+    if numbers.next().is_some() {
+        return None;
+    }
+    Some(alemian_saga_core::game::ViewPosition {
+        cursor: alemian_saga_core::Vector { x, y },
+        zoom,
+    })
+}
+
 // Future that yields an HtmlImageElement once the element has been fully loaded
 struct LoadedImageElement {
     element: Option<web_sys::HtmlImageElement>,
@@ -74,74 +148,494 @@ impl std::future::Future for LoadedImageElement {
     }
 }
 
-async fn send_async(
-    mut event_queue: mpsc::Sender<alemian_saga_core::Event<i32>>,
-    event: alemian_saga_core::Event<i32>,
-) {
-    let _ = event_queue.feed(event).await;
+// Dropping an image that's still loading (e.g. because a newer map load replaced the one it was
+// part of) stops the download and detaches the onload handler before it's freed
+impl Drop for LoadedImageElement {
+    fn drop(&mut self) {
+        if let Some(e) = self.element.as_ref() {
+            if !e.complete() {
+                e.set_onload(None);
+                e.set_src("");
+            }
+        }
+    }
 }
 
-fn send(
-    event_queue: &mut mpsc::Sender<alemian_saga_core::Event<i32>>,
-    event: alemian_saga_core::Event<i32>,
-) {
-    if let Err(_) = event_queue.try_send(event) {
-        wasm_bindgen_futures::spawn_local(send_async(event_queue.clone(), event));
+// Future that's ready once the browser is about to repaint, so that frames line up with the display
+struct AnimationFrame {
+    // Id of the requested frame along with its callback, or None if it hasn't been requested yet
+    request: Option<(i32, wasm_bindgen::closure::Closure<dyn FnMut()>)>,
+    fired: std::rc::Rc<std::cell::Cell<bool>>,
+}
+
+impl std::future::Future for AnimationFrame {
+    type Output = ();
+    fn poll(self: pin::Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<()> {
+        let future = self.get_mut();
+        if future.fired.get() {
+            return task::Poll::Ready(());
+        }
+        if future.request.is_none() {
+            let waker = cx.waker().clone();
+            let fired = future.fired.clone();
+            let closure = Box::new(move || {
+                fired.set(true);
+                waker.wake_by_ref();
+            }) as Box<dyn FnMut()>;
+            let closure = wasm_bindgen::closure::Closure::wrap(closure);
+            let id = web_sys::window().and_then(|w| {
+                w.request_animation_frame(closure.as_ref().unchecked_ref())
+                    .ok()
+            });
+            match id {
+                Some(id) => future.request = Some((id, closure)),
+                // Draw right away rather than never drawing at all
+                None => return task::Poll::Ready(()),
+            }
+        }
+        task::Poll::Pending
     }
 }
 
+// Frames that are no longer waited for (e.g. because an event came first) are cancelled
+impl Drop for AnimationFrame {
+    fn drop(&mut self) {
+        if let Some((id, _)) = self.request.as_ref() {
+            if !self.fired.get() {
+                if let Some(window) = web_sys::window() {
+                    let _ = window.cancel_animation_frame(*id);
+                }
+            }
+        }
+    }
+}
+
+// Calls back with the canvas's content box, in CSS pixels, whenever its size on the page changes,
+// including when it's first laid out or stops being hidden, neither of which fires a resize event
+// on the window
+struct CanvasResizeObserver {
+    observer: web_sys::ResizeObserver,
+    _callback: wasm_bindgen::closure::Closure<dyn FnMut(js_sys::Array)>,
+}
+
+impl CanvasResizeObserver {
+    fn new(
+        canvas: &web_sys::HtmlCanvasElement,
+        mut callback: impl FnMut(f64, f64) + 'static,
+    ) -> Option<CanvasResizeObserver> {
+        let callback =
+            wasm_bindgen::closure::Closure::wrap(Box::new(move |entries: js_sys::Array| {
+                // Only the canvas is observed, and its latest size is the only one that matters
+                if let Ok(entry) = entries.pop().dyn_into::<web_sys::ResizeObserverEntry>() {
+                    let rect = entry.content_rect();
+                    callback(rect.width(), rect.height());
+                }
+            }) as Box<dyn FnMut(js_sys::Array)>);
+        let observer = web_sys::ResizeObserver::new(callback.as_ref().unchecked_ref()).ok()?;
+        observer.observe(canvas);
+        Some(CanvasResizeObserver {
+            observer,
+            _callback: callback,
+        })
+    }
+}
+
+// The callback can't be called once it's been freed
+impl Drop for CanvasResizeObserver {
+    fn drop(&mut self) {
+        self.observer.disconnect();
+    }
+}
+
+// What a WebSocket's callbacks share with the stream of its messages and with whatever sends on it
+#[derive(Default)]
+struct SocketState {
+    received: std::collections::VecDeque<Vec<u8>>,
+    // Messages sent before the socket opened, which go out once it does
+    unsent: Vec<Vec<u8>>,
+    closed: bool,
+    waker: Option<task::Waker>,
+}
+
+// Wraps a closure so that it can be set as one of a WebSocket's event handlers
+fn socket_handler(
+    callback: impl FnMut(web_sys::Event) + 'static,
+) -> wasm_bindgen::closure::Closure<dyn FnMut(web_sys::Event)> {
+    wasm_bindgen::closure::Closure::wrap(Box::new(callback) as Box<dyn FnMut(web_sys::Event)>)
+}
+
+// Stream of the binary messages that arrive on a WebSocket, which ends once the socket closes
+// (including when it fails to open)
+struct WebSocketStream {
+    socket: web_sys::WebSocket,
+    state: std::rc::Rc<std::cell::RefCell<SocketState>>,
+    _handlers: [wasm_bindgen::closure::Closure<dyn FnMut(web_sys::Event)>; 3],
+}
+
+impl WebSocketStream {
+    fn open(url: &str) -> Result<WebSocketStream, JsValue> {
+        let socket = web_sys::WebSocket::new(url)?;
+        socket.set_binary_type(web_sys::BinaryType::Arraybuffer);
+        let state = std::rc::Rc::new(std::cell::RefCell::new(SocketState::default()));
+        let onopen = {
+            let socket = socket.clone();
+            let state = state.clone();
+            socket_handler(move |_| {
+                for message in state.borrow_mut().unsent.drain(..) {
+                    let _ = socket.send_with_u8_array(message.as_slice());
+                }
+            })
+        };
+        let onmessage = {
+            let state = state.clone();
+            socket_handler(move |e| {
+                // Text messages aren't part of the protocol
+                let data = match e.dyn_ref::<web_sys::MessageEvent>().map(|m| m.data()) {
+                    Some(d) if d.is_instance_of::<js_sys::ArrayBuffer>() => d,
+                    _ => return,
+                };
+                let mut state = state.borrow_mut();
+                state
+                    .received
+                    .push_back(js_sys::Uint8Array::new(&data).to_vec());
+                if let Some(waker) = state.waker.take() {
+                    waker.wake();
+                }
+            })
+        };
+        let onclose = {
+            let state = state.clone();
+            socket_handler(move |_| {
+                let mut state = state.borrow_mut();
+                state.closed = true;
+                if let Some(waker) = state.waker.take() {
+                    waker.wake();
+                }
+            })
+        };
+        socket.set_onopen(Some(onopen.as_ref().unchecked_ref()));
+        socket.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+        socket.set_onclose(Some(onclose.as_ref().unchecked_ref()));
+        Ok(WebSocketStream {
+            socket,
+            state,
+            _handlers: [onopen, onmessage, onclose],
+        })
+    }
+
+    // Gets a function that sends messages on the socket, holding on to them until it opens.
+    // Messages sent once it has closed are dropped
+    fn sender(&self) -> impl Fn(&[u8]) {
+        let socket = self.socket.clone();
+        let state = self.state.clone();
+        move |message| match socket.ready_state() {
+            web_sys::WebSocket::CONNECTING => state.borrow_mut().unsent.push(message.to_vec()),
+            web_sys::WebSocket::OPEN => {
+                let _ = socket.send_with_u8_array(message);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl futures::Stream for WebSocketStream {
+    type Item = Vec<u8>;
+    fn poll_next(
+        self: pin::Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<Option<Vec<u8>>> {
+        let mut state = self.state.borrow_mut();
+        match state.received.pop_front() {
+            Some(message) => task::Poll::Ready(Some(message)),
+            None if state.closed => task::Poll::Ready(None),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                task::Poll::Pending
+            }
+        }
+    }
+}
+
+// Closes the socket once the game stops listening to it, detaching the handlers before they're
+// freed
+impl Drop for WebSocketStream {
+    fn drop(&mut self) {
+        self.socket.set_onopen(None);
+        self.socket.set_onmessage(None);
+        self.socket.set_onclose(None);
+        let _ = self.socket.close();
+    }
+}
+
+type EventSender = alemian_saga_core::queue::OrderedSender<i32, String>;
+
+// Gets the mouse event if it was for one of the buttons that drag the map
+fn drag_button_event(e: &web_sys::Event) -> Option<&web_sys::MouseEvent> {
+    e.dyn_ref::<web_sys::MouseEvent>()
+        .filter(|m| m.button() == MIDDLE_BUTTON || m.button() == RIGHT_BUTTON)
+}
+
+// Gets the event for zooming with the mouse wheel, if it moved at all
+fn zoom_event(
+    delta_y: f64,
+    mouse_pos: alemian_saga_core::Vector<i32>,
+) -> Option<alemian_saga_core::Event<i32, String>> {
+    if delta_y < 0.0 {
+        Some(alemian_saga_core::Event::ZoomInAt(mouse_pos))
+    } else if delta_y > 0.0 {
+        Some(alemian_saga_core::Event::ZoomOutAt(mouse_pos))
+    } else {
+        None
+    }
+}
+
+// Starts audio that isn't already playing. Browsers refuse to play before the player has interacted
+// with the page, but the autoplay handlers try again after the next input so the refusal is ignored
+fn play_audio(audio: &web_sys::HtmlAudioElement) {
+    if audio.paused() {
+        if let Ok(promise) = audio.play() {
+            wasm_bindgen_futures::spawn_local(async {
+                let _ = wasm_bindgen_futures::JsFuture::from(promise).await;
+            });
+        }
+    }
+}
+
+// Converts a volume from 0 to sound::MAX_VOLUME into one from 0.0 to 1.0
+fn volume_fraction(volume: u32) -> f64 {
+    let max = alemian_saga_core::sound::MAX_VOLUME;
+    volume.min(max) as f64 / max as f64
+}
+
+// Looping sounds by path, each with how loud it is from 0.0 to 1.0 before the overall volume is
+// applied
+type AmbientSounds = std::collections::HashMap<String, (web_sys::HtmlAudioElement, f64)>;
+
+// Downloads a file by clicking a link to it that's never added to the page
+fn download(url: &str, file_name: &str) -> Option<()> {
+    let link = web_sys::window()?
+        .document()?
+        .create_element("a")
+        .ok()?
+        .dyn_into::<web_sys::HtmlAnchorElement>()
+        .ok()?;
+    link.set_href(url);
+    link.set_download(file_name);
+    link.click();
+    Some(())
+}
+
+// Downloads what's on a canvas as a PNG
+fn download_png(canvas: &web_sys::HtmlCanvasElement, name: &str) -> Option<()> {
+    let url = canvas.to_data_url().ok()?;
+    download(url.as_str(), format!("{}.png", name).as_str())
+}
+
+// Downloads bytes as a file by pointing a link at a blob holding them
+fn download_bytes(contents: &[u8], file_name: &str) -> Option<()> {
+    let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(contents));
+    let blob = web_sys::Blob::new_with_u8_array_sequence(&parts).ok()?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob).ok()?;
+    let downloaded = download(url.as_str(), file_name);
+    let _ = web_sys::Url::revoke_object_url(url.as_str());
+    downloaded
+}
+
+// Sends an event to the game, forwarding any backlog in the background if the queue is full
+fn send(event_queue: &mut EventSender, event: alemian_saga_core::Event<i32, String>) {
+    if let Some(forward) = event_queue.send(event) {
+        wasm_bindgen_futures::spawn_local(forward);
+    }
+}
+
+// A canvas along with the context that's drawn on it with
+type Surface = (
+    web_sys::HtmlCanvasElement,
+    web_sys::CanvasRenderingContext2d,
+);
+
+// Images drawn since begin_batch, waiting to be handed to JavaScript in one call so that each one
+// doesn't have to cross over from wasm on its own
+#[derive(Default)]
+struct DrawBatch {
+    images: Vec<web_sys::HtmlImageElement>,
+    // Left, top, width and height of each image
+    rects: Vec<f64>,
+}
+
+// Creates the function that draws a DrawBatch. Pages whose content security policy forbids creating
+// functions from strings refuse, which Function's constructor binding can't report, so it's called
+// through Reflect instead
+fn draw_batch_function() -> Option<js_sys::Function> {
+    let constructor: js_sys::Function = js_sys::Reflect::get(&js_sys::global(), &"Function".into())
+        .ok()?
+        .dyn_into()
+        .ok()?;
+    let args = js_sys::Array::of2(&DRAW_BATCH_ARGS.into(), &DRAW_BATCH_BODY.into());
+    js_sys::Reflect::construct(&constructor, &args)
+        .ok()?
+        .dyn_into()
+        .ok()
+}
+
 // Platform type that abstracts away logic that's specific to a web browser/wasm environment
 struct WebBrowser<'a> {
     canvas: web_sys::HtmlCanvasElement,
     context: web_sys::CanvasRenderingContext2d,
+    // Canvas that isn't on the page along with its context, while drawing goes to it instead of the
+    // game's canvas
+    offscreen: std::cell::RefCell<Option<Surface>>,
+    // Shared with the resize handler so that it can be reapplied after the canvas is reset
+    smoothing: std::rc::Rc<std::cell::Cell<bool>>,
+    // Draws a DrawBatch (None if the page won't let the function be created, e.g. because of its
+    // content security policy, in which case images are always drawn right away)
+    draw_batch: Option<js_sys::Function>,
+    // Images waiting to be drawn, while a batch is going
+    batch: std::cell::RefCell<Option<DrawBatch>>,
     web_client: reqwest::Client,
+    // URL that every asset is retrieved relative to
     host: &'a str,
-    _keyboard_handler: Option<gloo_events::EventListener>,
+    // Overrides the path of the keybindings file for every locale
+    keybindings_path: Option<String>,
+    retry_policy: alemian_saga_core::fetch::RetryPolicy,
+    // Set by the page's log query parameter (e.g. ?log=debug), overriding the settings file
+    log_level: Option<log::Level>,
+    // Shared with the autoplay handlers so that they can start music the browser refused to play
+    music: std::rc::Rc<std::cell::RefCell<Option<web_sys::HtmlAudioElement>>>,
+    // Sound effects that have already been loaded, by path, so that they aren't retrieved and
+    // decoded again every time they're played
+    sounds: std::cell::RefCell<std::collections::HashMap<String, web_sys::HtmlAudioElement>>,
+    // Shared with the autoplay handlers like the music
+    ambient: std::rc::Rc<std::cell::RefCell<AmbientSounds>>,
+    // From 0.0 to 1.0
+    volume: std::cell::Cell<f64>,
+    // Shared with the event listeners, which count the events that don't fit in the queue
+    event_queue: EventSender,
+    _keyboard_handler: gloo_events::EventListener,
+    _release_handler: gloo_events::EventListener,
     _resize_handler: gloo_events::EventListener,
-    _mouse_handler: gloo_events::EventListener,
+    _resize_observer: CanvasResizeObserver,
+    _mouse_handlers: [gloo_events::EventListener; 2],
     _scroll_handler: gloo_events::EventListener,
+    _click_handler: gloo_events::EventListener,
+    _drag_handlers: [gloo_events::EventListener; 3],
+    _unload_handlers: [gloo_events::EventListener; 2],
+    _visibility_handler: gloo_events::EventListener,
+    _autoplay_handlers: [gloo_events::EventListener; 2],
+    _focus_handlers: [gloo_events::EventListener; 3],
 }
 
 // Constructor and helper functions for the WebBrowser type
 impl<'a> WebBrowser<'a> {
-    fn handle_resize() -> Option<()> {
-        let canvas_element = web_sys::window()?.document()?.get_element_by_id("g")?;
-        let canvas = canvas_element.dyn_ref::<web_sys::HtmlCanvasElement>()?;
-        canvas.set_width(canvas.client_width() as u32);
-        canvas.set_height(canvas.client_height() as u32);
-        Some(())
+    // Sizes the canvas's backing store in physical pixels so rendering is crisp on high density
+    // displays, while scaling the context so that drawing still happens in CSS pixels. Returns
+    // whether the canvas changed, which it doesn't if it's already the right size
+    fn fit_canvas(
+        canvas: &web_sys::HtmlCanvasElement,
+        context: &web_sys::CanvasRenderingContext2d,
+        smoothing: bool,
+        fitted: &std::cell::Cell<Option<((u32, u32), f64)>>,
+        (width, height): (f64, f64),
+    ) -> Option<bool> {
+        let backing_store = (canvas.width(), canvas.height());
+        // A hidden canvas has no size, so its backing store is left as it is until it's shown. A
+        // canvas without a CSS size is laid out at the size of its backing store, so scaling the
+        // backing store up would only make it grow every time it was fitted, and it's drawn at
+        // one backing store pixel per CSS pixel instead
+        let hidden = width == 0.0 || height == 0.0;
+        let follows_backing_store =
+            (width, height) == (backing_store.0 as f64, backing_store.1 as f64);
+        let (size, ratio) = if hidden || follows_backing_store {
+            (backing_store, 1.0)
+        } else {
+            let ratio = web_sys::window()?.device_pixel_ratio();
+            let size = (
+                (width * ratio).round() as u32,
+                (height * ratio).round() as u32,
+            );
+            (size, ratio)
+        };
+        if fitted.get() == Some((size, ratio)) {
+            return Some(false);
+        }
+        canvas.set_width(size.0);
+        canvas.set_height(size.1);
+        fitted.set(Some((size, ratio)));
+        // Resizing the canvas resets the context's state
+        context
+            .set_transform(ratio, 0.0, 0.0, ratio, 0.0, 0.0)
+            .ok()?;
+        context.set_font(format!("{}rem {}", FONT_SIZE_REM, FONT_FAMILY).as_str());
+        context.set_image_smoothing_enabled(smoothing);
+        Some(true)
+    }
+
+    // The canvas's content box in CSS pixels, for when there's no ResizeObserverEntry to take it
+    // from
+    fn canvas_size(canvas: &web_sys::HtmlCanvasElement) -> (f64, f64) {
+        (
+            canvas.client_width().max(0) as f64,
+            canvas.client_height().max(0) as f64,
+        )
     }
 
-    async fn new(
+    fn new(
+        canvas_id: &str,
         host: &'a str,
-        mut event_queue: mpsc::Sender<alemian_saga_core::Event<i32>>,
+        keybindings_path: Option<String>,
+        mut event_queue: EventSender,
     ) -> Option<WebBrowser<'a>> {
         // Get handlers for various items from the Html document
         let window = web_sys::window()?;
         let document = window.document()?;
-        let canvas_element = document.get_element_by_id("g")?;
+        let canvas_element = document.get_element_by_id(canvas_id)?;
         let canvas = canvas_element
             .dyn_into::<web_sys::HtmlCanvasElement>()
             .ok()?;
-        let document_element = document.document_element()?;
-
-        // For whatever reason css doesn't populate the width and height field,
-        // so we have to do that manually
-        canvas.set_width(canvas.client_width() as u32);
-        canvas.set_height(canvas.client_height() as u32);
 
         // Create the WebBrowser object
         let context_object = canvas.get_context("2d").ok()??;
         let context = context_object
             .dyn_into::<web_sys::CanvasRenderingContext2d>()
             .ok()?;
-        context.set_font(FONT);
+
+        // For whatever reason css doesn't populate the width and height field,
+        // so we have to do that manually
+        let smoothing = std::rc::Rc::new(std::cell::Cell::new(true));
+        // Size of the backing store and the pixel ratio the last time the canvas was fitted, so
+        // that the resize handlers leave it alone when neither has changed
+        let fitted = std::rc::Rc::new(std::cell::Cell::new(None));
+        Self::fit_canvas(
+            &canvas,
+            &context,
+            smoothing.get(),
+            &fitted,
+            Self::canvas_size(&canvas),
+        )?;
         let web_client = reqwest::Client::new();
 
+        // Canvases can only receive keyboard events once they're focusable
+        if !canvas.has_attribute("tabindex") {
+            canvas.set_tab_index(0);
+        }
+
+        let log_level = window
+            .location()
+            .search()
+            .ok()
+            .and_then(|search| web_sys::UrlSearchParams::new_with_str(search.as_str()).ok())
+            .and_then(|params| params.get("log"))
+            .and_then(|name| log::Level::parse(name.as_str()));
+        // Applied right away so that it also covers messages logged before the settings are loaded
+        if let Some(level) = log_level {
+            log::set_verbosity(level);
+        }
+
         let mut mouse_event_queue = event_queue.clone();
+        let mut leave_event_queue = event_queue.clone();
 
-        let mouse_handler =
-            gloo_events::EventListener::new(&document_element, "mousemove", move |e| {
+        let mouse_handlers = [
+            gloo_events::EventListener::new(&canvas, "mousemove", move |e| {
                 if let Some(mouse_event) = e.dyn_ref::<web_sys::MouseEvent>() {
                     send(
                         &mut mouse_event_queue,
@@ -151,63 +645,325 @@ impl<'a> WebBrowser<'a> {
                         }),
                     );
                 }
-            });
+            }),
+            gloo_events::EventListener::new(&canvas, "mouseleave", move |_| {
+                send(&mut leave_event_queue, alemian_saga_core::Event::MouseLeave);
+            }),
+        ];
 
         let mut scroll_event_queue = event_queue.clone();
 
-        let scroll_handler =
-            gloo_events::EventListener::new(&document_element, "wheel", move |e| {
-                if let Some(wheel_event) = e.dyn_ref::<web_sys::WheelEvent>() {
-                    let delta_y = wheel_event.delta_y();
-                    if delta_y < 0.0 {
-                        send(&mut scroll_event_queue, alemian_saga_core::Event::ZoomIn);
-                    } else if delta_y > 0.0 {
-                        send(&mut scroll_event_queue, alemian_saga_core::Event::ZoomOut);
-                    }
+        let scroll_handler = gloo_events::EventListener::new(&canvas, "wheel", move |e| {
+            if let Some(wheel_event) = e.dyn_ref::<web_sys::WheelEvent>() {
+                let delta_x = wheel_event.delta_x();
+                let delta_y = wheel_event.delta_y();
+                let mouse_pos = alemian_saga_core::Vector {
+                    x: wheel_event.offset_x(),
+                    y: wheel_event.offset_y(),
+                };
+                let notches = |delta: f64| delta.signum() as i32;
+                // Browsers set ctrlKey on the wheel events they make up for pinch gestures, so
+                // those zoom even if they also moved sideways
+                let event = if wheel_event.ctrl_key() {
+                    zoom_event(delta_y, mouse_pos)
+                } else if delta_x.abs() > delta_y.abs() {
+                    Some(alemian_saga_core::Event::ScrollHorizontal(notches(delta_x)))
+                } else if delta_y == 0.0 {
+                    None
+                } else if wheel_event.shift_key() {
+                    // Not every browser turns shift+wheel into deltaX
+                    Some(alemian_saga_core::Event::ScrollHorizontal(notches(delta_y)))
+                } else if wheel_event.alt_key() {
+                    Some(alemian_saga_core::Event::ScrollVertical(notches(delta_y)))
+                } else {
+                    zoom_event(delta_y, mouse_pos)
+                };
+                if let Some(event) = event {
+                    send(&mut scroll_event_queue, event);
                 }
-            });
+            }
+        });
+
+        let mut click_event_queue = event_queue.clone();
+
+        let click_handler = gloo_events::EventListener::new(&canvas, "click", move |e| {
+            if let Some(mouse_event) = e.dyn_ref::<web_sys::MouseEvent>() {
+                send(
+                    &mut click_event_queue,
+                    alemian_saga_core::Event::Click(alemian_saga_core::Vector {
+                        x: mouse_event.offset_x(),
+                        y: mouse_event.offset_y(),
+                    }),
+                );
+            }
+        });
+
+        let mut drag_start_queue = event_queue.clone();
+        let mut drag_end_queue = event_queue.clone();
+        // The drag ends wherever the button is let go, so mouseup is caught on the whole window,
+        // but only drags that started on this canvas are ended
+        let dragging = std::rc::Rc::new(std::cell::Cell::new(false));
+        let drag_end = dragging.clone();
+        let prevent_default = gloo_events::EventListenerOptions::enable_prevent_default();
+        let drag_handlers = [
+            gloo_events::EventListener::new(&canvas, "mousedown", move |e| {
+                if let Some(mouse_event) = drag_button_event(e) {
+                    dragging.set(true);
+                    let mouse_pos = alemian_saga_core::Vector {
+                        x: mouse_event.offset_x(),
+                        y: mouse_event.offset_y(),
+                    };
+                    let event = alemian_saga_core::Event::DragStart(mouse_pos);
+                    send(&mut drag_start_queue, event);
+                }
+            }),
+            gloo_events::EventListener::new(&window, "mouseup", move |e| {
+                if drag_button_event(e).is_some() && drag_end.replace(false) {
+                    send(&mut drag_end_queue, alemian_saga_core::Event::DragEnd);
+                }
+            }),
+            // Keep the context menu from popping up when dragging with the right button
+            gloo_events::EventListener::new_with_options(
+                &canvas,
+                "contextmenu",
+                prevent_default,
+                |e| e.prevent_default(),
+            ),
+        ];
 
         let mut resize_event_queue = event_queue.clone();
 
+        let resize_canvas = canvas.clone();
+        let resize_context = context.clone();
+        let resize_smoothing = smoothing.clone();
+        let resize_fitted = fitted.clone();
         let resize_handler = gloo_events::EventListener::new(&window, "resize", move |_| {
-            Self::handle_resize();
-            send(&mut resize_event_queue, alemian_saga_core::Event::Redraw);
+            let fitted = Self::fit_canvas(
+                &resize_canvas,
+                &resize_context,
+                resize_smoothing.get(),
+                &resize_fitted,
+                Self::canvas_size(&resize_canvas),
+            );
+            if fitted == Some(true) {
+                send(&mut resize_event_queue, alemian_saga_core::Event::Redraw);
+            }
         });
+        // The canvas has no size while it's hidden, so the game doesn't draw until this reports
+        // that it has one
+        let mut observed_event_queue = event_queue.clone();
+        let observed_canvas = canvas.clone();
+        let observed_context = context.clone();
+        let observed_smoothing = smoothing.clone();
+        let observed_fitted = fitted.clone();
+        let resize_observer = CanvasResizeObserver::new(&canvas, move |width, height| {
+            let fitted = Self::fit_canvas(
+                &observed_canvas,
+                &observed_context,
+                observed_smoothing.get(),
+                &observed_fitted,
+                (width, height),
+            );
+            if fitted == Some(true) {
+                send(&mut observed_event_queue, alemian_saga_core::Event::Redraw);
+            }
+        })?;
+
+        // Browsers don't reliably fire beforeunload (particularly on mobile), so listen for both
+        let unload_handlers = ["beforeunload", "pagehide"].map(|event_type| {
+            let mut unload_event_queue = event_queue.clone();
+            gloo_events::EventListener::new(&window, event_type, move |_| {
+                send(&mut unload_event_queue, alemian_saga_core::Event::Quit);
+            })
+        });
+
+        let mut visibility_event_queue = event_queue.clone();
+        let hidden_document = document.clone();
+        let visibility_handler =
+            gloo_events::EventListener::new(&document, "visibilitychange", move |_| {
+                let event = if hidden_document.hidden() {
+                    alemian_saga_core::Event::Suspend
+                } else {
+                    alemian_saga_core::Event::Resume
+                };
+                send(&mut visibility_event_queue, event);
+            });
 
-        let mut ret = WebBrowser {
+        // Browsers block playing sound until the player has interacted with the page, so music and
+        // ambient sounds that were refused are started again on each input until they're playing
+        let music = std::rc::Rc::new(std::cell::RefCell::new(None));
+        let ambient = std::rc::Rc::new(std::cell::RefCell::new(AmbientSounds::new()));
+        let autoplay_handlers = ["keydown", "mousedown"].map(|event_type| {
+            let autoplay_music = music.clone();
+            let autoplay_ambient = ambient.clone();
+            gloo_events::EventListener::new(&canvas, event_type, move |_| {
+                if let Some(audio) = autoplay_music.borrow().as_ref() {
+                    play_audio(audio);
+                }
+                for (audio, _) in autoplay_ambient.borrow().values() {
+                    play_audio(audio);
+                }
+            })
+        });
+
+        let mut focus_event_queue = event_queue.clone();
+        let mut blur_event_queue = event_queue.clone();
+        let focus_canvas = canvas.clone();
+        let focus_handlers = [
+            gloo_events::EventListener::new(&canvas, "focus", move |_| {
+                send(
+                    &mut focus_event_queue,
+                    alemian_saga_core::Event::FocusGained,
+                );
+            }),
+            gloo_events::EventListener::new(&canvas, "blur", move |_| {
+                send(&mut blur_event_queue, alemian_saga_core::Event::FocusLost);
+            }),
+            // Clicking with any button gives the canvas keyboard focus
+            gloo_events::EventListener::new(&canvas, "mousedown", move |_| {
+                let _ = focus_canvas.focus();
+            }),
+        ];
+        // Take focus so that keys work without clicking first, unless something else on the page
+        // holds on to it
+        let _ = canvas.focus();
+        let focused = document
+            .active_element()
+            .is_some_and(|e| e.is_same_node(Some(&canvas)));
+        if !focused {
+            send(&mut event_queue, alemian_saga_core::Event::FocusLost);
+        }
+
+        let overflow_queue = event_queue.clone();
+        let mut release_event_queue = event_queue.clone();
+        let release_handler = gloo_events::EventListener::new(&canvas, "keyup", move |e| {
+            if let Some(keyboard_event) = e.dyn_ref::<web_sys::KeyboardEvent>() {
+                let key = keyboard_event.key();
+                send(
+                    &mut release_event_queue,
+                    alemian_saga_core::Event::Release(key),
+                );
+            }
+        });
+
+        let keyboard_handler = gloo_events::EventListener::new(&canvas, "keydown", move |e| {
+            if let Some(keyboard_event) = e.dyn_ref::<web_sys::KeyboardEvent>() {
+                let key = keyboard_event.key();
+                send(&mut event_queue, alemian_saga_core::Event::Input(key));
+            }
+        });
+
+        Some(WebBrowser {
             canvas,
             context,
+            offscreen: std::cell::RefCell::new(None),
+            smoothing,
+            draw_batch: draw_batch_function(),
+            batch: std::cell::RefCell::new(None),
             web_client,
             host,
-            _keyboard_handler: None,
+            keybindings_path,
+            retry_policy: alemian_saga_core::fetch::RetryPolicy {
+                attempts: FETCH_ATTEMPTS,
+                initial_backoff_ns: FETCH_INITIAL_BACKOFF_NS,
+                timeout_ns: FETCH_TIMEOUT_NS,
+            },
+            log_level,
+            music,
+            sounds: std::cell::RefCell::new(std::collections::HashMap::new()),
+            ambient,
+            volume: std::cell::Cell::new(1.0),
+            event_queue: overflow_queue,
+            _keyboard_handler: keyboard_handler,
+            _release_handler: release_handler,
             _resize_handler: resize_handler,
-            _mouse_handler: mouse_handler,
+            _resize_observer: resize_observer,
+            _mouse_handlers: mouse_handlers,
             _scroll_handler: scroll_handler,
-        };
-
-        let key_bindings = ret.get_keybindings(LOCALE).await?;
-
-        ret._keyboard_handler = Some(gloo_events::EventListener::new(
-            &document_element,
-            "keydown",
-            move |e| {
-                if let Some(keyboard_event) = e.dyn_ref::<web_sys::KeyboardEvent>() {
-                    if let Some(&game_event) = key_bindings.get(&keyboard_event.key()) {
-                        send(&mut event_queue, game_event);
-                    }
-                }
-            },
-        ));
+            _click_handler: click_handler,
+            _drag_handlers: drag_handlers,
+            _unload_handlers: unload_handlers,
+            _visibility_handler: visibility_handler,
+            _autoplay_handlers: autoplay_handlers,
+            _focus_handlers: focus_handlers,
+        })
+    }
 
-        Some(ret)
+    // Gets the URL of an asset
+    fn url(&self, path: &str) -> String {
+        self.host.to_owned() + path
     }
 
     async fn get_file_internal(
         &self,
         path: &str,
-    ) -> Result<bytes::buf::Reader<bytes::Bytes>, reqwest::Error> {
-        let response = self.web_client.get(&(self.host.to_owned() + path)).send();
-        Ok(response.await?.bytes().await?.reader())
+    ) -> Result<bytes::buf::Reader<bytes::Bytes>, alemian_saga_core::Error> {
+        let url = self.url(path);
+        let network_error = |err: reqwest::Error| alemian_saga_core::Error::Network {
+            path: url.clone(),
+            source_msg: err.to_string(),
+        };
+        // Dropping the returned future part way through aborts the request
+        let request = self.web_client.get(&url).send();
+        let response = request.await.map_err(network_error)?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(alemian_saga_core::Error::MissingAsset(path.to_owned()));
+        }
+        let response = response.error_for_status().map_err(network_error)?;
+        Ok(response.bytes().await.map_err(network_error)?.reader())
+    }
+
+    // Draws the images in the batch that's going, if any. Anything else that touches the canvas
+    // does this first so that the images aren't drawn over what was meant to go on top of them
+    fn flush_batch(&self) {
+        let mut batch = self.batch.borrow_mut();
+        let (batch, draw_batch) = match (batch.as_mut(), self.draw_batch.as_ref()) {
+            (Some(b), Some(d)) if !b.images.is_empty() => (b, d),
+            _ => return,
+        };
+        let images = JsValue::from(std::mem::take(&mut batch.images));
+        let rects = JsValue::from(std::mem::take(&mut batch.rects));
+        if let Err(e) = draw_batch.call3(&JsValue::NULL, &self.target(), &images, &rects) {
+            log::warn::<WebBrowser>(format!("Failed to draw batch: {:?}", e).as_str());
+        }
+    }
+
+    // Gets the context that's being drawn on: the offscreen canvas's if there is one, otherwise the
+    // game canvas's
+    fn target(&self) -> web_sys::CanvasRenderingContext2d {
+        match self.offscreen.borrow().as_ref() {
+            Some((_, context)) => context.clone(),
+            None => self.context.clone(),
+        }
+    }
+
+    // Creates a canvas that isn't added to the page, along with its context
+    fn create_canvas(width: u32, height: u32) -> Option<Surface> {
+        let document = web_sys::window()?.document()?;
+        let canvas = document
+            .create_element("canvas")
+            .ok()?
+            .dyn_into::<web_sys::HtmlCanvasElement>()
+            .ok()?;
+        canvas.set_width(width);
+        canvas.set_height(height);
+        let context = canvas
+            .get_context("2d")
+            .ok()??
+            .dyn_into::<web_sys::CanvasRenderingContext2d>()
+            .ok()?;
+        Some((canvas, context))
+    }
+
+    // Saves the canvas state and sets the font and color for a text style;
+    // callers must call context.restore() once they're done with the style
+    fn apply_style(&self, style: &TextStyle) {
+        let context = self.target();
+        context.save();
+        let weight = if style.bold { "bold " } else { "" };
+        let size = FONT_SIZE_REM * style.size;
+        context.set_font(format!("{}{}rem {}", weight, size, FONT_FAMILY).as_str());
+        context.set_fill_style_str(style.color.to_css().as_str());
     }
 }
 
@@ -230,6 +986,10 @@ impl alemian_saga_core::Platform for WebBrowser<'_> {
 
     type Duration = f64;
 
+    type SleepFuture = gloo_timers::future::TimeoutFuture;
+
+    type FrameFuture = AnimationFrame;
+
     fn now() -> Self::Instant {
         js_sys::Date::now()
     }
@@ -238,35 +998,161 @@ impl alemian_saga_core::Platform for WebBrowser<'_> {
         second - first
     }
 
-    fn nanoseconds(ns: usize) -> Self::Duration {
+    fn instant_after(instant: Self::Instant, duration: Self::Duration) -> Self::Instant {
+        instant + duration
+    }
+
+    fn nanoseconds(ns: u64) -> Self::Duration {
         ns as f64 * 0.000001
     }
 
+    fn to_nanoseconds(duration: Self::Duration) -> u64 {
+        (duration * 1000000.0) as u64
+    }
+
+    fn sleep(duration: Self::Duration) -> Self::SleepFuture {
+        gloo_timers::future::TimeoutFuture::new(duration as u32)
+    }
+
+    fn request_frame(&self) -> Self::FrameFuture {
+        AnimationFrame {
+            request: None,
+            fired: std::rc::Rc::new(std::cell::Cell::new(false)),
+        }
+    }
+
+    fn keybindings_path(&self, locale: &str) -> String {
+        match self.keybindings_path.as_ref() {
+            Some(path) => path.clone(),
+            None => alemian_saga_core::bindings::default_path(locale),
+        }
+    }
+
+    fn random(&self) -> u64 {
+        let mut bytes = [0; 8];
+        let filled = web_sys::window()
+            .and_then(|w| w.crypto().ok())
+            .and_then(|c| c.get_random_values_with_u8_array(&mut bytes).ok());
+        if filled.is_none() {
+            // Games are still playable with a poor seed, just less varied
+            log::warn::<WebBrowser>("Failed to get random values from the browser");
+            return js_sys::Date::now() as u64;
+        }
+        u64::from_le_bytes(bytes)
+    }
+
+    fn retry_policy(&self) -> alemian_saga_core::fetch::RetryPolicy {
+        self.retry_policy
+    }
+
+    fn log_level(&self) -> Option<log::Level> {
+        self.log_level
+    }
+
     fn draw_primitive(&self, image: &Self::Image, left: f64, top: f64, width: f64, height: f64) {
-        let context = &self.context;
+        if let Some(batch) = self.batch.borrow_mut().as_mut() {
+            batch.images.push(image.clone());
+            batch.rects.extend_from_slice(&[left, top, width, height]);
+            return;
+        }
+        let context = self.target();
         let _ = context
             .draw_image_with_html_image_element_and_dw_and_dh(image, left, top, width, height);
     }
 
-    fn draw_text_primitive(&self, text: &str, x: f64, y: f64, max_width: f64) {
-        let _ = self
-            .context
-            .fill_text_with_max_width(text, x, y + 10.0, max_width);
+    fn begin_batch(&self) {
+        if self.draw_batch.is_some() {
+            self.flush_batch();
+            *self.batch.borrow_mut() = Some(DrawBatch::default());
+        }
+    }
+
+    fn end_batch(&self) {
+        self.flush_batch();
+        *self.batch.borrow_mut() = None;
+    }
+
+    fn set_smoothing(&self, enabled: bool) {
+        self.flush_batch();
+        self.smoothing.set(enabled);
+        self.context.set_image_smoothing_enabled(enabled);
+    }
+
+    fn fill_rect_primitive(&self, left: f64, top: f64, width: f64, height: f64, color: Color) {
+        self.flush_batch();
+        let context = self.target();
+        context.save();
+        context.set_fill_style_str(color.to_css().as_str());
+        context.fill_rect(left, top, width, height);
+        context.restore();
+    }
+
+    fn clear(&self) {
+        self.flush_batch();
+        self.target()
+            .clear_rect(0.0, 0.0, self.get_width(), self.get_height());
     }
 
+    fn draw_text_primitive(
+        &self,
+        text: &str,
+        x: f64,
+        y: f64,
+        max_width: f64,
+        style: &TextStyle,
+        direction: text::Direction,
+    ) {
+        self.flush_batch();
+        let context = self.target();
+        self.apply_style(style);
+        // This version of web-sys doesn't bind the canvas's direction property, so it's set by name
+        let _ = js_sys::Reflect::set(&context, &"direction".into(), &direction.name().into());
+        let (anchor, align) = match style.alignment {
+            Alignment::Left => (x, "left"),
+            Alignment::Center => (x + max_width / 2.0, "center"),
+            Alignment::Right => (x + max_width, "right"),
+        };
+        context.set_text_align(align);
+        let ascent = match context.measure_text("M") {
+            Ok(metrics) => metrics.actual_bounding_box_ascent(),
+            Err(_) => 0.0,
+        };
+        let _ = context.fill_text_with_max_width(text, anchor, y + ascent, max_width);
+        context.restore();
+    }
+
+    // Hidden canvases report no size, which the game treats as not being ready to draw to
     fn get_width(&self) -> f64 {
-        self.canvas.client_width() as f64
+        self.canvas.client_width().max(0) as f64
     }
 
     fn get_height(&self) -> f64 {
-        self.canvas.client_height() as f64
+        self.canvas.client_height().max(0) as f64
     }
 
-    fn get_image(path: &str) -> Self::ImageFuture {
+    fn measure_text(&self, text: &str, style: &TextStyle) -> f64 {
+        self.apply_style(style);
+        let width = match self.target().measure_text(text) {
+            Ok(metrics) => metrics.width(),
+            Err(_) => 0.0,
+        };
+        self.target().restore();
+        width
+    }
+
+    fn get_text_height(&self, style: &TextStyle) -> Option<f64> {
+        self.apply_style(style);
+        let metrics = self.target().measure_text("M");
+        self.target().restore();
+        let metrics = metrics.ok()?;
+        Some(metrics.actual_bounding_box_ascent() + metrics.actual_bounding_box_descent())
+    }
+
+    fn get_image(&self, path: &str) -> Self::ImageFuture {
         let element = web_sys::HtmlImageElement::new();
         match element {
             Ok(e) => {
-                e.set_src(path);
+                e.set_src(self.url(path).as_str());
                 LoadedImageElement {
                     element: Some(e),
                     handler: None,
@@ -279,18 +1165,193 @@ impl alemian_saga_core::Platform for WebBrowser<'_> {
         }
     }
 
-    async fn get_file(&self, path: &str) -> Result<Self::File, String> {
-        match self.get_file_internal(path).await {
-            Ok(ret) => Ok(ret),
-            Err(err) => Err(err.to_string()),
-        }
+    fn tint(&self, image: &Self::Image, color: Color) -> Option<Self::ImageFuture> {
+        // Color over the image on a canvas of its own, then load the result back as an image
+        let (width, height) = (image.natural_width(), image.natural_height());
+        let (canvas, context) = Self::create_canvas(width, height)?;
+        context
+            .draw_image_with_html_image_element(image, 0.0, 0.0)
+            .ok()?;
+        // Only color the parts of the canvas that the image covers
+        context.set_global_composite_operation("source-atop").ok()?;
+        context.set_fill_style_str(color.to_css().as_str());
+        context.fill_rect(0.0, 0.0, width as f64, height as f64);
+        let element = web_sys::HtmlImageElement::new().ok()?;
+        element.set_src(canvas.to_data_url().ok()?.as_str());
+        Some(LoadedImageElement {
+            element: Some(element),
+            handler: None,
+        })
+    }
+
+    async fn get_file(&self, path: &str) -> Result<Self::File, alemian_saga_core::Error> {
+        self.get_file_internal(path).await
     }
 
     fn string_to_input(input: String) -> Self::InputType {
         input
     }
 
+    fn store(&self, key: &str, value: &str) {
+        let saved = web_sys::window()
+            .and_then(|w| w.local_storage().ok().flatten())
+            .and_then(|storage| storage.set_item(key, value).ok());
+        if saved.is_none() {
+            log::warn::<WebBrowser>(format!("Failed to save {}", key).as_str());
+        }
+    }
+
+    fn retrieve(&self, key: &str) -> Option<String> {
+        let storage = web_sys::window()?.local_storage().ok()??;
+        storage.get_item(key).ok()?
+    }
+
+    fn timestamp(&self) -> Option<u64> {
+        Some((js_sys::Date::now() / 1000.0) as u64)
+    }
+
+    fn play_music(&self, path: Option<&str>) -> Result<(), alemian_saga_core::Error> {
+        let mut music = self.music.borrow_mut();
+        if let Some(audio) = music.take() {
+            let _ = audio.pause();
+        }
+        if let Some(path) = path {
+            let audio =
+                web_sys::HtmlAudioElement::new_with_src(self.url(path).as_str()).map_err(|e| {
+                    alemian_saga_core::Error::Playback {
+                        path: path.to_owned(),
+                        source_msg: format!("{:?}", e),
+                    }
+                })?;
+            audio.set_loop(true);
+            audio.set_volume(self.volume.get());
+            play_audio(&audio);
+            *music = Some(audio);
+        }
+        Ok(())
+    }
+
+    fn play_sound(&self, path: &str, volume: u32) {
+        let mut sounds = self.sounds.borrow_mut();
+        let audio = match sounds.get(path) {
+            Some(a) => a,
+            None => match web_sys::HtmlAudioElement::new_with_src(self.url(path).as_str()) {
+                Ok(a) => sounds.entry(path.to_owned()).or_insert(a),
+                Err(_) => return,
+            },
+        };
+        // Start over if the sound is still playing from last time
+        audio.set_current_time(0.0);
+        audio.set_volume(self.volume.get() * volume_fraction(volume));
+        play_audio(audio);
+    }
+
+    fn set_ambient_sound(&self, path: &str, volume: u32) {
+        let mut ambient = self.ambient.borrow_mut();
+        if volume == 0 {
+            if let Some((audio, _)) = ambient.remove(path) {
+                let _ = audio.pause();
+            }
+            return;
+        }
+        let (audio, own_volume) = match ambient.get_mut(path) {
+            Some(a) => a,
+            None => match web_sys::HtmlAudioElement::new_with_src(self.url(path).as_str()) {
+                Ok(a) => {
+                    a.set_loop(true);
+                    ambient.entry(path.to_owned()).or_insert((a, 0.0))
+                }
+                Err(_) => return,
+            },
+        };
+        *own_volume = volume_fraction(volume);
+        audio.set_volume(self.volume.get() * *own_volume);
+        play_audio(audio);
+    }
+
+    fn set_volume(&self, volume: u32) {
+        let volume = volume_fraction(volume);
+        self.volume.set(volume);
+        if let Some(audio) = self.music.borrow().as_ref() {
+            audio.set_volume(volume);
+        }
+        for (audio, own_volume) in self.ambient.borrow().values() {
+            audio.set_volume(volume * own_volume);
+        }
+    }
+
+    fn set_clipboard(&self, text: &str) {
+        let promise = match web_sys::window() {
+            Some(w) => w.navigator().clipboard().write_text(text),
+            None => return,
+        };
+        wasm_bindgen_futures::spawn_local(async {
+            if wasm_bindgen_futures::JsFuture::from(promise).await.is_err() {
+                log::warn::<WebBrowser>("Failed to copy to the clipboard");
+            }
+        });
+    }
+
+    fn connect(
+        &self,
+        url: &str,
+    ) -> Result<alemian_saga_core::net::MessageStream, alemian_saga_core::Error> {
+        let stream = WebSocketStream::open(url).map_err(|e| alemian_saga_core::Error::Network {
+            path: url.to_owned(),
+            source_msg: format!("{:?}", e),
+        })?;
+        Ok(alemian_saga_core::net::MessageStream {
+            send: Box::new(stream.sender()),
+            incoming: Box::pin(stream),
+        })
+    }
+
+    fn event_queue_overflows(&self) -> u64 {
+        self.event_queue.overflows()
+    }
+
+    fn export_frame(&self, name: &str) {
+        self.flush_batch();
+        if download_png(&self.canvas, name).is_none() {
+            log::warn::<WebBrowser>(format!("Failed to save {}", name).as_str());
+        }
+    }
+
+    fn begin_offscreen(&self, size: alemian_saga_core::Vector<f64>) -> bool {
+        self.flush_batch();
+        let (width, height) = (size.x.ceil() as u32, size.y.ceil() as u32);
+        match Self::create_canvas(width, height) {
+            Some((canvas, context)) => {
+                context.set_image_smoothing_enabled(self.smoothing.get());
+                *self.offscreen.borrow_mut() = Some((canvas, context));
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn export_offscreen(&self, name: &str) {
+        self.flush_batch();
+        let offscreen = self.offscreen.borrow_mut().take();
+        if offscreen
+            .and_then(|(canvas, _)| download_png(&canvas, name))
+            .is_none()
+        {
+            log::warn::<WebBrowser>(format!("Failed to save {}", name).as_str());
+        }
+    }
+
+    fn export_file(&self, name: &str, contents: &[u8]) {
+        if download_bytes(contents, name).is_none() {
+            log::warn::<WebBrowser>(format!("Failed to save {}", name).as_str());
+        }
+    }
+
     fn log(msg: &str) {
         web_sys::console::log_1(&wasm_bindgen::JsValue::from_str(msg));
     }
+
+    fn log_error(msg: &str) {
+        web_sys::console::error_1(&wasm_bindgen::JsValue::from_str(msg));
+    }
 }
@@ -1,7 +1,7 @@
 use alemian_saga_core::serialization;
 use std::collections;
 
-const LANGUAGES: [&'static str; 1] = ["english"];
+const LANGUAGES: [&str; 1] = ["english"];
 
 #[allow(non_snake_case)]
 #[derive(serde::Deserialize)]
@@ -10,6 +10,18 @@ enum JsonContent {
     Map {
         tileTypes: collections::HashMap<String, TileTypeInfo>,
         map: ndarray::Array2<String>,
+        #[serde(default)]
+        background: Option<alemian_saga_core::style::Color>,
+        #[serde(default)]
+        fog: bool,
+        #[serde(default)]
+        triggers: Vec<serialization::Trigger>,
+        #[serde(default)]
+        intro_dialog: Vec<serialization::Message>,
+        #[serde(default)]
+        music: Option<String>,
+        #[serde(default)]
+        units: Vec<serialization::Unit>,
     },
 }
 
@@ -19,6 +31,12 @@ struct TileTypeInfo {
     move_cost: u32,
     defense: i32,
     evade: i32,
+    #[serde(default)]
+    minimap_color: Option<alemian_saga_core::style::Color>,
+    #[serde(default)]
+    ambient_sound: Option<String>,
+    #[serde(default)]
+    footstep_sound: Option<String>,
 }
 
 #[allow(non_snake_case)]
@@ -32,34 +50,49 @@ fn main() {
             let reader = std::io::BufReader::new(std::fs::File::open(&path).unwrap());
             let json: JsonContent = serde_json::from_reader(reader).unwrap();
             match json {
-                JsonContent::Map { tileTypes, map } => {
+                JsonContent::Map {
+                    tileTypes,
+                    map,
+                    background,
+                    fog,
+                    triggers,
+                    intro_dialog,
+                    music,
+                    units,
+                } => {
                     let mut name_to_index = collections::HashMap::new();
+                    // Names and dialog are left as string keys, which the game looks up in the
+                    // strings file for the player's locale
                     for l in LANGUAGES.iter() {
-                        let lang_file =
-                            std::fs::File::open(&format!("../../language/{}.json", l)).unwrap();
-                        let lang_reader = std::io::BufReader::new(lang_file);
-                        let string_map: collections::HashMap<String, String> =
-                            serde_json::from_reader(lang_reader).unwrap();
                         let mut tile_types = vec![];
                         for (i, (k, v)) in tileTypes.iter().enumerate() {
                             name_to_index.insert(k.clone(), i as u32);
                             tile_types.push(serialization::TileType {
-                                name: string_map.get(k).unwrap().clone(),
+                                name: k.clone(),
                                 image: v.image.clone(),
                                 defense: v.defense,
                                 evade: v.evade,
                                 move_cost: v.move_cost,
+                                minimap_color: v.minimap_color,
+                                ambient_sound: v.ambient_sound.clone(),
+                                footstep_sound: v.footstep_sound.clone(),
                             });
                         }
                         let new_map = serialization::Map {
-                            tile_types: tile_types,
+                            tile_types,
                             map: map.map(|x| *name_to_index.get(x).unwrap()),
+                            background,
+                            fog,
+                            triggers: triggers.clone(),
+                            intro_dialog: intro_dialog.clone(),
+                            music: music.clone(),
+                            units: units.clone(),
                         };
                         path.set_extension("map");
                         let out_path = out_folder.join(l).join(path.file_name().unwrap());
                         let _ = std::fs::create_dir(out_folder.join(l));
-                        let mut out_file = std::fs::File::create(out_path).unwrap();
-                        rmp_serde::encode::write(&mut out_file, &new_map).unwrap();
+                        let bytes = serialization::write_map(&new_map).unwrap();
+                        std::fs::write(out_path, bytes).unwrap();
                     }
                 }
             }
@@ -1,225 +1,100 @@
+mod platform;
+
 use alemian_saga_core::*;
-use async_trait::async_trait;
+use futures::task::LocalSpawnExt;
 use futures::SinkExt;
-use ndarray::array;
+use platform::*;
 use Event::*;
 
-#[derive(Clone)]
-enum Drawing {
-    Image {
-        source: String,
-        x: u32,
-        y: u32,
-        w: u32,
-        h: u32,
-    },
-    Text {
-        txt: String,
-        tx: u32,
-        ty: u32,
-    },
-}
-
-struct TestPlatform {
-    drawings: std::sync::mpsc::Receiver<Drawing>,
-}
-
-#[async_trait(?Send)]
-impl alemian_saga_core::Platform for TestPlatform {
-    type Image = String;
-    type InputType = ();
-    type MouseDistance = u32;
-    type ScreenDistance = u32;
-    type ImageFuture = std::future::Ready<Option<Self::Image>>;
-    type File = std::io::Cursor<Vec<u8>>;
-    type Instant = ();
-    type Duration = u32;
-    fn draw_primitive(
-        &self,
-        img: &Self::Image,
-        left: Self::ScreenDistance,
-        top: Self::ScreenDistance,
-        width: Self::ScreenDistance,
-        height: Self::ScreenDistance,
-    ) {
-        println!("drawing {}", img);
-        match self
-            .drawings
-            .try_recv()
-            .expect(format!("unexpected draw: {}", img).as_str())
-        {
-            Drawing::Image { source, x, y, w, h } => {
-                assert_eq!(img, &source);
-                assert_eq!(left, x);
-                assert_eq!(top, y);
-                assert_eq!(width, w);
-                assert_eq!(height, h);
-            }
-            _ => panic!(),
-        }
-    }
-    fn draw_text_primitive(
-        &self,
-        text: &str,
-        x: Self::ScreenDistance,
-        y: Self::ScreenDistance,
-        _max_width: Self::ScreenDistance,
-    ) {
-        match self
-            .drawings
-            .try_recv()
-            .expect(format!("unexpected write: {}", text).as_str())
-        {
-            Drawing::Text { txt, tx, ty } => {
-                assert_eq!(&txt, text);
-                assert_eq!(tx, x);
-                assert_eq!(ty, y);
-            }
-            _ => panic!(),
-        }
-    }
-    fn string_to_input(_input: String) -> Self::InputType {
-        panic!();
-    }
-    fn get_width(&self) -> Self::ScreenDistance {
-        80
-    }
-    fn get_height(&self) -> Self::ScreenDistance {
-        60
-    }
-    fn get_image(path: &str) -> Self::ImageFuture {
-        std::future::ready(Some(path.to_owned()))
-    }
-    async fn get_file(&self, path: &str) -> Result<Self::File, String> {
-        if path == "lang/map.map" {
-            Ok(std::io::Cursor::new(
-                rmp_serde::encode::to_vec(&serialization::Map {
-                    tile_types: vec![
-                        serialization::TileType {
-                            image: "a".to_owned(),
-                            name: "a".to_owned(),
-                            defense: 0,
-                            evade: 10,
-                            move_cost: 20,
-                        },
-                        serialization::TileType {
-                            image: "b".to_owned(),
-                            name: "b".to_owned(),
-                            defense: 1,
-                            evade: 11,
-                            move_cost: 21,
-                        },
-                        serialization::TileType {
-                            image: "c".to_owned(),
-                            name: "c".to_owned(),
-                            defense: 2,
-                            evade: 12,
-                            move_cost: 22,
-                        },
-                        serialization::TileType {
-                            image: "d".to_owned(),
-                            name: "d".to_owned(),
-                            defense: 3,
-                            evade: 13,
-                            move_cost: 23,
-                        },
-                    ],
-                    map: array![[0, 1], [2, 3]],
-                })
-                .unwrap(),
-            ))
-        } else {
-            panic!("Unknown file: {}", path);
-        }
-    }
-    fn log(path: &str) {
-        println!("{}", path);
-    }
-    fn now() -> Self::Instant {}
-    fn nanoseconds(_ns: usize) -> Self::Duration {
-        0
-    }
-    fn duration_between(_first: Self::Instant, _second: Self::Instant) -> Self::Duration {
-        1
-    }
-}
-
-fn image(source: &str, x: u32, y: u32, width: u32, height: u32) -> Drawing {
-    Drawing::Image {
-        source: source.to_owned(),
-        x,
-        y,
-        w: width,
-        h: height,
-    }
-}
-
-fn expect_infobar(sender: &mut std::sync::mpsc::Sender<Drawing>, text: &str) {
-    let _ = sender.send(image("infobar.png", 0, 0, 16, 4));
+// Expects the infobar for a tile to be drawn with its left edge at x
+fn expect_infobar(sender: &mut std::sync::mpsc::Sender<Drawing>, text: &str, x: u32) {
+    let _ = sender.send(image("infobar.png", x, 0, 16, 4));
     let _ = sender.send(Drawing::Text {
         txt: text.to_owned(),
-        tx: 1,
+        tx: x + 1,
         ty: 1,
     });
-    let mut defense = "0";
-    let mut move_cost = "20";
-    let mut evade = "10";
-    match text {
-        "b" => {
-            defense = "1";
-            move_cost = "21";
-            evade = "11";
-        }
-        "c" => {
-            defense = "2";
-            move_cost = "22";
-            evade = "12";
-        }
-        "d" => {
-            defense = "3";
-            move_cost = "23";
-            evade = "13";
-        }
-        _ => {}
+    let (coordinates, defense, move_cost, evade) = match text {
+        "b" => ("(1, 0)", "1", "21", "11"),
+        "c" => ("(0, 1)", "2", "22", "12"),
+        "d" => ("(1, 1)", "3", "23", "13"),
+        _ => ("(0, 0)", "0", "20", "10"),
     };
+    let _ = sender.send(Drawing::Text {
+        txt: coordinates.to_owned(),
+        tx: x + 11,
+        ty: 1,
+    });
     let _ = sender.send(Drawing::Text {
         txt: move_cost.to_owned(),
-        tx: 3,
+        tx: x + 3,
         ty: 2,
     });
     let _ = sender.send(Drawing::Text {
         txt: defense.to_owned(),
-        tx: 7,
+        tx: x + 7,
         ty: 2,
     });
     let _ = sender.send(Drawing::Text {
         txt: evade.to_owned(),
-        tx: 12,
+        tx: x + 12,
         ty: 2,
     });
 }
 
+// Expects the loading screen to be drawn with a message
+fn expect_loading_screen(sender: &mut std::sync::mpsc::Sender<Drawing>, text: &str) {
+    let _ = sender.send(Drawing::Fill {
+        x: 0,
+        y: 0,
+        w: 80,
+        h: 60,
+        color: style::BLACK,
+    });
+    let _ = sender.send(Drawing::Text {
+        txt: text.to_owned(),
+        tx: 0,
+        ty: 30,
+    });
+}
+
+// Leaves out everything drawn up to and including the last frame of the loading screen
+fn after_loading_screen(drawings: &[Drawing]) -> Vec<Drawing> {
+    drawings
+        .iter()
+        .skip_while(|d| !matches!(d, Drawing::Text { txt, .. } if txt == "Starting…"))
+        .skip(1)
+        .cloned()
+        .collect()
+}
+
+fn expect_first_frame(sender: &mut std::sync::mpsc::Sender<Drawing>) {
+    for text in ["Loading…", "Loading images…", "Starting…"] {
+        expect_loading_screen(sender, text);
+    }
+    let _ = sender.send(image("a", 0, 0, 40, 30));
+    let _ = sender.send(image("b", 40, 0, 40, 30));
+    let _ = sender.send(image("c", 0, 30, 40, 30));
+    let _ = sender.send(image("d", 40, 30, 40, 30));
+    let _ = sender.send(image("cursor.png", 0, 0, 40, 30));
+    expect_infobar(sender, "a", 64);
+}
+
 async fn run_test() {
     let (mut drawing_sender, drawing_receiver) = std::sync::mpsc::channel();
     let (mut event_sender, event_receiver) = futures::channel::mpsc::channel(512);
     let mut tile_height = 30;
     let mut tile_width = 40;
 
-    let platform = TestPlatform {
-        drawings: drawing_receiver,
-    };
-    let game_future = alemian_saga_core::run(platform, event_receiver, "lang");
+    let platform = TestPlatform::new(drawing_receiver);
+    let game_future = alemian_saga_core::run(platform, event_receiver, "lang", "us");
 
-    let _ = drawing_sender.send(image("a", 0, 0, tile_width, tile_height));
-    let _ = drawing_sender.send(image("b", tile_width, 0, tile_width, tile_height));
-    let _ = drawing_sender.send(image("c", 0, tile_height, tile_width, tile_height));
-    let _ = drawing_sender.send(image("d", tile_width, tile_height, tile_width, tile_height));
-    let _ = drawing_sender.send(image("cursor.png", 0, 0, tile_width, tile_height));
-    expect_infobar(&mut drawing_sender, "a");
+    expect_first_frame(&mut drawing_sender);
 
     let _ = drawing_sender.send(image("a", 0, 0, tile_width, tile_height));
+    let _ = drawing_sender.send(image("b", tile_width, 0, tile_width, tile_height));
     let _ = drawing_sender.send(image("cursor.png", tile_width, 0, tile_width, tile_height));
-    expect_infobar(&mut drawing_sender, "b");
+    expect_infobar(&mut drawing_sender, "b", 0);
     event_sender.send(Right).await.unwrap();
 
     let _ = drawing_sender.send(image("b", tile_width, 0, tile_width, tile_height));
@@ -230,35 +105,35 @@ async fn run_test() {
         tile_width,
         tile_height,
     ));
-    expect_infobar(&mut drawing_sender, "d");
+    expect_infobar(&mut drawing_sender, "d", 0);
     event_sender.send(Down).await.unwrap();
 
     tile_height *= 2;
     let _ = drawing_sender.send(image("c", 0, 0, tile_width, tile_height));
     let _ = drawing_sender.send(image("d", tile_width, 0, tile_width, tile_height));
     let _ = drawing_sender.send(image("cursor.png", tile_width, 0, tile_width, tile_height));
-    expect_infobar(&mut drawing_sender, "d");
+    expect_infobar(&mut drawing_sender, "d", 0);
     event_sender.send(ZoomIn).await.unwrap();
 
     tile_width *= 2;
     let _ = drawing_sender.send(image("d", 0, 0, tile_width, tile_height));
     let _ = drawing_sender.send(image("cursor.png", 0, 0, tile_width, tile_height));
-    expect_infobar(&mut drawing_sender, "d");
+    expect_infobar(&mut drawing_sender, "d", 64);
     event_sender.send(ZoomIn).await.unwrap();
 
     let _ = drawing_sender.send(image("c", 0, 0, tile_width, tile_height));
     let _ = drawing_sender.send(image("cursor.png", 0, 0, tile_width, tile_height));
-    expect_infobar(&mut drawing_sender, "c");
+    expect_infobar(&mut drawing_sender, "c", 64);
     event_sender.send(Left).await.unwrap();
 
     let _ = drawing_sender.send(image("a", 0, 0, tile_width, tile_height));
     let _ = drawing_sender.send(image("cursor.png", 0, 0, tile_width, tile_height));
-    expect_infobar(&mut drawing_sender, "a");
+    expect_infobar(&mut drawing_sender, "a", 64);
     event_sender.send(Up).await.unwrap();
 
     let _ = drawing_sender.send(image("b", 0, 0, tile_width, tile_height));
     let _ = drawing_sender.send(image("cursor.png", 0, 0, tile_width, tile_height));
-    expect_infobar(&mut drawing_sender, "b");
+    expect_infobar(&mut drawing_sender, "b", 64);
     event_sender
         .send(MouseMove(Vector { x: 79, y: 30 }))
         .await
@@ -268,18 +143,19 @@ async fn run_test() {
     let _ = drawing_sender.send(image("a", 0, 0, tile_width, tile_height));
     let _ = drawing_sender.send(image("b", tile_width, 0, tile_width, tile_height));
     let _ = drawing_sender.send(image("cursor.png", tile_width, 0, tile_width, tile_height));
-    expect_infobar(&mut drawing_sender, "b");
+    expect_infobar(&mut drawing_sender, "b", 0);
     event_sender.send(ZoomOut).await.unwrap();
 
     let _ = drawing_sender.send(image("a", 0, 0, tile_width, tile_height));
     let _ = drawing_sender.send(image("b", tile_width, 0, tile_width, tile_height));
     let _ = drawing_sender.send(image("cursor.png", tile_width, 0, tile_width, tile_height));
-    expect_infobar(&mut drawing_sender, "b");
+    expect_infobar(&mut drawing_sender, "b", 0);
     event_sender.send(Redraw).await.unwrap();
 
     let _ = drawing_sender.send(image("b", tile_width, 0, tile_width, tile_height));
+    let _ = drawing_sender.send(image("a", 0, 0, tile_width, tile_height));
     let _ = drawing_sender.send(image("cursor.png", 0, 0, tile_width, tile_height));
-    expect_infobar(&mut drawing_sender, "a");
+    expect_infobar(&mut drawing_sender, "a", 64);
     event_sender
         .send(MouseMove(Vector { x: 0, y: 0 }))
         .await
@@ -290,7 +166,6036 @@ async fn run_test() {
     game_future.await;
 }
 
+// Transient network failures should be retried with exponential backoff
+async fn test_retry() {
+    let (mut drawing_sender, drawing_receiver) = std::sync::mpsc::channel();
+    let (event_sender, event_receiver) = futures::channel::mpsc::channel(512);
+    let platform = TestPlatform::new(drawing_receiver);
+    platform.transient_failures.set(3);
+    SLEEPS.with(|s| s.borrow_mut().clear());
+
+    expect_first_frame(&mut drawing_sender);
+    drop(event_sender);
+    alemian_saga_core::run(platform, event_receiver, "lang", "us").await;
+
+    let policy = fetch::RetryPolicy::default();
+    let sleeps: Vec<u64> = SLEEPS.with(|s| s.borrow().clone());
+    let backoffs: Vec<u64> = sleeps
+        .into_iter()
+        .filter(|d| *d != policy.timeout_ns)
+        .collect();
+    let expected: Vec<u64> = (0..3).map(|i| policy.initial_backoff_ns << i).collect();
+    assert_eq!(backoffs, expected);
+}
+
+// Once every attempt has failed, the game should stop and report the failure
+async fn test_retry_exhausted() {
+    let (mut drawing_sender, drawing_receiver) = std::sync::mpsc::channel();
+    let (_event_sender, event_receiver) = futures::channel::mpsc::channel(512);
+    let platform = TestPlatform::new(drawing_receiver);
+    platform.transient_failures.set(100);
+    LOG.with(|l| l.borrow_mut().clear());
+
+    // The loading screen is replaced by the error
+    let attempts = fetch::RetryPolicy::default().attempts;
+    let error = format!(
+        "Failed to retrieve lang/map.map: connection reset (gave up after {} attempts)",
+        attempts
+    );
+    expect_loading_screen(&mut drawing_sender, "Loading…");
+    expect_loading_screen(&mut drawing_sender, error.as_str());
+
+    alemian_saga_core::run(platform, event_receiver, "lang", "us").await;
+
+    let expected = format!("ERROR: {}", error);
+    assert!(LOG.with(|l| l.borrow().contains(&expected)));
+}
+
+// Hashes should match the published SHA-256 test vectors however the data is split up
+fn test_sha256() {
+    let hash = |chunks: &[&[u8]]| {
+        let mut hasher = sha256::Sha256::default();
+        for chunk in chunks {
+            hasher.update(chunk);
+        }
+        sha256::to_hex(&hasher.finish())
+    };
+    assert_eq!(
+        hash(&[]),
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+    );
+    assert_eq!(
+        hash(&[b"abc"]),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+    // Just too long for the padding to fit in one block
+    let two_blocks = b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq";
+    let expected = "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1";
+    assert_eq!(hash(&[two_blocks]), expected);
+    assert_eq!(
+        hash(&[&two_blocks[..7], &two_blocks[7..50], &two_blocks[50..]]),
+        expected
+    );
+    let million = vec![b'a'; 1_000_000];
+    assert_eq!(
+        hash(&million.chunks(999).collect::<Vec<_>>()),
+        "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0"
+    );
+}
+
+// Files listed in the manifest should only be used if they have the listed hash, and a file that
+// doesn't should be reported on the error screen rather than drawn as garbage
+async fn test_asset_checksums() {
+    let contents = rmp_serde::encode::to_vec(&small_map()).unwrap();
+    let hash = sha256::to_hex(&{
+        let mut hasher = sha256::Sha256::default();
+        hasher.update(&contents);
+        hasher.finish()
+    });
+    let platform = TestPlatform::recording()
+        .0
+        .with_map(&small_map())
+        .with_manifest(&[("lang/map.map", hash.to_uppercase())]);
+    assert!(game::Game::load(platform, "lang", "us").await.is_ok());
+
+    // A map cut short on the way is reported as corrupt rather than as failing to decode
+    let truncated = contents[..contents.len() - 1].to_vec();
+    REPLACED_FILES.with(|f| {
+        f.borrow_mut()
+            .insert("lang/map.map".to_owned(), Some(truncated))
+    });
+    let (platform, recording) = TestPlatform::recording();
+    // The settings file isn't listed, so it's used without being checked
+    let platform = platform
+        .with_settings(Some(r#"{ "show_minimap": false }"#))
+        .with_manifest(&[("lang/map.map", hash.clone())]);
+    match game::Game::load(platform, "lang", "us").await {
+        Err(Error::ChecksumMismatch {
+            path,
+            expected,
+            actual,
+        }) => {
+            assert_eq!(path, "lang/map.map");
+            assert_eq!(expected, hash);
+            assert_ne!(actual, hash);
+        }
+        _ => panic!("a truncated map should fail its checksum"),
+    }
+    assert!(recording.borrow().iter().any(|d| matches!(
+        d,
+        Drawing::Text { txt, .. } if txt.starts_with("Error: lang/map.map is corrupt")
+    )));
+    REPLACED_FILES.with(|f| f.borrow_mut().clear());
+}
+
+// Tile images should all be requested before any of them finishes loading
+async fn test_concurrent_image_loading() {
+    let (mut drawing_sender, drawing_receiver) = std::sync::mpsc::channel();
+    let (event_sender, event_receiver) = futures::channel::mpsc::channel(512);
+    let platform = TestPlatform::new(drawing_receiver);
+    IMAGE_LOADS.with(|l| l.borrow_mut().clear());
+
+    expect_first_frame(&mut drawing_sender);
+    drop(event_sender);
+    alemian_saga_core::run(platform, event_receiver, "lang", "us").await;
+
+    let loads: Vec<ImageLoad> = IMAGE_LOADS.with(|l| {
+        l.borrow()
+            .iter()
+            .filter(|(_, path)| path.len() == 1)
+            .map(|(load, _)| *load)
+            .collect()
+    });
+    assert_eq!(loads.len(), 8);
+    assert!(loads[..4].iter().all(|l| *l == ImageLoad::Started));
+}
+
+// Only the last of several queued mouse movements should be processed
+async fn test_mouse_move_coalescing() {
+    let (mut drawing_sender, drawing_receiver) = std::sync::mpsc::channel();
+    let (mut event_sender, event_receiver) = futures::channel::mpsc::channel(512);
+    let platform = TestPlatform::new(drawing_receiver);
+
+    expect_first_frame(&mut drawing_sender);
+    let _ = drawing_sender.send(image("a", 0, 0, 40, 30));
+    let _ = drawing_sender.send(image("b", 40, 0, 40, 30));
+    let _ = drawing_sender.send(image("cursor.png", 40, 30, 40, 30));
+    expect_infobar(&mut drawing_sender, "d", 0);
+    let _ = drawing_sender.send(image("d", 40, 30, 40, 30));
+    let _ = drawing_sender.send(image("cursor.png", 0, 30, 40, 30));
+    expect_infobar(&mut drawing_sender, "c", 0);
+
+    for &(x, y) in [(79, 0), (0, 30), (79, 30)].iter() {
+        event_sender.send(MouseMove(Vector { x, y })).await.unwrap();
+    }
+    event_sender.send(Left).await.unwrap();
+    drop(event_sender);
+    alemian_saga_core::run(platform, event_receiver, "lang", "us").await;
+}
+
+// Closing the queue should stop the game even while other senders are still around, after the
+// events already sent have been handled
+fn test_close_queue() {
+    let (platform, recording) = TestPlatform::recording();
+    let (sender, event_receiver) = futures::channel::mpsc::channel(8);
+    let mut event_queue = queue::OrderedSender::new(sender);
+    // Stands in for the senders held by a platform's event listeners
+    let mut listener_queue = event_queue.clone();
+    let mut pool = futures::executor::LocalPool::new();
+    let game_future = alemian_saga_core::run(platform, event_receiver, "lang", "us");
+    let _ = pool.spawner().spawn_local(game_future);
+
+    assert!(listener_queue.send(Right).is_none());
+    event_queue.close();
+    assert!(listener_queue.send(Down).is_none());
+    pool.run();
+    assert_eq!(last_cursor(&recording), Some((40, 0)));
+}
+
+// Events sent faster than the game can handle them should still be handled in order, however small
+// the queue is, and the ones that didn't fit should be counted
+fn test_event_ordering() {
+    for capacity in [0, 1] {
+        check_event_ordering(capacity);
+    }
+}
+
+fn check_event_ordering(capacity: usize) {
+    let (platform, recording) = TestPlatform::recording();
+    let (mut event_queue, event_receiver) = queue::channel(capacity);
+    let mut pool = futures::executor::LocalPool::new();
+    let spawner = pool.spawner();
+    let game_future = alemian_saga_core::run(platform, event_receiver, "lang", "us");
+    let _ = spawner.spawn_local(game_future);
+
+    // Apply the events to a model of the 2x2 map as they're sent
+    let mut cursor = (0, 0);
+    let mut seed: u32 = 12345;
+    for _ in 0..1000 {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        let event = match (seed >> 16) % 5 {
+            0 => Right,
+            1 => Left,
+            2 => Up,
+            3 => Down,
+            _ => MouseMove(Vector {
+                x: (seed >> 8) % 80,
+                y: (seed >> 4) % 60,
+            }),
+        };
+        cursor = match event {
+            Right => (1, cursor.1),
+            Left => (0, cursor.1),
+            Up => (cursor.0, 0),
+            Down => (cursor.0, 1),
+            MouseMove(pos) => (pos.x / 40, pos.y / 30),
+            _ => cursor,
+        };
+        if let Some(forward) = event_queue.send(event) {
+            let _ = spawner.spawn_local(forward);
+        }
+    }
+    assert!(event_queue.overflows() > 0);
+    drop(event_queue);
+    pool.run();
+
+    let last_cursor = recording.borrow().iter().rev().find_map(|d| match d {
+        Drawing::Image { source, x, y, .. } if source == "cursor.png" => Some((x / 40, y / 30)),
+        _ => None,
+    });
+    assert_eq!(last_cursor, Some(cursor));
+}
+
+// Scrolling the screen should animate through intermediate positions
+fn test_smooth_scrolling() {
+    let (platform, recording) = TestPlatform::recording();
+    let (mut event_sender, event_receiver) = futures::channel::mpsc::channel(512);
+    let mut pool = futures::executor::LocalPool::new();
+    NOW_STEP.with(|s| s.set(0));
+    let game_future = alemian_saga_core::run(platform, event_receiver, "lang", "us");
+    let _ = pool.spawner().spawn_local(game_future);
+    pool.run_until_stalled();
+
+    // Zoom in so that only the top row is visible, then scroll down to the bottom row
+    let _ = event_sender.try_send(ZoomIn);
+    pool.run_until_stalled();
+    recording.borrow_mut().clear();
+    let _ = event_sender.try_send(Down);
+    pool.run_until_stalled();
+
+    let cursor_heights: Vec<u32> = recording
+        .borrow()
+        .iter()
+        .filter_map(|d| match d {
+            Drawing::Image { source, y, .. } if source == "cursor.png" => Some(*y),
+            _ => None,
+        })
+        .collect();
+    assert!(cursor_heights.len() > 2);
+    // Nothing is drawn until the next frame, by which point the scroll has already started
+    assert!(cursor_heights.first().is_some_and(|h| *h < 60));
+    assert_eq!(cursor_heights.last(), Some(&0));
+    assert!(cursor_heights.windows(2).all(|w| w[1] <= w[0]));
+
+    NOW_STEP.with(|s| s.set(1_000_000_000));
+    drop(event_sender);
+    pool.run();
+}
+
+// A burst of events should only be drawn once, when the platform is ready for the next frame
+fn test_frame_pacing() {
+    let (platform, recording) = TestPlatform::recording();
+    let (mut event_sender, event_receiver) = futures::channel::mpsc::channel(512);
+    let mut pool = futures::executor::LocalPool::new();
+    MANUAL_FRAMES.with(|m| m.set(true));
+    let game_future = alemian_saga_core::run(platform, event_receiver, "lang", "us");
+    let _ = pool.spawner().spawn_local(game_future);
+    pool.run_until_stalled();
+    let cursor_count = |recording: &Recording| {
+        recording
+            .borrow()
+            .iter()
+            .filter(|d| matches!(d, Drawing::Image { source, .. } if source == "cursor.png"))
+            .count()
+    };
+
+    recording.borrow_mut().clear();
+    for event in [ZoomIn, ZoomOut, ZoomIn] {
+        let _ = event_sender.try_send(event);
+    }
+    pool.run_until_stalled();
+    assert_eq!(cursor_count(&recording), 0);
+
+    pump_frame();
+    pool.run_until_stalled();
+    assert_eq!(cursor_count(&recording), 1);
+
+    // Nothing has changed since the last frame
+    pump_frame();
+    pool.run_until_stalled();
+    assert_eq!(cursor_count(&recording), 1);
+
+    // Moving the cursor around within the screen is drawn once per frame too
+    for round in 0..2 {
+        recording.borrow_mut().clear();
+        for event in [
+            Right,
+            MouseMove(Vector { x: 5, y: 5 }),
+            Right,
+            MouseMove(Vector { x: 50, y: 35 }),
+            Left,
+        ] {
+            let _ = event_sender.try_send(event);
+        }
+        pool.run_until_stalled();
+        assert_eq!(cursor_count(&recording), 0, "round {}", round);
+        pump_frame();
+        pool.run_until_stalled();
+        assert_eq!(cursor_count(&recording), 1, "round {}", round);
+    }
+    pump_frame();
+    pool.run_until_stalled();
+    assert_eq!(cursor_count(&recording), 1);
+
+    MANUAL_FRAMES.with(|m| m.set(false));
+    drop(event_sender);
+    pool.run();
+}
+
+// Zooming with the mouse should keep the tile under the mouse in place
+async fn test_zoom_at_mouse() {
+    let (mut drawing_sender, drawing_receiver) = std::sync::mpsc::channel();
+    let (mut event_sender, event_receiver) = futures::channel::mpsc::channel(512);
+    let platform = TestPlatform::new(drawing_receiver);
+
+    expect_first_frame(&mut drawing_sender);
+    let _ = drawing_sender.send(image("c", 0, 0, 40, 60));
+    let _ = drawing_sender.send(image("d", 40, 0, 40, 60));
+    let _ = drawing_sender.send(image("cursor.png", 40, 0, 40, 60));
+    expect_infobar(&mut drawing_sender, "d", 0);
+
+    event_sender
+        .send(ZoomInAt(Vector { x: 79, y: 59 }))
+        .await
+        .unwrap();
+    drop(event_sender);
+    alemian_saga_core::run(platform, event_receiver, "lang", "us").await;
+}
+
+// Finds the tiles drawn in the most recent frame as the top left tile and the number of columns
+// and rows
+fn last_visible_tiles(recording: &Recording) -> ((u32, u32), (u32, u32)) {
+    let drawings = recording.borrow();
+    let cursor = drawings
+        .iter()
+        .rposition(|d| matches!(d, Drawing::Image { source, .. } if source == "cursor.png"))
+        .expect("No frame was drawn");
+    // Fills drawn over the tiles, such as the scroll indicator, are skipped
+    let tiles: Vec<(u32, u32)> = drawings[..cursor]
+        .iter()
+        .rev()
+        .filter(|d| !matches!(d, Drawing::Fill { .. }))
+        .map_while(|d| match d {
+            Drawing::Image { source, .. } => {
+                let (x, y) = source.split_once(',')?;
+                Some((x.parse().ok()?, y.parse().ok()?))
+            }
+            _ => None,
+        })
+        .collect();
+    let left = tiles.iter().map(|t| t.0).min().unwrap();
+    let top = tiles.iter().map(|t| t.1).min().unwrap();
+    let right = tiles.iter().map(|t| t.0).max().unwrap();
+    let bottom = tiles.iter().map(|t| t.1).max().unwrap();
+    ((left, top), (right - left + 1, bottom - top + 1))
+}
+
+// Runs the game on a grid map with the given settings and returns the tiles visible at the end
+async fn zoom_scenario(
+    columns: usize,
+    rows: usize,
+    settings: &str,
+    events: Vec<Event<u32, String>>,
+) -> ((u32, u32), (u32, u32)) {
+    let (platform, recording) = TestPlatform::recording();
+    let platform = platform
+        .with_map(&grid_map(columns, rows))
+        .with_settings(Some(settings));
+    let (mut event_sender, event_receiver) = futures::channel::mpsc::channel(512);
+    for event in events {
+        event_sender.send(event).await.unwrap();
+    }
+    drop(event_sender);
+    alemian_saga_core::run(platform, event_receiver, "lang", "us").await;
+    last_visible_tiles(&recording)
+}
+
+async fn test_zoom_limits() {
+    let limits = r#"{ "min_visible_tiles": 4, "max_visible_tiles": 6, "zoom_step": 2 }"#;
+
+    // The screen starts at the maximum size and can't grow past it
+    let visible = zoom_scenario(8, 6, limits, vec![]).await;
+    assert_eq!(visible, ((0, 0), (6, 6)));
+    let visible = zoom_scenario(
+        8,
+        6,
+        limits,
+        vec![ZoomOut, ZoomOutAt(Vector { x: 0, y: 0 })],
+    )
+    .await;
+    assert_eq!(visible, ((0, 0), (6, 6)));
+
+    // Zooming in at the bottom right corner stops at the minimum size and stays in the map
+    let corner = Vector { x: 79, y: 59 };
+    let visible = zoom_scenario(8, 6, limits, vec![ZoomInAt(corner)]).await;
+    assert_eq!(visible, ((0, 2), (6, 4)));
+    let events = vec![ZoomInAt(corner), ZoomInAt(corner)];
+    let visible = zoom_scenario(8, 6, limits, events).await;
+    assert_eq!(visible, ((2, 2), (4, 4)));
+    let events = vec![ZoomInAt(corner), ZoomInAt(corner), ZoomInAt(corner)];
+    let visible = zoom_scenario(8, 6, limits, events).await;
+    assert_eq!(visible, ((2, 2), (4, 4)));
+
+    // Zooming back out at the top left corner keeps the left column in place up to the maximum size
+    let top_left = Vector { x: 0, y: 0 };
+    let events = vec![
+        ZoomInAt(corner),
+        ZoomInAt(corner),
+        ZoomOutAt(top_left),
+        ZoomOutAt(top_left),
+    ];
+    let visible = zoom_scenario(8, 6, limits, events).await;
+    assert_eq!(visible, ((2, 0), (6, 6)));
+
+    // Maps smaller than the minimum can't be zoomed at all
+    let events = vec![ZoomIn, ZoomInAt(corner), ZoomOut];
+    let visible = zoom_scenario(3, 2, limits, events).await;
+    assert_eq!(visible, ((0, 0), (3, 2)));
+
+    // Without a maximum the whole map is visible, and each zoom changes the size by the step
+    let step = r#"{ "min_visible_tiles": 2, "zoom_step": 3 }"#;
+    let visible = zoom_scenario(8, 6, step, vec![ZoomInAt(Vector { x: 0, y: 0 })]).await;
+    assert_eq!(visible, ((0, 0), (6, 4)));
+}
+
+// The second line of the infobar should be left out when the text is too tall to fit it
+async fn test_infobar_text_height() {
+    let (mut platform, recording) = TestPlatform::recording();
+    platform.text_height = Some(3);
+    let (event_sender, event_receiver) = futures::channel::mpsc::channel(512);
+    drop(event_sender);
+    alemian_saga_core::run(platform, event_receiver, "lang", "us").await;
+    let text: Vec<Drawing> = after_loading_screen(&recording.borrow())
+        .into_iter()
+        .filter(|d| matches!(d, Drawing::Text { .. }))
+        .collect();
+    let expected = vec![
+        Drawing::Text {
+            txt: "a".to_owned(),
+            tx: 65,
+            ty: 1,
+        },
+        Drawing::Text {
+            txt: "(0, 0)".to_owned(),
+            tx: 75,
+            ty: 1,
+        },
+    ];
+    assert_eq!(text, expected);
+}
+
+// Tile names too wide for the infobar should be cut short with an ellipsis
+async fn test_long_tile_name() {
+    let mut map = small_map();
+    map.tile_types[0].name = "Impassable Mountain Fortress".to_owned();
+    let (platform, recording) = TestPlatform::recording();
+    let platform = platform.with_map(&map);
+    let (event_sender, event_receiver) = futures::channel::mpsc::channel(512);
+    drop(event_sender);
+    alemian_saga_core::run(platform, event_receiver, "lang", "us").await;
+    let name = Drawing::Text {
+        txt: "Impassab…".to_owned(),
+        tx: 65,
+        ty: 1,
+    };
+    assert!(recording.borrow().contains(&name));
+}
+
+// Labels and values on the infobar should be drawn in their own styles
+async fn test_infobar_styles() {
+    TEXT_STYLES.with(|s| s.borrow_mut().clear());
+    let (platform, _) = TestPlatform::recording();
+    let (event_sender, event_receiver) = futures::channel::mpsc::channel(512);
+    drop(event_sender);
+    alemian_saga_core::run(platform, event_receiver, "lang", "us").await;
+    let expected = vec![
+        ("Loading…".to_owned(), style::LOADING_SCREEN),
+        ("Loading images…".to_owned(), style::LOADING_SCREEN),
+        ("Starting…".to_owned(), style::LOADING_SCREEN),
+        ("a".to_owned(), style::INFOBAR_LABEL),
+        ("(0, 0)".to_owned(), style::INFOBAR_VALUE),
+        ("20".to_owned(), style::INFOBAR_VALUE),
+        ("0".to_owned(), style::INFOBAR_VALUE),
+        ("10".to_owned(), style::INFOBAR_VALUE),
+    ];
+    TEXT_STYLES.with(|s| assert_eq!(*s.borrow(), expected));
+}
+
+// Runs the game until the first frame is drawn and returns everything that was drawn
+async fn first_frame(map: &serialization::Map) -> Vec<Drawing> {
+    let (platform, recording) = TestPlatform::recording();
+    let platform = platform.with_map(map);
+    let (event_sender, event_receiver) = futures::channel::mpsc::channel(512);
+    drop(event_sender);
+    alemian_saga_core::run(platform, event_receiver, "lang", "us").await;
+    let drawings = after_loading_screen(&recording.borrow());
+    drawings
+}
+
+// Parts of the screen that the map doesn't cover should be cleared (or filled with the map's
+// background color) before the tiles are drawn
+async fn test_uncovered_regions() {
+    // 80 pixels don't divide evenly into 3 columns, leaving a 2 pixel strip on the right
+    let drawings = first_frame(&grid_map(3, 2)).await;
+    assert_eq!(drawings[0], Drawing::Clear);
+    assert_eq!(drawings.iter().filter(|d| **d == Drawing::Clear).count(), 1);
+
+    let color = style::Color::rgb(10, 20, 30);
+    let mut map = grid_map(3, 2);
+    map.background = Some(color);
+    let drawings = first_frame(&map).await;
+    let strip = Drawing::Fill {
+        x: 78,
+        y: 0,
+        w: 2,
+        h: 60,
+        color,
+    };
+    assert_eq!(drawings[0], strip);
+    assert!(!drawings.contains(&Drawing::Clear));
+
+    // Maps that cover the whole screen don't need anything cleared
+    let mut map = small_map();
+    map.background = Some(color);
+    let drawings = first_frame(&map).await;
+    assert!(!drawings.contains(&Drawing::Clear));
+    assert!(!drawings.iter().any(|d| matches!(d, Drawing::Fill { .. })));
+}
+
+// Selected tiles should be tinted under the cursor until the selection is cancelled
+async fn test_highlights() {
+    let (mut drawing_sender, drawing_receiver) = std::sync::mpsc::channel();
+    let (mut event_sender, event_receiver) = futures::channel::mpsc::channel(512);
+    let platform = TestPlatform::new(drawing_receiver);
+    let highlight = |x, y| Drawing::Fill {
+        x,
+        y,
+        w: 40,
+        h: 30,
+        color: style::HighlightKind::Selection.color(),
+    };
+
+    expect_first_frame(&mut drawing_sender);
+
+    let _ = drawing_sender.send(image("a", 0, 0, 40, 30));
+    let _ = drawing_sender.send(highlight(0, 0));
+    let _ = drawing_sender.send(image("cursor.png", 0, 0, 40, 30));
+    expect_infobar(&mut drawing_sender, "a", 64);
+    event_sender.send(Select).await.unwrap();
+
+    let _ = drawing_sender.send(image("a", 0, 0, 40, 30));
+    let _ = drawing_sender.send(highlight(0, 0));
+    let _ = drawing_sender.send(image("b", 40, 0, 40, 30));
+    let _ = drawing_sender.send(image("cursor.png", 40, 0, 40, 30));
+    expect_infobar(&mut drawing_sender, "b", 0);
+    event_sender.send(Right).await.unwrap();
+
+    let _ = drawing_sender.send(image("a", 0, 0, 40, 30));
+    let _ = drawing_sender.send(highlight(0, 0));
+    let _ = drawing_sender.send(image("b", 40, 0, 40, 30));
+    let _ = drawing_sender.send(image("c", 0, 30, 40, 30));
+    let _ = drawing_sender.send(image("d", 40, 30, 40, 30));
+    let _ = drawing_sender.send(image("cursor.png", 40, 0, 40, 30));
+    expect_infobar(&mut drawing_sender, "b", 0);
+    event_sender.send(Redraw).await.unwrap();
+
+    let _ = drawing_sender.send(image("a", 0, 0, 40, 30));
+    let _ = drawing_sender.send(image("cursor.png", 40, 0, 40, 30));
+    expect_infobar(&mut drawing_sender, "b", 0);
+    event_sender.send(Cancel).await.unwrap();
+
+    drop(event_sender);
+    alemian_saga_core::run(platform, event_receiver, "lang", "us").await;
+}
+
+// Fills that outline the visible part of the map on an 8x6 minimap with 2x2 tiles
+fn minimap_outline(left: u32, top: u32, columns: u32, rows: u32) -> Vec<Drawing> {
+    let (x, y, w, h) = (64 + left * 2, 48 + top * 2, columns * 2, rows * 2);
+    let edge = |x, y, w, h| Drawing::Fill {
+        x,
+        y,
+        w,
+        h,
+        color: style::WHITE,
+    };
+    vec![
+        edge(x, y, w, 1),
+        edge(x, y, 1, h),
+        edge(x, y + h - 1, w, 1),
+        edge(x + w - 1, y, 1, h),
+    ]
+}
+
+// The minimap should show the whole map, and clicking on it should jump to the clicked tile
+async fn test_minimap() {
+    let color = style::Color::rgb(0, 100, 0);
+    let mut map = grid_map(8, 6);
+    for tile_type in map.tile_types.iter_mut() {
+        tile_type.minimap_color = Some(color);
+    }
+    let (platform, recording) = TestPlatform::recording();
+    let platform = platform.with_map(&map).with_settings(Some(
+        r#"{ "min_visible_tiles": 2, "show_minimap": true, "show_input_hints": false }"#,
+    ));
+    let (mut event_sender, event_receiver) = futures::channel::mpsc::channel(512);
+    event_sender.send(ZoomIn).await.unwrap();
+    event_sender
+        .send(Click(Vector { x: 77, y: 56 }))
+        .await
+        .unwrap();
+    drop(event_sender);
+    alemian_saga_core::run(platform, event_receiver, "lang", "us").await;
+
+    let fills: Vec<Drawing> = after_loading_screen(&recording.borrow())
+        .into_iter()
+        .filter(|d| matches!(d, Drawing::Fill { .. }))
+        .collect();
+    let mut first_frame: Vec<Drawing> = (0..6)
+        .map(|row| Drawing::Fill {
+            x: 64,
+            y: 48 + row * 2,
+            w: 16,
+            h: 2,
+            color,
+        })
+        .collect();
+    first_frame.extend(minimap_outline(0, 0, 8, 6));
+    assert_eq!(fills[..10], first_frame[..]);
+    assert_eq!(fills[fills.len() - 4..], minimap_outline(1, 1, 7, 5)[..]);
+    assert_eq!(last_visible_tiles(&recording), ((1, 1), (7, 5)));
+}
+
+// With fog of war, only tiles that have been near the cursor should be drawn, and tiles that
+// the cursor has moved away from should be darkened
+async fn test_fog_of_war() {
+    let mut map = grid_map(8, 6);
+    map.fog = true;
+    let (platform, recording) = TestPlatform::recording();
+    let platform = platform
+        .with_map(&map)
+        .with_settings(Some(r#"{ "sight_range": 1, "show_minimap": false }"#));
+    let (mut event_sender, event_receiver) = futures::channel::mpsc::channel(512);
+    event_sender.send(Right).await.unwrap();
+    drop(event_sender);
+    alemian_saga_core::run(platform, event_receiver, "lang", "us").await;
+
+    let drawings = recording.borrow();
+    let cursor = drawings
+        .iter()
+        .position(|d| matches!(d, Drawing::Image { source, .. } if source == "cursor.png"))
+        .unwrap();
+    let (first_frame, after_move) = drawings.split_at(cursor);
+    let hidden = |x, y| Drawing::Fill {
+        x,
+        y,
+        w: 10,
+        h: 10,
+        color: style::BLACK,
+    };
+    assert!(first_frame.contains(&image("0,0", 0, 0, 10, 10)));
+    assert!(first_frame.contains(&image("1,0", 10, 0, 10, 10)));
+    assert!(first_frame.contains(&image("0,1", 0, 10, 10, 10)));
+    assert!(first_frame.contains(&hidden(20, 0)));
+    assert!(first_frame.contains(&hidden(10, 10)));
+    assert!(!first_frame
+        .iter()
+        .any(|d| matches!(d, Drawing::Image { source, .. } if source == "2,0")));
+
+    let explored = Drawing::Fill {
+        x: 0,
+        y: 10,
+        w: 10,
+        h: 10,
+        color: style::Color {
+            alpha: 128,
+            ..style::BLACK
+        },
+    };
+    assert!(after_move.contains(&image("2,0", 20, 0, 10, 10)));
+    assert!(after_move.contains(&image("1,1", 10, 10, 10, 10)));
+    assert!(after_move.contains(&image("0,1", 0, 10, 10, 10)));
+    assert!(after_move.contains(&explored));
+}
+
+// Image smoothing should be set on the platform from the settings file
+async fn test_image_smoothing() {
+    for &enabled in [false, true].iter() {
+        SMOOTHING.with(|s| s.set(None));
+        let settings = format!(r#"{{ "image_smoothing": {} }}"#, enabled);
+        let (platform, _) = TestPlatform::recording();
+        let platform = platform.with_settings(Some(settings.as_str()));
+        let (event_sender, event_receiver) = futures::channel::mpsc::channel(512);
+        drop(event_sender);
+        alemian_saga_core::run(platform, event_receiver, "lang", "us").await;
+        assert_eq!(SMOOTHING.with(|s| s.get()), Some(enabled));
+    }
+}
+
+// Panning the screen with the mouse should never leave the cursor outside of the screen
+async fn test_cursor_follows_pan() {
+    let (platform, recording) = TestPlatform::recording();
+    let settings = r#"{ "min_visible_tiles": 1, "max_visible_tiles": 3, "show_minimap": false }"#;
+    let platform = platform
+        .with_map(&grid_map(9, 2))
+        .with_settings(Some(settings));
+    let (mut event_sender, event_receiver) = futures::channel::mpsc::channel(512);
+    // The last two pixels of the screen are past the last visible column; redraws between the
+    // mouse movements keep them from being coalesced
+    for _ in 0..8 {
+        event_sender
+            .send(MouseMove(Vector { x: 79, y: 30 }))
+            .await
+            .unwrap();
+        event_sender.send(Redraw).await.unwrap();
+    }
+    drop(event_sender);
+    alemian_saga_core::run(platform, event_receiver, "lang", "us").await;
+
+    let drawings = recording.borrow();
+    assert!(drawings.iter().all(|d| match d {
+        Drawing::Image { source, x, .. } if source == "cursor.png" => *x + 26 <= 78,
+        _ => true,
+    }));
+    drop(drawings);
+    assert_eq!(last_visible_tiles(&recording), ((6, 0), (3, 2)));
+    let drawings = recording.borrow();
+    let cursor = drawings
+        .iter()
+        .rev()
+        .find(|d| matches!(d, Drawing::Image { source, .. } if source == "cursor.png"));
+    // The cursor is pulled along by the left edge of the screen rather than jumping to the mouse
+    assert_eq!(cursor, Some(&image("cursor.png", 0, 0, 26, 30)));
+}
+
+// The game should stop handling events and say goodbye once it's told to quit, even if the event
+// queue is still open
+async fn test_quit() {
+    let (platform, recording) = TestPlatform::recording();
+    let (mut event_sender, event_receiver) = futures::channel::mpsc::channel(512);
+    for event in [Quit, Right, ZoomIn] {
+        event_sender.send(event).await.unwrap();
+    }
+    alemian_saga_core::run(platform, event_receiver, "lang", "us").await;
+    drop(event_sender);
+
+    let drawings = recording.borrow();
+    assert_eq!(
+        drawings[drawings.len() - 2..],
+        [
+            Drawing::Fill {
+                x: 0,
+                y: 0,
+                w: 80,
+                h: 60,
+                color: style::BLACK
+            },
+            Drawing::Text {
+                txt: "Goodbye".to_owned(),
+                tx: 0,
+                ty: 30
+            },
+        ]
+    );
+    assert!(!drawings.iter().any(
+        |d| matches!(d, Drawing::Image { source, x, .. } if source == "cursor.png" && *x != 0)
+    ));
+}
+
+// A scroll shouldn't advance while the game is suspended and should pick up where it left off
+fn test_suspend() {
+    let (platform, recording) = TestPlatform::recording();
+    let (mut event_sender, event_receiver) = futures::channel::mpsc::channel(512);
+    let mut pool = futures::executor::LocalPool::new();
+    NOW_STEP.with(|s| s.set(0));
+    let game_future = alemian_saga_core::run(platform, event_receiver, "lang", "us");
+    let _ = pool.spawner().spawn_local(game_future);
+    pool.run_until_stalled();
+
+    let _ = event_sender.try_send(ZoomIn);
+    pool.run_until_stalled();
+    recording.borrow_mut().clear();
+    let _ = event_sender.try_send(Down);
+    let _ = event_sender.try_send(Suspend);
+    pool.run_until_stalled();
+    let cursor_heights = |recording: &Recording| -> Vec<u32> {
+        recording
+            .borrow()
+            .iter()
+            .filter_map(|d| match d {
+                Drawing::Image { source, y, .. } if source == "cursor.png" => Some(*y),
+                _ => None,
+            })
+            .collect()
+    };
+    // Only the frame drawn before the game was suspended
+    let suspended_heights = cursor_heights(&recording);
+    assert_eq!(suspended_heights.len(), 1);
+    assert!(suspended_heights[0] > 0 && suspended_heights[0] < 60);
+
+    // Time spent suspended shouldn't count towards the scroll, so resuming redraws the screen
+    // partway through the scroll before continuing it
+    CLOCK.with(|c| c.set(c.get() + 1_000_000_000));
+    recording.borrow_mut().clear();
+    let _ = event_sender.try_send(Resume);
+    pool.run_until_stalled();
+    let heights = cursor_heights(&recording);
+    assert!(heights.len() > 2);
+    assert!(heights[0] > 0 && heights[0] <= suspended_heights[0]);
+    assert_eq!(heights.last(), Some(&0));
+    assert!(heights.windows(2).all(|w| w[1] <= w[0]));
+
+    NOW_STEP.with(|s| s.set(1_000_000_000));
+    drop(event_sender);
+    pool.run();
+}
+
+// Finds the position of the cursor in the most recent frame
+fn last_cursor(recording: &Recording) -> Option<(u32, u32)> {
+    recording.borrow().iter().rev().find_map(|d| match d {
+        Drawing::Image { source, x, y, .. } if source == "cursor.png" => Some((*x, *y)),
+        _ => None,
+    })
+}
+
+// Keys bound while the game is running should take effect immediately and be remembered by the
+// next game
+async fn test_rebind() {
+    STORAGE.with(|s| s.borrow_mut().clear());
+    let (platform, recording) = TestPlatform::recording();
+    let (mut event_sender, event_receiver) = futures::channel::mpsc::channel(512);
+    let key = || Input("x".to_owned());
+    for event in [key(), StartRebind(Action::Right), key()] {
+        event_sender.send(event).await.unwrap();
+    }
+    drop(event_sender);
+    alemian_saga_core::run(platform, event_receiver, "lang", "us").await;
+    assert_eq!(last_cursor(&recording), Some((0, 0)));
+    assert!(recording.borrow().contains(&Drawing::Text {
+        txt: "Press a key for Right".to_owned(),
+        tx: 0,
+        ty: 30
+    }));
+    let saved = STORAGE.with(|s| s.borrow().get("keybindings").cloned());
+    assert!(saved.unwrap().contains(r#""Right":["x"]"#));
+
+    let (platform, recording) = TestPlatform::recording();
+    let (mut event_sender, event_receiver) = futures::channel::mpsc::channel(512);
+    event_sender.send(key()).await.unwrap();
+    drop(event_sender);
+    alemian_saga_core::run(platform, event_receiver, "lang", "us").await;
+    assert_eq!(last_cursor(&recording), Some((40, 0)));
+    STORAGE.with(|s| s.borrow_mut().clear());
+}
+
+// Each key bound to an action should trigger it, and rebinding a key should only affect that key
+fn test_bindings_lookup() {
+    let keybindings = bindings::KeybindingsFile {
+        map: bindings::Keybindings {
+            Up: vec!["k".to_owned(), "w".to_owned()],
+            Down: vec!["j".to_owned()],
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let map = [bindings::Context::Map];
+    let mut table = bindings::Bindings::new(keybindings, |k| k);
+    assert_eq!(table.get(&map, &"k".to_owned()), Some(Action::Up));
+    assert_eq!(table.get(&map, &"w".to_owned()), Some(Action::Up));
+    assert_eq!(table.get(&map, &"j".to_owned()), Some(Action::Down));
+    assert_eq!(table.get(&map, &"q".to_owned()), None);
+
+    table.rebind(bindings::Context::Map, Action::Down, "k".to_owned());
+    assert_eq!(table.get(&map, &"k".to_owned()), Some(Action::Down));
+    assert_eq!(table.get(&map, &"w".to_owned()), Some(Action::Up));
+    assert_eq!(table.get(&map, &"j".to_owned()), None);
+    let keybindings = &table.keybindings().map;
+    assert_eq!(keybindings.keys(Action::Up), ["w"]);
+    assert_eq!(keybindings.keys(Action::Down), ["k"]);
+}
+
+// Keys from the keybindings file should be translated into game events
+async fn test_keybindings_file() {
+    let (platform, recording) = TestPlatform::recording();
+    let platform = platform
+        .with_keybindings(r#"{ "Right": ["l", "ArrowRight"], "Left": ["h"], "Down": ["j"] }"#);
+    let (mut event_sender, event_receiver) = futures::channel::mpsc::channel(512);
+    for key in ["l", "h", "ArrowRight", "j"].iter() {
+        event_sender.send(Input((*key).to_owned())).await.unwrap();
+    }
+    drop(event_sender);
+    alemian_saga_core::run(platform, event_receiver, "lang", "us").await;
+    assert_eq!(last_cursor(&recording), Some((40, 30)));
+
+    // Platforms may keep the keybindings file somewhere else
+    let (mut platform, recording) = TestPlatform::recording();
+    platform.keybindings_path = Some("custom/keys.json".to_owned());
+    let keybindings = r#"{ "Down": ["j"] }"#.as_bytes().to_vec();
+    platform
+        .files
+        .insert("custom/keys.json".to_owned(), keybindings);
+    let (mut event_sender, event_receiver) = futures::channel::mpsc::channel(512);
+    event_sender.send(Input("j".to_owned())).await.unwrap();
+    drop(event_sender);
+    alemian_saga_core::run(platform, event_receiver, "lang", "us").await;
+    assert_eq!(last_cursor(&recording), Some((0, 30)));
+}
+
+// Keys bound in the innermost context should take priority, with anything unbound there falling
+// back to the map context
+fn test_binding_contexts() {
+    let file = r#"{
+        "Right": ["l"],
+        "Down": ["j", "ArrowDown"],
+        "menu": { "Down": ["ArrowDown"], "Select": ["l"] }
+    }"#;
+    let keybindings: bindings::KeybindingsFile = serde_json::from_str(file).unwrap();
+    let table = bindings::Bindings::new(keybindings, |k| k);
+    let map = [bindings::Context::Map];
+    let menu = [bindings::Context::Map, bindings::Context::Menu];
+    let key = |k: &str| k.to_owned();
+    assert_eq!(table.get(&map, &key("l")), Some(Action::Right));
+    assert_eq!(table.get(&map, &key("ArrowDown")), Some(Action::Down));
+    assert_eq!(table.get(&menu, &key("l")), Some(Action::Select));
+    assert_eq!(table.get(&menu, &key("ArrowDown")), Some(Action::Down));
+    assert_eq!(table.get(&menu, &key("j")), Some(Action::Down));
+    assert_eq!(
+        table.get(&[bindings::Context::Dialog], &key("l")),
+        Some(Action::Right)
+    );
+}
+
+// Moving through a menu should wrap around at either end, and empty menus shouldn't break
+fn test_menu_navigation() {
+    let mut options = menu::Menu::new(vec![
+        ("a".to_owned(), 'a'),
+        ("b".to_owned(), 'b'),
+        ("c".to_owned(), 'c'),
+    ]);
+    assert_eq!(options.selected(), Some(&'a'));
+    options.previous();
+    assert_eq!(options.selected(), Some(&'c'));
+    options.next();
+    assert_eq!(options.selected(), Some(&'a'));
+    options.next();
+    assert_eq!(options.selected(), Some(&'b'));
+
+    let mut empty = menu::Menu::<char>::new(vec![]);
+    empty.next();
+    empty.previous();
+    assert_eq!(empty.selected(), None);
+    assert_eq!(empty.selected_index(), 0);
+}
+
+// Arrow keys should move through the menu while it's open and move the cursor otherwise
+async fn test_menu_context() {
+    let (platform, recording) = TestPlatform::recording();
+    let platform = platform
+        .with_keybindings(r#"{ "Down": ["ArrowDown"], "Menu": ["m"], "Cancel": ["Escape"] }"#);
+    let (mut event_sender, event_receiver) = futures::channel::mpsc::channel(512);
+    for key in ["m", "ArrowDown", "Escape", "ArrowDown"].iter() {
+        event_sender.send(Input((*key).to_owned())).await.unwrap();
+    }
+    drop(event_sender);
+    alemian_saga_core::run(platform, event_receiver, "lang", "us").await;
+
+    let drawings = recording.borrow();
+    let second_option = Drawing::Fill {
+        x: 68,
+        y: 25,
+        w: 12,
+        h: 5,
+        color: style::MENU_HIGHLIGHT,
+    };
+    let scrolled = drawings.iter().position(|d| *d == second_option).unwrap();
+    assert_eq!(
+        drawings[scrolled + 1],
+        Drawing::Text {
+            txt: "Save".to_owned(),
+            tx: 70,
+            ty: 26
+        }
+    );
+    let moved = drawings
+        .iter()
+        .position(|d| *d == image("cursor.png", 0, 30, 40, 30))
+        .unwrap();
+    assert!(moved > scrolled);
+    // Closing the menu uncovers the tiles it was drawn over
+    let uncovered = &drawings[scrolled..moved];
+    assert!(uncovered.contains(&image("b", 40, 0, 40, 30)));
+    assert!(uncovered.contains(&image("d", 40, 30, 40, 30)));
+    drop(drawings);
+    assert_eq!(last_cursor(&recording), Some((0, 30)));
+}
+
+// Creates a message for the dialog box
+fn message(speaker: Option<&str>, text: &str) -> serialization::Message {
+    serialization::Message {
+        speaker: speaker.map(str::to_owned),
+        text: text.to_owned(),
+    }
+}
+
+// Creates a trigger on a tile of the small map
+fn trigger(x: u32, y: u32, action: serialization::TriggerAction) -> serialization::Trigger {
+    serialization::Trigger {
+        x,
+        y,
+        repeat: false,
+        action,
+    }
+}
+
+// Runs a game on the small map with triggers
+async fn run_triggers(
+    triggers: Vec<serialization::Trigger>,
+    events: Vec<Event<u32, String>>,
+) -> Recording {
+    let mut map = small_map();
+    map.triggers = triggers;
+    let (platform, recording) = TestPlatform::recording();
+    let platform = platform.with_map(&map);
+    let (mut event_sender, event_receiver) = futures::channel::mpsc::channel(512);
+    for event in events {
+        event_sender.send(event).await.unwrap();
+    }
+    drop(event_sender);
+    alemian_saga_core::run(platform, event_receiver, "lang", "us").await;
+    recording
+}
+
+// Finds the messages that were shown in the dialog box
+fn dialog_text(recording: &Recording) -> Vec<String> {
+    recording
+        .borrow()
+        .iter()
+        .filter_map(|d| match d {
+            Drawing::Text { txt, tx: 1, ty: 49 } => Some(txt.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+// One-shot triggers should only fire the first time their tile is entered
+async fn test_trigger_once() {
+    let hi = serialization::TriggerAction::Message(message(None, "hi"));
+    let repeated = serialization::Trigger {
+        repeat: true,
+        ..trigger(
+            0,
+            1,
+            serialization::TriggerAction::Message(message(None, "again")),
+        )
+    };
+    let events = vec![Right, Select, Left, Down, Select, Up, Right, Left, Down];
+    let recording = run_triggers(vec![trigger(1, 0, hi), repeated], events).await;
+    assert_eq!(dialog_text(&recording), ["hi", "again", "again"]);
+}
+
+// Several triggers on one tile should fire in the order they were declared, and the map should only
+// end once the messages before it have been read
+async fn test_trigger_order() {
+    let triggers = vec![
+        trigger(
+            1,
+            0,
+            serialization::TriggerAction::Message(message(None, "first")),
+        ),
+        trigger(
+            1,
+            0,
+            serialization::TriggerAction::Message(message(None, "second")),
+        ),
+        trigger(1, 0, serialization::TriggerAction::EndMap),
+    ];
+    let recording = run_triggers(triggers, vec![Right, Down, Select, Select, Down]).await;
+    assert_eq!(dialog_text(&recording), ["first", "second"]);
+    assert_eq!(
+        recording.borrow().last(),
+        Some(&Drawing::Text {
+            txt: "Map complete".to_owned(),
+            tx: 0,
+            ty: 30
+        })
+    );
+    assert!(!recording
+        .borrow()
+        .contains(&image("cursor.png", 40, 30, 40, 30)));
+}
+
+// Long messages should be wrapped and split into pages, and the map should stay out of reach until
+// every page of the intro has been read
+async fn test_intro_dialog() {
+    let word = "aaaaaaaaa";
+    let line = |words: usize| vec![word; words].join(" ");
+    let mut map = small_map();
+    map.intro_dialog = vec![
+        message(Some("Bob"), vec![word; 32].join(" ").as_str()),
+        message(Some("Ann"), ""),
+    ];
+    let (platform, recording) = TestPlatform::recording();
+    let platform = platform.with_map(&map);
+    let (mut event_sender, event_receiver) = futures::channel::mpsc::channel(512);
+    for event in [Right, Select, Right, Select, Select, Right] {
+        event_sender.send(event).await.unwrap();
+    }
+    drop(event_sender);
+    alemian_saga_core::run(platform, event_receiver, "lang", "us").await;
+
+    let text: Vec<(String, u32)> = recording
+        .borrow()
+        .iter()
+        .filter_map(|d| match d {
+            Drawing::Text { txt, tx: 1, ty } if *ty >= 45 => Some((txt.clone(), *ty)),
+            _ => None,
+        })
+        .collect();
+    let expected = vec![
+        ("Bob".to_owned(), 46),
+        (line(7), 49),
+        (line(7), 52),
+        (line(7), 55),
+        ("Bob".to_owned(), 46),
+        (line(7), 49),
+        (line(4), 52),
+        ("Ann".to_owned(), 46),
+    ];
+    assert_eq!(text, expected);
+    let cursor_moves = recording
+        .borrow()
+        .iter()
+        .filter(|d| **d == image("cursor.png", 40, 0, 40, 30))
+        .count();
+    assert_eq!(cursor_moves, 1);
+}
+
+// Holding the mouse at the edge of the screen shouldn't pan any faster than the pan delay allows
+async fn test_pan_delay_setting() {
+    for &(delay, expected_left) in [(None, 3), (Some(3_600_000), 0)].iter() {
+        let (platform, recording) = TestPlatform::recording();
+        let delay = delay.map_or(String::new(), |d| {
+            format!(r#", "mouse_pan_delay_ms": {}"#, d)
+        });
+        let settings = format!(
+            r#"{{ "min_visible_tiles": 1, "max_visible_tiles": 3, "show_minimap": false{} }}"#,
+            delay
+        );
+        let platform = platform
+            .with_map(&grid_map(9, 2))
+            .with_settings(Some(settings.as_str()));
+        let (mut event_sender, event_receiver) = futures::channel::mpsc::channel(512);
+        for _ in 0..3 {
+            event_sender
+                .send(MouseMove(Vector { x: 79, y: 30 }))
+                .await
+                .unwrap();
+            event_sender.send(Redraw).await.unwrap();
+        }
+        drop(event_sender);
+        alemian_saga_core::run(platform, event_receiver, "lang", "us").await;
+        assert_eq!(last_visible_tiles(&recording).0, (expected_left, 0));
+    }
+}
+
+// The infobar should be sized according to the settings
+async fn test_infobar_size_setting() {
+    let (platform, recording) = TestPlatform::recording();
+    let settings =
+        r#"{ "min_visible_tiles": 1, "show_minimap": false, "infobar_screen_fraction": 6 }"#;
+    let platform = platform.with_settings(Some(settings));
+    let (event_sender, event_receiver) = futures::channel::mpsc::channel(512);
+    drop(event_sender);
+    alemian_saga_core::run(platform, event_receiver, "lang", "us").await;
+    assert!(recording
+        .borrow()
+        .contains(&image("infobar.png", 40, 0, 40, 10)));
+}
+
+// The game should be drivable one event at a time without an event queue
+async fn test_handle_event() {
+    let (platform, _recording) = TestPlatform::recording();
+    let settings = r#"{
+        "min_visible_tiles": 1,
+        "max_visible_tiles": 3,
+        "show_minimap": false,
+        "show_scroll_indicator": false
+    }"#;
+    let platform = platform
+        .with_map(&grid_map(9, 2))
+        .with_settings(Some(settings));
+    NOW_STEP.with(|s| s.set(0));
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    assert_eq!(game.map_size(), Vector { x: 9, y: 2 });
+    // Moving the cursor within the screen is drawn on the next frame, along with anything else
+    // that changes before then
+    assert_eq!(game.handle_event(Right), game::Redraw::Changed);
+    assert_eq!(game.handle_event(Right), game::Redraw::Changed);
+    assert_eq!(game.frame(), game::Redraw::Idle);
+    assert_eq!(game.cursor_pos(), Vector { x: 2, y: 0 });
+    // Moving past the edge of the screen starts a scroll, which is drawn from the next frame
+    assert_eq!(game.handle_event(Right), game::Redraw::Changed);
+    assert_eq!(game.cursor_pos(), Vector { x: 3, y: 0 });
+    assert_eq!(
+        game.visible_rect(),
+        Rectangle {
+            top_left: Vector { x: 1, y: 0 },
+            size: Vector { x: 3, y: 2 }
+        }
+    );
+    assert_eq!(game.frame(), game::Redraw::NextFrame);
+    CLOCK.with(|c| c.set(c.get() + 1_000_000_000));
+    assert_eq!(game.frame(), game::Redraw::Idle);
+    NOW_STEP.with(|s| s.set(1_000_000_000));
+    assert_eq!(game.handle_event(Quit), game::Redraw::Finished);
+    assert!(game.is_finished());
+    assert_eq!(game.handle_event(Left), game::Redraw::Finished);
+    assert_eq!(game.cursor_pos(), Vector { x: 3, y: 0 });
+}
+
+// Replaying a recorded session should draw the same frames and leave the game in the same state
+async fn test_record_replay() {
+    STORAGE.with(|s| s.borrow_mut().clear());
+    let settings = r#"{ "min_visible_tiles": 1, "max_visible_tiles": 3, "show_minimap": false }"#;
+    let platform = || {
+        let (platform, recording) = TestPlatform::recording();
+        let platform = platform
+            .with_map(&grid_map(9, 4))
+            .with_settings(Some(settings))
+            .with_keybindings(r#"{ "Down": ["s"] }"#);
+        (platform, recording)
+    };
+    let (original, recording) = platform();
+    let (mut event_sender, event_receiver) = futures::channel::mpsc::channel(512);
+    let events = [
+        Right,
+        Right,
+        Right,
+        Input("s".to_owned()),
+        ZoomOut,
+        Click(Vector { x: 10, y: 40 }),
+        Redraw,
+    ];
+    for event in events {
+        event_sender.send(event).await.unwrap();
+    }
+    drop(event_sender);
+    let mut recorder = record::Recorder::new();
+    alemian_saga_core::run_recorded(original, event_receiver, "lang", "us", &mut recorder).await;
+    assert_eq!(recorder.events().len(), 7);
+    assert_eq!(recorder.recording().seed, DEFAULT_SEED);
+    record::save(&TestPlatform::recording().0, "replay", recorder.recording());
+
+    // The replay uses the recorded seed rather than asking the platform for a new one
+    let (mut replayed, replay_recording) = platform();
+    replayed.seed = DEFAULT_SEED + 1;
+    let saved = record::load(&replayed, "replay").unwrap();
+    let mut game = record::replay(saved, replayed, "lang", "us", record::Pacing::Original)
+        .await
+        .unwrap();
+    assert_eq!(game.seed(), DEFAULT_SEED);
+    assert_eq!(game.rng(), &rng::Rng::new(DEFAULT_SEED));
+    assert_eq!(*replay_recording.borrow(), *recording.borrow());
+    let ((left, top), (width, height)) = last_visible_tiles(&recording);
+    assert_eq!(
+        game.visible_rect(),
+        Rectangle {
+            top_left: Vector { x: left, y: top },
+            size: Vector {
+                x: width,
+                y: height
+            }
+        }
+    );
+    assert_eq!(game.cursor_pos(), Vector { x: 1, y: 2 });
+    STORAGE.with(|s| s.borrow_mut().clear());
+}
+
+// Watching a recorded game should play it on the map it was recorded on, with controls for pausing,
+// speeding it up and stepping through it either way, and should end in the recorded state
+fn test_replay_viewer() {
+    STORAGE.with(|s| s.borrow_mut().clear());
+    LOG.with(|l| l.borrow_mut().clear());
+    let mut first_map = grid_map(9, 9);
+    first_map.units = vec![serialization::Unit {
+        x: 1,
+        y: 1,
+        ..unit_map().units.remove(0)
+    }];
+    let platform = || {
+        let (platform, recording) = TestPlatform::recording();
+        let platform = platform
+            .with_campaign(&["one.map", "two.map"])
+            .with_map_at("lang/one.map", &first_map)
+            .with_map_at("lang/two.map", &grid_map(5, 4))
+            .with_settings(Some(r#"{ "show_minimap": false }"#));
+        (platform, recording)
+    };
+    let (original, _) = platform();
+    let (mut event_sender, event_receiver) = futures::channel::mpsc::channel(512);
+    for event in [Down, Right, Select, Left, Select, Select, Down, Down] {
+        event_sender.try_send(event).unwrap();
+    }
+    drop(event_sender);
+    let mut recorder = record::Recorder::new();
+    let recorded =
+        alemian_saga_core::run_recorded(original, event_receiver, "lang", "us", &mut recorder);
+    futures::executor::block_on(recorded);
+    record::save(&TestPlatform::recording().0, "replay", recorder.recording());
+    let fixture = record::load(&TestPlatform::recording().0, "replay").unwrap();
+    assert_eq!(fixture.map.as_deref(), Some("one.map"));
+    let state_hash = fixture.state_hash.clone().unwrap();
+    // The player has since moved on to the next map, but the replay starts on the recorded one
+    STORAGE.with(|s| {
+        s.borrow_mut()
+            .insert(campaign::STORAGE_KEY.to_owned(), "two.map".to_owned())
+    });
+
+    let (platform, recording) = platform();
+    let (mut event_sender, event_receiver) = futures::channel::mpsc::channel(512);
+    let watched = std::rc::Rc::new(std::cell::RefCell::new(None));
+    let result = watched.clone();
+    let viewer = async move {
+        let game = alemian_saga_core::run_replay(platform, event_receiver, "lang", "us", fixture);
+        *result.borrow_mut() = game.await;
+    };
+    let mut pool = futures::executor::LocalPool::new();
+    NOW_STEP.with(|s| s.set(0));
+    // Pausing right away stops the replay after the first event, which happens as soon as it starts
+    for event in [Select, ZoomIn, ZoomIn, ZoomIn] {
+        event_sender.try_send(event).unwrap();
+    }
+    let _ = pool.spawner().spawn_local(viewer);
+    pool.run_until_stalled();
+    let transport_shown = |status: &str| {
+        drawn_text(&recording)
+            .iter()
+            .any(|text| text.starts_with(status))
+    };
+    assert!(transport_shown("Paused at event 1 of 8"));
+    recording.borrow_mut().clear();
+    event_sender.try_send(Select).unwrap();
+    pool.run_until_stalled();
+    // Speeding up past the fastest speed leaves it there
+    assert!(transport_shown("Event 1 of 8 at 4x"));
+    assert!(transport_shown("Replay over: 8 of 8 events"));
+
+    // Stepping back starts over and plays all but the last event, so the cursor hasn't moved down
+    // the second time yet
+    event_sender.try_send(Left).unwrap();
+    pool.run_until_stalled();
+    assert!(transport_shown("Paused at event 7 of 8"));
+    event_sender.try_send(Right).unwrap();
+    pool.run_until_stalled();
+    drop(event_sender);
+    pool.run();
+    let game = watched.borrow_mut().take().unwrap();
+    assert_eq!(game.current_map(), "one.map");
+    assert_eq!(game.cursor_pos(), Vector { x: 0, y: 3 });
+    assert_eq!(game.state_hash(), state_hash);
+    assert!(!LOG.with(|l| l.borrow().iter().any(|m| m.contains("different state"))));
+    NOW_STEP.with(|s| s.set(1_000_000_000));
+}
+
+// Games started with the same seed should make the same random decisions, and decisions should
+// stay within the range asked for
+async fn test_random_seed() {
+    let decisions = |game: &mut game::Game<TestPlatform>| -> Vec<u64> {
+        (0..32).map(|_| game.rng().below(6)).collect()
+    };
+    let mut first = game::Game::load(TestPlatform::recording().0, "lang", "us")
+        .await
+        .unwrap();
+    let mut second = game::Game::load(TestPlatform::recording().0, "lang", "us")
+        .await
+        .unwrap();
+    assert_eq!(first.seed(), DEFAULT_SEED);
+    let first_decisions = decisions(&mut first);
+    assert_eq!(first_decisions, decisions(&mut second));
+    assert!(first_decisions.iter().all(|d| *d < 6));
+
+    let mut other = game::Game::load_seeded(TestPlatform::recording().0, "lang", "us", 7)
+        .await
+        .unwrap();
+    assert_ne!(first_decisions, decisions(&mut other));
+
+    let mut rng = rng::Rng::new(0);
+    assert_ne!(rng.next_u64(), rng.next_u64());
+    assert_eq!(rng.below(0), 0);
+    assert!(!rng.chance(0, 10));
+    assert!(rng.chance(10, 10));
+}
+
+// Vector and Rectangle math should give None instead of panicking or wrapping on bad input
+fn test_geometry_properties() {
+    use proptest::prelude::*;
+    let mut runner = proptest::test_runner::TestRunner::default();
+    let vector = || (any::<u32>(), any::<u32>()).prop_map(|(x, y)| Vector { x, y });
+    runner
+        .run(&(vector(), vector()), |(a, b)| {
+            let quotient = a.checked_piecewise_divide(b);
+            prop_assert_eq!(quotient.is_none(), b.x == 0 || b.y == 0);
+            if let Some(q) = quotient {
+                prop_assert_eq!(q, a.piecewise_divide(b));
+            }
+            let sum = a.x.checked_add(b.x).zip(a.y.checked_add(b.y));
+            prop_assert_eq!(a.checked_add(&b), sum.map(|(x, y)| Vector { x, y }));
+            let rectangle = Rectangle {
+                top_left: a,
+                size: b,
+            };
+            prop_assert_eq!(rectangle.checked_right(), a.x.checked_add(b.x));
+            prop_assert_eq!(rectangle.checked_bottom(), a.y.checked_add(b.y));
+            Ok(())
+        })
+        .unwrap();
+    let in_u32_range = |v: f64| (0.0..4_294_967_296.0).contains(&v);
+    runner
+        .run(&(any::<f64>(), any::<f64>()), |(x, y)| {
+            let cast = Vector { x, y }.try_cast::<u32>();
+            prop_assert_eq!(cast.is_some(), in_u32_range(x) && in_u32_range(y));
+            if let Some(c) = cast {
+                prop_assert_eq!(
+                    c,
+                    Vector {
+                        x: x as u32,
+                        y: y as u32
+                    }
+                );
+            }
+            Ok(())
+        })
+        .unwrap();
+    // Negative values that fit are still truncated toward zero for signed types
+    runner
+        .run(&(-1e9..1e9, -1e9..1e9), |(x, y): (f64, f64)| {
+            let cast = Vector { x, y }.try_cast::<i32>();
+            prop_assert_eq!(
+                cast,
+                Some(Vector {
+                    x: x as i32,
+                    y: y as i32
+                })
+            );
+            Ok(())
+        })
+        .unwrap();
+}
+
+// Mouse events anywhere on (or off) the screen shouldn't crash the game, even when tiles are too
+// small to take up any space
+fn test_mouse_hardening() {
+    use proptest::prelude::*;
+    let (platform, _recording) = TestPlatform::recording();
+    let settings = r#"{ "min_visible_tiles": 1, "max_visible_tiles": 200, "show_minimap": false }"#;
+    let platform = platform
+        .with_map(&grid_map(200, 2))
+        .with_settings(Some(settings));
+    let game = futures::executor::block_on(game::Game::load(platform, "lang", "us")).unwrap();
+    let game = std::cell::RefCell::new(game);
+    let position = (any::<u32>(), any::<u32>()).prop_map(|(x, y)| Vector { x, y });
+    let event = prop_oneof![
+        position.clone().prop_map(MouseMove),
+        position.clone().prop_map(Click),
+        position.clone().prop_map(ZoomInAt),
+        position.prop_map(ZoomOutAt),
+    ];
+    let mut runner = proptest::test_runner::TestRunner::default();
+    runner
+        .run(&event, |event| {
+            let mut game = game.borrow_mut();
+            game.handle_event(event);
+            let cursor = game.cursor_pos();
+            let map_size = game.map_size();
+            prop_assert!(cursor.x < map_size.x && cursor.y < map_size.y);
+            Ok(())
+        })
+        .unwrap();
+}
+
+// The name of the tile under the mouse should show up once the mouse has rested for a moment and
+// go away as soon as it moves again
+async fn test_tooltip() {
+    let (platform, recording) = TestPlatform::recording();
+    let platform = platform.with_map(&grid_map(2, 2));
+    NOW_STEP.with(|s| s.set(0));
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    let tooltip = [
+        Drawing::Fill {
+            x: 11,
+            y: 11,
+            w: 5,
+            h: 3,
+            color: style::TOOLTIP_BACKGROUND,
+        },
+        Drawing::Text {
+            txt: "0,0".to_owned(),
+            tx: 12,
+            ty: 11,
+        },
+    ];
+    let shown = |recording: &Recording| {
+        let drawings = recording.borrow();
+        drawings.ends_with(&tooltip)
+    };
+    let half_delay = 250_000_000;
+    let mouse_move = MouseMove(Vector { x: 10, y: 10 });
+    assert_eq!(
+        game.handle_event(mouse_move),
+        game::Redraw::Wait(2 * half_delay)
+    );
+    CLOCK.with(|c| c.set(c.get() + half_delay));
+    assert_eq!(game.frame(), game::Redraw::Wait(half_delay));
+    assert!(!shown(&recording));
+    CLOCK.with(|c| c.set(c.get() + half_delay));
+    assert_eq!(game.frame(), game::Redraw::Idle);
+    assert!(shown(&recording));
+
+    // Moving the mouse uncovers the tile under the tooltip on the next frame and starts waiting
+    // again
+    recording.borrow_mut().clear();
+    let mouse_move = MouseMove(Vector { x: 12, y: 12 });
+    assert_eq!(game.handle_event(mouse_move), game::Redraw::Changed);
+    assert!(recording.borrow().is_empty());
+    assert_eq!(game.frame(), game::Redraw::Wait(2 * half_delay));
+    assert!(recording.borrow().contains(&image("0,0", 0, 0, 40, 30)));
+    assert!(!recording.borrow().contains(&tooltip[0]));
+
+    // Anything else the player does cancels the tooltip
+    assert_eq!(game.handle_event(Right), game::Redraw::Changed);
+    assert_eq!(game.frame(), game::Redraw::Idle);
+    NOW_STEP.with(|s| s.set(1_000_000_000));
+}
+
+// The mouse leaving the screen should take down the tooltip and forget the tile it was over
+async fn test_mouse_leave() {
+    let (platform, recording) = TestPlatform::recording();
+    let platform = platform.with_map(&grid_map(2, 2));
+    NOW_STEP.with(|s| s.set(0));
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    let delay = 500_000_000;
+    game.handle_event(MouseMove(Vector { x: 10, y: 10 }));
+    CLOCK.with(|c| c.set(c.get() + delay));
+    assert_eq!(game.frame(), game::Redraw::Idle);
+    assert_eq!(
+        drawn_text(&recording).last().map(String::as_str),
+        Some("0,0")
+    );
+
+    recording.borrow_mut().clear();
+    assert_eq!(game.handle_event(MouseLeave), game::Redraw::Changed);
+    assert_eq!(game.frame(), game::Redraw::Idle);
+    assert!(recording.borrow().contains(&image("0,0", 0, 0, 40, 30)));
+    // Nothing is left waiting for the mouse to rest
+    recording.borrow_mut().clear();
+    CLOCK.with(|c| c.set(c.get() + delay));
+    assert_eq!(game.frame(), game::Redraw::Idle);
+    assert!(drawn_text(&recording).is_empty());
+
+    // Coming back onto the tile it left from moves the cursor there like any other hover
+    game.handle_event(Right);
+    assert_eq!(game.cursor_pos(), Vector { x: 1, y: 0 });
+    game.handle_event(MouseMove(Vector { x: 10, y: 10 }));
+    assert_eq!(game.cursor_pos(), Vector { x: 0, y: 0 });
+    NOW_STEP.with(|s| s.set(1_000_000_000));
+}
+
+// Diagonal moves should scroll both axes at once when needed and slide along the edges of the map
+async fn test_diagonal_movement() {
+    let (platform, _recording) = TestPlatform::recording();
+    let settings = r#"{ "min_visible_tiles": 1, "max_visible_tiles": 3, "show_minimap": false }"#;
+    let platform = platform
+        .with_map(&grid_map(5, 5))
+        .with_settings(Some(settings))
+        .with_keybindings(r#"{ "DownRight": ["n"] }"#);
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    let mut expect = |events: &[Event<u32, String>], cursor: (u32, u32), top_left: (u32, u32)| {
+        for event in events {
+            game.handle_event(event.clone());
+        }
+        assert_eq!(
+            game.cursor_pos(),
+            Vector {
+                x: cursor.0,
+                y: cursor.1
+            }
+        );
+        assert_eq!(
+            game.visible_rect().top_left,
+            Vector {
+                x: top_left.0,
+                y: top_left.1
+            }
+        );
+    };
+    expect(&[DownRight, Input("n".to_owned())], (2, 2), (0, 0));
+    // The screen corner is on both scroll boundaries
+    expect(&[DownRight], (3, 3), (1, 1));
+    // Stops at the map corner
+    expect(&[DownRight, DownRight, DownRight], (4, 4), (2, 2));
+    // Slides along the right edge of the map
+    expect(&[UpRight], (4, 3), (2, 2));
+    expect(&[UpLeft, UpLeft, UpLeft], (1, 0), (1, 0));
+    expect(&[DownLeft], (0, 1), (0, 0));
+}
+
+// The cursor should be hidden while the game doesn't have keyboard focus, and the fast move
+// modifier should be let go of since its release may go elsewhere
+async fn test_focus() {
+    let (platform, recording) = TestPlatform::recording();
+    let platform = platform
+        .with_map(&grid_map(20, 12))
+        .with_keybindings(r#"{ "FastModifier": ["Shift"] }"#);
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    let sources = |recording: &Recording| -> Vec<String> {
+        recording
+            .borrow()
+            .iter()
+            .filter_map(|d| match d {
+                Drawing::Image { source, .. } => Some(source.clone()),
+                _ => None,
+            })
+            .collect()
+    };
+
+    game.handle_event(Input("Shift".to_owned()));
+    recording.borrow_mut().clear();
+    game.handle_event(FocusLost);
+    game.frame();
+    assert_eq!(sources(&recording), ["0,0", "infobar.png"]);
+
+    recording.borrow_mut().clear();
+    game.handle_event(FocusGained);
+    game.frame();
+    assert_eq!(sources(&recording), ["0,0", "cursor.png", "infobar.png"]);
+    game.handle_event(Right);
+    assert_eq!(game.cursor_pos(), Vector { x: 1, y: 0 });
+}
+
+// Holding the fast move modifier should move the cursor several tiles at a time, scrolling the
+// screen by the same amount when the cursor leaves it
+async fn test_fast_move() {
+    let (platform, _recording) = TestPlatform::recording();
+    let settings = r#"{ "min_visible_tiles": 1, "max_visible_tiles": 8, "show_minimap": false }"#;
+    let platform = platform
+        .with_map(&grid_map(20, 12))
+        .with_settings(Some(settings))
+        .with_keybindings(r#"{ "FastModifier": ["Shift"] }"#);
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    let shift = || Input("Shift".to_owned());
+    let mut expect = |events: Vec<Event<u32, String>>, cursor: (u32, u32), top_left: (u32, u32)| {
+        for event in events {
+            game.handle_event(event);
+        }
+        assert_eq!(
+            game.cursor_pos(),
+            Vector {
+                x: cursor.0,
+                y: cursor.1
+            }
+        );
+        assert_eq!(
+            game.visible_rect().top_left,
+            Vector {
+                x: top_left.0,
+                y: top_left.1
+            }
+        );
+    };
+    expect(vec![shift(), Right], (5, 0), (0, 0));
+    expect(vec![Right], (10, 0), (5, 0));
+    // Stops at the edge of the map
+    expect(vec![Right, Right], (19, 0), (12, 0));
+    expect(vec![Release("Shift".to_owned()), Left], (18, 0), (12, 0));
+    expect(vec![shift(), Down, Down], (18, 10), (12, 4));
+    expect(vec![Down], (18, 11), (12, 4));
+    // The modifier might be let go of without the game finding out while it's in the background
+    expect(vec![Suspend, Resume, Left], (17, 11), (12, 4));
+}
+
+// Jumping to an edge of the map should only scroll the screen along the axis the cursor left it on
+async fn test_jump_to_edge() {
+    let (platform, _recording) = TestPlatform::recording();
+    let settings = r#"{ "min_visible_tiles": 1, "max_visible_tiles": 8, "show_minimap": false }"#;
+    let platform = platform
+        .with_map(&grid_map(30, 20))
+        .with_settings(Some(settings))
+        .with_keybindings(r#"{ "JumpLeft": ["Home"] }"#);
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    let mut expect = |events: Vec<Event<u32, String>>, cursor: (u32, u32), top_left: (u32, u32)| {
+        for event in events {
+            game.handle_event(event);
+        }
+        assert_eq!(
+            game.cursor_pos(),
+            Vector {
+                x: cursor.0,
+                y: cursor.1
+            }
+        );
+        assert_eq!(
+            game.visible_rect().top_left,
+            Vector {
+                x: top_left.0,
+                y: top_left.1
+            }
+        );
+    };
+    // The destination is already on the screen
+    expect(vec![Right, Right, Down, JumpTop], (2, 0), (0, 0));
+    expect(vec![Input("Home".to_owned())], (0, 0), (0, 0));
+    // Centering on the destination would go past the edge of the map
+    expect(vec![JumpRight], (29, 0), (22, 0));
+    expect(vec![JumpBottom], (29, 19), (22, 12));
+    expect(vec![Up, Up, Up, Up, Up, Up, JumpLeft], (0, 13), (0, 12));
+    expect(vec![JumpTop], (0, 0), (0, 0));
+}
+
+// Centering the view should put the cursor in the middle of the screen unless that would go past
+// the edge of the map, and double clicking a tile should center on it
+async fn test_center_view() {
+    let (platform, _recording) = TestPlatform::recording();
+    let settings = r#"{ "min_visible_tiles": 1, "max_visible_tiles": 8, "show_minimap": false }"#;
+    let platform = platform
+        .with_map(&grid_map(30, 20))
+        .with_settings(Some(settings));
+    NOW_STEP.with(|s| s.set(0));
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    let mut expect = |events: Vec<Event<u32, String>>, cursor: (u32, u32), top_left: (u32, u32)| {
+        for event in events {
+            game.handle_event(event);
+        }
+        assert_eq!(
+            game.cursor_pos(),
+            Vector {
+                x: cursor.0,
+                y: cursor.1
+            }
+        );
+        assert_eq!(
+            game.visible_rect().top_left,
+            Vector {
+                x: top_left.0,
+                y: top_left.1
+            }
+        );
+    };
+    // Tiles are 10x7 on the screen
+    let click = |x, y| Click(Vector { x, y });
+    expect(vec![CenterView], (0, 0), (0, 0));
+    expect(vec![click(75, 50)], (7, 7), (0, 0));
+    expect(vec![click(71, 53)], (7, 7), (3, 3));
+    expect(vec![Right, Right, Right, CenterView], (10, 7), (6, 3));
+    // Clicks on different tiles or too far apart aren't double clicks
+    expect(vec![click(5, 5), click(15, 5)], (7, 3), (6, 3));
+    CLOCK.with(|c| c.set(c.get() + 1_000_000_000));
+    expect(vec![click(15, 5)], (7, 3), (6, 3));
+    expect(vec![JumpRight, JumpBottom, CenterView], (29, 19), (22, 12));
+    NOW_STEP.with(|s| s.set(1_000_000_000));
+}
+
+async fn test_drag_pan() {
+    let (platform, _recording) = TestPlatform::recording();
+    let settings = r#"{ "min_visible_tiles": 1, "max_visible_tiles": 8, "show_minimap": false }"#;
+    let platform = platform
+        .with_map(&grid_map(30, 20))
+        .with_settings(Some(settings));
+    NOW_STEP.with(|s| s.set(0));
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    let mut expect = |events: Vec<Event<u32, String>>, cursor: (u32, u32), top_left: (u32, u32)| {
+        for event in events {
+            game.handle_event(event);
+        }
+        assert_eq!(
+            game.cursor_pos(),
+            Vector {
+                x: cursor.0,
+                y: cursor.1
+            }
+        );
+        assert_eq!(
+            game.visible_rect().top_left,
+            Vector {
+                x: top_left.0,
+                y: top_left.1
+            }
+        );
+    };
+    // Tiles are 10x7 on the screen, and the map only moves once the mouse has covered a whole tile
+    let mouse_move = |x, y| MouseMove(Vector { x, y });
+    expect(vec![DragStart(Vector { x: 50, y: 30 })], (0, 0), (0, 0));
+    expect(vec![mouse_move(45, 30)], (0, 0), (0, 0));
+    expect(vec![mouse_move(35, 30)], (1, 0), (1, 0));
+    expect(vec![mouse_move(15, 16)], (3, 2), (3, 2));
+    // Dragging past the edge of the map stops at the edge
+    expect(vec![mouse_move(300, 16)], (3, 2), (0, 2));
+    // Once the button is released, the mouse goes back to moving the cursor
+    expect(vec![DragEnd, mouse_move(75, 50)], (7, 9), (0, 2));
+    NOW_STEP.with(|s| s.set(1_000_000_000));
+}
+
+async fn test_scroll() {
+    let (platform, _recording) = TestPlatform::recording();
+    let settings = r#"{ "min_visible_tiles": 1, "max_visible_tiles": 8, "show_minimap": false }"#;
+    let platform = platform
+        .with_map(&grid_map(30, 20))
+        .with_settings(Some(settings));
+    NOW_STEP.with(|s| s.set(0));
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    let mut expect = |events: Vec<Event<u32, String>>, cursor: (u32, u32), top_left: (u32, u32)| {
+        for event in events {
+            game.handle_event(event);
+        }
+        assert_eq!(
+            game.cursor_pos(),
+            Vector {
+                x: cursor.0,
+                y: cursor.1
+            }
+        );
+        assert_eq!(
+            game.visible_rect().top_left,
+            Vector {
+                x: top_left.0,
+                y: top_left.1
+            }
+        );
+    };
+    // The cursor is kept on the screen as it scrolls
+    expect(vec![ScrollHorizontal(1)], (1, 0), (1, 0));
+    expect(vec![ScrollVertical(3)], (1, 3), (1, 3));
+    expect(vec![ScrollHorizontal(-5)], (1, 3), (0, 3));
+    // Scrolling stops at the edges of the map
+    expect(vec![ScrollHorizontal(100)], (22, 3), (22, 3));
+    expect(vec![ScrollVertical(-100)], (22, 3), (22, 0));
+    NOW_STEP.with(|s| s.set(1_000_000_000));
+}
+
+// The mouse should pan faster the closer it gets to the edge of the screen
+async fn test_edge_pan_speed() {
+    let (platform, _recording) = TestPlatform::recording();
+    let settings = r#"{
+        "min_visible_tiles": 1,
+        "max_visible_tiles": 8,
+        "show_minimap": false,
+        "mouse_pan_delay_ms": 90,
+        "mouse_pan_zone": 2.0
+    }"#;
+    let platform = platform
+        .with_map(&grid_map(30, 20))
+        .with_settings(Some(settings));
+    NOW_STEP.with(|s| s.set(0));
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    // Tiles are 10 pixels wide, so the zone is 20 pixels wide and starts 60 pixels in
+    let mut expect = |x: u32, wait_ms: u64, left: u32| {
+        CLOCK.with(|c| c.set(c.get() + wait_ms * 1_000_000));
+        game.handle_event(MouseMove(Vector { x, y: 30 }));
+        assert_eq!(game.visible_rect().left(), left);
+    };
+    // Halfway into the zone pans twice as fast
+    expect(70, 1000, 1);
+    expect(70, 40, 1);
+    expect(70, 10, 2);
+    // At the very edge pans almost three times as fast
+    expect(79, 35, 3);
+    expect(79, 25, 3);
+    expect(79, 10, 4);
+    // Just inside the zone pans at about the normal speed
+    expect(61, 70, 4);
+    expect(61, 15, 5);
+    // Outside of the zone doesn't pan at all
+    expect(60, 1000, 5);
+    NOW_STEP.with(|s| s.set(1_000_000_000));
+}
+
+// Panning with the mouse and moving the mouse around within a tile shouldn't move the cursor
+async fn test_edge_pan_keeps_cursor() {
+    let (platform, _recording) = TestPlatform::recording();
+    let settings = r#"{ "min_visible_tiles": 1, "max_visible_tiles": 8, "show_minimap": false }"#;
+    let platform = platform
+        .with_map(&grid_map(30, 20))
+        .with_settings(Some(settings));
+    NOW_STEP.with(|s| s.set(0));
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    let mut expect = |events: Vec<Event<u32, String>>, cursor: (u32, u32), left: u32| {
+        for event in events {
+            CLOCK.with(|c| c.set(c.get() + 1_000_000_000));
+            game.handle_event(event);
+        }
+        assert_eq!(
+            game.cursor_pos(),
+            Vector {
+                x: cursor.0,
+                y: cursor.1
+            }
+        );
+        assert_eq!(game.visible_rect().left(), left);
+    };
+    // Tiles are 10x7 on the screen
+    let mouse_move = |x, y| MouseMove(Vector { x, y });
+    expect(
+        vec![Right, Right, Right, Right, Right, Down, Down],
+        (5, 2),
+        0,
+    );
+    expect(vec![mouse_move(79, 30); 5], (5, 2), 5);
+    // The cursor only moves once the screen leaves it behind
+    expect(vec![mouse_move(79, 30)], (6, 2), 6);
+    expect(vec![mouse_move(25, 30)], (8, 4), 6);
+    expect(vec![Down, mouse_move(28, 33)], (8, 5), 6);
+    expect(vec![mouse_move(35, 33)], (9, 4), 6);
+    NOW_STEP.with(|s| s.set(1_000_000_000));
+}
+
+// Moving the mouse around within a tile or onto the cursor shouldn't draw anything
+async fn test_hover_draw_count() {
+    let (platform, recording) = TestPlatform::recording();
+    let settings = r#"{ "min_visible_tiles": 1, "max_visible_tiles": 8, "show_minimap": false }"#;
+    let platform = platform
+        .with_map(&grid_map(30, 20))
+        .with_settings(Some(settings));
+    NOW_STEP.with(|s| s.set(0));
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    // Tiles are 10x7 on the screen
+    game.handle_event(MouseMove(Vector { x: 35, y: 24 }));
+    assert_eq!(game.cursor_pos(), Vector { x: 3, y: 3 });
+    let draw_count = recording.borrow().len();
+    for i in 0..10 {
+        game.handle_event(MouseMove(Vector {
+            x: 30 + i,
+            y: 21 + i % 7,
+        }));
+    }
+    assert_eq!(recording.borrow().len(), draw_count);
+    // The cursor is already on the tile that the mouse moves onto
+    game.handle_event(Right);
+    let draw_count = recording.borrow().len();
+    game.handle_event(MouseMove(Vector { x: 45, y: 24 }));
+    assert_eq!(game.cursor_pos(), Vector { x: 4, y: 3 });
+    assert_eq!(recording.borrow().len(), draw_count);
+    NOW_STEP.with(|s| s.set(1_000_000_000));
+}
+
+// Redrawing the screen should draw the tiles in a batch of their own, which is sent to the screen
+// in order before anything that goes on top of the tiles
+async fn test_draw_batching() {
+    let (platform, recording) = TestPlatform::recording();
+    let settings = r#"{ "min_visible_tiles": 1, "max_visible_tiles": 8, "show_minimap": false }"#;
+    let platform = platform
+        .with_map(&grid_map(3, 2))
+        .with_settings(Some(settings));
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    BATCHES.with(|b| b.borrow_mut().clear());
+    recording.borrow_mut().clear();
+    game.handle_event(Redraw);
+    game.frame();
+    let batches = BATCHES.with(|b| b.borrow_mut().split_off(0));
+    assert_eq!(batches.len(), 1);
+    let tiles: Vec<_> = recording
+        .borrow()
+        .iter()
+        .filter(|d| matches!(d, Drawing::Image { source, .. } if source.contains(',')))
+        .cloned()
+        .collect();
+    assert_eq!(tiles.len(), 6);
+    assert_eq!(batches[0], tiles);
+    let drawn = recording.borrow();
+    let cursor = drawn
+        .iter()
+        .position(|d| matches!(d, Drawing::Image { source, .. } if source == "cursor.png"))
+        .unwrap();
+    let last_tile = drawn.iter().rposition(|d| *d == tiles[5]).unwrap();
+    assert!(last_tile < cursor);
+}
+
+// The scroll indicator should show which part of the map is visible along the right and bottom
+// edges, fade out a second after the screen stops moving by redrawing only the tiles under it, and
+// come back when the screen moves again
+async fn test_scroll_indicator() {
+    let (platform, recording) = TestPlatform::recording();
+    let settings = r#"{ "min_visible_tiles": 1, "max_visible_tiles": 3, "show_minimap": false }"#;
+    let platform = platform
+        .with_map(&grid_map(9, 6))
+        .with_settings(Some(settings));
+    NOW_STEP.with(|s| s.set(0));
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    let indicator_fills = |alpha: u8| -> Vec<Drawing> {
+        recording
+            .borrow()
+            .iter()
+            .filter(|d| {
+                matches!(d, Drawing::Fill { color, .. }
+                    if color.red == 255 && color.blue == 255 && color.alpha == alpha)
+            })
+            .cloned()
+            .collect()
+    };
+    let fill = |x, y, w, h, alpha| Drawing::Fill {
+        x,
+        y,
+        w,
+        h,
+        color: style::Color {
+            alpha,
+            ..style::SCROLL_INDICATOR
+        },
+    };
+    // 3x3 of the 9x6 tiles are visible, each 26x20, with the hint bar below them from y = 58. The
+    // vertical bar is drawn a tile at a time
+    assert_eq!(
+        indicator_fills(112),
+        [
+            fill(77, 0, 1, 20, 112),
+            fill(77, 20, 1, 8, 112),
+            fill(0, 57, 25, 1, 112)
+        ]
+    );
+    assert_eq!(game.frame(), game::Redraw::Wait(1_000_000_000));
+
+    // Halfway through fading out, only the tiles under the bars are redrawn
+    CLOCK.with(|c| c.set(c.get() + 1_250_000_000));
+    recording.borrow_mut().clear();
+    assert_eq!(game.frame(), game::Redraw::NextFrame);
+    assert_eq!(
+        indicator_fills(55),
+        [
+            fill(77, 0, 1, 20, 55),
+            fill(77, 20, 1, 8, 55),
+            fill(0, 57, 25, 1, 55)
+        ]
+    );
+    let tiles = |recording: &Recording| -> Vec<String> {
+        recording
+            .borrow()
+            .iter()
+            .filter_map(|d| match d {
+                Drawing::Image { source, .. } if source.contains(',') => Some(source.clone()),
+                _ => None,
+            })
+            .collect()
+    };
+    assert_eq!(tiles(&recording), ["2,0", "2,1", "0,2"]);
+
+    // Once it has faded out the tiles are redrawn without it
+    CLOCK.with(|c| c.set(c.get() + 250_000_000));
+    recording.borrow_mut().clear();
+    assert_eq!(game.frame(), game::Redraw::Idle);
+    assert_eq!(tiles(&recording), ["2,0", "2,1", "0,2"]);
+    assert!(!recording
+        .borrow()
+        .iter()
+        .any(|d| matches!(d, Drawing::Fill { color, .. }
+        if color.red == 255 && color.blue == 255)));
+
+    // Moving the screen to the bottom right shows it again at the other ends of the edges, with the
+    // horizontal bar starting just inside the second column of tiles
+    game.handle_event(JumpRight);
+    game.handle_event(JumpBottom);
+    recording.borrow_mut().clear();
+    CLOCK.with(|c| c.set(c.get() + 1_000_000_000));
+    game.frame();
+    assert_eq!(
+        indicator_fills(112),
+        [
+            fill(77, 28, 1, 12, 112),
+            fill(51, 57, 1, 1, 112),
+            fill(77, 40, 1, 17, 112),
+            fill(52, 57, 25, 1, 112)
+        ]
+    );
+    NOW_STEP.with(|s| s.set(1_000_000_000));
+}
+
+// Tile sizes should be worked out again after zooming and resizing
+async fn test_layout_invalidation() {
+    let (platform, _recording) = TestPlatform::recording();
+    let settings = r#"{ "min_visible_tiles": 1, "max_visible_tiles": 8, "show_minimap": false }"#;
+    let platform = platform
+        .with_map(&grid_map(30, 20))
+        .with_settings(Some(settings));
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    assert_eq!(game.tile_size(), Vector { x: 10, y: 7 });
+    game.handle_event(ZoomIn);
+    assert_eq!(game.tile_size(), Vector { x: 10, y: 8 });
+    game.handle_event(ZoomIn);
+    assert_eq!(game.tile_size(), Vector { x: 10, y: 10 });
+    SCREEN_SIZE.with(|s| s.set((160, 120)));
+    game.handle_event(Redraw);
+    assert_eq!(game.tile_size(), Vector { x: 20, y: 20 });
+    // Clicks land on the tiles at their new size
+    game.handle_event(Click(Vector { x: 50, y: 40 }));
+    let top_left = game.visible_rect().top_left;
+    assert_eq!(game.cursor_pos(), top_left + Vector { x: 2, y: 2 });
+    SCREEN_SIZE.with(|s| s.set((80, 60)));
+}
+
+// Tile types should be drawn with their own images even when they share them with other types or
+// don't exist
+async fn test_tile_type_images() {
+    let mut map = grid_map(3, 1);
+    map.tile_types[1].image = "0,0".to_owned();
+    map.map[[0, 2]] = 99;
+    let (platform, recording) = TestPlatform::recording();
+    let platform = platform
+        .with_map(&map)
+        .with_settings(Some(r#"{ "min_visible_tiles": 1, "show_minimap": false }"#));
+    let _game = game::Game::load(platform, "lang", "us").await.unwrap();
+    let drawings = recording.borrow();
+    let tiles: Vec<&Drawing> = drawings
+        .iter()
+        .filter(|d| matches!(d, Drawing::Image { source, .. } if source.contains(',')))
+        .collect();
+    assert_eq!(
+        tiles,
+        [&image("0,0", 0, 0, 26, 60), &image("0,0", 26, 0, 26, 60)]
+    );
+}
+
+// Reloading the map should switch to the new map while keeping the view within it, and failing to
+// reload should keep the old map running
+async fn test_reload_map() {
+    let (platform, recording) = TestPlatform::recording();
+    let settings = r#"{
+        "min_visible_tiles": 1,
+        "max_visible_tiles": 3,
+        "show_minimap": false,
+        "show_input_hints": false,
+        "show_scroll_indicator": false
+    }"#;
+    let platform = platform
+        .with_map(&grid_map(6, 5))
+        .with_settings(Some(settings));
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    for event in [JumpRight, JumpBottom, ReloadAssets] {
+        game.handle_event(event);
+    }
+    assert_eq!(game.cursor_pos(), Vector { x: 5, y: 4 });
+
+    let replace_map = |map: Option<&serialization::Map>| {
+        let contents = map.map(|m| rmp_serde::encode::to_vec(m).unwrap());
+        REPLACED_FILES.with(|f| f.borrow_mut().insert("lang/map.map".to_owned(), contents));
+    };
+    replace_map(Some(&grid_map(4, 2)));
+    assert!(game.load_pending());
+    game.load_pending_map().await;
+    assert!(!game.load_pending());
+    assert_eq!(game.map_size(), Vector { x: 4, y: 2 });
+    assert_eq!(game.cursor_pos(), Vector { x: 3, y: 1 });
+    // The new map is drawn at the next frame
+    assert_eq!(game.frame(), game::Redraw::Idle);
+    assert_eq!(
+        game.visible_rect(),
+        Rectangle {
+            top_left: Vector { x: 1, y: 0 },
+            size: Vector { x: 3, y: 2 }
+        }
+    );
+    assert_eq!(last_visible_tiles(&recording), ((1, 0), (3, 2)));
+
+    replace_map(None);
+    game.handle_event(ReloadAssets);
+    game.load_pending_map().await;
+    assert_eq!(game.map_size(), Vector { x: 4, y: 2 });
+    game.frame();
+    assert!(dialog_text(&recording)
+        .iter()
+        .any(|t| t.starts_with("Failed to reload map")));
+    REPLACED_FILES.with(|f| f.borrow_mut().clear());
+}
+
+// Skipping to the next map while the current one is being reloaded should drop the reload, so that
+// none of the images it was waiting on are loaded or drawn, and load the next map in its place
+async fn test_superseded_load() {
+    STORAGE.with(|s| s.borrow_mut().clear());
+    IMAGE_LOADS.with(|l| l.borrow_mut().clear());
+    let mut second_map = grid_map(2, 1);
+    for tile_type in second_map.tile_types.iter_mut() {
+        tile_type.image = format!("next {}", tile_type.name);
+    }
+    let (platform, recording) = TestPlatform::recording();
+    let platform = platform
+        .with_campaign(&["one.map", "two.map"])
+        .with_map_at("lang/one.map", &grid_map(2, 1))
+        .with_map_at("lang/two.map", &second_map);
+    let (mut event_sender, event_receiver) = futures::channel::mpsc::channel(512);
+    event_sender.send(ReloadAssets).await.unwrap();
+    event_sender.send(NextMap).await.unwrap();
+    drop(event_sender);
+    alemian_saga_core::run(platform, event_receiver, "lang", "us").await;
+
+    let loads = IMAGE_LOADS.with(|l| l.borrow().clone());
+    let count = |load: ImageLoad, path: &str| {
+        loads
+            .iter()
+            .filter(|(l, p)| *l == load && p == path)
+            .count()
+    };
+    for path in ["0,0", "1,0"] {
+        // Once when the game started and once for the reload
+        assert_eq!(count(ImageLoad::Started, path), 2);
+        assert_eq!(count(ImageLoad::Finished, path), 1);
+        assert_eq!(count(ImageLoad::Dropped, path), 1);
+    }
+    assert_eq!(count(ImageLoad::Finished, "next 1,0"), 1);
+    let drawn: Vec<_> = recording
+        .borrow()
+        .iter()
+        .filter_map(|d| match d {
+            Drawing::Image { source, .. } if source.contains(',') => Some(source.clone()),
+            _ => None,
+        })
+        .collect();
+    let next_map = drawn.iter().position(|s| s.starts_with("next")).unwrap();
+    assert!(drawn[next_map..].iter().all(|s| s.starts_with("next")));
+    STORAGE.with(|s| s.borrow_mut().clear());
+}
+
+// Finishing a map in a campaign should move on to the next one and remember it, and finishing the
+// last map should end the campaign
+async fn test_campaign() {
+    STORAGE.with(|s| s.borrow_mut().clear());
+    let mut first_map = grid_map(3, 2);
+    first_map.triggers = vec![trigger(1, 0, serialization::TriggerAction::EndMap)];
+    let mut second_map = grid_map(5, 4);
+    second_map.intro_dialog = vec![message(None, "Chapter two")];
+    let campaign_platform = |maps: &[&str]| {
+        let (platform, recording) = TestPlatform::recording();
+        let platform = platform
+            .with_campaign(maps)
+            .with_map_at("lang/one.map", &first_map)
+            .with_map_at("lang/two.map", &second_map);
+        (platform, recording)
+    };
+    let saved = || STORAGE.with(|s| s.borrow().get(campaign::STORAGE_KEY).cloned());
+
+    let (platform, recording) = campaign_platform(&["one.map", "two.map"]);
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    assert_eq!(game.map_size(), Vector { x: 3, y: 2 });
+    game.handle_event(Right);
+    assert!(game.load_pending());
+    game.load_pending_map().await;
+    assert_eq!(game.map_size(), Vector { x: 5, y: 4 });
+    assert_eq!(game.cursor_pos(), Vector { x: 0, y: 0 });
+    game.frame();
+    assert_eq!(dialog_text(&recording).last().unwrap(), "Chapter two");
+    assert_eq!(saved().as_deref(), Some("two.map"));
+
+    // Progress is picked up where it was left off
+    let (platform, _recording) = campaign_platform(&["one.map", "two.map"]);
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    assert_eq!(game.map_size(), Vector { x: 5, y: 4 });
+    for event in [Select, NextMap] {
+        game.handle_event(event);
+    }
+    assert!(game.is_finished());
+    assert_eq!(saved().as_deref(), Some("one.map"));
+    drop(game);
+
+    // A missing map ends the game, but the campaign picks up there once it's been fixed
+    let (platform, recording) = campaign_platform(&["one.map", "missing.map"]);
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    game.handle_event(Right);
+    assert_eq!(game.load_pending_map().await, game::Redraw::Finished);
+    assert!(recording.borrow().iter().any(|d| matches!(
+        d,
+        Drawing::Text { txt, .. } if txt.starts_with("Failed to load the next map")
+    )));
+    assert_eq!(saved().as_deref(), Some("missing.map"));
+    STORAGE.with(|s| s.borrow_mut().clear());
+}
+
+// A map's music should start when it loads and only change when a map with different music loads,
+// and muting should be remembered by later games
+async fn test_music() {
+    STORAGE.with(|s| s.borrow_mut().clear());
+    MUSIC.with(|m| m.borrow_mut().clear());
+    let mut map = grid_map(3, 2);
+    map.music = Some("theme.ogg".to_owned());
+    let platform = TestPlatform::recording().0.with_map(&map);
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    let played = || MUSIC.with(|m| m.borrow().clone());
+    assert_eq!(played(), vec![Some("theme.ogg".to_owned())]);
+    assert_eq!(VOLUME.with(|v| v.get()), Some(sound::MAX_VOLUME));
+
+    game.handle_event(ToggleMute);
+    assert_eq!(VOLUME.with(|v| v.get()), Some(0));
+    assert!(STORAGE.with(|s| s.borrow().contains_key(sound::STORAGE_KEY)));
+
+    let replace_map = |map: &serialization::Map| {
+        let contents = rmp_serde::encode::to_vec(map).unwrap();
+        REPLACED_FILES.with(|f| {
+            f.borrow_mut()
+                .insert("lang/map.map".to_owned(), Some(contents))
+        });
+    };
+    replace_map(&map);
+    game.handle_event(ReloadAssets);
+    game.load_pending_map().await;
+    assert_eq!(played().len(), 1);
+    map.music = None;
+    replace_map(&map);
+    game.handle_event(ReloadAssets);
+    game.load_pending_map().await;
+    assert_eq!(played().last().unwrap(), &None);
+    REPLACED_FILES.with(|f| f.borrow_mut().clear());
+    drop(game);
+
+    let platform = TestPlatform::recording().0.with_map(&map);
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    assert_eq!(VOLUME.with(|v| v.get()), Some(0));
+    game.handle_event(ToggleMute);
+    assert_eq!(VOLUME.with(|v| v.get()), Some(sound::MAX_VOLUME));
+
+    STORAGE.with(|s| {
+        let saved = r#"{ "volume": 40 }"#.to_owned();
+        s.borrow_mut().insert(sound::STORAGE_KEY.to_owned(), saved)
+    });
+    let platform = TestPlatform::recording().0.with_map(&map);
+    game::Game::load(platform, "lang", "us").await.unwrap();
+    assert_eq!(VOLUME.with(|v| v.get()), Some(40));
+    STORAGE.with(|s| s.borrow_mut().clear());
+}
+
+// Signals should play the sounds configured for them in the settings file, once per event, and
+// signals without a sound should be silent
+async fn test_sound_effects() {
+    SOUNDS.with(|s| s.borrow_mut().clear());
+    let settings = r#"{
+        "min_visible_tiles": 1,
+        "show_minimap": false,
+        "sounds": { "CursorMoved": "move.wav", "SelectionConfirmed": "confirm.wav", "Error": "error.wav" }
+    }"#;
+    let platform = TestPlatform::recording()
+        .0
+        .with_map(&grid_map(4, 3))
+        .with_settings(Some(settings));
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    let played = || {
+        SOUNDS.with(|s| {
+            s.borrow()
+                .iter()
+                .map(|(p, _)| p.clone())
+                .collect::<Vec<_>>()
+        })
+    };
+    assert!(played().is_empty());
+
+    // Deselecting and opening the menu make no sound
+    for event in [DownRight, Select, Select, Menu, Select] {
+        game.handle_event(event);
+    }
+    assert_eq!(played(), vec!["move.wav", "confirm.wav", "confirm.wav"]);
+
+    REPLACED_FILES.with(|f| f.borrow_mut().insert("lang/map.map".to_owned(), None));
+    game.handle_event(ReloadAssets);
+    game.load_pending_map().await;
+    assert_eq!(played().last().unwrap(), "error.wav");
+    REPLACED_FILES.with(|f| f.borrow_mut().clear());
+}
+
+// Tile types' ambient sounds should loop while they're on the screen, louder the more of it they
+// cover, and stop once they've been scrolled off of it
+async fn test_ambient_sounds() {
+    AMBIENT.with(|a| a.borrow_mut().clear());
+    let mut map = grid_map(4, 1);
+    for (i, path) in [(0, "river.ogg"), (1, "river.ogg"), (3, "wind.ogg")] {
+        map.tile_types[i].ambient_sound = Some(path.to_owned());
+    }
+    let platform = TestPlatform::recording().0.with_map(&map);
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    game.frame();
+    let playing = || {
+        let mut playing: Vec<_> = AMBIENT.with(|a| a.borrow().clone()).into_iter().collect();
+        playing.sort();
+        playing
+    };
+    let river = |volume| ("river.ogg".to_owned(), volume);
+    let wind = |volume| ("wind.ogg".to_owned(), volume);
+    assert_eq!(playing(), [river(50), wind(25)]);
+
+    // Volumes are rounded up, and sounds whose tiles are off the screen stop
+    game.handle_event(ZoomIn);
+    game.frame();
+    assert_eq!(game.visible_rect().size, Vector { x: 3, y: 1 });
+    assert_eq!(playing(), [river(67)]);
+    game.handle_event(ZoomIn);
+    game.frame();
+    assert_eq!(playing(), [river(100)]);
+
+    game.handle_event(JumpRight);
+    game.frame();
+    assert_eq!(game.visible_rect().top_left, Vector { x: 2, y: 0 });
+    assert_eq!(playing(), [wind(50)]);
+    drop(game);
+    AMBIENT.with(|a| a.borrow_mut().clear());
+}
+
+// A moving unit should play the footstep of each tile as it steps onto it, once per tile, and tiles
+// without a footstep should be silent
+async fn test_footsteps() {
+    let knight = serialization::Unit {
+        x: 0,
+        y: 0,
+        movement: 4,
+        ..unit_map().units.remove(0)
+    };
+    let mut map = grid_map(5, 1);
+    map.units = vec![knight];
+    for (i, path) in [(1, "splash.wav"), (2, "splash.wav"), (4, "step.wav")] {
+        map.tile_types[i].footstep_sound = Some(path.to_owned());
+    }
+    let platform = TestPlatform::recording().0.with_map(&map);
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    for event in [Select, Right, Right, Right, Right] {
+        game.handle_event(event);
+    }
+    NOW_STEP.with(|s| s.set(0));
+    SOUNDS.with(|s| s.borrow_mut().clear());
+    game.handle_event(Select);
+    let played = || SOUNDS.with(|s| s.borrow().clone());
+    let footstep = |path: &str| (path.to_owned(), sound::MAX_VOLUME);
+    game.frame();
+    game.frame();
+    assert_eq!(played(), [footstep("splash.wav")]);
+    // Each step takes 150ms
+    for _ in 0..4 {
+        CLOCK.with(|c| c.set(c.get() + 150_000_000));
+        game.frame();
+    }
+    let steps = ["splash.wav", "splash.wav", "step.wav"];
+    assert_eq!(played(), steps.map(footstep));
+    NOW_STEP.with(|s| s.set(1_000_000_000));
+}
+
+// The debug overlay should show the stats of the frame it's drawn on top of without counting its
+// own draw calls
+async fn test_debug_overlay() {
+    let (platform, recording) = TestPlatform::recording();
+    let settings = r#"{
+        "min_visible_tiles": 1,
+        "max_visible_tiles": 3,
+        "show_minimap": false,
+        "show_scroll_indicator": false
+    }"#;
+    let platform = platform
+        .with_map(&grid_map(9, 2))
+        .with_settings(Some(settings));
+    NOW_STEP.with(|s| s.set(0));
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    let overlay_text = |recording: &Recording| -> Vec<String> {
+        recording
+            .borrow()
+            .iter()
+            .filter_map(|d| match d {
+                Drawing::Text { txt, .. } if txt.contains(": ") => Some(txt.clone()),
+                _ => None,
+            })
+            .collect()
+    };
+
+    assert_eq!(game.handle_event(ToggleDebugOverlay), game::Redraw::Changed);
+    recording.borrow_mut().clear();
+    assert_eq!(game.frame(), game::Redraw::Idle);
+    let drawings = recording.borrow().clone();
+    let (frame, overlay) = drawings.split_at(drawings.len() - 5);
+    // The infobar is in the top right corner since the cursor is underneath the top left one
+    assert!(matches!(
+        overlay[0],
+        Drawing::Fill { x: 0, y: 0, color, .. } if color == style::DEBUG_OVERLAY_BACKGROUND
+    ));
+    assert_eq!(
+        overlay_text(&recording),
+        [
+            "FPS: 2".to_owned(),
+            format!("Draw calls: {}", frame.len()),
+            "Queue overflows: 0".to_owned(),
+            "Screen: (0, 0) 3x2".to_owned(),
+        ]
+    );
+
+    recording.borrow_mut().clear();
+    game.handle_event(ToggleDebugOverlay);
+    game.frame();
+    assert!(!recording.borrow().is_empty());
+    assert!(overlay_text(&recording).is_empty());
+    NOW_STEP.with(|s| s.set(1_000_000_000));
+}
+
+// Events that didn't fit in the event queue should be logged, but no more than every ten seconds
+async fn test_queue_overflow_log() {
+    LOG.with(|l| l.borrow_mut().clear());
+    let (platform, _recording) = TestPlatform::recording();
+    NOW_STEP.with(|s| s.set(0));
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    let overflow_log = || {
+        LOG.with(|l| {
+            l.borrow()
+                .iter()
+                .filter(|m| m.contains("event queue"))
+                .cloned()
+                .collect::<Vec<_>>()
+        })
+    };
+    game.handle_event(Right);
+    assert!(overflow_log().is_empty());
+    QUEUE_OVERFLOWS.with(|o| o.set(3));
+    game.handle_event(Left);
+    let first = "WARN: 3 events didn't fit in the event queue (3 in total)";
+    assert_eq!(overflow_log(), [first]);
+
+    QUEUE_OVERFLOWS.with(|o| o.set(5));
+    CLOCK.with(|c| c.set(c.get() + 9_000_000_000));
+    game.handle_event(Right);
+    assert_eq!(overflow_log(), [first]);
+    CLOCK.with(|c| c.set(c.get() + 1_000_000_000));
+    game.handle_event(Left);
+    let second = "WARN: 2 events didn't fit in the event queue (5 in total)";
+    assert_eq!(overflow_log(), [first, second]);
+    QUEUE_OVERFLOWS.with(|o| o.set(0));
+    NOW_STEP.with(|s| s.set(1_000_000_000));
+}
+
+// Messages should have their level in front and be dropped if they're less serious than the
+// verbosity, and a warning repeated for every bad tile should only be logged a few times
+async fn test_log_levels() {
+    LOG.with(|l| l.borrow_mut().clear());
+    let mut map = grid_map(3, 2);
+    map.map.fill(99);
+    let (platform, _recording) = TestPlatform::recording();
+    let platform = platform
+        .with_map(&map)
+        .with_settings(Some(r#"{ "log_level": "error" }"#));
+    game::Game::load(platform, "lang", "us").await.unwrap();
+    let logged = LOG.with(|l| l.borrow().clone());
+    let invalid = "WARN: Error: Invalid map file: tile type 99 does not exist";
+    assert_eq!(
+        logged.iter().filter(|m| *m == invalid).count(),
+        log::MAX_REPEATS as usize
+    );
+    assert!(logged.contains(&"WARN: 3 more like this were suppressed".to_owned()));
+
+    // The settings file turned the verbosity down once it was loaded
+    assert_eq!(log::verbosity(), log::Level::Error);
+    LOG.with(|l| l.borrow_mut().clear());
+    log::warn::<TestPlatform>("dropped");
+    log::error::<TestPlatform>("kept");
+    assert_eq!(LOG.with(|l| l.borrow().clone()), ["ERROR: kept"]);
+
+    assert_eq!(log::Level::parse("Debug"), Some(log::Level::Debug));
+    assert_eq!(log::Level::parse("verbose"), None);
+    log::set_verbosity(log::Level::Info);
+}
+
+// Images that failed to load should be skipped with one warning naming each of them, which comes
+// up again if they're still missing after the map is reloaded
+async fn test_missing_images() {
+    LOG.with(|l| l.borrow_mut().clear());
+    let (mut platform, recording) = TestPlatform::recording();
+    platform.missing_images.insert("cursor.png".to_owned());
+    platform.missing_images.insert("a".to_owned());
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    for event in [Right, Left, Redraw] {
+        game.handle_event(event);
+        game.frame();
+    }
+    assert!(!recording.borrow().iter().any(
+        |d| matches!(d, Drawing::Image { source, .. } if source == "cursor.png" || source == "a")
+    ));
+    let warnings = || -> Vec<String> {
+        LOG.with(|l| {
+            l.borrow()
+                .iter()
+                .filter(|m| m.contains("failed to load"))
+                .cloned()
+                .collect()
+        })
+    };
+    let expected = [
+        "WARN: a failed to load; a will be drawn without it",
+        "WARN: cursor.png failed to load; cursor will be drawn without it",
+    ];
+    assert_eq!(warnings(), expected);
+
+    game.handle_event(ReloadAssets);
+    game.load_pending_map().await;
+    game.frame();
+    assert_eq!(
+        warnings(),
+        [expected[0], expected[1], expected[0], expected[1]]
+    );
+}
+
+// Tiles whose image failed to load should be drawn as a checkerboard, with the missing path shown
+// on the infobar while the cursor is on one
+async fn test_placeholder_tiles() {
+    let (mut platform, recording) = TestPlatform::recording();
+    platform.missing_images.insert("a".to_owned());
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    recording.borrow_mut().clear();
+    game.handle_event(Redraw);
+    game.frame();
+    let drawings = recording.borrow().clone();
+    let fill = |x, y, w, h, color| Drawing::Fill { x, y, w, h, color };
+    let placeholder = [
+        fill(0, 0, 40, 30, style::BLACK),
+        fill(0, 0, 20, 15, style::MISSING_IMAGE),
+        fill(20, 15, 20, 15, style::MISSING_IMAGE),
+    ];
+    assert!(drawings.windows(3).any(|w| w == placeholder));
+    let texts: Vec<&str> = drawings
+        .iter()
+        .filter_map(|d| match d {
+            Drawing::Text { txt, .. } => Some(txt.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(texts, ["a", "(0, 0)", "missing: a"]);
+
+    // Tiles that loaded fine don't get a placeholder or a warning
+    recording.borrow_mut().clear();
+    game.handle_event(Right);
+    game.frame();
+    let drawings = recording.borrow().clone();
+    assert!(drawings.contains(&fill(0, 0, 40, 30, style::BLACK)));
+    assert!(!drawings.contains(&fill(40, 0, 40, 30, style::BLACK)));
+    assert!(!drawings
+        .iter()
+        .any(|d| matches!(d, Drawing::Text { txt, .. } if txt.starts_with("missing"))));
+}
+
+// A small map with a unit on the bottom right tile
+fn unit_map() -> serialization::Map {
+    let mut map = small_map();
+    map.units.push(serialization::Unit {
+        name: "Knight".to_owned(),
+        x: 1,
+        y: 1,
+        image: "knight.png".to_owned(),
+        portrait: Some("knight-portrait.png".to_owned()),
+        hp: 15,
+        max_hp: 20,
+        strength: 7,
+        defense: 5,
+        speed: 4,
+        movement: 3,
+        range: 1,
+        range_pattern: None,
+        team: serialization::PLAYER_TEAM.to_owned(),
+        statuses: vec![],
+        level: 1,
+        exp: 0,
+    });
+    map
+}
+
+// Gets the color that a team's units are tinted with by default
+fn team_color(team: &str) -> style::Color {
+    settings::Settings::default().team_colors[team]
+}
+
+// The unit panel should show the unit under the cursor and uncover the tiles beneath it once the
+// cursor leaves the unit
+async fn test_unit_panel() {
+    let (platform, recording) = TestPlatform::recording();
+    let platform = platform.with_map(&unit_map());
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    let drawings = recording.borrow().clone();
+    let sprite = tinted("knight.png", team_color(serialization::PLAYER_TEAM));
+    assert!(drawings.contains(&image(sprite.as_str(), 40, 30, 40, 30)));
+    let background = |x| Drawing::Fill {
+        x,
+        y: 4,
+        w: 20,
+        h: 35,
+        color: style::UNIT_PANEL_BACKGROUND,
+    };
+    assert!(!drawings.contains(&background(0)));
+
+    // The cursor is under the right side of the screen, so the panel goes on the left
+    recording.borrow_mut().clear();
+    game.handle_event(Right);
+    game.handle_event(Down);
+    game.frame();
+    let text = |txt: &str, tx, ty| Drawing::Text {
+        txt: txt.to_owned(),
+        tx,
+        ty,
+    };
+    let fill = |x, y, w, color| Drawing::Fill {
+        x,
+        y,
+        w,
+        h: 1,
+        color,
+    };
+    let panel = vec![
+        background(0),
+        image("knight-portrait.png", 1, 5, 18, 18),
+        text("Knight", 1, 24),
+        text("HP", 1, 26),
+        text("15/20", 14, 26),
+        fill(1, 28, 18, style::HP_BAR_EMPTY),
+        fill(1, 28, 14, style::HP_BAR_HEALTHY),
+        text("Strength", 1, 30),
+        text("7", 18, 30),
+        text("Defense", 1, 32),
+        text("5", 18, 32),
+        text("Speed", 1, 34),
+        text("4", 18, 34),
+        text("Move", 1, 36),
+        text("3", 18, 36),
+    ];
+    let drawings = recording.borrow().clone();
+    assert_eq!(drawings[drawings.len() - panel.len()..], panel[..]);
+
+    recording.borrow_mut().clear();
+    game.handle_event(Left);
+    game.frame();
+    let drawings = recording.borrow().clone();
+    assert!(!drawings.contains(&background(0)));
+    assert!(drawings.contains(&image("a", 0, 0, 40, 30)));
+    assert!(drawings.contains(&image("c", 0, 30, 40, 30)));
+
+    // The panel can be turned off for small screens
+    let (platform, recording) = TestPlatform::recording();
+    let platform = platform.with_map(&unit_map()).with_settings(Some(
+        r#"{ "min_visible_tiles": 1, "show_minimap": false, "show_unit_panel": false }"#,
+    ));
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    game.handle_event(Right);
+    game.handle_event(Down);
+    game.frame();
+    assert!(!recording.borrow().iter().any(
+        |d| matches!(d, Drawing::Fill { color, .. } if *color == style::UNIT_PANEL_BACKGROUND)
+    ));
+}
+
+// Unit sprites should be tinted with their team's color, with each sprite only tinted once for
+// each color
+async fn test_team_colors() {
+    TINTS.with(|t| t.borrow_mut().clear());
+    let mut map = unit_map();
+    let knight = map.units[0].clone();
+    for (x, y, team) in [(0, 0, "player"), (1, 0, "enemy"), (0, 1, "nobody")] {
+        map.units.push(serialization::Unit {
+            x,
+            y,
+            team: team.to_owned(),
+            ..knight.clone()
+        });
+    }
+    let (platform, recording) = TestPlatform::recording();
+    let platform = platform.with_map(&map);
+    game::Game::load(platform, "lang", "us").await.unwrap();
+    let player = team_color("player");
+    let enemy = team_color("enemy");
+    TINTS.with(|t| {
+        assert_eq!(
+            *t.borrow(),
+            [
+                ("knight.png".to_owned(), player),
+                ("knight.png".to_owned(), enemy)
+            ]
+        )
+    });
+    let drawings = recording.borrow().clone();
+    for (sprite, x, y) in [
+        (tinted("knight.png", player), 40, 30),
+        (tinted("knight.png", player), 0, 0),
+        (tinted("knight.png", enemy), 40, 0),
+        ("knight.png".to_owned(), 0, 30),
+    ] {
+        assert!(drawings.contains(&image(sprite.as_str(), x, y, 40, 30)));
+    }
+}
+
+// Units should have a hp bar along the bottom of their tile, colored by how much hp they have
+// left and sized to fit the tile at every zoom level, with their statuses in the top right corner
+async fn test_unit_overlays() {
+    let mut map = unit_map();
+    let knight = map.units[0].clone();
+    map.units[0].statuses = vec![serialization::Status::Acted];
+    for (x, y, hp, max_hp) in [(0, 0, 20, 20), (1, 0, 5, 20), (0, 1, 1, 3)] {
+        map.units.push(serialization::Unit {
+            x,
+            y,
+            hp,
+            max_hp,
+            ..knight.clone()
+        });
+    }
+    let fill = |x, y, w, h, color| Drawing::Fill { x, y, w, h, color };
+    let bar = |x, y, w, h, hp, max_hp| {
+        [
+            fill(x, y, w, h, style::HP_BAR_EMPTY),
+            fill(x, y, hp * w / max_hp, h, style::hp_bar_color(hp, max_hp)),
+        ]
+    };
+    let (platform, recording) = TestPlatform::recording();
+    let platform = platform.with_map(&map);
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    let drawings = recording.borrow().clone();
+    for expected in [
+        bar(0, 27, 40, 3, 20, 20),
+        bar(40, 27, 40, 3, 5, 20),
+        bar(40, 57, 40, 3, 15, 20),
+    ] {
+        assert!(drawings.windows(2).any(|w| w == expected));
+    }
+    assert_eq!(style::hp_bar_color(1, 3), style::HP_BAR_WOUNDED);
+    // A third of 40 pixels rounds to 13
+    assert!(drawings.contains(&fill(0, 57, 13, 3, style::HP_BAR_WOUNDED)));
+    // Statuses without an icon get a plain marker
+    assert!(drawings.contains(&fill(70, 30, 10, 7, style::STATUS_MARKER)));
+
+    // Zoomed in on the knight until its tile fills the screen
+    recording.borrow_mut().clear();
+    game.handle_event(Right);
+    game.handle_event(Down);
+    game.handle_event(ZoomIn);
+    game.handle_event(ZoomIn);
+    game.frame();
+    let drawings = recording.borrow().clone();
+    assert!(drawings.windows(2).any(|w| w == bar(0, 53, 80, 7, 15, 20)));
+    assert!(drawings.contains(&fill(60, 0, 20, 15, style::STATUS_MARKER)));
+
+    // Hp too large to multiply by the width in screen units without overflowing
+    map.units.truncate(1);
+    map.units[0].hp = 3_000_000_000;
+    map.units[0].max_hp = 4_000_000_000;
+    let settings = r#"{
+        "min_visible_tiles": 1,
+        "show_minimap": false,
+        "status_icons": { "Acted": "acted.png" }
+    }"#;
+    let (platform, recording) = TestPlatform::recording();
+    let platform = platform.with_map(&map).with_settings(Some(settings));
+    game::Game::load(platform, "lang", "us").await.unwrap();
+    let drawings = recording.borrow().clone();
+    assert!(drawings.contains(&fill(40, 57, 30, 3, style::HP_BAR_HEALTHY)));
+    assert!(drawings.contains(&image("acted.png", 70, 30, 10, 7)));
+    assert!(!drawings.contains(&fill(70, 30, 10, 7, style::STATUS_MARKER)));
+}
+
+// Builds a set of map positions from coordinates
+fn positions(coordinates: &[(u32, u32)]) -> std::collections::HashSet<Vector<u32>> {
+    coordinates.iter().map(|&(x, y)| Vector { x, y }).collect()
+}
+
+// Reachable tiles should be found within a movement budget, going around tiles that can't be
+// entered or cost too much and stopping at the edges of the map
+fn test_reachable() {
+    let size = Vector { x: 5, y: 5 };
+    let center = Vector { x: 2, y: 2 };
+    let moves = pathfinding::reachable(center, 1, size, |_| Some(1));
+    let expected = positions(&[(2, 2), (1, 2), (3, 2), (2, 1), (2, 3)]);
+    assert_eq!(
+        moves
+            .keys()
+            .copied()
+            .collect::<std::collections::HashSet<_>>(),
+        expected
+    );
+    assert_eq!(moves[&center], 0);
+
+    // The wall on the left has to be walked around and the swamp above is too costly to enter
+    let cost = |p: Vector<u32>| match (p.x, p.y) {
+        (1, _) => None,
+        (2, 1) => Some(3),
+        _ => Some(1),
+    };
+    let moves = pathfinding::reachable(center, 2, size, cost);
+    let expected = positions(&[(2, 2), (3, 2), (4, 2), (3, 1), (3, 3), (2, 3), (2, 4)]);
+    assert_eq!(
+        moves
+            .keys()
+            .copied()
+            .collect::<std::collections::HashSet<_>>(),
+        expected
+    );
+
+    let corner = Vector { x: 0, y: 0 };
+    let moves = pathfinding::reachable(corner, 1, size, |_| Some(1));
+    let expected = positions(&[(0, 0), (1, 0), (0, 1)]);
+    assert_eq!(
+        moves
+            .keys()
+            .copied()
+            .collect::<std::collections::HashSet<_>>(),
+        expected
+    );
+}
+
+// Units should be able to move through allies (if allowed) but not enemies, never stop on another
+// unit and always be able to stay where they are
+fn test_occupied_moves() {
+    let size = Vector { x: 5, y: 5 };
+    let center = Vector { x: 2, y: 2 };
+    let destinations = |moves: &pathfinding::Moves| moves.destinations.clone();
+    let passed = |moves: &pathfinding::Moves| {
+        moves
+            .costs
+            .keys()
+            .copied()
+            .collect::<std::collections::HashSet<_>>()
+    };
+
+    // Surrounded on all four sides, a unit can only stay put whoever it's surrounded by
+    let around = positions(&[(1, 2), (3, 2), (2, 1), (2, 3)]);
+    for (neighbor, pass) in [
+        (pathfinding::Occupant::Enemy, true),
+        (pathfinding::Occupant::Ally, false),
+    ] {
+        let occupant = |p| {
+            if p == center {
+                pathfinding::Occupant::Ally
+            } else if around.contains(&p) {
+                neighbor
+            } else {
+                pathfinding::Occupant::Empty
+            }
+        };
+        let moves = pathfinding::moves(center, 3, size, |_| Some(1), occupant, pass);
+        assert_eq!(destinations(&moves), positions(&[(2, 2)]));
+        assert_eq!(passed(&moves), positions(&[(2, 2)]));
+    }
+
+    // Two enemies touching corners leave no way between them, as units can't move diagonally
+    let corner = Vector { x: 0, y: 0 };
+    let enemies = positions(&[(1, 0), (0, 1)]);
+    let occupant = |p| match enemies.contains(&p) {
+        true => pathfinding::Occupant::Enemy,
+        false => pathfinding::Occupant::Empty,
+    };
+    let moves = pathfinding::moves(corner, 4, size, |_| Some(1), occupant, true);
+    assert_eq!(destinations(&moves), positions(&[(0, 0)]));
+
+    // An ally in the way can be moved through but not stopped on
+    let ally = Vector { x: 3, y: 2 };
+    let occupant = |p| match p == ally || p == center {
+        true => pathfinding::Occupant::Ally,
+        false => pathfinding::Occupant::Empty,
+    };
+    let wall = |p: Vector<u32>| (p.y == 2 || p.x == 4).then_some(1);
+    let moves = pathfinding::moves(center, 2, size, wall, occupant, true);
+    assert_eq!(
+        destinations(&moves),
+        positions(&[(2, 2), (1, 2), (0, 2), (4, 2)])
+    );
+    assert_eq!(moves.costs[&ally], 1);
+    let moves = pathfinding::moves(center, 2, size, wall, occupant, false);
+    assert_eq!(destinations(&moves), positions(&[(2, 2), (1, 2), (0, 2)]));
+    assert!(!moves.costs.contains_key(&ally));
+}
+
+// Attack ranges should cover every tile within reach of a weapon from anywhere the unit can move,
+// clipped to the map and leaving out excluded tiles
+fn test_attack_range() {
+    let reach = |max| geometry::RangePattern::MinMax { min: 1, max };
+    let size = Vector { x: 3, y: 3 };
+    let corner = [Vector { x: 0, y: 0 }];
+    let range = pathfinding::attack_range(corner, &reach(1), size, |_| false);
+    assert_eq!(range, positions(&[(1, 0), (0, 1)]));
+    let range = pathfinding::attack_range(corner, &reach(2), size, |_| false);
+    assert_eq!(range, positions(&[(1, 0), (0, 1), (2, 0), (1, 1), (0, 2)]));
+    let ally = Vector { x: 1, y: 1 };
+    let range = pathfinding::attack_range(corner, &reach(2), size, |p| p == ally);
+    assert_eq!(range, positions(&[(1, 0), (0, 1), (2, 0), (0, 2)]));
+
+    // Origins in range of each other are included
+    let origins = [Vector { x: 0, y: 0 }, Vector { x: 1, y: 0 }];
+    let range = pathfinding::attack_range(origins, &reach(1), size, |_| false);
+    assert_eq!(range, positions(&[(0, 0), (1, 0), (2, 0), (0, 1), (1, 1)]));
+}
+
+// Selecting a unit should highlight where it can move and, separately, what it could attack from
+// there without covering any of the tiles it can move to
+async fn test_unit_ranges() {
+    let knight = unit_map().units.remove(0);
+    let unit = |x, y, team: &str| serialization::Unit {
+        x,
+        y,
+        movement: 1,
+        team: team.to_owned(),
+        ..knight.clone()
+    };
+    let mut map = grid_map(5, 5);
+    map.units = vec![
+        unit(2, 2, "player"),
+        unit(3, 3, "player"),
+        unit(1, 2, "enemy"),
+    ];
+    let settings = r#"{ "min_visible_tiles": 1, "show_minimap": false, "show_unit_panel": false }"#;
+    let (platform, recording) = TestPlatform::recording();
+    let platform = platform.with_map(&map).with_settings(Some(settings));
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    game.handle_event(Right);
+    game.handle_event(Right);
+    game.handle_event(Down);
+    game.handle_event(Down);
+    game.frame();
+    recording.borrow_mut().clear();
+    game.handle_event(Select);
+    game.frame();
+    let highlighted = |kind: style::HighlightKind| {
+        recording
+            .borrow()
+            .iter()
+            .filter_map(|d| match d {
+                Drawing::Fill { x, y, color, .. } if *color == kind.color() => {
+                    Some((x / 16, y / 12))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+    let moves = highlighted(style::HighlightKind::Movement);
+    let attacks = highlighted(style::HighlightKind::Attack);
+    let set = |tiles: &[(u32, u32)]| {
+        tiles
+            .iter()
+            .copied()
+            .collect::<std::collections::HashSet<_>>()
+    };
+    // The enemy blocks the way left but can be attacked; the ally can't be
+    assert_eq!(set(&moves), set(&[(2, 2), (3, 2), (2, 1), (2, 3)]));
+    assert_eq!(
+        set(&attacks),
+        set(&[(1, 2), (4, 2), (2, 0), (2, 4), (1, 1), (3, 1), (1, 3)])
+    );
+    assert_eq!(moves.len() + attacks.len(), 11);
+
+    // Selecting the unit again hides its ranges
+    recording.borrow_mut().clear();
+    game.handle_event(Select);
+    game.frame();
+    assert!(highlighted(style::HighlightKind::Movement).is_empty());
+    assert!(highlighted(style::HighlightKind::Attack).is_empty());
+}
+
+// Starts a game on a 5x5 grid with a knight that can move two tiles in the middle, and selects it
+async fn select_knight() -> (game::Game<TestPlatform>, Recording) {
+    let knight = serialization::Unit {
+        x: 2,
+        y: 2,
+        movement: 2,
+        ..unit_map().units.remove(0)
+    };
+    let mut map = grid_map(5, 5);
+    map.units = vec![knight];
+    let settings = r#"{ "min_visible_tiles": 1, "show_minimap": false, "show_unit_panel": false }"#;
+    let (platform, recording) = TestPlatform::recording();
+    let platform = platform.with_map(&map).with_settings(Some(settings));
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    for event in [Right, Right, Down, Down, Select] {
+        game.handle_event(event);
+    }
+    game.frame();
+    (game, recording)
+}
+
+// Finds the tiles on the 5x5 grid that the knight was drawn on
+fn knight_tiles(recording: &Recording) -> Vec<(u32, u32)> {
+    let sprite = tinted("knight.png", team_color(serialization::PLAYER_TEAM));
+    recording
+        .borrow()
+        .iter()
+        .filter_map(|d| match d {
+            Drawing::Image { source, x, y, .. } if *source == sprite => Some((x / 16, y / 12)),
+            _ => None,
+        })
+        .collect()
+}
+
+// A move should only be committed once an action is chosen for it, and cancelling the action menu
+// should put the unit back where it was with its ranges showing so that it can move elsewhere
+async fn test_undo_move() {
+    let (mut game, recording) = select_knight().await;
+    let movement = Drawing::Fill {
+        x: 32,
+        y: 24,
+        w: 16,
+        h: 12,
+        color: style::HighlightKind::Movement.color(),
+    };
+    game.handle_event(Right);
+    recording.borrow_mut().clear();
+    game.handle_event(Select);
+    // The knight is drawn once it gets there
+    game.frame();
+    assert_eq!(knight_tiles(&recording), [(3, 2)]);
+    assert!(recording
+        .borrow()
+        .iter()
+        .any(|d| matches!(d, Drawing::Text { txt, .. } if txt == "Wait")));
+
+    recording.borrow_mut().clear();
+    game.handle_event(Cancel);
+    game.frame();
+    assert_eq!(knight_tiles(&recording), [(2, 2)]);
+    assert!(recording.borrow().contains(&movement));
+    assert_eq!(game.cursor_pos(), Vector { x: 2, y: 2 });
+
+    game.handle_event(Left);
+    game.handle_event(Left);
+    game.handle_event(Select);
+    game.handle_event(Select);
+    recording.borrow_mut().clear();
+    game.handle_event(Redraw);
+    game.frame();
+    assert_eq!(knight_tiles(&recording), [(0, 2)]);
+    let acted = Drawing::Fill {
+        x: 12,
+        y: 24,
+        w: 4,
+        h: 3,
+        color: style::STATUS_MARKER,
+    };
+    assert!(recording.borrow().contains(&acted));
+    assert!(!recording.borrow().contains(&movement));
+
+    // Units that have acted can't move again until the turn ends
+    game.handle_event(Select);
+    game.handle_event(Right);
+    game.handle_event(Select);
+    recording.borrow_mut().clear();
+    game.handle_event(Redraw);
+    game.frame();
+    assert_eq!(knight_tiles(&recording), [(0, 2)]);
+    game.handle_event(Menu);
+    game.handle_event(Select);
+    recording.borrow_mut().clear();
+    game.frame();
+    assert!(!recording.borrow().contains(&acted));
+}
+
+// Ending the turn or reloading the map should take back a move that hasn't been committed
+async fn test_pending_move_cleared() {
+    let (mut game, recording) = select_knight().await;
+    game.handle_event(Down);
+    game.handle_event(Select);
+    game.handle_event(ReloadAssets);
+    game.load_pending_map().await;
+    recording.borrow_mut().clear();
+    game.frame();
+    assert_eq!(knight_tiles(&recording), [(2, 2)]);
+    // The action menu went away along with the move
+    game.handle_event(Select);
+    recording.borrow_mut().clear();
+    game.handle_event(Redraw);
+    game.frame();
+    assert_eq!(knight_tiles(&recording), [(2, 2)]);
+}
+
+// Choosing the selected unit's own tile should keep it where it is and ask what it should do there
+async fn test_stay_put() {
+    let (mut game, recording) = select_knight().await;
+    recording.borrow_mut().clear();
+    game.handle_event(Select);
+    game.frame();
+    assert!(recording
+        .borrow()
+        .iter()
+        .any(|d| matches!(d, Drawing::Text { txt, .. } if txt == "Wait")));
+    game.handle_event(Select);
+    recording.borrow_mut().clear();
+    game.handle_event(Redraw);
+    game.frame();
+    assert_eq!(knight_tiles(&recording), [(2, 2)]);
+    let acted = Drawing::Fill {
+        x: 44,
+        y: 24,
+        w: 4,
+        h: 3,
+        color: style::STATUS_MARKER,
+    };
+    assert!(recording.borrow().contains(&acted));
+
+    // Once it has acted, choosing its tile again just hides its ranges
+    game.handle_event(Select);
+    recording.borrow_mut().clear();
+    game.handle_event(Select);
+    game.frame();
+    assert!(!recording
+        .borrow()
+        .iter()
+        .any(|d| matches!(d, Drawing::Text { txt, .. } if txt == "Wait")));
+}
+
+// Whether units can move through their allies should come from the settings
+async fn test_pass_through_allies_setting() {
+    let knight = unit_map().units.remove(0);
+    let knight = |x| serialization::Unit {
+        x,
+        y: 0,
+        movement: 2,
+        ..knight.clone()
+    };
+    let mut map = grid_map(5, 1);
+    map.units = vec![knight(0), knight(1)];
+    let beyond = Drawing::Fill {
+        x: 32,
+        y: 0,
+        w: 16,
+        h: 60,
+        color: style::HighlightKind::Movement.color(),
+    };
+    for pass in [true, false] {
+        let settings = format!(
+            r#"{{ "min_visible_tiles": 1, "show_minimap": false, "show_unit_panel": false,
+                "pass_through_allies": {} }}"#,
+            pass
+        );
+        let (platform, recording) = TestPlatform::recording();
+        let platform = platform.with_map(&map).with_settings(Some(&settings));
+        let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+        game.frame();
+        recording.borrow_mut().clear();
+        game.handle_event(Select);
+        game.frame();
+        assert_eq!(recording.borrow().contains(&beyond), pass);
+    }
+}
+
+// Loads a 9x9 map showing 3x3 tiles at a time, with the player's knights on the tiles listed (in
+// order) and an enemy knight in the middle
+async fn ready_units_game(players: &[(u32, u32)]) -> game::Game<TestPlatform> {
+    let knight = unit_map().units.remove(0);
+    let knight = |(x, y), team: &str| serialization::Unit {
+        x,
+        y,
+        team: team.to_owned(),
+        ..knight.clone()
+    };
+    let mut map = grid_map(9, 9);
+    map.units = players.iter().map(|&p| knight(p, "player")).collect();
+    map.units.push(knight((4, 4), "enemy"));
+    let settings = r#"{ "min_visible_tiles": 1, "max_visible_tiles": 3, "show_minimap": false,
+        "show_unit_panel": false }"#;
+    let (platform, _) = TestPlatform::recording();
+    let platform = platform.with_map(&map).with_settings(Some(settings));
+    game::Game::load(platform, "lang", "us").await.unwrap()
+}
+
+// NextUnit should go through the player's units that can still act in order, centering the screen
+// on each and going back to the first after the last. Units that have acted are skipped until the
+// next turn
+async fn test_next_unit() {
+    let mut game = ready_units_game(&[(1, 1), (7, 7), (7, 1)]).await;
+    let visit = |game: &mut game::Game<TestPlatform>| {
+        game.handle_event(NextUnit);
+        let top_left = game.visible_rect().top_left;
+        let cursor = game.cursor_pos();
+        ((cursor.x, cursor.y), (top_left.x, top_left.y))
+    };
+    // The cursor starts away from every unit, so the first one comes first
+    assert_eq!(visit(&mut game), ((1, 1), (0, 0)));
+    assert_eq!(visit(&mut game), ((7, 7), (6, 6)));
+    assert_eq!(visit(&mut game), ((7, 1), (6, 0)));
+    assert_eq!(visit(&mut game), ((1, 1), (0, 0)));
+
+    // Waiting where it stands takes the first unit out of the cycle
+    for event in [Select, Select, Select] {
+        game.handle_event(event);
+    }
+    assert_eq!(visit(&mut game), ((7, 7), (6, 6)));
+    assert_eq!(visit(&mut game), ((7, 1), (6, 0)));
+    assert_eq!(visit(&mut game), ((7, 7), (6, 6)));
+
+    // Every unit can act again once the turn is over
+    game.handle_event(Menu);
+    game.handle_event(Select);
+    assert_eq!(visit(&mut game), ((7, 1), (6, 0)));
+    assert_eq!(visit(&mut game), ((1, 1), (0, 0)));
+}
+
+// Once a unit has acted, the cursor should snap to the nearest unit that can still act, or stay
+// where it is once none can
+async fn test_snap_to_ready_unit() {
+    let mut game = ready_units_game(&[(1, 1), (7, 7), (2, 6)]).await;
+    let wait_at = |game: &mut game::Game<TestPlatform>, path: &[Event<u32, String>]| {
+        game.handle_event(Select);
+        for event in path {
+            game.handle_event(event.clone());
+        }
+        // The test clock moves on a second for each event, so the unit is there before the action
+        // menu is used to wait
+        game.handle_event(Select);
+        game.handle_event(Select);
+        let cursor = game.cursor_pos();
+        (cursor.x, cursor.y)
+    };
+    game.handle_event(NextUnit);
+    // (2, 6) is five steps from (1, 2), but (7, 7) is eleven, even though it comes first
+    assert_eq!(wait_at(&mut game, &[Down]), (2, 6));
+    // However far away the last unit that can act is, the cursor goes to it
+    assert_eq!(wait_at(&mut game, &[Right, Right]), (7, 7));
+    // The last unit to act leaves the cursor where it ended up
+    assert_eq!(wait_at(&mut game, &[Left]), (6, 7));
+    game.handle_event(NextUnit);
+    assert_eq!(game.cursor_pos(), Vector { x: 6, y: 7 });
+}
+
+// Loads a 9x9 map where a player's knight at (1, 1) and an enemy knight at (7, 7) are played by
+// two people taking turns. Any extra settings are added to the end of the settings file
+async fn hot_seat_game(fog: bool, extra_settings: &str) -> (game::Game<TestPlatform>, Recording) {
+    let knight = unit_map().units.remove(0);
+    let mut map = grid_map(9, 9);
+    map.fog = fog;
+    map.units = vec![
+        serialization::Unit {
+            x: 1,
+            y: 1,
+            ..knight.clone()
+        },
+        serialization::Unit {
+            x: 7,
+            y: 7,
+            team: "enemy".to_owned(),
+            ..knight
+        },
+    ];
+    let settings = format!(
+        r#"{{ "show_minimap": false, "show_unit_panel": false, "show_scroll_indicator": false,
+        "sight_range": 9, "players": ["player", "enemy"]{} }}"#,
+        extra_settings
+    );
+    let (platform, recording) = TestPlatform::recording();
+    let platform = platform
+        .with_map(&map)
+        .with_settings(Some(settings.as_str()));
+    let game = game::Game::load(platform, "lang", "us").await.unwrap();
+    (game, recording)
+}
+
+// With two players taking turns on the same machine, ending a turn should hand over to the other
+// player behind a banner that only Select takes down. Each player can only move their own units,
+// and the turn count should go up once both have had a go
+async fn test_hot_seat() {
+    let (mut game, recording) = hot_seat_game(false, "").await;
+    game.frame();
+    let unit_positions = |game: &game::Game<TestPlatform>| -> Vec<(u32, u32)> {
+        game.snapshot().units.iter().map(|u| (u.x, u.y)).collect()
+    };
+    let move_left = |game: &mut game::Game<TestPlatform>| {
+        for event in [Select, Left, Select, Select] {
+            game.handle_event(event);
+        }
+    };
+
+    recording.borrow_mut().clear();
+    game.handle_event(Menu);
+    game.handle_event(Select);
+    game.frame();
+    assert!(drawn_text(&recording).contains(&"Enemy Phase".to_owned()));
+    let snapshot = game.snapshot();
+    assert_eq!((snapshot.turn, snapshot.player), (1, 1));
+    // The cursor goes to the next player's unit, and stays there until the banner is down
+    assert_eq!(game.cursor_pos(), Vector { x: 7, y: 7 });
+    for event in [Left, Cancel, Menu, NextUnit] {
+        game.handle_event(event);
+    }
+    game.frame();
+    assert_eq!(game.cursor_pos(), Vector { x: 7, y: 7 });
+    recording.borrow_mut().clear();
+    game.handle_event(Select);
+    game.frame();
+    assert!(!drawn_text(&recording).contains(&"Enemy Phase".to_owned()));
+
+    // The enemy's knight moves on this turn, and the player's knight doesn't
+    move_left(&mut game);
+    assert_eq!(unit_positions(&game), [(1, 1), (6, 7)]);
+    for event in std::iter::repeat_n(Left, 5).chain(std::iter::repeat_n(Up, 6)) {
+        game.handle_event(event);
+    }
+    assert_eq!(game.cursor_pos(), Vector { x: 1, y: 1 });
+    move_left(&mut game);
+    assert_eq!(unit_positions(&game), [(1, 1), (6, 7)]);
+
+    game.handle_event(Menu);
+    game.handle_event(Select);
+    game.frame();
+    let snapshot = game.snapshot();
+    assert_eq!((snapshot.turn, snapshot.player), (2, 0));
+    game.handle_event(Select);
+    assert_eq!(game.cursor_pos(), Vector { x: 1, y: 1 });
+    move_left(&mut game);
+    assert_eq!(unit_positions(&game), [(0, 1), (6, 7)]);
+}
+
+// With fog of war, the map should be hidden while the turn banner is up, and shown again once the
+// next player takes it down
+async fn test_fog_handoff() {
+    let (mut game, recording) = hot_seat_game(true, "").await;
+    game.frame();
+    recording.borrow_mut().clear();
+    game.handle_event(Menu);
+    game.handle_event(Select);
+    game.frame();
+    let handoff = Drawing::Fill {
+        x: 0,
+        y: 0,
+        w: 80,
+        h: 60,
+        color: style::HANDOFF_BACKGROUND,
+    };
+    let drawings = recording.borrow().clone();
+    let hidden_at = drawings.iter().position(|d| *d == handoff).unwrap();
+    assert!(!drawings[hidden_at..]
+        .iter()
+        .any(|d| matches!(d, Drawing::Image { .. })));
+    assert_eq!(
+        drawn_text(&recording).last(),
+        Some(&"Enemy Phase".to_owned())
+    );
+
+    recording.borrow_mut().clear();
+    game.handle_event(Select);
+    game.frame();
+    let drawings = recording.borrow();
+    assert!(!drawings.contains(&handoff));
+    assert!(drawings
+        .iter()
+        .any(|d| matches!(d, Drawing::Image { source, .. } if source == "7,7")));
+}
+
+// Takes the messages that a game has sent so far over one of the connections it opened
+fn take_sent(connection: usize) -> Vec<Vec<u8>> {
+    CONNECTIONS.with(|c| std::mem::take(&mut *c.borrow()[connection].sent.borrow_mut()))
+}
+
+// Passes messages back and forth between two games, each paired with the connection it's sending
+// over, until neither has anything more to say
+fn exchange(
+    (first, first_connection): (&mut game::Game<TestPlatform>, usize),
+    (second, second_connection): (&mut game::Game<TestPlatform>, usize),
+) {
+    loop {
+        let (to_second, to_first) = (take_sent(first_connection), take_sent(second_connection));
+        if to_second.is_empty() && to_first.is_empty() {
+            return;
+        }
+        for message in to_second {
+            second.handle_event(Remote(message));
+        }
+        for message in to_first {
+            first.handle_event(Remote(message));
+        }
+    }
+}
+
+// Two connected games should take turns in lockstep: what the player whose turn it is does on
+// their game should happen the same way on the other, which shouldn't let its own player act until
+// the turn comes back to them. Commands that are out of order are refused. One given in a different
+// state means the games have fallen out of step, so it drops the connection, and the game that
+// refused it picks up from where the other is once they reconnect
+async fn test_remote_play() {
+    CONNECTIONS.with(|c| c.borrow_mut().clear());
+    let remote =
+        |teams: &str| format!(r#", "remote_url": "ws://relay", "remote_teams": {}"#, teams);
+    let (mut home, home_recording) = hot_seat_game(false, remote(r#"["enemy"]"#).as_str()).await;
+    let (mut away, _) = hot_seat_game(false, remote(r#"["player"]"#).as_str()).await;
+    // Both games start from the same seed
+    assert_eq!(home.seed(), away.seed());
+    let hello = |received, resync| net::encode(&net::Message::Hello { received, resync }).unwrap();
+    let hello_0 = hello(0, false);
+    assert_eq!(take_sent(0), std::slice::from_ref(&hello_0));
+    assert_eq!(take_sent(1), [hello_0]);
+    let move_left = |game: &mut game::Game<TestPlatform>| {
+        for event in [NextUnit, Select, Left, Select, Select] {
+            game.handle_event(event);
+        }
+    };
+    let end_turn = |game: &mut game::Game<TestPlatform>| {
+        for event in [Menu, Select, Select] {
+            game.handle_event(event);
+        }
+    };
+    let unit_positions = |game: &game::Game<TestPlatform>| -> Vec<(u32, u32)> {
+        game.snapshot().units.iter().map(|u| (u.x, u.y)).collect()
+    };
+
+    move_left(&mut home);
+    exchange((&mut home, 0), (&mut away, 1));
+    assert_eq!(unit_positions(&away), [(0, 1), (7, 7)]);
+    assert_eq!(away.snapshot().units, home.snapshot().units);
+    // It isn't the away player's turn, so they can't move or end it
+    for event in std::iter::repeat_n(DownRight, 7) {
+        away.handle_event(event);
+    }
+    move_left(&mut away);
+    end_turn(&mut away);
+    assert!(take_sent(1).is_empty());
+    assert_eq!(unit_positions(&away), [(0, 1), (7, 7)]);
+    assert_eq!(away.snapshot().player, 0);
+
+    end_turn(&mut home);
+    exchange((&mut home, 0), (&mut away, 1));
+    assert_eq!(away.snapshot().player, 1);
+    // The away player takes down their own banner, and then it's their turn
+    away.handle_event(Select);
+    move_left(&mut away);
+    exchange((&mut home, 0), (&mut away, 1));
+    assert_eq!(unit_positions(&home), [(0, 1), (6, 7)]);
+    assert_eq!(home.snapshot().units, away.snapshot().units);
+
+    // Commands that skip ahead are refused without losing the connection
+    LOG.with(|l| l.borrow_mut().clear());
+    let command = net::Command::Wait {
+        origin: Vector { x: 6, y: 7 },
+        destination: Vector { x: 6, y: 6 },
+    };
+    let state_hash = String::new();
+    let skipping = net::Message::Command {
+        seq: 2,
+        command,
+        state_hash,
+    };
+    home.handle_event(Remote(net::encode(&skipping).unwrap()));
+    assert_eq!(unit_positions(&home), [(0, 1), (6, 7)]);
+    let refusals = || LOG.with(|l| l.borrow().iter().filter(|m| m.contains("Refusing")).count());
+    assert_eq!(refusals(), 1);
+    assert!(take_sent(0).is_empty());
+
+    // Once the away game's random decisions have gone out of step, the turn it ends is refused as
+    // having been given in a different state, and the connection is dropped
+    away.rng().next_u64();
+    home_recording.borrow_mut().clear();
+    end_turn(&mut away);
+    exchange((&mut home, 0), (&mut away, 1));
+    assert_eq!(refusals(), 2);
+    assert_eq!(home.snapshot().player, 1);
+    assert_eq!(away.snapshot().player, 0);
+    home.frame();
+    let disconnected = "Connection lost: Select to reconnect".to_owned();
+    assert!(drawn_text(&home_recording).contains(&disconnected));
+
+    // Reconnecting asks the away game for its state rather than having the refused command sent
+    // again, and the home game picks up from there
+    home.handle_event(Select);
+    let reconnected = CONNECTIONS.with(|c| c.borrow().len() - 1);
+    assert_eq!(reconnected, 2);
+    assert_eq!(take_sent(reconnected), [hello(1, true)]);
+    away.handle_event(Remote(hello(1, true)));
+    exchange((&mut home, reconnected), (&mut away, 1));
+    assert!(home.load_pending());
+    home.load_pending_map().await;
+    let snapshot = home.snapshot();
+    assert_eq!((snapshot.turn, snapshot.player), (2, 0));
+    assert_eq!(snapshot.units, away.snapshot().units);
+    assert_eq!(snapshot.rng, away.snapshot().rng);
+    // The games are back in step, so the home player's next move is carried out on both
+    move_left(&mut home);
+    exchange((&mut home, reconnected), (&mut away, 1));
+    assert_eq!(refusals(), 2);
+    assert_eq!(unit_positions(&away), [(0, 1), (6, 7)]);
+    assert_eq!(away.snapshot().units, home.snapshot().units);
+    end_turn(&mut home);
+    exchange((&mut home, reconnected), (&mut away, 1));
+    away.handle_event(Select);
+    move_left(&mut away);
+    end_turn(&mut away);
+    exchange((&mut home, reconnected), (&mut away, 1));
+    assert_eq!(refusals(), 2);
+    assert_eq!(unit_positions(&home), [(0, 1), (5, 7)]);
+    home.handle_event(Select);
+
+    // A command given just as the connection was lost is sent again once the games reconnect
+    for event in [NextUnit, Select, Right, Select, Select] {
+        home.handle_event(event);
+    }
+    assert_eq!(unit_positions(&home), [(1, 1), (5, 7)]);
+    take_sent(reconnected);
+    home_recording.borrow_mut().clear();
+    home.handle_event(ConnectionLost);
+    home.frame();
+    assert!(drawn_text(&home_recording).contains(&disconnected));
+    home.handle_event(Select);
+    let reconnected = CONNECTIONS.with(|c| c.borrow().len() - 1);
+    assert_eq!(reconnected, 3);
+    exchange((&mut home, reconnected), (&mut away, 1));
+    assert_eq!(unit_positions(&away), [(1, 1), (5, 7)]);
+    assert_eq!(away.snapshot().units, home.snapshot().units);
+}
+
+// Messages from the other game should arrive alongside the player's own events, and losing the
+// connection should put up a banner that reconnects once it's taken down
+fn test_remote_connection() {
+    CONNECTIONS.with(|c| c.borrow_mut().clear());
+    let (platform, recording) = TestPlatform::recording();
+    let settings = r#"{ "remote_url": "ws://relay", "remote_teams": ["enemy"],
+        "players": ["player", "enemy"] }"#;
+    let platform = platform.with_settings(Some(settings));
+    let (mut event_sender, event_receiver) = futures::channel::mpsc::channel(512);
+    let mut pool = futures::executor::LocalPool::new();
+    let game_future = alemian_saga_core::run(platform, event_receiver, "lang", "us");
+    let _ = pool.spawner().spawn_local(game_future);
+    pool.run_until_stalled();
+
+    assert_eq!(
+        CONNECTIONS.with(|c| c.borrow()[0].url.clone()),
+        "ws://relay"
+    );
+    take_sent(0);
+    let hello = net::Message::Hello {
+        received: 0,
+        resync: false,
+    };
+    let hello = net::encode(&hello).unwrap();
+    CONNECTIONS.with(|c| {
+        let _ = c.borrow()[0]
+            .incoming
+            .as_ref()
+            .unwrap()
+            .unbounded_send(hello);
+    });
+    pool.run_until_stalled();
+    let welcome = net::encode(&net::Message::Welcome { received: 0 }).unwrap();
+    assert_eq!(take_sent(0), [welcome]);
+
+    recording.borrow_mut().clear();
+    CONNECTIONS.with(|c| c.borrow_mut()[0].incoming = None);
+    pool.run_until_stalled();
+    let disconnected = "Connection lost: Select to reconnect".to_owned();
+    assert!(drawn_text(&recording).contains(&disconnected));
+    let _ = event_sender.try_send(Select);
+    pool.run_until_stalled();
+    assert_eq!(CONNECTIONS.with(|c| c.borrow().len()), 2);
+}
+
+// Finds the tiles on the 5x5 grid that were shaded as being in an enemy's danger zone
+fn danger_tiles(recording: &Recording) -> std::collections::HashSet<(u32, u32)> {
+    recording
+        .borrow()
+        .iter()
+        .filter_map(|d| match d {
+            Drawing::Fill { x, y, color, .. } if *color == style::DANGER_ZONE => {
+                Some((x / 16, y / 12))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+// The danger zone should shade every tile an enemy could move to or attack, follow units that
+// block the enemy's way, stay shaded through full redraws and sit underneath selection highlights
+async fn test_danger_zone() {
+    let knight = unit_map().units.remove(0);
+    let knight = |x, y, movement, team: &str| serialization::Unit {
+        x,
+        y,
+        movement,
+        team: team.to_owned(),
+        ..knight.clone()
+    };
+    let mut map = grid_map(5, 5);
+    map.units = vec![knight(2, 2, 3, "player"), knight(4, 4, 1, "enemy")];
+    let settings = r#"{ "min_visible_tiles": 1, "show_minimap": false, "show_unit_panel": false }"#;
+    let (platform, recording) = TestPlatform::recording();
+    let platform = platform.with_map(&map).with_settings(Some(settings));
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    game.frame();
+    let zone = [(4, 4), (3, 4), (4, 3), (2, 4), (3, 3), (4, 2)];
+    recording.borrow_mut().clear();
+    game.handle_event(ToggleDangerZone);
+    game.frame();
+    assert_eq!(danger_tiles(&recording), zone.iter().copied().collect());
+
+    // Shading is part of each tile, so drawing the whole screen again keeps it
+    recording.borrow_mut().clear();
+    game.handle_event(Redraw);
+    game.frame();
+    assert_eq!(danger_tiles(&recording), zone.iter().copied().collect());
+
+    // Highlights are drawn over the shading
+    for event in [Right, Right, Down, Down] {
+        game.handle_event(event);
+    }
+    game.frame();
+    recording.borrow_mut().clear();
+    game.handle_event(Select);
+    game.frame();
+    let fills: Vec<_> = recording
+        .borrow()
+        .iter()
+        .filter_map(|d| match d {
+            Drawing::Fill { x, y, color, .. } if (x / 16, y / 12) == (3, 3) => Some(*color),
+            _ => None,
+        })
+        .filter(|c| *c == style::DANGER_ZONE || *c == style::HighlightKind::Movement.color())
+        .collect();
+    assert_eq!(
+        fills,
+        [style::DANGER_ZONE, style::HighlightKind::Movement.color()]
+    );
+
+    // Moving next to the enemy cuts off one of its ways out
+    for event in [Right, Right, Down] {
+        game.handle_event(event);
+    }
+    game.frame();
+    recording.borrow_mut().clear();
+    game.handle_event(Select);
+    game.frame();
+    // Each tile of the grid is drawn with an image named after its position
+    let drawn_at = |tile: (u32, u32)| {
+        let name = format!("{},{}", tile.0, tile.1);
+        recording.borrow().iter().any(|d| match d {
+            Drawing::Image { source, .. } => *source == name,
+            _ => false,
+        })
+    };
+    assert!(drawn_at((4, 2)));
+    assert!(!danger_tiles(&recording).contains(&(4, 2)));
+    recording.borrow_mut().clear();
+    game.handle_event(Redraw);
+    game.frame();
+    let moved_zone = [(4, 4), (3, 4), (4, 3), (2, 4), (3, 3)];
+    assert_eq!(
+        danger_tiles(&recording),
+        moved_zone.iter().copied().collect()
+    );
+
+    // Taking the move back opens the way again
+    recording.borrow_mut().clear();
+    game.handle_event(Cancel);
+    game.frame();
+    assert!(danger_tiles(&recording).contains(&(4, 2)));
+
+    // Turning the overlay off only redraws the shaded tiles
+    game.handle_event(Cancel);
+    game.frame();
+    recording.borrow_mut().clear();
+    game.handle_event(ToggleDangerZone);
+    game.frame();
+    assert!(danger_tiles(&recording).is_empty());
+    assert!(drawn_at((4, 2)) && !drawn_at((1, 1)));
+}
+
+// Experience should add up towards a level every LEVEL_UP_EXP, carrying the rest over, and a fight
+// should be worth more the more damage it dealt and more again for a kill
+fn test_experience_thresholds() {
+    let mut rng = rng::Rng::new(DEFAULT_SEED);
+    let mut unit = unit_map().units.remove(0);
+    assert!(experience::award(&mut unit, experience::LEVEL_UP_EXP - 1, &mut rng).is_none());
+    assert_eq!((unit.level, unit.exp), (1, experience::LEVEL_UP_EXP - 1));
+    let level_up = experience::award(&mut unit, 1, &mut rng).unwrap();
+    assert_eq!(level_up.levels, 1);
+    assert_eq!((unit.level, unit.exp), (2, 0));
+    let level_up = experience::award(&mut unit, experience::LEVEL_UP_EXP * 2 + 50, &mut rng);
+    assert_eq!(level_up.unwrap().levels, 2);
+    assert_eq!((unit.level, unit.exp), (4, 50));
+
+    let hit = combat::Outcome {
+        damage_dealt: 3,
+        ..combat::Outcome::default()
+    };
+    let harder_hit = combat::Outcome {
+        damage_dealt: 6,
+        ..hit
+    };
+    let kill = combat::Outcome {
+        defender_killed: true,
+        ..harder_hit
+    };
+    assert!(experience::earned(&hit) > 0);
+    assert!(experience::earned(&harder_hit) > experience::earned(&hit));
+    assert!(experience::earned(&kill) > experience::earned(&harder_hit));
+}
+
+// Level ups should be decided by the seed alone, and each stat should go up by at most one a level
+fn test_seeded_level_ups() {
+    let level_up = |seed| {
+        let mut unit = unit_map().units.remove(0);
+        let level_up = experience::award(
+            &mut unit,
+            experience::LEVEL_UP_EXP * 10,
+            &mut rng::Rng::new(seed),
+        );
+        (unit, level_up.unwrap())
+    };
+    let (first, first_gains) = level_up(DEFAULT_SEED);
+    let (second, second_gains) = level_up(DEFAULT_SEED);
+    assert_eq!(first_gains, second_gains);
+    assert_eq!(roster::Progress::of(&first), roster::Progress::of(&second));
+    assert_eq!((first.level, first_gains.levels), (11, 10));
+    assert!(first_gains.gains.iter().all(|(_, gain)| *gain <= 10));
+    // Stats are listed in order, each only once
+    let stats: Vec<_> = first_gains.gains.iter().map(|(stat, _)| *stat).collect();
+    assert!(stats.windows(2).all(|w| w[0] < w[1]));
+    let original = unit_map().units.remove(0);
+    for (stat, gain) in first_gains.gains.iter() {
+        let (before, after) = match stat {
+            experience::Stat::MaxHp => (original.max_hp, first.max_hp),
+            experience::Stat::Strength => (original.strength, first.strength),
+            experience::Stat::Defense => (original.defense, first.defense),
+            experience::Stat::Speed => (original.speed, first.speed),
+        };
+        assert_eq!(after, before + gain);
+    }
+    // Max hp going up heals the unit by as much
+    assert_eq!(first.max_hp - first.hp, original.max_hp - original.hp);
+    let (_, other_gains) = level_up(DEFAULT_SEED + 1);
+    assert_ne!(first_gains, other_gains);
+}
+
+// An attacker killed by the counterattack shouldn't earn anything, and dead units can't be given
+// experience at all
+fn test_no_exp_when_dead() {
+    let knight = unit_map().units.remove(0);
+    let mut attacker = serialization::Unit {
+        hp: 1,
+        strength: 0,
+        ..knight.clone()
+    };
+    let mut defender = serialization::Unit {
+        team: "enemy".to_owned(),
+        ..knight.clone()
+    };
+    let plain = &small_map().tile_types[0];
+    let forecast =
+        combat::forecast(&attacker, plain, &defender, plain, Vector { x: 1, y: 0 }).unwrap();
+    let outcome = combat::resolve(&mut attacker, &mut defender, &forecast);
+    assert!(outcome.attacker_killed && !outcome.defender_killed);
+    assert_eq!(attacker.hp, 0);
+    assert_eq!(experience::earned(&outcome), 0);
+    let mut rng = rng::Rng::new(DEFAULT_SEED);
+    assert!(experience::award(&mut attacker, experience::LEVEL_UP_EXP, &mut rng).is_none());
+    assert_eq!((attacker.level, attacker.exp), (1, 0));
+
+    // Defenders that can't reach the attacker don't strike back
+    let mut attacker = serialization::Unit {
+        hp: 1,
+        range: 2,
+        ..knight.clone()
+    };
+    let forecast =
+        combat::forecast(&attacker, plain, &defender, plain, Vector { x: 0, y: 2 }).unwrap();
+    assert_eq!(forecast.defender.hits, 0);
+    let outcome = combat::resolve(&mut attacker, &mut defender, &forecast);
+    assert_eq!(outcome.damage_taken, 0);
+    assert!(!outcome.attacker_killed);
+}
+
+// Killing an enemy should take it off the map and level the attacker up, showing what went up on a
+// popup that goes away on its own and saving the new stats for later maps
+async fn test_level_up_popup() {
+    STORAGE.with(|s| s.borrow_mut().clear());
+    let knight = unit_map().units.remove(0);
+    let mut map = grid_map(5, 5);
+    map.units = vec![
+        serialization::Unit {
+            x: 2,
+            y: 2,
+            exp: 95,
+            ..knight.clone()
+        },
+        serialization::Unit {
+            name: "Brigand".to_owned(),
+            x: 4,
+            y: 2,
+            hp: 2,
+            team: "enemy".to_owned(),
+            ..knight.clone()
+        },
+    ];
+    let settings = r#"{ "min_visible_tiles": 1, "show_minimap": false, "show_unit_panel": false }"#;
+    let (platform, recording) = TestPlatform::recording();
+    let platform = platform.with_map(&map).with_settings(Some(settings));
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    game.frame();
+    for event in [Right, Right, Down, Down, Select, Right] {
+        game.handle_event(event);
+    }
+    recording.borrow_mut().clear();
+    game.handle_event(Select);
+    game.frame();
+    let drew_text = |text: &str| {
+        recording
+            .borrow()
+            .iter()
+            .any(|d| matches!(d, Drawing::Text { txt, .. } if txt == text))
+    };
+    assert!(drew_text("Attack Brigand") && drew_text("Wait"));
+    recording.borrow_mut().clear();
+    NOW_STEP.with(|s| s.set(0));
+    game.handle_event(Select);
+    // The popup comes up once the fight has played out
+    CLOCK.with(|c| c.set(c.get() + 750_000_000));
+    game.frame();
+    assert!(drew_text("Knight reached level 2"));
+
+    // The popup keeps anything else from happening until it's gone
+    recording.borrow_mut().clear();
+    game.handle_event(Left);
+    assert!(matches!(game.frame(), game::Redraw::Wait(_)));
+    assert!(recording.borrow().is_empty());
+    CLOCK.with(|c| c.set(c.get() + 5_000_000_000));
+    assert_eq!(game.frame(), game::Redraw::Idle);
+    NOW_STEP.with(|s| s.set(1_000_000_000));
+    recording.borrow_mut().clear();
+    game.handle_event(Left);
+    game.frame();
+    assert!(!recording.borrow().is_empty());
+
+    // The brigand is gone, and the knight's progress is saved
+    recording.borrow_mut().clear();
+    game.handle_event(Redraw);
+    game.frame();
+    let brigand = tinted("knight.png", team_color("enemy"));
+    assert!(!recording
+        .borrow()
+        .iter()
+        .any(|d| matches!(d, Drawing::Image { source, .. } if *source == brigand)));
+    let saved = roster::load(&TestPlatform::recording().0);
+    assert_eq!(saved["Knight"].level, 2);
+    assert_eq!(
+        saved["Knight"].exp,
+        95 + 10 + 2 + 30 - experience::LEVEL_UP_EXP
+    );
+
+    // Saved progress is picked up when the map is loaded again
+    let mut knight = serialization::Unit { hp: 10, ..knight };
+    saved["Knight"].apply(&mut knight);
+    assert_eq!(knight.level, 2);
+    assert_eq!(knight.max_hp - knight.hp, 10);
+    STORAGE.with(|s| s.borrow_mut().clear());
+}
+
+// Selecting should take down the level up popup on the next frame rather than once it times out
+async fn test_level_up_popup_dismissed() {
+    STORAGE.with(|s| s.borrow_mut().clear());
+    let knight = unit_map().units.remove(0);
+    let mut map = grid_map(5, 5);
+    map.units = vec![
+        serialization::Unit {
+            x: 2,
+            y: 2,
+            exp: 95,
+            ..knight.clone()
+        },
+        serialization::Unit {
+            x: 3,
+            y: 3,
+            hp: 20,
+            max_hp: 20,
+            team: "enemy".to_owned(),
+            ..knight
+        },
+    ];
+    let settings = r#"{ "min_visible_tiles": 1, "show_minimap": false, "show_unit_panel": false }"#;
+    let (platform, recording) = TestPlatform::recording();
+    let platform = platform.with_map(&map).with_settings(Some(settings));
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    game.frame();
+    // Attacking without killing is still enough for a level up at 95 exp
+    for event in [Right, Right, Down, Down, Select, Right, Select, Select] {
+        game.handle_event(event);
+    }
+    recording.borrow_mut().clear();
+    game.handle_event(Select);
+    assert_eq!(game.frame(), game::Redraw::Idle);
+    assert!(!recording.borrow().is_empty());
+    // The reloaded map starts the knight at level 2
+    game.handle_event(ReloadAssets);
+    game.load_pending_map().await;
+    assert_eq!(
+        roster::load(&TestPlatform::recording().0)["Knight"].level,
+        2
+    );
+    STORAGE.with(|s| s.borrow_mut().clear());
+}
+
+// Fights should play out exactly as forecast for any units on any terrain: each side deals its
+// forecast damage for each of its forecast hits unless someone dies first
+fn test_forecast_matches_combat() {
+    use proptest::prelude::*;
+    let mut runner = proptest::test_runner::TestRunner::default();
+    let unit = |(hp, strength, defense, speed, range)| serialization::Unit {
+        hp,
+        max_hp: hp,
+        strength,
+        defense,
+        speed,
+        range,
+        ..unit_map().units.remove(0)
+    };
+    let tile = |defense| serialization::TileType {
+        image: "".to_owned(),
+        name: "".to_owned(),
+        defense,
+        evade: 0,
+        move_cost: 1,
+        minimap_color: None,
+        ambient_sound: None,
+        footstep_sound: None,
+    };
+    let stats = || (1..40u32, 0..40u32, 0..20u32, 0..20u32, 1..3u32);
+    runner
+        .run(
+            &(stats(), -10..10i32, stats(), -10..10i32, 1..3u32),
+            |(attacker, attacker_tile, defender, defender_tile, distance)| {
+                let (mut attacker, mut defender) = (unit(attacker), unit(defender));
+                let (attacker_tile, defender_tile) = (tile(attacker_tile), tile(defender_tile));
+                let forecast = combat::forecast(
+                    &attacker,
+                    &attacker_tile,
+                    &defender,
+                    &defender_tile,
+                    Vector {
+                        x: distance as i64,
+                        y: 0,
+                    },
+                );
+                // Attackers can't reach targets beyond their range
+                let forecast = match forecast {
+                    Some(f) => f,
+                    None => {
+                        prop_assert!(distance > attacker.range);
+                        return Ok(());
+                    }
+                };
+                let defense = (defender.defense as i64 + defender_tile.defense as i64).max(0);
+                let expected = (attacker.strength as i64 - defense).max(0) as u32;
+                prop_assert_eq!(forecast.attacker.damage, expected);
+                prop_assert_eq!(forecast.defender.terrain_defense, defender_tile.defense);
+                let doubles = attacker.speed >= defender.speed + combat::DOUBLE_STRIKE_SPEED;
+                prop_assert_eq!(forecast.attacker.hits, if doubles { 2 } else { 1 });
+                if distance > defender.range {
+                    prop_assert_eq!(forecast.defender.hits, 0);
+                }
+
+                let (attacker_hp, defender_hp) = (attacker.hp, defender.hp);
+                let outcome = combat::resolve(&mut attacker, &mut defender, &forecast);
+                prop_assert_eq!(outcome.damage_dealt, defender_hp - defender.hp);
+                prop_assert_eq!(outcome.damage_taken, attacker_hp - attacker.hp);
+                prop_assert_eq!(outcome.defender_killed, defender.hp == 0);
+                prop_assert_eq!(outcome.attacker_killed, attacker.hp == 0);
+                let dealt = forecast.attacker.damage * forecast.attacker.hits;
+                let taken = forecast.defender.damage * forecast.defender.hits;
+                prop_assert!(outcome.damage_dealt <= dealt);
+                prop_assert!(outcome.damage_taken <= taken);
+                if !outcome.attacker_killed {
+                    prop_assert_eq!(outcome.damage_dealt, dealt.min(defender_hp));
+                }
+                if !outcome.defender_killed {
+                    prop_assert_eq!(outcome.damage_taken, taken.min(attacker_hp));
+                }
+                Ok(())
+            },
+        )
+        .unwrap();
+}
+
+// The battle forecast should compare the two sides of the attack picked on the action menu,
+// counting the defense of the defender's tile, and follow the menu as another target is picked
+async fn test_battle_forecast() {
+    let knight = unit_map().units.remove(0);
+    let mut map = grid_map(5, 5);
+    let enemy = |name: &str, x, y| serialization::Unit {
+        name: name.to_owned(),
+        x,
+        y,
+        defense: 1,
+        team: "enemy".to_owned(),
+        ..knight.clone()
+    };
+    map.units = vec![
+        serialization::Unit {
+            x: 2,
+            y: 2,
+            ..knight.clone()
+        },
+        enemy("Brigand", 4, 2),
+        enemy("Bandit", 3, 3),
+    ];
+    // The brigand stands on a tile that adds 2 defense
+    map.tile_types[2 * 5 + 4].defense = 2;
+    let settings = r#"{ "min_visible_tiles": 1, "show_minimap": false, "show_unit_panel": false }"#;
+    let (platform, recording) = TestPlatform::recording();
+    let platform = platform.with_map(&map).with_settings(Some(settings));
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    game.frame();
+    for event in [Right, Right, Down, Down, Select, Right] {
+        game.handle_event(event);
+    }
+    let texts = || {
+        recording
+            .borrow()
+            .iter()
+            .filter_map(|d| match d {
+                Drawing::Text { txt, .. } => Some(txt.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+    recording.borrow_mut().clear();
+    game.handle_event(Select);
+    game.frame();
+    let drawn = texts();
+    assert!(drawn.contains(&"Attack Brigand".to_owned()));
+    // Strength 7 against the brigand's 1 defense and 2 from its tile
+    let brigand = [
+        "Knight", "Brigand", "HP", "Damage", "Hits", "Terrain", "4", "+2",
+    ];
+    assert!(brigand.iter().all(|t| drawn.contains(&t.to_string())));
+
+    // Picking the bandit instead updates the forecast on the next frame
+    recording.borrow_mut().clear();
+    game.handle_event(Down);
+    game.frame();
+    let drawn = texts();
+    assert!(["Bandit", "6", "+0"]
+        .iter()
+        .all(|t| drawn.contains(&t.to_string())));
+    assert!(!drawn.contains(&"Brigand".to_owned()) && !drawn.contains(&"+2".to_owned()));
+
+    // Waiting isn't an attack, so the forecast is taken away
+    recording.borrow_mut().clear();
+    game.handle_event(Down);
+    game.frame();
+    assert!(!texts().contains(&"Damage".to_owned()));
+    assert!(recording.borrow().iter().any(|d| matches!(
+        d,
+        Drawing::Image { source, .. } if source == "2,4"
+    )));
+    recording.borrow_mut().clear();
+    game.handle_event(Redraw);
+    game.frame();
+    assert!(!texts().contains(&"Damage".to_owned()));
+}
+
+// Text should come from the strings file, falling back to the built in text and then to the key
+// itself, which is warned about once
+fn test_strings() {
+    LOG.with(|l| l.borrow_mut().clear());
+    let file = std::collections::HashMap::from([
+        ("menu.save".to_owned(), "Sauver".to_owned()),
+        ("pair".to_owned(), "{} et {}".to_owned()),
+    ]);
+    let strings = strings::Strings::new(file);
+    assert_eq!(strings.get::<TestPlatform>("menu.save"), "Sauver");
+    assert_eq!(strings.get::<TestPlatform>("menu.load"), "Load");
+    assert_eq!(
+        strings.format::<TestPlatform>("pair", &["a", "b"]),
+        "a et b"
+    );
+    assert_eq!(strings.lookup("nowhere"), None);
+    assert_eq!(strings.get::<TestPlatform>("nowhere"), "nowhere");
+    assert_eq!(strings.get::<TestPlatform>("nowhere"), "nowhere");
+    assert_eq!(
+        LOG.with(|l| l.borrow().clone()),
+        ["WARN: No text for string key nowhere"]
+    );
+}
+
+// Names and dialog from the map should be looked up in the strings file, and switching locales
+// from the options menu should reload the strings and redraw with them
+async fn test_switch_locale() {
+    let strings_file = |pairs: &[(&str, &str)]| {
+        let table: std::collections::HashMap<_, _> = pairs.iter().copied().collect();
+        Some(serde_json::to_vec(&table).unwrap())
+    };
+    REPLACED_FILES.with(|f| {
+        let mut files = f.borrow_mut();
+        files.insert(
+            "strings/us.json".to_owned(),
+            strings_file(&[("intro", "Welcome"), ("0,0", "Grass")]),
+        );
+        files.insert(
+            "strings/fr.json".to_owned(),
+            strings_file(&[("0,0", "Herbe"), ("menu.end_turn", "Fin du tour")]),
+        );
+    });
+    let mut map = grid_map(3, 2);
+    map.intro_dialog = vec![serialization::Message {
+        speaker: None,
+        text: "intro".to_owned(),
+    }];
+    let settings = r#"{ "locales": ["us", "fr"] }"#;
+    let (platform, recording) = TestPlatform::recording();
+    let platform = platform.with_map(&map).with_settings(Some(settings));
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    game.frame();
+    let texts = drawn_text(&recording);
+    assert!(texts.contains(&"Welcome".to_owned()));
+    assert!(texts.contains(&"Grass".to_owned()));
+
+    // Locales are listed by the name the strings file gives them, or by their code if it has none
+    game.handle_event(Select);
+    recording.borrow_mut().clear();
+    for event in [Menu, Down, Down, Down, Select, Select] {
+        game.handle_event(event);
+    }
+    game.frame();
+    let texts = drawn_text(&recording);
+    assert!(texts.contains(&"English".to_owned()));
+    assert!(texts.contains(&"fr".to_owned()));
+    game.handle_event(Down);
+    game.handle_event(Select);
+    assert!(game.load_pending());
+    recording.borrow_mut().clear();
+    game.load_pending_map().await;
+    game.frame();
+    assert!(drawn_text(&recording).contains(&"Herbe".to_owned()));
+
+    // Keys the new strings file doesn't have fall back to the built in text
+    recording.borrow_mut().clear();
+    game.handle_event(Menu);
+    game.frame();
+    let texts = drawn_text(&recording);
+    assert!(texts.contains(&"Fin du tour".to_owned()));
+    assert!(texts.contains(&"Save".to_owned()));
+    REPLACED_FILES.with(|f| f.borrow_mut().clear());
+    STORAGE.with(|s| s.borrow_mut().clear());
+}
+
+// Text should be split into the pieces a reader sees as single characters, with accents, vowel
+// points, vowel signs, joined emoji and flags kept together
+fn test_graphemes() {
+    let split = |s: &str| text::graphemes(s).map(str::to_owned).collect::<Vec<_>>();
+    assert_eq!(split("Cafe\u{301}!"), ["C", "a", "f", "e\u{301}", "!"]);
+    assert_eq!(
+        split("\u{5E9}\u{5B8}\u{5C1}\u{5DC}\u{5D5}\u{5B9}\u{5DD}"),
+        [
+            "\u{5E9}\u{5B8}\u{5C1}",
+            "\u{5DC}",
+            "\u{5D5}\u{5B9}",
+            "\u{5DD}"
+        ]
+    );
+    assert_eq!(split("한국어"), ["한", "국", "어"]);
+    assert_eq!(
+        split("\u{1100}\u{1161}\u{11A8}"),
+        ["\u{1100}\u{1161}\u{11A8}"]
+    );
+    assert_eq!(
+        split("\u{1F44D}\u{1F3FD}\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}"),
+        [
+            "\u{1F44D}\u{1F3FD}",
+            "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}"
+        ]
+    );
+    assert_eq!(split("🇯🇵🇫🇷🇩"), ["🇯🇵", "🇫🇷", "🇩"]);
+    assert_eq!(split("a\r\n\u{301}"), ["a", "\r\n", "\u{301}"]);
+    // Spacing vowel signs, viramas and nuktas in Thai, Tamil and Bengali
+    assert_eq!(split("\u{E01}\u{E33}"), ["\u{E01}\u{E33}"]);
+    assert_eq!(
+        split("\u{B95}\u{BCD}\u{BB7}"),
+        ["\u{B95}\u{BCD}", "\u{BB7}"]
+    );
+    assert_eq!(
+        split("\u{995}\u{9BC}\u{9C7}\u{9A4}\u{982}"),
+        ["\u{995}\u{9BC}\u{9C7}", "\u{9A4}\u{982}"]
+    );
+    assert!(split("").is_empty());
+}
+
+// Shortening and wrapping text should measure it a grapheme at a time, so that accents aren't cut
+// from their letters and text without spaces still wraps
+fn test_grapheme_truncation() {
+    let platform = TestPlatform::recording().0;
+    let style = &style::DIALOG_TEXT;
+    // Combining accents take up no width, so cutting between bytes or characters would strand them
+    assert_eq!(
+        text::fit(&platform, "Re\u{301}sume\u{301}s", 5, style),
+        "Re\u{301}su…"
+    );
+    // Each of these takes up two columns
+    assert_eq!(text::fit(&platform, "東京タワー", 7, style), "東京タ…");
+    assert_eq!(text::fit(&platform, "東京タワー", 10, style), "東京タワー");
+    let families =
+        "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+    assert_eq!(
+        text::fit(&platform, families, 7, style),
+        "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}…"
+    );
+
+    let wrap = |s: &str, width: u32| dialog::wrap_text(&platform, s, width, style);
+    assert_eq!(wrap("日本語のテキスト", 6), ["日本語", "のテキ", "スト"]);
+    assert_eq!(
+        wrap("Hi 日本語のテキスト", 9),
+        ["Hi", "日本語の", "テキスト"]
+    );
+    // Words too wide for a line start a line of their own before they're broken
+    assert_eq!(wrap("ab cdefgh ij", 4), ["ab", "cdef", "gh", "ij"]);
+    // Lines always get at least one grapheme, even one too wide for them
+    assert_eq!(wrap("語", 1), ["語"]);
+}
+
+// Text should be placed from the right edge of its space when the locale is read right to left,
+// with text that mixes directions measured as a whole
+fn test_text_layout() {
+    let platform = TestPlatform::recording().0;
+    let offset = Vector { x: 10, y: 5 };
+    let place = |s: &str, style: &style::TextStyle, direction: text::Direction| {
+        let layout = text::layout(&platform, s, offset, 20, style, direction);
+        (layout.left(), layout.width())
+    };
+    let left = &style::MENU_ITEM;
+    let center = &style::INFOBAR_VALUE;
+    let right = &style::TextStyle {
+        alignment: style::Alignment::Right,
+        ..style::MENU_ITEM
+    };
+    let (ltr, rtl) = (text::Direction::LeftToRight, text::Direction::RightToLeft);
+    // Hebrew with vowel points and an English name in the middle of it
+    let mixed = "תקוף את Knight";
+    assert_eq!(place(mixed, left, ltr), (10, 14));
+    assert_eq!(place(mixed, left, rtl), (16, 14));
+    assert_eq!(place(mixed, right, rtl), (10, 14));
+    assert_eq!(place(mixed, center, rtl), (13, 14));
+    let pointed = "\u{5E9}\u{5B8}\u{5C1}\u{5DC}\u{5D5}\u{5B9}\u{5DD} 42";
+    assert_eq!(place(pointed, left, rtl), (23, 7));
+    assert_eq!(place(pointed, right, ltr), (23, 7));
+    // Arabic with an English name and wide characters
+    assert_eq!(place("هجوم Knight 東京", left, rtl), (14, 16));
+    assert_eq!(place("مرحبا بالعالم Knight", left, rtl), (10, 20));
+    // Text too wide for its space is squeezed into it
+    assert_eq!(place("مرحبا بالعالم مرة أخرى Knight", left, rtl), (10, 20));
+    assert_eq!(
+        text::layout(&platform, "a", offset, 20, left, rtl).height(),
+        0
+    );
+}
+
+// A locale read right to left should have its panels' text drawn from the right edge, in the
+// direction its strings file gives
+async fn test_right_to_left_locale() {
+    let strings = serde_json::json!({
+        "text.direction": "rtl",
+        "0,0": "שדה",
+        "1,0": "גבעה גבוהה מאוד",
+    });
+    REPLACED_FILES.with(|f| {
+        let contents = serde_json::to_vec(&strings).unwrap();
+        f.borrow_mut()
+            .insert("strings/us.json".to_owned(), Some(contents))
+    });
+    TEXT_LAYOUTS.with(|l| l.borrow_mut().clear());
+    let platform = TestPlatform::recording().0.with_map(&grid_map(3, 2));
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    let layout_of = |s: &str| {
+        TEXT_LAYOUTS.with(|l| {
+            l.borrow()
+                .iter()
+                .rev()
+                .find(|(t, _, _)| t == s)
+                .map(|&(_, layout, direction)| (layout.left(), layout.width(), direction))
+        })
+    };
+    // The tile name ends at the right edge of the infobar's 9 wide name space, which starts at 65
+    // since the cursor is under the top left corner of the screen
+    let rtl = text::Direction::RightToLeft;
+    assert_eq!(layout_of("שדה"), Some((71, 3, rtl)));
+    // Names too wide for it are shortened from the end they're read from. The cursor has moved
+    // out from under the top left corner, so the infobar is back there
+    game.handle_event(Right);
+    game.frame();
+    assert_eq!(layout_of("גבעה גבו…"), Some((1, 9, rtl)));
+    REPLACED_FILES.with(|f| f.borrow_mut().clear());
+}
+
+// With integer scaling, tiles should be a whole number of pixels centered on the screen with the
+// margins letterboxed, and the mouse should be measured from the corner of the tiles
+async fn test_integer_scaling() {
+    let (platform, recording) = TestPlatform::recording();
+    let settings =
+        r#"{ "scale_mode": "integer", "letterbox_color": { "red": 9, "green": 9, "blue": 9 } }"#;
+    let platform = platform
+        .with_map(&grid_map(7, 7))
+        .with_settings(Some(settings));
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    // The 80x60 screen fits 11x8 tiles, which leaves 3 columns and 4 rows of pixels to split
+    assert_eq!(game.tile_size(), Vector { x: 11, y: 8 });
+    let drawn = recording.borrow().clone();
+    let letterbox = style::Color::rgb(9, 9, 9);
+    let fill = |x, y, w, h| Drawing::Fill {
+        x,
+        y,
+        w,
+        h,
+        color: letterbox,
+    };
+    for margin in [
+        fill(0, 0, 80, 2),
+        fill(0, 58, 80, 2),
+        fill(0, 2, 1, 56),
+        fill(78, 2, 2, 56),
+    ] {
+        assert!(drawn.contains(&margin), "{:?}", margin);
+    }
+    assert!(drawn.contains(&image("0,0", 1, 2, 11, 8)));
+    assert!(drawn.contains(&image("6,6", 67, 50, 11, 8)));
+
+    game.handle_event(MouseMove(Vector {
+        x: 1 + 11 * 3 + 10,
+        y: 2 + 8 * 2,
+    }));
+    assert_eq!(game.cursor_pos(), Vector { x: 3, y: 2 });
+    // The letterbox isn't part of any tile
+    for pos in [
+        Vector { x: 0, y: 30 },
+        Vector { x: 78, y: 30 },
+        Vector { x: 40, y: 59 },
+    ] {
+        game.handle_event(MouseMove(pos));
+        assert_eq!(game.cursor_pos(), Vector { x: 3, y: 2 });
+    }
+    game.handle_event(Click(Vector { x: 1, y: 57 }));
+    assert_eq!(game.cursor_pos(), Vector { x: 0, y: 6 });
+
+    // Zooming keeps the tile under the mouse in place
+    game.handle_event(ZoomInAt(Vector {
+        x: 1 + 11 * 5 + 5,
+        y: 2 + 8 * 4 + 4,
+    }));
+    assert_eq!(game.tile_size(), Vector { x: 11, y: 10 });
+    assert_eq!(game.visible_rect().top_left, Vector { x: 0, y: 1 });
+    assert_eq!(game.cursor_pos(), Vector { x: 5, y: 4 });
+}
+
+// The grid should be drawn along the edges of each tile under the cursor, with row and column
+// numbers along the left and top of the screen, and be redrawn with the tiles it's on
+async fn test_grid_overlay() {
+    let (platform, recording) = TestPlatform::recording();
+    let platform = platform.with_map(&grid_map(3, 2));
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    let color = style::Color {
+        alpha: 160,
+        ..style::BLACK
+    };
+    let lines = |recording: &Recording| -> Vec<(u32, u32, u32, u32)> {
+        recording
+            .borrow()
+            .iter()
+            .filter_map(|d| match *d {
+                Drawing::Fill {
+                    x,
+                    y,
+                    w,
+                    h,
+                    color: c,
+                } if c == color => Some((x, y, w, h)),
+                _ => None,
+            })
+            .collect()
+    };
+    assert!(lines(&recording).is_empty());
+
+    recording.borrow_mut().clear();
+    assert_eq!(game.handle_event(ToggleGrid), game::Redraw::Changed);
+    game.frame();
+    let size = game.tile_size();
+    let drawn = lines(&recording);
+    assert!(drawn.contains(&(size.x - 1, 0, 1, size.y)));
+    assert!(drawn.contains(&(0, size.y - 1, size.x - 1, 1)));
+    assert!(drawn.contains(&(2 * size.x - 1, size.y, 1, size.y)));
+    // The grid is drawn under the cursor
+    let position = |recording: &Recording, wanted: &Drawing| {
+        recording.borrow().iter().position(|d| d == wanted)
+    };
+    let first_line = Drawing::Fill {
+        x: size.x - 1,
+        y: 0,
+        w: 1,
+        h: size.y,
+        color,
+    };
+    let cursor = image("cursor.png", 0, 0, size.x, size.y);
+    assert!(position(&recording, &first_line) < position(&recording, &cursor));
+    // Columns are numbered along the top and rows down the left, counting from 0
+    let labels: Vec<_> = recording
+        .borrow()
+        .iter()
+        .filter_map(|d| match d {
+            Drawing::Text { txt, tx, ty } if txt.len() == 1 => Some((txt.clone(), *tx, *ty)),
+            _ => None,
+        })
+        .collect();
+    for (label, x, y) in [
+        ("1", size.x, 0),
+        ("2", 2 * size.x, 0),
+        ("1", 0, size.y + size.y / 2),
+    ] {
+        assert!(
+            labels
+                .iter()
+                .any(|(t, tx, ty)| t == label && *ty == y && (x..x + size.x).contains(tx)),
+            "{} at {}, {} in {:?}",
+            label,
+            x,
+            y,
+            labels
+        );
+    }
+
+    // Moving the cursor redraws the grid on the tile it left and the one the infobar moved off of,
+    // but not on tiles that weren't covered
+    recording.borrow_mut().clear();
+    game.handle_event(Right);
+    game.frame();
+    let drawn = lines(&recording);
+    assert!(drawn.contains(&(size.x - 1, 0, 1, size.y)));
+    assert!(drawn.contains(&(3 * size.x - 1, 0, 1, size.y)));
+    assert!(!drawn.contains(&(2 * size.x - 1, size.y, 1, size.y)));
+
+    // The grid scales with the tiles
+    game.handle_event(ZoomIn);
+    let zoomed = game.tile_size();
+    assert_ne!(zoomed, size);
+    recording.borrow_mut().clear();
+    game.handle_event(Redraw);
+    game.frame();
+    let offset = game.visible_rect().top_left;
+    let drawn = lines(&recording);
+    assert!(
+        drawn.contains(&(zoomed.x - 1, 0, 1, zoomed.y)),
+        "{:?} {:?}",
+        offset,
+        drawn
+    );
+
+    recording.borrow_mut().clear();
+    game.handle_event(ToggleGrid);
+    game.frame();
+    assert!(lines(&recording).is_empty());
+
+    // Row and column numbers can be left out
+    let (platform, recording) = TestPlatform::recording();
+    let platform = platform
+        .with_map(&grid_map(3, 2))
+        .with_settings(Some(r#"{ "grid_labels": false }"#));
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    recording.borrow_mut().clear();
+    game.handle_event(ToggleGrid);
+    game.frame();
+    assert!(!lines(&recording).is_empty());
+    let size = game.tile_size();
+    assert!(!recording.borrow().iter().any(|d| matches!(
+        d,
+        Drawing::Text { txt, ty, .. } if txt == "1" && *ty == size.y + size.y / 2
+    )));
+}
+
+// Screenshots should be exported under when they were taken and be confirmed with a toast that goes
+// away on its own, and exporting the map should draw every tile offscreen however far it's zoomed
+async fn test_screenshot_and_map_export() {
+    EXPORTS.with(|e| e.borrow_mut().clear());
+    TIMESTAMP.with(|t| t.set(Some(1_600_000_000)));
+    let (platform, recording) = TestPlatform::recording();
+    let settings = r#"{ "min_visible_tiles": 1, "export_tile_size": 4 }"#;
+    let platform = platform
+        .with_map(&grid_map(3, 2))
+        .with_settings(Some(settings));
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+
+    recording.borrow_mut().clear();
+    assert_eq!(game.handle_event(Screenshot), game::Redraw::Changed);
+    assert!(matches!(game.frame(), game::Redraw::Wait(_)));
+    let exports = EXPORTS.with(|e| e.borrow_mut().split_off(0));
+    assert_eq!(
+        exports,
+        [("screenshot-2020-09-13-12-26-40".to_owned(), None)]
+    );
+    assert_eq!(drawn_text(&recording), ["Screenshot saved"]);
+    // The toast is over the top middle tile, which is drawn again once the toast times out
+    recording.borrow_mut().clear();
+    for _ in 0..3 {
+        game.frame();
+    }
+    assert!(recording.borrow().contains(&image("1,0", 26, 0, 26, 30)));
+    assert!(drawn_text(&recording)
+        .iter()
+        .all(|t| t != "Screenshot saved"));
+    assert_eq!(game.frame(), game::Redraw::Idle);
+
+    game.handle_event(ZoomIn);
+    game.frame();
+    recording.borrow_mut().clear();
+    game.handle_event(ExportMap);
+    game.frame();
+    let (name, surface) = EXPORTS.with(|e| e.borrow_mut().remove(0));
+    assert_eq!(name, "map-2020-09-13-12-26-40");
+    let (size, drawings) = surface.unwrap();
+    assert_eq!(size, Vector { x: 12, y: 8 });
+    for y in 0..2 {
+        for x in 0..3 {
+            let tile = image(format!("{},{}", x, y).as_str(), x * 4, y * 4, 4, 4);
+            assert!(drawings.contains(&tile), "{:?}", tile);
+        }
+    }
+    // Nothing from the export ends up on the screen, which only gets the toast
+    assert!(!recording
+        .borrow()
+        .iter()
+        .any(|d| matches!(d, Drawing::Image { w: 4, .. })));
+    assert_eq!(drawn_text(&recording), ["Map exported"]);
+    TIMESTAMP.with(|t| t.set(None));
+}
+
+// Copying the position should put where the cursor is and how many columns are on the screen on
+// the clipboard, and a shared position should put the cursor and screen back there, clamped to fit
+// the map
+async fn test_share_position() {
+    CLIPBOARD.with(|c| c.borrow_mut().take());
+    let (platform, recording) = TestPlatform::recording();
+    let mut game = game::Game::load(platform.with_map(&grid_map(10, 8)), "lang", "us")
+        .await
+        .unwrap();
+    for event in [ZoomIn, ZoomIn, Right, Down, Down] {
+        game.handle_event(event);
+    }
+    game.frame();
+    recording.borrow_mut().clear();
+    game.handle_event(CopyPosition);
+    game.frame();
+    let position = game.view_position();
+    assert_eq!(position.cursor, Vector { x: 1, y: 2 });
+    assert_eq!(position.zoom, game.visible_rect().width());
+    assert_eq!(
+        CLIPBOARD.with(|c| c.borrow().clone()),
+        Some(format!("1,2@{}", position.zoom))
+    );
+    assert_eq!(drawn_text(&recording), ["Position copied"]);
+
+    game.show_position(game::ViewPosition {
+        cursor: Vector { x: 20, y: 20 },
+        zoom: 4,
+    });
+    assert_eq!(game.cursor_pos(), Vector { x: 9, y: 7 });
+    let screen = game.visible_rect();
+    assert_eq!(
+        (screen.width(), screen.right(), screen.bottom()),
+        (4, 10, 8)
+    );
+    game.show_position(game::ViewPosition {
+        cursor: Vector { x: 0, y: 0 },
+        zoom: 100,
+    });
+    assert_eq!(game.visible_rect().width(), 10);
+    assert_eq!(game.visible_rect().top_left, Vector { x: 0, y: 0 });
+
+    // Games can start from a shared position
+    let (platform, recording) = TestPlatform::recording();
+    let (event_sender, event_receiver) = futures::channel::mpsc::channel(512);
+    drop(event_sender);
+    let start = game::ViewPosition {
+        cursor: Vector { x: 9, y: 0 },
+        zoom: 4,
+    };
+    let platform = platform.with_map(&grid_map(10, 8));
+    alemian_saga_core::run_at(platform, event_receiver, "lang", "us", Some(start)).await;
+    let cursor = recording.borrow().iter().rev().find_map(|d| match d {
+        Drawing::Image { source, x, w, .. } if source == "cursor.png" => Some((*x, *w)),
+        _ => None,
+    });
+    assert_eq!(cursor, Some((60, 20)));
+    CLIPBOARD.with(|c| c.borrow_mut().take());
+}
+
+// Only tiles on the map should be drawn, whichever way the screen is bigger than it, and points on
+// the screen should lead back to the tiles drawn under them
+fn test_visible_region() {
+    let tile = Vector { x: 10.0, y: 5.0 };
+    let no_scroll = Vector { x: 0.0, y: 0.0 };
+    let screen = |left, top, width, height| Rectangle {
+        top_left: Vector { x: left, y: top },
+        size: Vector {
+            x: width,
+            y: height,
+        },
+    };
+    let map = |x, y| Vector { x, y };
+    let region =
+        |screen, scroll, map_size| viewport::VisibleRegion::new(screen, scroll, map_size, tile);
+    let point = |x, y| Vector { x, y };
+
+    // Map bigger than the screen both ways
+    let r = region(screen(2, 1, 4, 3), no_scroll, map(10, 8));
+    assert_eq!((r.start, r.end), (map(2, 1), map(6, 4)));
+    assert_eq!(r.offset, point(0.0, 0.0));
+    assert_eq!(r.tile_at(point(15.0, 7.0)), Some(map(3, 2)));
+    assert_eq!(r.tile_at(point(40.0, 0.0)), None);
+    assert_eq!(r.tile_offset(map(0, 0)), point(-20.0, -5.0));
+
+    // Map wider than the screen but not as tall
+    let r = region(screen(0, 0, 5, 6), no_scroll, map(10, 4));
+    assert_eq!((r.start, r.end), (map(0, 0), map(5, 4)));
+    assert_eq!(r.bounds().size, point(50.0, 20.0));
+    assert_eq!(r.tile_at(point(49.0, 19.0)), Some(map(4, 3)));
+    assert_eq!(r.tile_at(point(0.0, 20.0)), None);
+
+    // Map taller than the screen but not as wide
+    let r = region(screen(0, 2, 5, 6), no_scroll, map(3, 8));
+    assert_eq!((r.start, r.end), (map(0, 2), map(3, 8)));
+    assert_eq!(r.bounds().size, point(30.0, 30.0));
+    assert_eq!(r.tile_at(point(29.0, 0.0)), Some(map(2, 2)));
+    assert_eq!(r.tile_at(point(30.0, 0.0)), None);
+
+    // Screen bigger than the map both ways
+    let r = region(screen(0, 0, 5, 6), no_scroll, map(3, 2));
+    assert_eq!((r.start, r.end), (map(0, 0), map(3, 2)));
+    assert_eq!(r.positions().count(), 6);
+
+    // Screen entirely past the edge of the map
+    let r = region(screen(12, 0, 4, 3), no_scroll, map(10, 8));
+    assert!(r.is_empty());
+    assert_eq!(r.positions().count(), 0);
+    assert_eq!(r.tile_at(point(0.0, 0.0)), None);
+
+    // Mid-scroll, tiles hang off the top left of the viewport
+    let r = region(screen(2, 1, 4, 3), point(0.5, -0.25), map(10, 8));
+    assert_eq!((r.start, r.end), (map(1, 1), map(6, 5)));
+    assert_eq!(r.offset, point(-5.0, -1.25));
+    assert_eq!(r.tile_at(point(0.0, 0.0)), Some(map(1, 1)));
+    assert_eq!(r.tile_offset(map(2, 1)), point(5.0, -1.25));
+
+    // Scrolling in from past the left edge of the map leaves a gap before the first column
+    let r = region(screen(0, 0, 4, 3), point(0.5, 0.0), map(10, 8));
+    assert_eq!((r.start, r.end), (map(0, 0), map(4, 3)));
+    assert_eq!(r.offset, point(5.0, 0.0));
+    assert_eq!(r.tile_at(point(2.0, 0.0)), None);
+    assert_eq!(r.tile_at(point(5.0, 0.0)), Some(map(0, 0)));
+
+    // Every combination of map sizes, screen sizes, screen positions and scroll offsets
+    let scrolls = [-1.5, -0.25, 0.0, 0.5];
+    for (map_width, map_height) in (1..6).flat_map(|x| (1..6).map(move |y| (x, y))) {
+        for (width, height) in (1..8).flat_map(|x| (1..8).map(move |y| (x, y))) {
+            for (left, top) in (0..4).flat_map(|x| (0..4).map(move |y| (x, y))) {
+                for scroll in scrolls
+                    .iter()
+                    .flat_map(|&x| scrolls.iter().map(move |&y| (x, y)))
+                {
+                    let r = region(
+                        screen(left, top, width, height),
+                        point(scroll.0, scroll.1),
+                        map(map_width, map_height),
+                    );
+                    let case = (map_width, map_height, width, height, left, top, scroll);
+                    assert!(r.start.x <= r.end.x && r.end.x <= map_width, "{:?}", case);
+                    assert!(r.start.y <= r.end.y && r.end.y <= map_height, "{:?}", case);
+                    let count = (r.end.x - r.start.x) * (r.end.y - r.start.y);
+                    assert_eq!(r.positions().count(), count as usize, "{:?}", case);
+                    for pos in r.positions() {
+                        let offset = r.tile_offset(pos);
+                        let center = point(offset.x + tile.x / 2.0, offset.y + tile.y / 2.0);
+                        assert_eq!(r.tile_at(center), Some(pos), "{:?}", case);
+                    }
+                    // Sample the viewport at every half tile
+                    let bounds = r.bounds();
+                    for i in 0..width * 2 {
+                        for j in 0..height * 2 {
+                            let p = point(i as f64 * tile.x / 2.0, j as f64 * tile.y / 2.0);
+                            let inside = p.x >= bounds.left()
+                                && p.y >= bounds.top()
+                                && p.x < bounds.right()
+                                && p.y < bounds.bottom();
+                            let under = r.tile_at(p);
+                            assert_eq!(under.is_some(), inside, "{:?} at {:?}", case, p);
+                            assert!(under.is_none_or(|t| r.contains(t)), "{:?}", case);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Gets the text of everything drawn since the recording was last cleared
+fn drawn_text(recording: &Recording) -> Vec<String> {
+    recording
+        .borrow()
+        .iter()
+        .filter_map(|d| match d {
+            Drawing::Text { txt, .. } => Some(txt.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+// Progress saved on earlier maps should carry over to the first map loaded when the game starts
+async fn test_roster_applied_at_start() {
+    let platform = TestPlatform::recording().0;
+    let mut progress = roster::Progress::of(&unit_map().units[0]);
+    progress.level = 3;
+    progress.strength = 9;
+    roster::save(
+        &platform,
+        &roster::Roster::from([("Knight".to_owned(), progress)]),
+    );
+    let game = game::Game::load(platform.with_map(&unit_map()), "lang", "us")
+        .await
+        .unwrap();
+    let knight = &game.snapshot().units[0];
+    assert_eq!((knight.level, knight.strength), (3, 9));
+    STORAGE.with(|s| s.borrow_mut().clear());
+}
+
+// Saving should keep everything needed to pick the game back up, so that loading the save after
+// playing on puts the game back exactly as it was
+async fn test_save_and_load() {
+    STORAGE.with(|s| s.borrow_mut().clear());
+    TIMESTAMP.with(|t| t.set(Some(1_600_000_000)));
+    let knight = unit_map().units.remove(0);
+    let mut map = grid_map(5, 5);
+    map.fog = true;
+    map.units = vec![
+        serialization::Unit {
+            x: 0,
+            y: 0,
+            ..knight.clone()
+        },
+        serialization::Unit {
+            name: "Brigand".to_owned(),
+            x: 4,
+            y: 4,
+            team: "enemy".to_owned(),
+            ..knight
+        },
+    ];
+    let settings = r#"{ "min_visible_tiles": 1, "show_unit_panel": false }"#;
+    let (platform, recording) = TestPlatform::recording();
+    let platform = platform.with_map(&map).with_settings(Some(settings));
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    // The knight moves right and waits, then the game is saved in the first slot
+    for event in [Select, Right, Select, Select, Menu, Down, Select, Select] {
+        game.handle_event(event);
+    }
+    let saved = game.snapshot();
+    assert_eq!((saved.units[0].x, saved.units[0].y), (1, 0));
+    assert!(saved.units[0]
+        .statuses
+        .contains(&serialization::Status::Acted));
+    assert_eq!(saved.turn, 1);
+    assert_eq!(saved.saved_at, Some(1_600_000_000));
+
+    // Play on: end the turn, wander off and use up some randomness
+    for event in [Menu, Select, Down, Down, Down] {
+        game.handle_event(event);
+    }
+    game.rng().next_u64();
+    assert_eq!(game.snapshot().turn, 2);
+    assert_ne!(game.snapshot(), saved);
+
+    // The load menu lists when each slot was saved
+    recording.borrow_mut().clear();
+    for event in [Menu, Down, Down, Select] {
+        game.handle_event(event);
+    }
+    game.frame();
+    let slots = drawn_text(&recording);
+    assert!(slots.contains(&"Slot 1: 2020-09-13 12:26".to_owned()));
+    assert!(slots.contains(&"Slot 2: Empty".to_owned()));
+    game.handle_event(Select);
+    assert!(game.load_pending());
+    game.load_pending_map().await;
+    assert_eq!(game.snapshot(), saved);
+    assert_eq!(game.cursor_pos(), Vector { x: 1, y: 0 });
+
+    // Empty slots have nothing to load
+    for event in [Menu, Down, Down, Select, Down, Select] {
+        game.handle_event(event);
+    }
+    assert!(!game.load_pending());
+    STORAGE.with(|s| s.borrow_mut().clear());
+    TIMESTAMP.with(|t| t.set(None));
+}
+
+// Saves made in another format should be listed as incompatible and refused rather than loaded
+async fn test_save_version_mismatch() {
+    STORAGE.with(|s| s.borrow_mut().clear());
+    let (platform, recording) = TestPlatform::recording();
+    let platform = platform.with_map(&unit_map());
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    let mut snapshot = game.snapshot();
+    snapshot.version = save::VERSION + 1;
+    let storage = TestPlatform::recording().0;
+    save::store(&storage, "1", &snapshot);
+    assert_eq!(
+        save::describe(&storage, &strings::Strings::default(), "1"),
+        "Slot 1: Incompatible"
+    );
+    assert!(matches!(
+        save::retrieve(&storage, "1"),
+        Err(Error::SaveVersion { found, expected })
+            if found == save::VERSION + 1 && expected == save::VERSION
+    ));
+
+    for event in [Menu, Down, Down, Select, Select] {
+        game.handle_event(event);
+    }
+    game.frame();
+    assert!(!game.load_pending());
+    assert!(dialog_text(&recording)
+        .iter()
+        .any(|t| t.starts_with("Failed to load saved game")));
+    STORAGE.with(|s| s.borrow_mut().clear());
+}
+
+// Autosaves should take turns between two slots and only be marked as the latest once they read
+// back intact, at most once per interval. Loading should fall back to the autosave before when the
+// latest was cut short
+async fn test_autosave() {
+    STORAGE.with(|s| s.borrow_mut().clear());
+    LOG.with(|l| l.borrow_mut().clear());
+    NOW_STEP.with(|s| s.set(0));
+    let settings = r#"{ "autosave_interval_ms": 10000 }"#;
+    let (platform, recording) = TestPlatform::recording();
+    let platform = platform
+        .with_map(&grid_map(3, 3))
+        .with_settings(Some(settings));
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    let stored = |key: &str| STORAGE.with(|s| s.borrow().get(key).cloned());
+    let storage = TestPlatform::recording().0;
+    let autosaved_turn = || save::retrieve_autosave(&storage).unwrap().unwrap().turn;
+    let end_turn = |game: &mut game::Game<TestPlatform>| {
+        game.handle_event(Menu);
+        game.handle_event(Select);
+    };
+    // Autosaves are written over several frames, which come straight after each other until the
+    // autosave is done
+    let settle = |game: &mut game::Game<TestPlatform>| {
+        while game.frame() == game::Redraw::Wait(0) {}
+    };
+    // Cuts off the end of what's stored under a key, as if the page closed while it was written
+    let tear = |key: &str| {
+        STORAGE.with(|s| {
+            let mut storage = s.borrow_mut();
+            let text = storage.get_mut(key).unwrap();
+            text.truncate(text.len() / 2);
+        })
+    };
+    let wait_out_interval = || CLOCK.with(|c| c.set(c.get() + 10_000_000_000));
+
+    end_turn(&mut game);
+    settle(&mut game);
+    assert!(stored("save_autosave_a").is_none());
+    assert_eq!(
+        stored(save::AUTOSAVE_POINTER_KEY).as_deref(),
+        Some("autosave_b")
+    );
+    assert_eq!(autosaved_turn(), 2);
+
+    // Turns that end before the interval is up are saved together once it is
+    end_turn(&mut game);
+    assert_eq!(game.frame(), game::Redraw::Wait(10_000_000_000));
+    end_turn(&mut game);
+    settle(&mut game);
+    assert!(stored("save_autosave_a").is_none());
+    wait_out_interval();
+    settle(&mut game);
+    assert_eq!(
+        stored(save::AUTOSAVE_POINTER_KEY).as_deref(),
+        Some("autosave_a")
+    );
+    assert_eq!(autosaved_turn(), 4);
+
+    // An autosave that doesn't read back intact is given up on, leaving the last one as the latest
+    end_turn(&mut game);
+    wait_out_interval();
+    game.frame();
+    game.frame();
+    assert!(stored("save_autosave_b").is_some());
+    tear("save_autosave_b");
+    settle(&mut game);
+    assert_eq!(
+        stored(save::AUTOSAVE_POINTER_KEY).as_deref(),
+        Some("autosave_a")
+    );
+    assert_eq!(autosaved_turn(), 4);
+    assert!(LOG.with(|l| l
+        .borrow()
+        .iter()
+        .any(|m| m.contains("Autosave autosave_b failed"))));
+
+    end_turn(&mut game);
+    wait_out_interval();
+    settle(&mut game);
+    assert_eq!(
+        stored(save::AUTOSAVE_POINTER_KEY).as_deref(),
+        Some("autosave_b")
+    );
+    assert_eq!(autosaved_turn(), 6);
+
+    // The load menu lists the autosave after the slots, and loading it falls back to the autosave
+    // before when the latest was cut short
+    tear("save_autosave_b");
+    recording.borrow_mut().clear();
+    for event in [Menu, Down, Down, Select] {
+        game.handle_event(event);
+    }
+    game.frame();
+    assert!(drawn_text(&recording).contains(&"Autosave: Saved".to_owned()));
+    for event in [Down, Down, Down, Select] {
+        game.handle_event(event);
+    }
+    assert!(game.load_pending());
+    game.load_pending_map().await;
+    assert_eq!(game.snapshot().turn, 4);
+
+    // With both autosaves damaged, there's nothing to load
+    tear("save_autosave_a");
+    assert!(save::retrieve_autosave(&storage).is_err());
+    assert_eq!(
+        save::describe_autosave(&storage, &strings::Strings::default()),
+        "Autosave: Unreadable"
+    );
+    NOW_STEP.with(|s| s.set(1_000_000_000));
+    STORAGE.with(|s| s.borrow_mut().clear());
+}
+
+// Save times should be shown as UTC dates, including leap days
+fn test_format_timestamp() {
+    assert_eq!(save::format_timestamp(0), "1970-01-01 00:00");
+    assert_eq!(save::format_timestamp(951_782_400), "2000-02-29 00:00");
+    assert_eq!(save::format_timestamp(1_600_000_000), "2020-09-13 12:26");
+    assert_eq!(save::format_timestamp(4_102_444_799), "2099-12-31 23:59");
+}
+
+// The map editor should paint the tile under the cursor with the tile type picked by number key or
+// from the palette, take paints back one at a time with Cancel and save the painted map as a map
+// file that can be read back
+async fn test_map_editor() {
+    FILES.with(|f| f.borrow_mut().clear());
+    let (platform, recording) = TestPlatform::recording();
+    let mut game = game::Game::load(platform.with_map(&grid_map(3, 2)), "lang", "us")
+        .await
+        .unwrap();
+    game.frame();
+
+    recording.borrow_mut().clear();
+    game.handle_event(ToggleEditor);
+    game.frame();
+    assert_eq!(drawn_text(&recording), ["Editor on"]);
+    game.handle_event(Input("3".to_owned()));
+    recording.borrow_mut().clear();
+    game.handle_event(Select);
+    game.frame();
+    assert!(recording.borrow().contains(&image("2,0", 0, 0, 26, 30)));
+    // There are only six tile types, so 0 doesn't pick one
+    game.handle_event(Input("0".to_owned()));
+    game.handle_event(Right);
+    game.frame();
+    recording.borrow_mut().clear();
+    game.handle_event(Select);
+    game.frame();
+    assert!(recording.borrow().contains(&image("2,0", 26, 0, 26, 30)));
+
+    recording.borrow_mut().clear();
+    game.handle_event(Cancel);
+    game.frame();
+    assert!(recording.borrow().contains(&image("1,0", 26, 0, 26, 30)));
+    recording.borrow_mut().clear();
+    game.handle_event(Cancel);
+    game.frame();
+    assert!(recording.borrow().contains(&image("0,0", 0, 0, 26, 30)));
+    // Nothing is left to undo, once the toast is gone from over the top middle tile
+    for _ in 0..3 {
+        game.frame();
+    }
+    recording.borrow_mut().clear();
+    game.handle_event(Cancel);
+    game.frame();
+    assert!(!recording
+        .borrow()
+        .iter()
+        .any(|d| matches!(d, Drawing::Image { .. })));
+
+    recording.borrow_mut().clear();
+    game.handle_event(Menu);
+    game.frame();
+    let labels = ["0,0", "1,0", "2,0", "0,1", "1,1", "2,1", "Save Map File"];
+    assert!(labels
+        .iter()
+        .all(|l| drawn_text(&recording).iter().any(|t| t == l)));
+    for event in [Down, Down, Down, Select, Down, Select] {
+        game.handle_event(event);
+    }
+    game.frame();
+    assert!(recording.borrow().contains(&image("0,1", 26, 30, 26, 30)));
+    game.handle_event(Menu);
+    game.handle_event(Up);
+    game.frame();
+    recording.borrow_mut().clear();
+    game.handle_event(Select);
+    game.frame();
+    assert_eq!(
+        drawn_text(&recording).last().map(String::as_str),
+        Some("Map file saved")
+    );
+    let (name, bytes) = FILES.with(|f| f.borrow_mut().remove(0));
+    assert_eq!(name, "map.map");
+    let saved: serialization::Map = rmp_serde::from_slice(&bytes).unwrap();
+    assert_eq!(saved.tile_types.len(), 6);
+    let mut expected = grid_map(3, 2).map;
+    expected[[1, 1]] = 3;
+    assert_eq!(saved.map, expected);
+
+    // Select goes back to selecting once the editor is off
+    game.handle_event(ToggleEditor);
+    game.handle_event(Right);
+    game.frame();
+    recording.borrow_mut().clear();
+    game.handle_event(Select);
+    game.frame();
+    assert!(!recording.borrow().contains(&image("0,1", 52, 30, 26, 30)));
+}
+
+// Flood fill should change every tile joined to the start by tiles of its type, including along
+// the edges of the map, and change nothing when the start already has the new type. Brushes should
+// be cut off at the edges of the map
+fn test_flood_fill_and_brush() {
+    let rows = [[1, 1, 0, 2], [1, 0, 0, 1], [1, 1, 2, 1]];
+    let map = ndarray::Array2::from_shape_fn((3, 4), |(y, x)| rows[y][x]);
+    let sorted = |mut tiles: Vec<Vector<game::MapDistance>>| {
+        tiles.sort_by_key(|p| (p.y, p.x));
+        tiles.into_iter().map(|p| (p.x, p.y)).collect::<Vec<_>>()
+    };
+    let filled = editor::flood_fill(&map, Vector { x: 0, y: 2 }, 5);
+    assert_eq!(sorted(filled), [(0, 0), (1, 0), (0, 1), (0, 2), (1, 2)]);
+    // Diagonals don't join tiles
+    let filled = editor::flood_fill(&map, Vector { x: 3, y: 1 }, 5);
+    assert_eq!(sorted(filled), [(3, 1), (3, 2)]);
+    assert!(editor::flood_fill(&map, Vector { x: 2, y: 1 }, 0).is_empty());
+    // Off the map
+    assert!(editor::flood_fill(&map, Vector { x: 4, y: 0 }, 5).is_empty());
+
+    // Filling a huge region doesn't recurse
+    let open = ndarray::Array2::zeros((1000, 1000));
+    let filled = editor::flood_fill(&open, Vector { x: 500, y: 500 }, 1);
+    assert_eq!(filled.len(), 1_000_000);
+
+    let map_size = Vector { x: 4, y: 3 };
+    let brush =
+        |x, y, width| sorted(editor::brush_area(Vector { x, y }, width, map_size).collect());
+    assert_eq!(brush(0, 0, 3), [(0, 0), (1, 0), (0, 1), (1, 1)]);
+    assert_eq!(brush(3, 2, 1), [(3, 2)]);
+    assert_eq!(brush(3, 1, 5).len(), 9);
+    assert_eq!(brush(1, 1, 5).len(), 12);
+}
+
+// A 3x3 brush stroke should only redraw the tiles it painted and be undone in one step, and fill
+// should paint the whole region under the cursor
+async fn test_editor_brush_and_fill() {
+    FILES.with(|f| f.borrow_mut().clear());
+    let (platform, recording) = TestPlatform::recording();
+    let mut game = game::Game::load(platform.with_map(&grid_map(5, 4)), "lang", "us")
+        .await
+        .unwrap();
+    game.frame();
+    let save_map = |game: &mut game::Game<TestPlatform>| {
+        game.handle_event(Menu);
+        game.handle_event(Up);
+        game.handle_event(Select);
+        let (_, bytes) = FILES.with(|f| f.borrow_mut().remove(0));
+        rmp_serde::from_slice::<serialization::Map>(&bytes)
+            .unwrap()
+            .map
+    };
+    let drawn_tiles = |recording: &Recording| {
+        let mut tiles: Vec<_> = recording
+            .borrow()
+            .iter()
+            .filter_map(|d| match d {
+                Drawing::Image { source, .. } if source.contains(',') => Some(source.clone()),
+                _ => None,
+            })
+            .collect();
+        tiles.sort();
+        tiles
+    };
+
+    game.handle_event(ToggleEditor);
+    // The palette lists the 20 tile types, then the brushes, fill and saving the map
+    game.handle_event(Menu);
+    for event in [Up, Up, Up, Up, Select, Right, Down] {
+        game.handle_event(event);
+    }
+    game.frame();
+    recording.borrow_mut().clear();
+    game.handle_event(Select);
+    game.frame();
+    // The top left tile already has the type being painted with, so only eight tiles change
+    assert_eq!(drawn_tiles(&recording), ["0,0"; 8]);
+    let mut expected = grid_map(5, 4).map;
+    for y in 0..3 {
+        for x in 0..3 {
+            expected[[y, x]] = 0;
+        }
+    }
+    assert_eq!(save_map(&mut game), expected);
+
+    game.frame();
+    recording.borrow_mut().clear();
+    game.handle_event(Cancel);
+    game.frame();
+    let mut restored: Vec<_> = (0..3)
+        .flat_map(|y| (0..3).map(move |x| format!("{},{}", x, y)))
+        .filter(|t| t != "0,0")
+        .collect();
+    restored.sort();
+    assert_eq!(drawn_tiles(&recording), restored);
+    assert_eq!(save_map(&mut game), grid_map(5, 4).map);
+
+    // Paint a 3x3 block of one type, then fill it with another
+    game.handle_event(Select);
+    game.handle_event(Menu);
+    for event in [Up, Up, Select, Input("2".to_owned()), Select] {
+        game.handle_event(event);
+    }
+    let mut filled = expected.clone();
+    for y in 0..3 {
+        for x in 0..3 {
+            filled[[y, x]] = 1;
+        }
+    }
+    assert_eq!(save_map(&mut game), filled);
+    // Filling a region with its own type changes nothing, so undoing takes back the first fill
+    game.handle_event(Select);
+    game.handle_event(Cancel);
+    assert_eq!(save_map(&mut game), expected);
+    game.handle_event(Cancel);
+    assert_eq!(save_map(&mut game), grid_map(5, 4).map);
+}
+
+// Bookmarks should remember the cursor and zoom of each map separately, take the screen back there
+// and clamp bookmarks that no longer fit the map
+async fn test_bookmarks() {
+    STORAGE.with(|s| s.borrow_mut().remove(bookmarks::STORAGE_KEY));
+    let (platform, recording) = TestPlatform::recording();
+    let mut game = game::Game::load(platform.with_map(&grid_map(10, 8)), "lang", "us")
+        .await
+        .unwrap();
+    for event in [ZoomIn, ZoomIn, Right, Right, Down] {
+        game.handle_event(event);
+    }
+    game.frame();
+    let bookmarked = game.view_position();
+    recording.borrow_mut().clear();
+    game.handle_event(SetBookmark(1));
+    game.frame();
+    assert_eq!(drawn_text(&recording), ["Bookmark 2 set"]);
+
+    for event in [ZoomOut, JumpBottom, JumpRight] {
+        game.handle_event(event);
+    }
+    game.handle_event(GoToBookmark(1));
+    assert_eq!(game.view_position(), bookmarked);
+    // Bookmarks that haven't been set leave the screen alone
+    game.handle_event(Left);
+    let before = game.view_position();
+    game.handle_event(GoToBookmark(0));
+    assert_eq!(game.view_position(), before);
+
+    // A bookmark set on a bigger version of the map
+    let stale = game::ViewPosition {
+        cursor: Vector { x: 50, y: 40 },
+        zoom: 100,
+    };
+    // Storage is shared between test platforms
+    let (storage, _) = TestPlatform::recording();
+    bookmarks::set(&storage, "map.map", 2, stale);
+    assert_eq!(bookmarks::get(&storage, "map.map", 1), Some(bookmarked));
+    game.handle_event(GoToBookmark(2));
+    let position = game.view_position();
+    assert_eq!(position.cursor, Vector { x: 9, y: 7 });
+    assert_eq!(position.zoom, game.map_size().x);
+    for _ in 0..3 {
+        game.frame();
+    }
+    let screen = game.visible_rect();
+    assert!(position.cursor.x >= screen.left() && position.cursor.x < screen.right());
+    assert!(position.cursor.y >= screen.top() && position.cursor.y < screen.bottom());
+}
+
+// Hovering over a tile that the selected unit can move to should label it with the cheapest cost of
+// getting there over the map's terrain and the movement left after arriving. The label only changes
+// when the mouse moves onto another tile
+async fn test_path_cost_readout() {
+    let knight = serialization::Unit {
+        x: 2,
+        y: 2,
+        movement: 4,
+        ..unit_map().units.remove(0)
+    };
+    let mut map = grid_map(5, 5);
+    map.units = vec![knight];
+    // Hills right of the knight and a swamp below it
+    map.tile_types[2 * 5 + 3].move_cost = 3;
+    map.tile_types[3 * 5 + 2].move_cost = 4;
+    let settings = r#"{ "min_visible_tiles": 1, "show_minimap": false, "show_unit_panel": false }"#;
+    let (platform, recording) = TestPlatform::recording();
+    let platform = platform.with_map(&map).with_settings(Some(settings));
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    for event in [Right, Right, Down, Down, Select] {
+        game.handle_event(event);
+    }
+    game.frame();
+    let mut hover = |x: u32, y: u32| {
+        recording.borrow_mut().clear();
+        game.handle_event(MouseMove(Vector {
+            x: x * 16 + 8,
+            y: y * 12 + 6,
+        }));
+        game.frame();
+        drawn_text(&recording)
+            .into_iter()
+            .filter(|t| t.contains("left"))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(hover(1, 2), ["1 (3 left)"]);
+    assert_eq!(hover(3, 2), ["3 (1 left)"]);
+    assert_eq!(hover(2, 3), ["4 (0 left)"]);
+    // Going around the swamp is cheaper than going through it
+    assert_eq!(hover(2, 4), ["4 (0 left)"]);
+    // Out of reach, and the knight's own tile
+    assert!(hover(4, 4).is_empty());
+    assert!(hover(2, 2).is_empty());
+    assert_eq!(hover(3, 2), ["3 (1 left)"]);
+    // Still on the same tile
+    assert!(hover(3, 2).is_empty());
+}
+
+// Range patterns should reach every tile in a ring for weapons with a minimum range, even when it's
+// clipped by the edges of the map, and exactly the given offsets for custom ones
+fn test_range_patterns() {
+    let size = Vector { x: 3, y: 3 };
+    let bow = geometry::RangePattern::MinMax { min: 2, max: 2 };
+    assert!(!bow.contains(Vector { x: 1, y: 0 }) && bow.contains(Vector { x: -1, y: 1 }));
+    let range = pathfinding::attack_range([Vector { x: 0, y: 1 }], &bow, size, |_| false);
+    assert_eq!(range, positions(&[(2, 1), (1, 0), (1, 2)]));
+    let range = pathfinding::attack_range([Vector { x: 0, y: 0 }], &bow, size, |_| false);
+    assert_eq!(range, positions(&[(2, 0), (1, 1), (0, 2)]));
+    let longbow = geometry::RangePattern::MinMax { min: 2, max: 3 };
+    let range = pathfinding::attack_range([Vector { x: 2, y: 2 }], &longbow, size, |_| false);
+    assert_eq!(range, positions(&[(0, 2), (1, 1), (2, 0), (0, 1), (1, 0)]));
+
+    // A spear that only reaches up, two tiles straight ahead or one diagonally to the right
+    let spear = geometry::RangePattern::Fixed(vec![
+        Vector { x: 0, y: -1 },
+        Vector { x: 0, y: -2 },
+        Vector { x: 1, y: -1 },
+    ]);
+    assert!(spear.contains(Vector { x: 1, y: -1 }));
+    assert!(!spear.contains(Vector { x: 0, y: 1 }) && !spear.contains(Vector { x: -1, y: -1 }));
+    let range = pathfinding::attack_range([Vector { x: 1, y: 1 }], &spear, size, |_| false);
+    assert_eq!(range, positions(&[(1, 0), (2, 0)]));
+    let range = pathfinding::attack_range([Vector { x: 2, y: 2 }], &spear, size, |_| false);
+    assert_eq!(range, positions(&[(2, 1), (2, 0)]));
+
+    // Forecasts refuse targets outside the attacker's pattern, and defenders only strike back along
+    // their own
+    let knight = unit_map().units.remove(0);
+    let archer = serialization::Unit {
+        range_pattern: Some(bow),
+        ..knight.clone()
+    };
+    let spearman = serialization::Unit {
+        range_pattern: Some(spear),
+        ..knight.clone()
+    };
+    let plain = &small_map().tile_types[0];
+    let forecast = |a, d, x, y| combat::forecast(a, plain, d, plain, Vector { x, y });
+    assert!(forecast(&archer, &knight, 1, 0).is_none());
+    assert_eq!(forecast(&archer, &knight, 0, -2).unwrap().defender.hits, 0);
+    assert_eq!(forecast(&knight, &archer, 1, 0).unwrap().defender.hits, 0);
+    assert!(forecast(&spearman, &knight, 0, 1).is_none());
+    assert_eq!(
+        forecast(&spearman, &knight, 0, -1).unwrap().defender.hits,
+        1
+    );
+    assert_eq!(forecast(&knight, &spearman, 0, 1).unwrap().defender.hits, 1);
+    assert_eq!(
+        forecast(&knight, &spearman, 0, -1).unwrap().defender.hits,
+        0
+    );
+}
+
+// Units with a minimum range should only be offered targets that their pattern reaches from where
+// they move to
+async fn test_range_pattern_targets() {
+    let knight = unit_map().units.remove(0);
+    let enemy = |name: &str, x, y| serialization::Unit {
+        name: name.to_owned(),
+        x,
+        y,
+        team: "enemy".to_owned(),
+        ..knight.clone()
+    };
+    let mut map = grid_map(5, 5);
+    map.units = vec![
+        serialization::Unit {
+            x: 2,
+            y: 2,
+            range_pattern: Some(geometry::RangePattern::MinMax { min: 2, max: 2 }),
+            ..knight.clone()
+        },
+        enemy("Brigand", 4, 2),
+        enemy("Bandit", 2, 1),
+        enemy("Pirate", 3, 3),
+    ];
+    let settings = r#"{ "min_visible_tiles": 1, "show_minimap": false, "show_unit_panel": false }"#;
+    let (platform, recording) = TestPlatform::recording();
+    let platform = platform.with_map(&map).with_settings(Some(settings));
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    game.frame();
+    for event in [Right, Right, Down, Down, Select, Down] {
+        game.handle_event(event);
+    }
+    recording.borrow_mut().clear();
+    game.handle_event(Select);
+    game.frame();
+    let drew_text = |text: &str| {
+        recording
+            .borrow()
+            .iter()
+            .any(|d| matches!(d, Drawing::Text { txt, .. } if txt == text))
+    };
+    // The bandit was right next to the unit before it moved, but now the pirate is, and the brigand
+    // is 3 tiles away
+    assert!(drew_text("Attack Bandit") && drew_text("Wait"));
+    assert!(!drew_text("Attack Brigand") && !drew_text("Attack Pirate"));
+}
+
+// Options on the options menu should change with Left and Right (or Select), take effect straight
+// away, and be remembered by later games in place of the settings file
+async fn test_options_menu() {
+    STORAGE.with(|s| s.borrow_mut().clear());
+    let strings = [
+        ("option.language", "Langue : {}"),
+        ("menu.end_turn", "Fin du tour"),
+    ];
+    let table: std::collections::HashMap<_, _> = strings.iter().copied().collect();
+    let contents = Some(serde_json::to_vec(&table).unwrap());
+    REPLACED_FILES.with(|f| {
+        f.borrow_mut()
+            .insert("strings/fr.json".to_owned(), contents)
+    });
+    let map = grid_map(3, 2);
+    let settings = r#"{ "locales": ["us", "fr"], "mouse_pan_delay_ms": 100 }"#;
+    let start = || {
+        let (platform, recording) = TestPlatform::recording();
+        let platform = platform.with_map(&map).with_settings(Some(settings));
+        (platform, recording)
+    };
+    let (platform, recording) = start();
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    recording.borrow_mut().clear();
+    for event in [Menu, Down, Down, Down, Select] {
+        game.handle_event(event);
+    }
+    game.frame();
+    let texts = drawn_text(&recording);
+    for label in [
+        "Language: English",
+        "Volume: 100",
+        "Image Smoothing: On",
+        "Edge Pan Delay: 100 ms",
+        "Grid: Off",
+    ] {
+        assert!(texts.contains(&label.to_owned()), "{}", label);
+    }
+
+    let adjust = |game: &mut game::Game<TestPlatform>, events: &[Event<u32, String>]| {
+        recording.borrow_mut().clear();
+        for event in events.iter().cloned() {
+            game.handle_event(event);
+        }
+        game.frame();
+        drawn_text(&recording)
+    };
+    assert!(adjust(&mut game, &[Down, Left]).contains(&"Volume: 90".to_owned()));
+    assert_eq!(VOLUME.with(|v| v.get()), Some(90));
+    let texts = adjust(&mut game, &[Down, Right]);
+    assert!(texts.contains(&"Image Smoothing: Off".to_owned()));
+    assert_eq!(SMOOTHING.with(|s| s.get()), Some(false));
+    let texts = adjust(&mut game, &[Down, Left, Left]);
+    assert!(texts.contains(&"Edge Pan Delay: 50 ms".to_owned()));
+    // Select changes options in place rather than closing the menu
+    assert!(adjust(&mut game, &[Down, Select]).contains(&"Grid: On".to_owned()));
+    assert!(adjust(&mut game, &[Select]).contains(&"Grid: Off".to_owned()));
+    adjust(&mut game, &[Select]);
+
+    // Changing the language loads its strings file and relabels the menu with it
+    adjust(&mut game, &[Down, Right]);
+    assert!(game.load_pending());
+    recording.borrow_mut().clear();
+    game.load_pending_map().await;
+    game.frame();
+    let texts = drawn_text(&recording);
+    assert!(texts.contains(&"Langue : fr".to_owned()));
+    assert!(texts.contains(&"Volume: 90".to_owned()));
+
+    // A later game starts with the options chosen
+    SMOOTHING.with(|s| s.set(None));
+    let (platform, recording) = start();
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    assert_eq!(SMOOTHING.with(|s| s.get()), Some(false));
+    assert_eq!(VOLUME.with(|v| v.get()), Some(90));
+    recording.borrow_mut().clear();
+    game.handle_event(Menu);
+    game.frame();
+    for event in [Down, Down, Down, Select] {
+        game.handle_event(event);
+    }
+    game.frame();
+    let texts = drawn_text(&recording);
+    assert!(texts.contains(&"Fin du tour".to_owned()));
+    assert!(texts.contains(&"Langue : fr".to_owned()));
+    assert!(texts.contains(&"Edge Pan Delay: 50 ms".to_owned()));
+    assert!(texts.contains(&"Grid: On".to_owned()));
+    REPLACED_FILES.with(|f| f.borrow_mut().clear());
+    STORAGE.with(|s| s.borrow_mut().clear());
+}
+
+// Reverse lookups should find the first key bound to an action, skipping keys that a context higher
+// up the stack binds to something else
+fn test_first_key() {
+    let keybindings = r#"{
+        "Up": ["k", "ArrowUp"],
+        "Select": ["Enter"],
+        "menu": { "Cancel": ["k"], "Select": [" "] }
+    }"#;
+    let keybindings = serde_json::from_str(keybindings).unwrap();
+    let bindings = bindings::Bindings::new(keybindings, |k| k);
+    let map = [bindings::Context::Map];
+    let menu = [bindings::Context::Map, bindings::Context::Menu];
+    assert_eq!(bindings.first_key(&map, Action::Up), Some("k"));
+    assert_eq!(bindings.first_key(&menu, Action::Up), Some("ArrowUp"));
+    assert_eq!(bindings.first_key(&map, Action::Select), Some("Enter"));
+    assert_eq!(bindings.first_key(&menu, Action::Select), Some(" "));
+    assert_eq!(bindings.first_key(&map, Action::Cancel), None);
+    assert_eq!(bindings.first_key(&menu, Action::Cancel), Some("k"));
+    assert_eq!(bindings::key_label("ArrowUp"), "\u{2191}");
+    assert_eq!(bindings::key_label(" "), "Space");
+    assert_eq!(bindings::key_label("k"), "k");
+}
+
+// The hint bar should list the keys for what can be done in the current context, follow rebinding,
+// leave off hints that don't fit, and stay hidden when turned off or when the screen is too short
+async fn test_hint_bar() {
+    STORAGE.with(|s| s.borrow_mut().clear());
+    let strings = [(
+        "hint.zoom",
+        "Zoom in or out to see more or less of the map at once",
+    )];
+    let table: std::collections::HashMap<_, _> = strings.iter().copied().collect();
+    let contents = Some(serde_json::to_vec(&table).unwrap());
+    REPLACED_FILES.with(|f| {
+        f.borrow_mut()
+            .insert("strings/us.json".to_owned(), contents)
+    });
+    let keybindings = r#"{
+        "Up": ["ArrowUp", "k"],
+        "Down": ["ArrowDown"],
+        "Left": ["ArrowLeft"],
+        "Right": ["ArrowRight"],
+        "Select": ["Enter", " "],
+        "Cancel": ["Escape"],
+        "Menu": ["m"],
+        "ZoomIn": ["="],
+        "ZoomOut": ["-"]
+    }"#;
+    let mut map = grid_map(3, 2);
+    map.intro_dialog = vec![message(None, "hello")];
+    let start = |settings: &str| {
+        let (platform, recording) = TestPlatform::recording();
+        let platform = platform
+            .with_map(&map)
+            .with_settings(Some(settings))
+            .with_keybindings(keybindings);
+        (platform, recording)
+    };
+    let hints = |recording: &Recording| {
+        recording.borrow().iter().rev().find_map(|d| match d {
+            Drawing::Text { txt, ty: 58, .. } => Some(txt.clone()),
+            _ => None,
+        })
+    };
+    let (platform, recording) = start(r#"{ "min_visible_tiles": 1, "show_minimap": false }"#);
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    game.frame();
+    assert_eq!(hints(&recording).as_deref(), Some("Enter Next"));
+
+    // The zoom hint is too long to fit, so it's left off
+    let map_hints = "\u{2190}\u{2192}\u{2191}\u{2193} Move \u{b7} Enter Select \u{b7} m Menu";
+    recording.borrow_mut().clear();
+    game.handle_event(Select);
+    game.frame();
+    assert_eq!(hints(&recording).as_deref(), Some(map_hints));
+    recording.borrow_mut().clear();
+    game.handle_event(Menu);
+    game.frame();
+    let menu_hints = "\u{2191}\u{2193} Choose \u{b7} Enter Select \u{b7} Esc Back";
+    assert_eq!(hints(&recording).as_deref(), Some(menu_hints));
+    recording.borrow_mut().clear();
+    game.handle_event(Cancel);
+    game.frame();
+    assert_eq!(hints(&recording).as_deref(), Some(map_hints));
+
+    // Rebinding a key shows the new one
+    game.handle_event(StartRebind(Action::Select));
+    recording.borrow_mut().clear();
+    game.handle_event(Input("x".to_owned()));
+    game.frame();
+    let rebound = "\u{2190}\u{2192}\u{2191}\u{2193} Move \u{b7} x Select \u{b7} m Menu";
+    assert_eq!(hints(&recording).as_deref(), Some(rebound));
+    STORAGE.with(|s| s.borrow_mut().clear());
+
+    // An infobar the height of the screen leaves no room for the hint bar
+    let settings = r#"{
+        "min_visible_tiles": 1,
+        "infobar_screen_fraction": 1,
+        "infobar_aspect_ratio": 1
+    }"#;
+    let (platform, recording) = start(settings);
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    game.handle_event(Select);
+    game.frame();
+    assert!(hints(&recording).is_none());
+    let settings = r#"{ "min_visible_tiles": 1, "show_input_hints": false }"#;
+    let (platform, recording) = start(settings);
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    game.handle_event(Select);
+    game.frame();
+    assert!(hints(&recording).is_none());
+    REPLACED_FILES.with(|f| f.borrow_mut().clear());
+}
+
+// A screen with no size (e.g. a hidden canvas) shouldn't be drawn to, and the first frame should be
+// drawn at the size that it's resized to
+async fn test_zero_sized_screen() {
+    SCREEN_SIZE.with(|s| s.set((0, 0)));
+    let (platform, recording) = TestPlatform::recording();
+    let platform = platform
+        .with_map(&grid_map(3, 1))
+        .with_settings(Some(r#"{ "min_visible_tiles": 1, "show_minimap": false }"#));
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    // Mouse positions are worked out from tiles with no size, so zooming can't be anchored to one
+    // and clicks and moves don't land anywhere
+    let cursor = game.cursor_pos();
+    for event in [
+        MouseMove(Vector { x: 0, y: 0 }),
+        ZoomInAt(Vector { x: 0, y: 0 }),
+        Click(Vector { x: 0, y: 0 }),
+    ] {
+        game.handle_event(event);
+    }
+    assert_eq!(game.frame(), game::Redraw::Idle);
+    assert_eq!(game.cursor_pos(), cursor);
+    assert_eq!(game.visible_rect().top_left, Vector { x: 0, y: 0 });
+    assert_eq!(recording.borrow().len(), 0);
+    SCREEN_SIZE.with(|s| s.set((800, 600)));
+    game.handle_event(Redraw);
+    game.frame();
+    let drawings = recording.borrow();
+    let tiles: Vec<&Drawing> = drawings
+        .iter()
+        .filter(|d| matches!(d, Drawing::Image { source, .. } if source.contains(',')))
+        .collect();
+    assert_eq!(
+        tiles,
+        [
+            &image("0,0", 0, 0, 400, 600),
+            &image("1,0", 400, 0, 400, 600)
+        ]
+    );
+    SCREEN_SIZE.with(|s| s.set((80, 60)));
+}
+
+// A moving unit should glide along its path a tile at a time, and nothing but skipping to the end
+// should be handled until it gets there
+async fn test_move_animation() {
+    let knight = serialization::Unit {
+        x: 0,
+        y: 0,
+        movement: 4,
+        ..unit_map().units.remove(0)
+    };
+    let mut map = grid_map(5, 1);
+    map.units = vec![knight];
+    let settings = r#"{ "min_visible_tiles": 1, "show_minimap": false, "show_unit_panel": false }"#;
+    let (platform, recording) = TestPlatform::recording();
+    let platform = platform.with_map(&map).with_settings(Some(settings));
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    let sprite = tinted("knight.png", team_color(serialization::PLAYER_TEAM));
+    let drawn = |recording: &Recording| {
+        let drawings = recording.borrow();
+        let knight = drawings.iter().filter_map(|d| match d {
+            Drawing::Image { source, x, .. } if *source == sprite => Some(*x),
+            _ => None,
+        });
+        let menu = drawings
+            .iter()
+            .any(|d| matches!(d, Drawing::Text { txt, .. } if txt == "Wait"));
+        (knight.collect::<Vec<_>>(), menu)
+    };
+    for event in [Select, Right, Right, Right] {
+        game.handle_event(event);
+    }
+    NOW_STEP.with(|s| s.set(0));
+    game.handle_event(Select);
+    // Tiles are 16 wide, and each frame comes halfway through a step
+    let mut positions = Vec::new();
+    for _ in 0..6 {
+        recording.borrow_mut().clear();
+        assert_eq!(game.frame(), game::Redraw::NextFrame);
+        let (knight, menu) = drawn(&recording);
+        assert!(!menu);
+        positions.extend(knight);
+        CLOCK.with(|c| c.set(c.get() + 75_000_000));
+        game.handle_event(Left);
+        assert_eq!(game.cursor_pos(), Vector { x: 3, y: 0 });
+    }
+    assert_eq!(positions, [0, 8, 16, 24, 32, 40]);
+    recording.borrow_mut().clear();
+    assert_eq!(game.frame(), game::Redraw::Idle);
+    assert_eq!(drawn(&recording), (vec![48], true));
+
+    // Cancelling skips to the end of the move rather than taking it back
+    for event in [Cancel, Right, Right] {
+        game.handle_event(event);
+    }
+    game.frame();
+    game.handle_event(Select);
+    recording.borrow_mut().clear();
+    game.handle_event(Cancel);
+    game.frame();
+    assert_eq!(drawn(&recording), (vec![32], true));
+    NOW_STEP.with(|s| s.set(1_000_000_000));
+}
+
+// The screen should follow a moving unit whose path leaves it
+async fn test_move_animation_scrolling() {
+    let knight = serialization::Unit {
+        x: 0,
+        y: 0,
+        movement: 5,
+        ..unit_map().units.remove(0)
+    };
+    let mut map = grid_map(10, 1);
+    map.units = vec![knight];
+    let settings = r#"{
+        "min_visible_tiles": 1,
+        "max_visible_tiles": 3,
+        "show_minimap": false,
+        "show_unit_panel": false
+    }"#;
+    let (platform, _recording) = TestPlatform::recording();
+    let platform = platform.with_map(&map).with_settings(Some(settings));
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    for event in [Select, Right, Right, Right, Right] {
+        game.handle_event(event);
+    }
+    game.frame();
+    assert_eq!(game.visible_rect().left(), 2);
+    NOW_STEP.with(|s| s.set(0));
+    game.handle_event(Select);
+    let mut lefts = Vec::new();
+    for _ in 0..4 {
+        game.frame();
+        lefts.push(game.visible_rect().left());
+        CLOCK.with(|c| c.set(c.get() + 150_000_000));
+    }
+    // Back to the start for the first steps, then along to where the knight is headed
+    assert_eq!(lefts, [0, 0, 2, 2]);
+    game.frame();
+    assert_eq!(game.cursor_pos(), Vector { x: 4, y: 0 });
+    NOW_STEP.with(|s| s.set(1_000_000_000));
+}
+
+// A fight should be played out over a few frames, with the attacker lunging at its target and the
+// damage floating up and fading while hp bars drain, before whoever died is taken off the map
+async fn test_combat_animation() {
+    let knight = unit_map().units.remove(0);
+    let mut map = grid_map(5, 2);
+    map.units = vec![
+        serialization::Unit {
+            x: 0,
+            y: 0,
+            ..knight.clone()
+        },
+        serialization::Unit {
+            x: 0,
+            y: 1,
+            ..knight.clone()
+        },
+        serialization::Unit {
+            name: "Brigand".to_owned(),
+            x: 2,
+            y: 0,
+            team: "enemy".to_owned(),
+            ..knight.clone()
+        },
+    ];
+    let settings = r#"{
+        "min_visible_tiles": 1,
+        "show_minimap": false,
+        "show_unit_panel": false,
+        "combat_nudge_ms": 100,
+        "combat_damage_ms": 200
+    }"#;
+    let (platform, recording) = TestPlatform::recording();
+    let platform = platform.with_map(&map).with_settings(Some(settings));
+    let mut game = game::Game::load(platform, "lang", "us").await.unwrap();
+    for event in [Select, Right, Select] {
+        game.handle_event(event);
+    }
+    game.frame();
+    NOW_STEP.with(|s| s.set(0));
+    TEXT_STYLES.with(|s| s.borrow_mut().clear());
+    game.handle_event(Select);
+    // Where the knight is drawn, how much of the brigand's hp bar is filled in, and how opaque the
+    // damage numbers are (from both sides, since the brigand strikes back)
+    let sprite = tinted("knight.png", team_color(serialization::PLAYER_TEAM));
+    let brigand_bar = |d: &Drawing| match d {
+        Drawing::Fill {
+            x: 32,
+            y: 27,
+            w,
+            color,
+            ..
+        } if *color != style::HP_BAR_EMPTY => Some(*w),
+        _ => None,
+    };
+    let mut frames = Vec::new();
+    loop {
+        recording.borrow_mut().clear();
+        let redraw = game.frame();
+        let drawings = recording.borrow();
+        let knight = drawings.iter().find_map(|d| match d {
+            Drawing::Image {
+                source, x, y: 0, ..
+            } if *source == sprite => Some(*x),
+            _ => None,
+        });
+        let bar = drawings.iter().find_map(brigand_bar);
+        let alphas: Vec<_> = TEXT_STYLES.with(|s| {
+            s.borrow_mut()
+                .drain(..)
+                .filter(|(text, _)| text == "2")
+                .map(|(_, style)| style.color.alpha)
+                .collect()
+        });
+        frames.push((knight, bar, alphas));
+        if redraw != game::Redraw::NextFrame {
+            break;
+        }
+        CLOCK.with(|c| c.set(c.get() + 50_000_000));
+    }
+    assert_eq!(
+        frames,
+        [
+            (Some(16), Some(12), vec![]),
+            (Some(20), Some(12), vec![]),
+            (Some(16), Some(12), vec![255, 255]),
+            (Some(16), Some(11), vec![191, 191]),
+            (Some(16), Some(11), vec![128, 128]),
+            // The numbers have floated off the top of the screen
+            (Some(16), Some(10), vec![]),
+            (Some(16), Some(10), vec![]),
+        ]
+    );
+
+    // Cancelling skips to the end of the fight
+    for event in [Down, Left, Select, Right, Right, Select] {
+        game.handle_event(event);
+    }
+    CLOCK.with(|c| c.set(c.get() + 1_000_000_000));
+    game.frame();
+    game.handle_event(Select);
+    recording.borrow_mut().clear();
+    game.handle_event(Cancel);
+    assert_eq!(game.frame(), game::Redraw::Idle);
+    assert_eq!(recording.borrow().iter().find_map(brigand_bar), Some(9));
+    TEXT_STYLES.with(|s| s.borrow_mut().clear());
+    NOW_STEP.with(|s| s.set(1_000_000_000));
+}
+
 fn main() {
     std::env::set_var("RUST_BACKTRACE", "1");
     futures::executor::block_on(run_test());
+    futures::executor::block_on(test_retry());
+    futures::executor::block_on(test_retry_exhausted());
+    test_sha256();
+    futures::executor::block_on(test_asset_checksums());
+    futures::executor::block_on(test_concurrent_image_loading());
+    futures::executor::block_on(test_mouse_move_coalescing());
+    test_event_ordering();
+    test_close_queue();
+    test_smooth_scrolling();
+    test_frame_pacing();
+    futures::executor::block_on(test_zoom_at_mouse());
+    futures::executor::block_on(test_zoom_limits());
+    futures::executor::block_on(test_infobar_text_height());
+    futures::executor::block_on(test_long_tile_name());
+    futures::executor::block_on(test_infobar_styles());
+    futures::executor::block_on(test_uncovered_regions());
+    futures::executor::block_on(test_highlights());
+    futures::executor::block_on(test_minimap());
+    futures::executor::block_on(test_fog_of_war());
+    futures::executor::block_on(test_image_smoothing());
+    futures::executor::block_on(test_cursor_follows_pan());
+    futures::executor::block_on(test_quit());
+    test_suspend();
+    futures::executor::block_on(test_rebind());
+    test_bindings_lookup();
+    futures::executor::block_on(test_keybindings_file());
+    test_binding_contexts();
+    test_menu_navigation();
+    futures::executor::block_on(test_menu_context());
+    futures::executor::block_on(test_trigger_once());
+    futures::executor::block_on(test_trigger_order());
+    futures::executor::block_on(test_intro_dialog());
+    futures::executor::block_on(test_pan_delay_setting());
+    futures::executor::block_on(test_infobar_size_setting());
+    futures::executor::block_on(test_handle_event());
+    futures::executor::block_on(test_record_replay());
+    test_replay_viewer();
+    futures::executor::block_on(test_random_seed());
+    test_geometry_properties();
+    test_mouse_hardening();
+    futures::executor::block_on(test_tooltip());
+    futures::executor::block_on(test_diagonal_movement());
+    futures::executor::block_on(test_fast_move());
+    futures::executor::block_on(test_focus());
+    futures::executor::block_on(test_jump_to_edge());
+    futures::executor::block_on(test_center_view());
+    futures::executor::block_on(test_drag_pan());
+    futures::executor::block_on(test_scroll());
+    futures::executor::block_on(test_edge_pan_speed());
+    futures::executor::block_on(test_edge_pan_keeps_cursor());
+    futures::executor::block_on(test_hover_draw_count());
+    futures::executor::block_on(test_draw_batching());
+    futures::executor::block_on(test_scroll_indicator());
+    futures::executor::block_on(test_layout_invalidation());
+    futures::executor::block_on(test_tile_type_images());
+    futures::executor::block_on(test_reload_map());
+    futures::executor::block_on(test_campaign());
+    futures::executor::block_on(test_superseded_load());
+    futures::executor::block_on(test_music());
+    futures::executor::block_on(test_sound_effects());
+    futures::executor::block_on(test_ambient_sounds());
+    futures::executor::block_on(test_footsteps());
+    futures::executor::block_on(test_debug_overlay());
+    futures::executor::block_on(test_log_levels());
+    futures::executor::block_on(test_missing_images());
+    futures::executor::block_on(test_placeholder_tiles());
+    futures::executor::block_on(test_unit_panel());
+    futures::executor::block_on(test_team_colors());
+    futures::executor::block_on(test_unit_overlays());
+    test_reachable();
+    test_occupied_moves();
+    test_attack_range();
+    futures::executor::block_on(test_unit_ranges());
+    futures::executor::block_on(test_undo_move());
+    futures::executor::block_on(test_pending_move_cleared());
+    futures::executor::block_on(test_stay_put());
+    futures::executor::block_on(test_pass_through_allies_setting());
+    futures::executor::block_on(test_next_unit());
+    futures::executor::block_on(test_snap_to_ready_unit());
+    futures::executor::block_on(test_hot_seat());
+    futures::executor::block_on(test_fog_handoff());
+    futures::executor::block_on(test_remote_play());
+    test_remote_connection();
+    futures::executor::block_on(test_danger_zone());
+    test_experience_thresholds();
+    test_seeded_level_ups();
+    test_no_exp_when_dead();
+    test_forecast_matches_combat();
+    futures::executor::block_on(test_battle_forecast());
+    futures::executor::block_on(test_level_up_popup());
+    futures::executor::block_on(test_level_up_popup_dismissed());
+    futures::executor::block_on(test_roster_applied_at_start());
+    futures::executor::block_on(test_save_and_load());
+    futures::executor::block_on(test_save_version_mismatch());
+    futures::executor::block_on(test_autosave());
+    test_format_timestamp();
+    test_strings();
+    futures::executor::block_on(test_switch_locale());
+    test_graphemes();
+    test_grapheme_truncation();
+    test_text_layout();
+    futures::executor::block_on(test_right_to_left_locale());
+    futures::executor::block_on(test_integer_scaling());
+    futures::executor::block_on(test_grid_overlay());
+    futures::executor::block_on(test_screenshot_and_map_export());
+    futures::executor::block_on(test_share_position());
+    test_visible_region();
+    futures::executor::block_on(test_mouse_leave());
+    futures::executor::block_on(test_queue_overflow_log());
+    futures::executor::block_on(test_map_editor());
+    test_flood_fill_and_brush();
+    futures::executor::block_on(test_editor_brush_and_fill());
+    futures::executor::block_on(test_bookmarks());
+    futures::executor::block_on(test_path_cost_readout());
+    test_range_patterns();
+    futures::executor::block_on(test_range_pattern_targets());
+    futures::executor::block_on(test_options_menu());
+    test_first_key();
+    futures::executor::block_on(test_hint_bar());
+    futures::executor::block_on(test_zero_sized_screen());
+    futures::executor::block_on(test_move_animation());
+    futures::executor::block_on(test_move_animation_scrolling());
+    futures::executor::block_on(test_combat_animation());
 }
@@ -0,0 +1,697 @@
+use alemian_saga_core::*;
+use async_trait::async_trait;
+use ndarray::array;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Drawing {
+    Image {
+        source: String,
+        x: u32,
+        y: u32,
+        w: u32,
+        h: u32,
+    },
+    Text {
+        txt: String,
+        tx: u32,
+        ty: u32,
+    },
+    Fill {
+        x: u32,
+        y: u32,
+        w: u32,
+        h: u32,
+        color: style::Color,
+    },
+    Clear,
+}
+
+// Size of an offscreen surface along with what was drawn on it
+pub type Surface = (Vector<u32>, Vec<Drawing>);
+
+thread_local! {
+    // Virtual clock in nanoseconds; advanced by sleeps and by each call to now
+    pub static CLOCK: std::cell::Cell<u64> = const { std::cell::Cell::new(0) };
+    // Amount of virtual time that passes between calls to now
+    pub static NOW_STEP: std::cell::Cell<u64> = const { std::cell::Cell::new(1_000_000_000) };
+    // Durations passed to sleep
+    pub static SLEEPS: std::cell::RefCell<Vec<u64>> = const { std::cell::RefCell::new(vec![]) };
+    // Starts, completions and abandonments of image loads, in the order they happened
+    pub static IMAGE_LOADS: std::cell::RefCell<Vec<(ImageLoad, String)>> =
+        const { std::cell::RefCell::new(vec![]) };
+    // Whether frames only come when a test calls pump_frame; otherwise each frame comes as soon
+    // as it's requested, after FRAME_INTERVAL_NS of virtual time
+    pub static MANUAL_FRAMES: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+    // Number of times pump_frame has been called
+    static FRAMES_PUMPED: std::cell::Cell<u64> = const { std::cell::Cell::new(0) };
+    // Wakes whatever is waiting for the next pumped frame
+    static FRAME_WAKER: std::cell::RefCell<Option<std::task::Waker>> =
+        const { std::cell::RefCell::new(None) };
+    // Images passed to tint along with the color they were tinted with
+    pub static TINTS: std::cell::RefCell<Vec<(String, style::Color)>> =
+        const { std::cell::RefCell::new(vec![]) };
+    // Messages passed to log
+    pub static LOG: std::cell::RefCell<Vec<String>> = const { std::cell::RefCell::new(vec![]) };
+    // Width and height of the screen
+    pub static SCREEN_SIZE: std::cell::Cell<(u32, u32)> = const { std::cell::Cell::new((80, 60)) };
+    // Most recent value passed to set_smoothing
+    pub static SMOOTHING: std::cell::Cell<Option<bool>> = const { std::cell::Cell::new(None) };
+    // Text passed to draw_text_primitive along with the style it was drawn in
+    pub static TEXT_STYLES: std::cell::RefCell<Vec<(String, style::TextStyle)>> =
+        const { std::cell::RefCell::new(vec![]) };
+    // Text passed to draw_text_primitive along with where it landed and the direction it was read in
+    pub static TEXT_LAYOUTS: std::cell::RefCell<Vec<(String, Rectangle<u32>, text::Direction)>> =
+        const { std::cell::RefCell::new(vec![]) };
+    // Files served instead of the platform's own, e.g. to change a file after the game has loaded
+    // it (None to serve no file at all)
+    pub static REPLACED_FILES: std::cell::RefCell<std::collections::HashMap<String, Option<Vec<u8>>>> =
+        std::cell::RefCell::new(std::collections::HashMap::new());
+    // Paths passed to play_music, in order
+    pub static MUSIC: std::cell::RefCell<Vec<Option<String>>> =
+        const { std::cell::RefCell::new(vec![]) };
+    // Paths passed to play_sound along with their volumes, in order
+    pub static SOUNDS: std::cell::RefCell<Vec<(String, u32)>> =
+        const { std::cell::RefCell::new(vec![]) };
+    // Volume of each ambient sound that's playing, by path
+    pub static AMBIENT: std::cell::RefCell<std::collections::HashMap<String, u32>> =
+        std::cell::RefCell::new(std::collections::HashMap::new());
+    // Most recent value passed to set_volume
+    pub static VOLUME: std::cell::Cell<Option<u32>> = const { std::cell::Cell::new(None) };
+    // Values saved with store
+    pub static STORAGE: std::cell::RefCell<std::collections::HashMap<String, String>> =
+        std::cell::RefCell::new(std::collections::HashMap::new());
+    // Value returned by timestamp
+    pub static TIMESTAMP: std::cell::Cell<Option<u64>> = const { std::cell::Cell::new(None) };
+    // Names passed to export_frame and export_offscreen, along with the size of the offscreen
+    // surface and what was drawn on it (None for the screen)
+    pub static EXPORTS: std::cell::RefCell<Vec<(String, Option<Surface>)>> =
+        const { std::cell::RefCell::new(vec![]) };
+    // Names and contents of files passed to export_file
+    pub static FILES: std::cell::RefCell<Vec<(String, Vec<u8>)>> =
+        const { std::cell::RefCell::new(vec![]) };
+    // Value returned by event_queue_overflows
+    pub static QUEUE_OVERFLOWS: std::cell::Cell<u64> = const { std::cell::Cell::new(0) };
+    // Most recent text passed to set_clipboard
+    pub static CLIPBOARD: std::cell::RefCell<Option<String>> = const { std::cell::RefCell::new(None) };
+    // Size of the offscreen surface and what's been drawn on it, while drawing goes to it
+    static OFFSCREEN: std::cell::RefCell<Option<Surface>> =
+        const { std::cell::RefCell::new(None) };
+    // Images drawn in each batch, in order
+    pub static BATCHES: std::cell::RefCell<Vec<Vec<Drawing>>> =
+        const { std::cell::RefCell::new(vec![]) };
+    // Images held back by the batch that's going, if any
+    static BATCH: std::cell::RefCell<Option<Vec<Drawing>>> =
+        const { std::cell::RefCell::new(None) };
+    // Connections that games have opened to other games, in the order they were opened
+    pub static CONNECTIONS: std::cell::RefCell<Vec<TestConnection>> =
+        const { std::cell::RefCell::new(vec![]) };
+}
+
+// Connection opened by a game, whose other end is played by the test
+pub struct TestConnection {
+    pub url: String,
+    // Messages the game has sent over the connection
+    pub sent: std::rc::Rc<std::cell::RefCell<Vec<Vec<u8>>>>,
+    // Sends messages to the game; dropping it loses the connection
+    pub incoming: Option<futures::channel::mpsc::UnboundedSender<Vec<u8>>>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ImageLoad {
+    Started,
+    Finished,
+    // The load was dropped before it finished
+    Dropped,
+}
+
+// Future that yields an image on its second poll, recording when the load starts and finishes or
+// is dropped
+pub struct TestImage {
+    path: String,
+    started: bool,
+    finished: bool,
+    // Whether the image fails to load
+    missing: bool,
+}
+
+impl TestImage {
+    fn new(path: String, missing: bool) -> TestImage {
+        TestImage {
+            path,
+            started: false,
+            finished: false,
+            missing,
+        }
+    }
+}
+
+impl Drop for TestImage {
+    fn drop(&mut self) {
+        if self.started && !self.finished {
+            IMAGE_LOADS.with(|l| l.borrow_mut().push((ImageLoad::Dropped, self.path.clone())));
+        }
+    }
+}
+
+impl std::future::Future for TestImage {
+    type Output = Option<String>;
+    fn poll(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Self::Output> {
+        let image = self.get_mut();
+        if image.started {
+            image.finished = true;
+            IMAGE_LOADS.with(|l| {
+                l.borrow_mut()
+                    .push((ImageLoad::Finished, image.path.clone()))
+            });
+            std::task::Poll::Ready(Some(image.path.clone()).filter(|_| !image.missing))
+        } else {
+            image.started = true;
+            IMAGE_LOADS.with(|l| {
+                l.borrow_mut()
+                    .push((ImageLoad::Started, image.path.clone()))
+            });
+            cx.waker().wake_by_ref();
+            std::task::Poll::Pending
+        }
+    }
+}
+
+// Lets the game draw a frame while MANUAL_FRAMES is set
+pub fn pump_frame() {
+    FRAMES_PUMPED.with(|f| f.set(f.get() + 1));
+    if let Some(waker) = FRAME_WAKER.with(|w| w.borrow_mut().take()) {
+        waker.wake();
+    }
+}
+
+// Future that's ready once the next frame has been pumped (or right away unless MANUAL_FRAMES is
+// set)
+pub struct TestFrame {
+    // Value of FRAMES_PUMPED once the frame has come, or None if it came when it was requested
+    frame: Option<u64>,
+}
+
+impl std::future::Future for TestFrame {
+    type Output = ();
+    fn poll(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<()> {
+        match self.frame {
+            Some(frame) if FRAMES_PUMPED.with(|f| f.get()) < frame => {
+                FRAME_WAKER.with(|w| *w.borrow_mut() = Some(cx.waker().clone()));
+                std::task::Poll::Pending
+            }
+            _ => std::task::Poll::Ready(()),
+        }
+    }
+}
+
+pub type Recording = std::rc::Rc<std::cell::RefCell<Vec<Drawing>>>;
+
+pub struct TestPlatform {
+    // Drawings the game is expected to make, in order; if None, any drawing is accepted
+    pub drawings: Option<std::sync::mpsc::Receiver<Drawing>>,
+    // Every drawing the game has made
+    pub recording: Recording,
+    // Number of times get_file will fail with a network error before succeeding
+    pub transient_failures: std::cell::Cell<u32>,
+    // Contents of the files available through get_file
+    pub files: std::collections::HashMap<String, Vec<u8>>,
+    // Height reported for rendered text
+    pub text_height: Option<u32>,
+    // Path reported for the keybindings file of every locale (None for the default path)
+    pub keybindings_path: Option<String>,
+    // Returned by random so that games are repeatable
+    pub seed: u64,
+    // Paths of images that fail to load
+    pub missing_images: std::collections::HashSet<String>,
+}
+
+// Seed used unless a test specifies otherwise
+pub const DEFAULT_SEED: u64 = 42;
+
+// Settings used unless a test specifies otherwise
+pub const DEFAULT_SETTINGS: &str = r#"{
+    "min_visible_tiles": 1,
+    "show_minimap": false,
+    "show_input_hints": false,
+    "show_scroll_indicator": false
+}"#;
+
+// A 2x2 map with tile types a, b, c, and d
+pub fn small_map() -> serialization::Map {
+    let tile_type = |name: &str, stat: i32| serialization::TileType {
+        image: name.to_owned(),
+        name: name.to_owned(),
+        defense: stat,
+        evade: stat + 10,
+        move_cost: stat as u32 + 20,
+        minimap_color: None,
+        ambient_sound: None,
+        footstep_sound: None,
+    };
+    serialization::Map {
+        tile_types: vec![
+            tile_type("a", 0),
+            tile_type("b", 1),
+            tile_type("c", 2),
+            tile_type("d", 3),
+        ],
+        map: array![[0, 1], [2, 3]],
+        background: None,
+        fog: false,
+        triggers: vec![],
+        intro_dialog: vec![],
+        music: None,
+        units: vec![],
+    }
+}
+
+// A map in which every tile has its own type, named and drawn as "x,y"
+pub fn grid_map(columns: usize, rows: usize) -> serialization::Map {
+    let tile_types = (0..rows)
+        .flat_map(|y| (0..columns).map(move |x| format!("{},{}", x, y)))
+        .map(|name| serialization::TileType {
+            image: name.clone(),
+            name,
+            defense: 0,
+            evade: 0,
+            move_cost: 1,
+            minimap_color: None,
+            ambient_sound: None,
+            footstep_sound: None,
+        })
+        .collect();
+    serialization::Map {
+        tile_types,
+        map: ndarray::Array2::from_shape_fn((rows, columns), |(y, x)| (y * columns + x) as u32),
+        background: None,
+        fog: false,
+        triggers: vec![],
+        intro_dialog: vec![],
+        music: None,
+        units: vec![],
+    }
+}
+
+impl TestPlatform {
+    pub fn new(drawings: std::sync::mpsc::Receiver<Drawing>) -> TestPlatform {
+        TestPlatform::with_recording(Some(drawings), Recording::default())
+    }
+
+    // Creates a platform that accepts any drawing, along with a handle to the drawings it records
+    pub fn recording() -> (TestPlatform, Recording) {
+        let recording = Recording::default();
+        (
+            TestPlatform::with_recording(None, recording.clone()),
+            recording,
+        )
+    }
+
+    fn with_recording(
+        drawings: Option<std::sync::mpsc::Receiver<Drawing>>,
+        recording: Recording,
+    ) -> TestPlatform {
+        TestPlatform {
+            drawings,
+            recording,
+            transient_failures: std::cell::Cell::new(0),
+            files: std::collections::HashMap::new(),
+            text_height: None,
+            keybindings_path: None,
+            seed: DEFAULT_SEED,
+            missing_images: std::collections::HashSet::new(),
+        }
+        .with_map(&small_map())
+        .with_settings(Some(DEFAULT_SETTINGS))
+    }
+
+    // Replaces the map served to the game
+    pub fn with_map(self, map: &serialization::Map) -> TestPlatform {
+        self.with_map_at("lang/map.map", map)
+    }
+
+    // Serves a map at a path
+    pub fn with_map_at(mut self, path: &str, map: &serialization::Map) -> TestPlatform {
+        let contents = rmp_serde::encode::to_vec(map).unwrap();
+        self.files.insert(path.to_owned(), contents);
+        self
+    }
+
+    // Serves a campaign file listing maps for the "lang" language
+    pub fn with_campaign(mut self, maps: &[&str]) -> TestPlatform {
+        let campaign = serde_json::json!({ "maps": maps }).to_string();
+        self.files
+            .insert("lang/campaign.json".to_owned(), campaign.into_bytes());
+        self
+    }
+
+    // Replaces the settings file served to the game (None to serve no settings file)
+    pub fn with_settings(mut self, settings: Option<&str>) -> TestPlatform {
+        match settings {
+            Some(s) => self
+                .files
+                .insert("settings.json".to_owned(), s.as_bytes().to_vec()),
+            None => self.files.remove("settings.json"),
+        };
+        self
+    }
+
+    // Serves a keybindings file for the "us" locale
+    pub fn with_keybindings(mut self, keybindings: &str) -> TestPlatform {
+        self.files.insert(
+            "keybindings/us.json".to_owned(),
+            keybindings.as_bytes().to_vec(),
+        );
+        self
+    }
+
+    // Serves a manifest listing the SHA-256 hash of each path
+    pub fn with_manifest(mut self, hashes: &[(&str, String)]) -> TestPlatform {
+        let hashes: std::collections::HashMap<_, _> = hashes.iter().cloned().collect();
+        let manifest = serde_json::json!({ "sha256": hashes }).to_string();
+        self.files
+            .insert(fetch::MANIFEST_PATH.to_owned(), manifest.into_bytes());
+        self
+    }
+
+    // Holds images back while a batch is going, and otherwise draws whatever was held back before
+    // the drawing so that everything is drawn in order
+    fn check(&self, drawing: Drawing) {
+        let held = BATCH.with(|b| match (b.borrow_mut().as_mut(), &drawing) {
+            (Some(batch), Drawing::Image { .. }) => {
+                batch.push(drawing.clone());
+                BATCHES.with(|b| b.borrow_mut().last_mut().unwrap().push(drawing.clone()));
+                true
+            }
+            _ => false,
+        });
+        if !held {
+            self.flush_batch();
+            self.draw(drawing);
+        }
+    }
+
+    // Draws the images held back by the batch that's going, if any, leaving it going
+    fn flush_batch(&self) {
+        let held = BATCH.with(|b| b.borrow_mut().as_mut().map(std::mem::take));
+        for drawing in held.into_iter().flatten() {
+            self.draw(drawing);
+        }
+    }
+
+    fn draw(&self, drawing: Drawing) {
+        // Offscreen drawing isn't what's expected on the screen, so it's only kept for the export
+        let drawing = OFFSCREEN.with(|o| match o.borrow_mut().as_mut() {
+            Some((_, drawings)) => {
+                drawings.push(drawing);
+                None
+            }
+            None => Some(drawing),
+        });
+        let drawing = match drawing {
+            Some(d) => d,
+            None => return,
+        };
+        if let Some(drawings) = self.drawings.as_ref() {
+            let expected = drawings
+                .try_recv()
+                .unwrap_or_else(|_| panic!("unexpected drawing: {:?}", drawing));
+            assert_eq!(drawing, expected);
+        }
+        self.recording.borrow_mut().push(drawing);
+    }
+}
+
+// Every expected drawing should have been drawn by the time the game is over
+impl Drop for TestPlatform {
+    fn drop(&mut self) {
+        if let Some(drawings) = self.drawings.as_ref() {
+            if !std::thread::panicking() {
+                if let Ok(missing) = drawings.try_recv() {
+                    panic!("expected drawing never drawn: {:?}", missing);
+                }
+            }
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl alemian_saga_core::Platform for TestPlatform {
+    type Image = String;
+    type InputType = String;
+    type MouseDistance = u32;
+    type ScreenDistance = u32;
+    type ImageFuture = TestImage;
+    type File = std::io::Cursor<Vec<u8>>;
+    type Instant = u64;
+    type Duration = u64;
+    type SleepFuture = std::future::Ready<()>;
+    type FrameFuture = TestFrame;
+    fn draw_primitive(
+        &self,
+        img: &Self::Image,
+        left: Self::ScreenDistance,
+        top: Self::ScreenDistance,
+        width: Self::ScreenDistance,
+        height: Self::ScreenDistance,
+    ) {
+        println!("drawing {}", img);
+        self.check(image(img, left, top, width, height));
+    }
+    fn draw_text_primitive(
+        &self,
+        text: &str,
+        x: Self::ScreenDistance,
+        y: Self::ScreenDistance,
+        max_width: Self::ScreenDistance,
+        style: &style::TextStyle,
+        direction: text::Direction,
+    ) {
+        TEXT_STYLES.with(|s| s.borrow_mut().push((text.to_owned(), *style)));
+        // The alignment has already been mirrored for the direction, so it's laid out as is
+        let offset = Vector { x, y };
+        let ltr = text::Direction::LeftToRight;
+        let layout = text::layout(self, text, offset, max_width, style, ltr);
+        TEXT_LAYOUTS.with(|l| l.borrow_mut().push((text.to_owned(), layout, direction)));
+        self.check(Drawing::Text {
+            txt: text.to_owned(),
+            tx: x,
+            ty: y,
+        });
+    }
+    fn string_to_input(input: String) -> Self::InputType {
+        input
+    }
+    fn store(&self, key: &str, value: &str) {
+        STORAGE.with(|s| s.borrow_mut().insert(key.to_owned(), value.to_owned()));
+    }
+    fn retrieve(&self, key: &str) -> Option<String> {
+        STORAGE.with(|s| s.borrow().get(key).cloned())
+    }
+    fn timestamp(&self) -> Option<u64> {
+        TIMESTAMP.with(|t| t.get())
+    }
+    fn play_music(&self, path: Option<&str>) -> Result<(), Error> {
+        MUSIC.with(|m| m.borrow_mut().push(path.map(str::to_owned)));
+        Ok(())
+    }
+    fn play_sound(&self, path: &str, volume: u32) {
+        SOUNDS.with(|s| s.borrow_mut().push((path.to_owned(), volume)));
+    }
+    fn set_ambient_sound(&self, path: &str, volume: u32) {
+        AMBIENT.with(|a| match volume {
+            0 => a.borrow_mut().remove(path),
+            _ => a.borrow_mut().insert(path.to_owned(), volume),
+        });
+    }
+    fn keybindings_path(&self, locale: &str) -> String {
+        match self.keybindings_path.as_ref() {
+            Some(path) => path.clone(),
+            None => bindings::default_path(locale),
+        }
+    }
+    fn random(&self) -> u64 {
+        self.seed
+    }
+    fn set_volume(&self, volume: u32) {
+        VOLUME.with(|v| v.set(Some(volume)));
+    }
+    fn event_queue_overflows(&self) -> u64 {
+        QUEUE_OVERFLOWS.with(|o| o.get())
+    }
+    fn set_clipboard(&self, text: &str) {
+        CLIPBOARD.with(|c| *c.borrow_mut() = Some(text.to_owned()));
+    }
+    fn connect(&self, url: &str) -> Result<net::MessageStream, Error> {
+        let (sender, receiver) = futures::channel::mpsc::unbounded();
+        let sent = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let outgoing = sent.clone();
+        CONNECTIONS.with(|c| {
+            c.borrow_mut().push(TestConnection {
+                url: url.to_owned(),
+                sent,
+                incoming: Some(sender),
+            })
+        });
+        Ok(net::MessageStream {
+            incoming: Box::pin(receiver),
+            send: Box::new(move |m| outgoing.borrow_mut().push(m.to_vec())),
+        })
+    }
+    fn begin_batch(&self) {
+        BATCH.with(|b| {
+            assert!(b.borrow().is_none(), "batch begun while another is going");
+            *b.borrow_mut() = Some(vec![]);
+        });
+        BATCHES.with(|b| b.borrow_mut().push(vec![]));
+    }
+    fn end_batch(&self) {
+        let going = BATCH.with(|b| b.borrow().is_some());
+        assert!(going, "batch ended without being begun");
+        self.flush_batch();
+        BATCH.with(|b| *b.borrow_mut() = None);
+    }
+    fn export_frame(&self, name: &str) {
+        self.flush_batch();
+        EXPORTS.with(|e| e.borrow_mut().push((name.to_owned(), None)));
+    }
+    fn begin_offscreen(&self, size: Vector<Self::ScreenDistance>) -> bool {
+        self.flush_batch();
+        OFFSCREEN.with(|o| *o.borrow_mut() = Some((size, vec![])));
+        true
+    }
+    fn export_offscreen(&self, name: &str) {
+        self.flush_batch();
+        let surface = OFFSCREEN.with(|o| o.borrow_mut().take());
+        EXPORTS.with(|e| e.borrow_mut().push((name.to_owned(), surface)));
+    }
+    fn export_file(&self, name: &str, contents: &[u8]) {
+        FILES.with(|f| f.borrow_mut().push((name.to_owned(), contents.to_vec())));
+    }
+    fn get_width(&self) -> Self::ScreenDistance {
+        SCREEN_SIZE.with(|s| s.get().0)
+    }
+    fn get_height(&self) -> Self::ScreenDistance {
+        SCREEN_SIZE.with(|s| s.get().1)
+    }
+    fn set_smoothing(&self, enabled: bool) {
+        SMOOTHING.with(|s| s.set(Some(enabled)));
+    }
+    fn fill_rect_primitive(
+        &self,
+        x: Self::ScreenDistance,
+        y: Self::ScreenDistance,
+        w: Self::ScreenDistance,
+        h: Self::ScreenDistance,
+        color: style::Color,
+    ) {
+        self.check(Drawing::Fill { x, y, w, h, color });
+    }
+    fn clear(&self) {
+        self.check(Drawing::Clear);
+    }
+    fn measure_text(&self, text: &str, _style: &style::TextStyle) -> Self::ScreenDistance {
+        text.chars().map(char_width).sum()
+    }
+    fn get_text_height(&self, _style: &style::TextStyle) -> Option<Self::ScreenDistance> {
+        self.text_height
+    }
+    fn get_image(&self, path: &str) -> Self::ImageFuture {
+        TestImage::new(path.to_owned(), self.missing_images.contains(path))
+    }
+    fn tint(&self, img: &Self::Image, color: style::Color) -> Option<Self::ImageFuture> {
+        TINTS.with(|t| t.borrow_mut().push((img.clone(), color)));
+        Some(TestImage::new(tinted(img, color), false))
+    }
+    async fn get_file(&self, path: &str) -> Result<Self::File, Error> {
+        let failures = self.transient_failures.get();
+        if failures > 0 {
+            self.transient_failures.set(failures - 1);
+            Err(Error::Network {
+                path: path.to_owned(),
+                source_msg: "connection reset".to_owned(),
+            })
+        } else {
+            let replaced = REPLACED_FILES.with(|f| f.borrow().get(path).cloned());
+            match replaced.unwrap_or_else(|| self.files.get(path).cloned()) {
+                Some(contents) => Ok(std::io::Cursor::new(contents)),
+                None => Err(Error::MissingAsset(path.to_owned())),
+            }
+        }
+    }
+    fn log(path: &str) {
+        println!("{}", path);
+        LOG.with(|l| l.borrow_mut().push(path.to_owned()));
+    }
+    fn now() -> Self::Instant {
+        let step = NOW_STEP.with(|s| s.get());
+        CLOCK.with(|c| c.replace(c.get() + step))
+    }
+    fn nanoseconds(ns: u64) -> Self::Duration {
+        ns
+    }
+    fn duration_between(first: Self::Instant, second: Self::Instant) -> Self::Duration {
+        second - first
+    }
+    fn instant_after(instant: Self::Instant, duration: Self::Duration) -> Self::Instant {
+        instant + duration
+    }
+    fn to_nanoseconds(duration: Self::Duration) -> u64 {
+        duration
+    }
+    fn request_frame(&self) -> Self::FrameFuture {
+        if MANUAL_FRAMES.with(|m| m.get()) {
+            TestFrame {
+                frame: Some(FRAMES_PUMPED.with(|f| f.get()) + 1),
+            }
+        } else {
+            CLOCK.with(|c| c.set(c.get() + game::FRAME_INTERVAL_NS));
+            TestFrame { frame: None }
+        }
+    }
+    fn sleep(duration: Self::Duration) -> Self::SleepFuture {
+        SLEEPS.with(|s| s.borrow_mut().push(duration));
+        CLOCK.with(|c| c.set(c.get() + duration));
+        std::future::ready(())
+    }
+}
+
+// Gets how wide a character is drawn: marks that combine with the character before them take up
+// no space, and East Asian characters and emoji take up two columns like in a terminal
+pub fn char_width(c: char) -> u32 {
+    match c {
+        '\u{0300}'..='\u{036F}'
+        | '\u{0591}'..='\u{05C7}'
+        | '\u{064B}'..='\u{065F}'
+        | '\u{200D}'
+        | '\u{FE0F}'
+        | '\u{1F3FB}'..='\u{1F3FF}' => 0,
+        '\u{1100}'..='\u{115F}'
+        | '\u{2E80}'..='\u{A4CF}'
+        | '\u{AC00}'..='\u{D7A3}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '\u{FF00}'..='\u{FF60}'
+        | '\u{1F1E6}'..='\u{1FAFF}' => 2,
+        _ => 1,
+    }
+}
+
+// Gets the name of the image that tint makes from an image and a color
+pub fn tinted(source: &str, color: style::Color) -> String {
+    format!("{} tinted {}", source, color.to_css())
+}
+
+pub fn image(source: &str, x: u32, y: u32, width: u32, height: u32) -> Drawing {
+    Drawing::Image {
+        source: source.to_owned(),
+        x,
+        y,
+        w: width,
+        h: height,
+    }
+}